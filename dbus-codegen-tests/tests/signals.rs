@@ -0,0 +1,32 @@
+extern crate dbus;
+
+#[allow(dead_code)]
+mod policykit;
+
+use dbus::{SignalArgs, Message};
+use dbus::arg::{Variant, RefArg};
+use policykit::{OrgFreedesktopDBusPropertiesPropertiesChanged as PC, OrgFreedesktopPolicyKit1AuthorityChanged as AC};
+use std::collections::HashMap;
+
+#[test]
+fn signal_roundtrip() {
+    let mut props: HashMap<String, Variant<Box<RefArg + 'static>>> = HashMap::new();
+    props.insert("BackendName".into(), Variant(Box::new("local".to_string())));
+    let pc = PC { interface_name: "org.freedesktop.PolicyKit1.Authority".into(), changed_properties: props,
+        invalidated_properties: vec!("BackendVersion".into()) };
+
+    let m = pc.to_emit_message(&"/org/freedesktop/PolicyKit1/Authority".into());
+    assert_eq!(&*m.interface().unwrap(), "org.freedesktop.DBus.Properties");
+    assert_eq!(&*m.member().unwrap(), "PropertiesChanged");
+    let pc2 = PC::from_message(&m).unwrap();
+    assert_eq!(pc2.interface_name, pc.interface_name);
+    assert_eq!(pc2.changed_properties["BackendName"].0.as_str(), Some("local"));
+    assert_eq!(pc2.invalidated_properties, pc.invalidated_properties);
+
+    // A signal with no arguments, and a signal of another type
+    let m = AC::default().to_emit_message(&"/".into());
+    assert!(AC::from_message(&m).is_some());
+    assert!(PC::from_message(&m).is_none());
+    let m = Message::new_method_call("org.freedesktop.PolicyKit1", "/", "org.freedesktop.PolicyKit1.Authority", "Changed").unwrap();
+    assert!(AC::from_message(&m).is_none());
+}
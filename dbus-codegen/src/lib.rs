@@ -1,3 +1,40 @@
+//! Generates Rust code from D-Bus XML introspection data.
+//!
+//! This is the library part of dbus-codegen-rust. Use it from a build script to keep
+//! generated bindings in sync with the XML files they are generated from.
+//!
+//! # Example
+//!
+//! In `build.rs`, generate code for every XML file in a directory:
+//!
+//! ```rust,no_run
+//! extern crate dbus_codegen;
+//! use std::{env, fs, path::Path};
+//!
+//! fn main() {
+//!     let out_dir = env::var("OUT_DIR").unwrap();
+//!     let opts = dbus_codegen::GenOpts { methodtype: None, ..Default::default() };
+//!     println!("cargo:rerun-if-changed=interfaces");
+//!     for entry in fs::read_dir("interfaces").unwrap() {
+//!         let path = entry.unwrap().path();
+//!         if path.extension().map(|e| e != "xml").unwrap_or(true) { continue; }
+//!         let xml = fs::read_to_string(&path).unwrap();
+//!         let code = dbus_codegen::generate(&xml, &opts).unwrap();
+//!         let name = path.file_stem().unwrap().to_str().unwrap();
+//!         fs::write(Path::new(&out_dir).join(format!("{}.rs", name)), code).unwrap();
+//!     }
+//! }
+//! ```
+//!
+//! Then include each generated file in a module of its own (since every file starts
+//! with the same `use` declarations):
+//!
+//! ```rust,ignore
+//! mod networkmanager {
+//!     include!(concat!(env!("OUT_DIR"), "/networkmanager.rs"));
+//! }
+//! ```
+
 extern crate dbus;
 extern crate xml;
 
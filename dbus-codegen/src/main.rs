@@ -24,9 +24,14 @@ impl<'a, C: ::std::ops::Deref<Target=::dbus::Connection>> OrgFreedesktopDBusIntr
 }
 
 
-// Unwrapping is fine here, this is just a test program.
-
 fn main() {
+    if let Err(e) = run() {
+        eprintln!("dbus-codegen-rust: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<std::error::Error>> {
     let matches = clap::App::new("D-Bus Rust code generator").about("Generates Rust code from xml introspection data")
         .arg(clap::Arg::with_name("destination").short("d").long("destination").takes_value(true).value_name("BUSNAME")
              .help("If present, connects to the supplied service to get introspection data. Reads from stdin otherwise."))  
@@ -53,21 +58,21 @@ Defaults to 'RefClosure'."))
         .get_matches();
 
     if matches.is_present("destination") && matches.is_present("file") {
-        panic!("Expected either xml file path as argument or destination option. But both are provided.");
+        Err("Expected either xml file path as argument or destination option. But both are provided.")?;
     }
-	
-    let s = 
+
+    let s =
     if let Some(dest) = matches.value_of("destination") {
         let path = matches.value_of("path").unwrap_or("/");
         let bus = if matches.is_present("systembus") { dbus::BusType::System } else { dbus::BusType::Session };
-        let c = dbus::Connection::get_private(bus).unwrap();
+        let c = dbus::Connection::get_private(bus)?;
         let p = c.with_path(dest, path, 10000);
-        p.introspect().unwrap()
+        p.introspect().map_err(|e| format!("Failed to introspect {} {}: {}", dest, path, e.message().unwrap_or("")))?
     } else if let Some(file_path) = matches.value_of("file")  {
-        std::fs::read_to_string(file_path.to_string()).unwrap()
+        std::fs::read_to_string(file_path).map_err(|e| format!("Failed to read {}: {}", file_path, e))?
     } else {
         let mut s = String::new();
-        (&mut std::io::stdin() as &mut std::io::Read).read_to_string(&mut s).unwrap();
+        (&mut std::io::stdin() as &mut std::io::Read).read_to_string(&mut s)?;
         s
     };
 
@@ -81,7 +86,7 @@ Defaults to 'RefClosure'."))
         Some("generic") => (Some("MethodType"), None),
         Some("par") => (None, Some("Par")),
         Some("none") => (None, None),
-        _ => panic!("Invalid methodtype specified"),
+        Some(x) => Err(format!("Invalid methodtype specified: {}", x))?,
     };
 
     let maccess = matches.value_of("methodaccess").map(|s| s.to_lowercase());
@@ -89,7 +94,7 @@ Defaults to 'RefClosure'."))
         None | Some("refclosure") => ServerAccess::RefClosure,
         Some("asrefclosure") => ServerAccess::AsRefClosure,
         Some("methodinfo") => ServerAccess::MethodInfo,
-        _ => panic!("Invalid methodaccess specified"),
+        Some(x) => Err(format!("Invalid methodaccess specified: {}", x))?,
     };
 
     let opts = generate::GenOpts { methodtype: mtype.map(|x| x.into()), dbuscrate: dbuscrate.into(),
@@ -99,14 +104,16 @@ Defaults to 'RefClosure'."))
         crhandler: crhandler.map(|x| x.to_string()),
     };
 
+    // Generate before creating the output file, so that a failure does not leave an empty file behind.
+    let code = generate::generate(&s, &opts).map_err(|e| format!("Failed to parse introspection data: {}", e))?;
+
     let mut h: Box<std::io::Write> = match matches.value_of("output") {
         Some(file_path) => Box::new(std::fs::File::create(file_path)
-            .unwrap_or_else(|e| {
-                panic!("Failed to open {}", e);
-            })),
+            .map_err(|e| format!("Failed to open {}: {}", file_path, e))?),
         None => Box::new(std::io::stdout()),
     };
 
-    h.write(generate::generate(&s, &opts).unwrap().as_bytes()).unwrap();
-    h.flush().unwrap();
+    h.write_all(code.as_bytes())?;
+    h.flush()?;
+    Ok(())
 }
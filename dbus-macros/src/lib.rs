@@ -0,0 +1,164 @@
+//! Procedural macros for the dbus crate.
+//!
+//! Currently contains the `dbus_interface` attribute, which turns an ordinary `impl` block
+//! into a D-Bus interface that can be added to a `dbus::tree::ObjectPath`.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse_macro_input, FnArg, GenericArgument, ImplItem, ItemImpl, LitStr, Pat, PathArguments, ReturnType, Type, Visibility};
+
+/// Implements a D-Bus interface from the public `&self` methods of an `impl` block.
+///
+/// The attribute takes the interface name as argument. Every public method taking `&self`
+/// becomes a D-Bus method, with the method name converted to CamelCase. The method arguments
+/// become D-Bus input arguments, and must implement `dbus::arg::Arg` and `dbus::arg::Get`.
+/// The return type must be a `Result<T, E>` where `E` converts into `dbus::tree::MethodErr`.
+/// `T` is either `()` (no output arguments), a tuple (one output argument per element), or a
+/// single type implementing `dbus::arg::Arg` and `dbus::arg::Append`.
+///
+/// An associated function `<interface_name_in_snake_case>_server` is added to the impl block.
+/// It takes a factory, data for the interface, and a closure that returns something that references
+/// the object implementing the interface (e g an `Arc`), and returns the interface, including
+/// introspection data.
+///
+/// # Example
+///
+/// ```
+/// use dbus_macros::dbus_interface;
+/// use dbus::tree::{Factory, MethodErr};
+/// use std::sync::Arc;
+///
+/// struct Greeter { greeting: String }
+///
+/// #[dbus_interface("com.example.Greeter")]
+/// impl Greeter {
+///     pub fn greet(&self, name: &str) -> Result<String, MethodErr> {
+///         Ok(format!("{}, {}!", self.greeting, name))
+///     }
+/// }
+///
+/// let greeter = Arc::new(Greeter { greeting: "Hello".into() });
+/// let f = Factory::new_sync::<()>();
+/// let iface = Greeter::com_example_greeter_server(&f, (), move |_| greeter.clone());
+/// let tree = f.tree(()).add(f.object_path("/greeter", ()).introspectable().add(iface));
+/// ```
+#[proc_macro_attribute]
+pub fn dbus_interface(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let name = parse_macro_input!(attr as LitStr);
+    let mut imp = parse_macro_input!(item as ItemImpl);
+    match interface_fn(&name, &imp) {
+        Ok(f) => {
+            imp.items.push(ImplItem::Method(syn::parse2(f).expect("generated invalid function")));
+            quote!(#imp).into()
+        }
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn make_camel(s: &str) -> String {
+    s.split('_').filter(|p| !p.is_empty()).map(|p| {
+        let mut c = p.chars();
+        c.next().map(|f| f.to_uppercase().chain(c).collect::<String>()).unwrap_or_default()
+    }).collect()
+}
+
+fn make_snake(s: &str) -> String {
+    s.chars().map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '_' }).collect()
+}
+
+// Extracts T from Result<T, E>.
+fn result_ok_type(r: &ReturnType) -> Option<&Type> {
+    let t = match r { ReturnType::Type(_, t) => t, ReturnType::Default => return None };
+    let seg = match **t { Type::Path(ref p) => p.path.segments.last()?, _ => return None };
+    if seg.ident != "Result" { return None }
+    match seg.arguments {
+        PathArguments::AngleBracketed(ref a) => match a.args.first() {
+            Some(GenericArgument::Type(t)) => Some(t),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn method(m: &syn::ImplItemMethod) -> syn::Result<Option<TokenStream2>> {
+    if let Visibility::Public(_) = m.vis {} else { return Ok(None) };
+    match m.sig.inputs.first() {
+        Some(FnArg::Receiver(r)) if r.reference.is_some() && r.mutability.is_none() => {},
+        _ => return Ok(None),
+    }
+
+    let fname = &m.sig.ident;
+    let dbus_name = make_camel(&fname.to_string());
+    let (mut names, mut types) = (vec!(), vec!());
+    for a in m.sig.inputs.iter().skip(1) {
+        let a = match a { FnArg::Typed(a) => a, _ => unreachable!() };
+        let n = match *a.pat {
+            Pat::Ident(ref i) => i.ident.clone(),
+            _ => return Err(syn::Error::new_spanned(&a.pat, "D-Bus method arguments must be plain identifiers")),
+        };
+        names.push(n);
+        types.push(&*a.ty);
+    }
+    let name_strs: Vec<_> = names.iter().map(|n| n.to_string()).collect();
+
+    let ok = result_ok_type(&m.sig.output).ok_or_else(||
+        syn::Error::new_spanned(&m.sig, "D-Bus methods must return Result<T, E>, where E converts into MethodErr"))?;
+    let outs: Vec<&Type> = match *ok {
+        Type::Tuple(ref t) => t.elems.iter().collect(),
+        ref t => vec!(t),
+    };
+    let outvars: Vec<_> = (0..outs.len()).map(|i| syn::Ident::new(&format!("out{}", i), Span::call_site())).collect();
+    let out_strs: Vec<_> = if outs.len() == 1 { vec!("out".to_string()) } else { outvars.iter().map(|v| v.to_string()).collect() };
+    let outpat = if outs.len() == 1 { quote!(#(#outvars)*) } else { quote!((#(#outvars),*)) };
+    let read = if names.is_empty() { quote!() } else { quote!(let mut i = minfo.msg.iter_init();) };
+
+    Ok(Some(quote! {
+        let fclone = f.clone();
+        let h = move |minfo: &::dbus::tree::MethodInfo<M, D>| {
+            #read
+            #(let #names: #types = i.read()?;)*
+            let dd = fclone(minfo);
+            let d = dd.as_ref();
+            let #outpat = d.#fname(#(#names),*)?;
+            let rm = minfo.msg.method_return();
+            #(let rm = rm.append1(#outvars);)*
+            Ok(vec!(rm))
+        };
+        let m = factory.method_sync(#dbus_name, Default::default(), h);
+        #(let m = m.inarg::<#types, _>(#name_strs);)*
+        #(let m = m.outarg::<#outs, _>(#out_strs);)*
+        let i = i.add_m(m);
+    }))
+}
+
+fn interface_fn(name: &LitStr, imp: &ItemImpl) -> syn::Result<TokenStream2> {
+    let fname = syn::Ident::new(&format!("{}_server", make_snake(&name.value())), name.span());
+    let mut methods = vec!();
+    for item in &imp.items {
+        if let ImplItem::Method(ref m) = *item {
+            if let Some(m) = method(m)? { methods.push(m) }
+        }
+    }
+    Ok(quote! {
+        /// Creates the D-Bus interface implemented by this type.
+        ///
+        /// `f` returns something that references the object implementing the interface, e g an `Arc`.
+        #[allow(dead_code)]
+        pub fn #fname<M, D, F, T>(factory: &::dbus::tree::Factory<M, D>, data: D::Interface, f: F) -> ::dbus::tree::Interface<M, D>
+        where
+            M: ::dbus::tree::MethodType<D>,
+            D: ::dbus::tree::DataType,
+            D::Method: Default,
+            T: AsRef<Self>,
+            F: 'static + Send + Sync + Fn(&::dbus::tree::MethodInfo<M, D>) -> T,
+        {
+            let i = factory.interface(#name, data);
+            let f = ::std::sync::Arc::new(f);
+            #(#methods)*
+            i
+        }
+    })
+}
@@ -0,0 +1,71 @@
+use dbus_macros::dbus_interface;
+use dbus::tree::{Factory, MethodErr};
+use dbus::Connection;
+use dbus::testing::TestBus;
+use dbus::stdintf::org_freedesktop_dbus::Introspectable;
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Counter {
+    count: AtomicUsize,
+}
+
+#[dbus_interface("com.example.Counter")]
+impl Counter {
+    pub fn add(&self, n: u32) -> Result<u32, MethodErr> {
+        Ok((self.count.fetch_add(n as usize, Ordering::SeqCst) + n as usize) as u32)
+    }
+
+    pub fn describe(&self, prefix: &str) -> Result<(String, u32), MethodErr> {
+        Ok((format!("{} counter", prefix), self.count.load(Ordering::SeqCst) as u32))
+    }
+
+    pub fn reset(&self) -> Result<(), MethodErr> {
+        if self.count.swap(0, Ordering::SeqCst) == 0 { Err(MethodErr::failed(&"Already zero"))? }
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    fn not_exported(&self) -> u32 { 5 }
+}
+
+#[test]
+fn counter() {
+    let counter = Arc::new(Counter { count: AtomicUsize::new(0) });
+    let f = Factory::new_sync::<()>();
+    let c2 = counter.clone();
+    let t = f.tree(()).add(f.object_path("/counter", ()).introspectable()
+        .add(Counter::com_example_counter_server(&f, (), move |_| c2.clone())));
+
+    let bus = TestBus::new().unwrap();
+    let c = bus.connect().unwrap();
+    t.set_registered(&c, true).unwrap();
+    c.add_handler(t);
+    let name = c.unique_name();
+    let address = bus.address().to_owned();
+    let (tx, rx) = mpsc::channel();
+    let client = thread::spawn(move || {
+        let c = Connection::open_address(&address).unwrap();
+        let p = c.with_path(name, "/counter", 2000);
+        let r: (u32,) = p.method_call("com.example.Counter", "Add", (5u32,)).unwrap();
+        assert_eq!(r, (5,));
+        let r: (String, u32) = p.method_call("com.example.Counter", "Describe", ("A",)).unwrap();
+        assert_eq!(r, ("A counter".into(), 5));
+        let () = p.method_call("com.example.Counter", "Reset", ()).unwrap();
+        let e = p.method_call::<_, _, _, ()>("com.example.Counter", "Reset", ()).unwrap_err();
+        assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.Failed"));
+        assert!(p.method_call::<_, _, _, (u32,)>("com.example.Counter", "Add", ("wrong type",)).is_err());
+
+        let xml = p.introspect().unwrap();
+        assert!(xml.contains(r#"<method name="Describe">"#));
+        assert!(xml.contains(r#"<arg name="prefix" type="s" direction="in"/>"#));
+        assert!(xml.contains(r#"<arg name="out0" type="s" direction="out"/>"#));
+        assert!(xml.contains(r#"<arg name="out1" type="u" direction="out"/>"#));
+        assert!(!xml.contains("NotExported"));
+        tx.send(()).unwrap();
+    });
+    while let Err(mpsc::TryRecvError::Empty) = rx.try_recv() { c.incoming(100).next(); }
+    client.join().unwrap();
+    assert_eq!(counter.count.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,70 @@
+//! Benchmarks for marshalling (appending) and unmarshalling (reading) message arguments.
+//!
+//! Run with `cargo bench`. See BENCHMARKS.md for results.
+
+#[macro_use]
+extern crate criterion;
+extern crate dbus;
+
+use criterion::{Criterion, black_box};
+use dbus::{Message, MessageItem};
+use dbus::arg::{Variant, RefArg};
+use std::collections::HashMap;
+
+fn msg() -> Message { Message::new_signal("/bench", "com.example.Bench", "Bench").unwrap() }
+
+fn append_typed(c: &mut Criterion) {
+    let bytes: Vec<u8> = (0..65536).map(|i| i as u8).collect();
+    c.bench_function("append ay (slice, 64K)", |b| b.iter(|| msg().append1(&bytes[..])));
+    c.bench_function("append ay (vec, 64K)", |b| b.iter(|| msg().append1(bytes.clone())));
+
+    let ints: Vec<i32> = (0..16384).collect();
+    c.bench_function("append ai (slice, 16K)", |b| b.iter(|| msg().append1(&ints[..])));
+
+    let strs: Vec<String> = (0..4096).map(|i| format!("string number {}", i)).collect();
+    c.bench_function("append as (4K)", |b| b.iter(|| msg().append1(&strs)));
+
+    let mut dict: HashMap<String, Variant<Box<RefArg>>> = HashMap::new();
+    for i in 0..1024 {
+        dict.insert(format!("key{}", i), Variant(if i % 2 == 0 { Box::new(i as u32) } else { Box::new(format!("value{}", i)) }));
+    }
+    c.bench_function("append a{sv} (1K)", |b| b.iter(|| msg().append_ref(&[&dict as &RefArg])));
+
+    let structs: Vec<(u32, String, f64)> = (0..4096).map(|i| (i, format!("s{}", i), i as f64)).collect();
+    c.bench_function("append a(usd) (4K)", |b| b.iter(|| msg().append1(&structs)));
+}
+
+fn append_messageitem(c: &mut Criterion) {
+    let bytes = MessageItem::new_array((0..65536).map(|i| MessageItem::Byte(i as u8)).collect()).unwrap();
+    c.bench_function("append_items ay (64K)", |b| b.iter(|| msg().append_items(&[bytes.clone()]).unwrap()));
+
+    let ints = MessageItem::new_array((0..16384).map(MessageItem::Int32).collect()).unwrap();
+    c.bench_function("append_items ai (16K)", |b| b.iter(|| msg().append_items(&[ints.clone()]).unwrap()));
+
+    let dict = MessageItem::new_array((0..1024).map(|i| MessageItem::DictEntry(
+        Box::new(MessageItem::Str(format!("key{}", i))),
+        Box::new(MessageItem::Variant(Box::new(MessageItem::Struct(vec!(MessageItem::UInt32(i), MessageItem::Str(format!("v{}", i)))))))
+    )).collect()).unwrap();
+    c.bench_function("append_items a{sv} (1K)", |b| b.iter(|| msg().append_items(&[dict.clone()]).unwrap()));
+}
+
+fn read(c: &mut Criterion) {
+    let m = msg().append1((0..65536).map(|i| i as u8).collect::<Vec<u8>>());
+    c.bench_function("read ay (slice, 64K)", |b| b.iter(|| black_box(m.read1::<&[u8]>().unwrap().len())));
+    c.bench_function("read ay (vec, 64K)", |b| b.iter(|| black_box(m.read1::<Vec<u8>>().unwrap().len())));
+
+    let strs: Vec<String> = (0..4096).map(|i| format!("string number {}", i)).collect();
+    let m = msg().append1(&strs);
+    c.bench_function("read as (Vec<String>, 4K)", |b| b.iter(|| black_box(m.read1::<Vec<String>>().unwrap().len())));
+    c.bench_function("read as (Vec<&str>, 4K)", |b| b.iter(|| black_box(m.read1::<Vec<&str>>().unwrap().len())));
+
+    let mut dict: HashMap<String, Variant<Box<RefArg>>> = HashMap::new();
+    for i in 0..1024 { dict.insert(format!("key{}", i), Variant(Box::new(i as u32))); }
+    let m = msg().append_ref(&[&dict as &RefArg]);
+    c.bench_function("read a{sv} (1K)", |b| b.iter(||
+        black_box(m.read1::<HashMap<String, Variant<Box<RefArg>>>>().unwrap().len())));
+    c.bench_function("get_items a{sv} (1K)", |b| b.iter(|| black_box(m.get_items().unwrap().len())));
+}
+
+criterion_group!(benches, append_typed, append_messageitem, read);
+criterion_main!(benches);
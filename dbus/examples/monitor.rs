@@ -0,0 +1,27 @@
+// Prints all messages on the session bus, like dbus-monitor.
+// Any command line arguments are used as match rules, e g:
+//
+// cargo run --example monitor -- "type='signal'" "interface='org.freedesktop.Notifications'"
+
+extern crate dbus;
+
+use dbus::{Connection, BusType, DBusProxy};
+use std::env;
+
+fn main() {
+    let rules: Vec<String> = env::args().skip(1).collect();
+    let c = Connection::get_private(BusType::Session).unwrap();
+
+    let r: Vec<&str> = rules.iter().map(|s| &**s).collect();
+    if let Err(e) = DBusProxy::new(&c).become_monitor(&r, 0) {
+        // Older D-Bus servers do not support monitoring; fall back to eavesdropping.
+        println!("BecomeMonitor failed ({}), falling back to eavesdropping", e);
+        let all = ["type='signal'", "type='method_call'", "type='method_return'", "type='error'"];
+        let r: Vec<String> = if rules.is_empty() { all.iter().map(|s| s.to_string()).collect() } else { rules.clone() };
+        for rule in r { c.add_match(&format!("{},eavesdrop='true'", rule)).unwrap(); }
+    }
+
+    loop {
+        for m in c.incoming(1000) { println!("{}", m.pretty_print()); }
+    }
+}
@@ -17,7 +17,7 @@ use dbus::tree::Factory;
 fn main() {
     // Let's start by starting up a connection to the session bus and register a name.
     let c = Connection::get_private(BusType::Session).unwrap();
-    c.register_name("com.example.dbustest", NameFlag::ReplaceExisting as u32).unwrap();
+    c.register_name("com.example.dbustest", NameFlag::ReplaceExisting).unwrap();
 
     // The choice of factory tells us what type of tree we want,
     // and if we want any extra data inside. We pick the simplest variant.
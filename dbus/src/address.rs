@@ -0,0 +1,194 @@
+use {Error, ffi, to_c_str, c_str_to_slice};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_void};
+use std::{fmt, ptr, str};
+
+fn bad_address(msg: &str) -> Error { Error::new_custom("org.freedesktop.DBus.Error.BadAddress", msg) }
+
+/// One entry of a D-Bus address, e g `unix:path=/run/dbus/system_bus_socket`.
+///
+/// An entry consists of a transport method (e g "unix" or "tcp") and key/value pairs.
+/// Values are stored unescaped, and escaped when the entry is formatted.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AddressEntry {
+    method: String,
+    pairs: Vec<(String, String)>,
+}
+
+impl AddressEntry {
+    /// Creates an entry with the transport method and no key/value pairs.
+    pub fn new(method: &str) -> AddressEntry { AddressEntry { method: method.into(), pairs: vec!() } }
+
+    /// The transport method, e g "unix".
+    pub fn method(&self) -> &str { &self.method }
+
+    /// The key/value pairs, in the order they appear in the address.
+    pub fn pairs(&self) -> &[(String, String)] { &self.pairs }
+
+    /// Returns the (unescaped) value of the key, if present.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| &**v)
+    }
+
+    /// Sets the value of the key, replacing the current value if there is one.
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.pairs.iter_mut().find(|&&mut (ref k, _)| k == key) {
+            Some(p) => { p.1 = value.into(); return },
+            None => {},
+        }
+        self.pairs.push((key.into(), value.into()));
+    }
+
+    /// Removes the key, returning its value if it was present.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let i = self.pairs.iter().position(|&(ref k, _)| k == key)?;
+        Some(self.pairs.remove(i).1)
+    }
+}
+
+impl fmt::Display for AddressEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}:", self.method));
+        for (i, &(ref k, ref v)) in self.pairs.iter().enumerate() {
+            try!(write!(f, "{}{}={}", if i > 0 { "," } else { "" }, k, Address::escape_value(v)));
+        }
+        Ok(())
+    }
+}
+
+/// A D-Bus address, consisting of one or more entries that are tried in order when connecting.
+///
+/// Parsing is done by libdbus, so an address that parses here is accepted when connecting
+/// (as far as the syntax is concerned). The entries can be inspected and modified, and the
+/// address is formatted back into a string with `to_string`.
+///
+/// # Example
+///
+/// ```
+/// use dbus::Address;
+///
+/// // Make a socket path valid inside a container, where the host's /run is mounted at /host/run.
+/// let mut a: Address = "unix:path=/run/user/1000/bus;tcp:host=localhost,port=1234".parse().unwrap();
+/// for e in a.entries_mut() {
+///     if let Some(p) = e.get("path").map(|p| format!("/host{}", p)) { e.set("path", &p) };
+/// }
+/// assert_eq!(a.to_string(), "unix:path=/host/run/user/1000/bus;tcp:host=localhost,port=1234");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Address {
+    entries: Vec<AddressEntry>,
+}
+
+impl Address {
+    /// Parses an address. Returns an `ErrorKind::BadAddress` error if it is malformed.
+    pub fn parse(s: &str) -> Result<Address, Error> {
+        let cs = try!(CString::new(s).map_err(|_| bad_address("Address contains a nul character")));
+        let mut e = Error::empty();
+        let mut entries: *mut *mut ffi::DBusAddressEntry = ptr::null_mut();
+        let mut len: c_int = 0;
+        if unsafe { ffi::dbus_parse_address(cs.as_ptr(), &mut entries, &mut len, e.get_mut()) } == 0 {
+            return Err(e);
+        }
+        let methods: Vec<String> = (0..len as isize).map(|i| unsafe {
+            let m = ffi::dbus_address_entry_get_method(*entries.offset(i));
+            c_str_to_slice(&m).unwrap_or("").to_string()
+        }).collect();
+        unsafe { ffi::dbus_address_entries_free(entries) };
+
+        // libdbus has no way to list the keys of an entry, so split them up here. Special characters
+        // in values are escaped, so this cannot go wrong once libdbus has accepted the address.
+        let mut r = vec!();
+        for (entry, method) in s.split(';').filter(|e| !e.is_empty()).zip(methods) {
+            let mut a = AddressEntry::new(&method);
+            let pairs = &entry[entry.find(':').unwrap() + 1..];
+            for pair in pairs.split(',').filter(|p| !p.is_empty()) {
+                let eq = pair.find('=').unwrap();
+                a.pairs.push((pair[..eq].into(), try!(Address::unescape_value(&pair[eq + 1..]))));
+            }
+            r.push(a);
+        }
+        Ok(Address { entries: r })
+    }
+
+    /// Creates an address from entries.
+    pub fn from_entries(entries: Vec<AddressEntry>) -> Address { Address { entries: entries } }
+
+    /// The entries of the address, in the order they are tried.
+    pub fn entries(&self) -> &[AddressEntry] { &self.entries }
+
+    /// The entries of the address, for modifying them.
+    pub fn entries_mut(&mut self) -> &mut Vec<AddressEntry> { &mut self.entries }
+
+    /// Escapes a value for use in an address, e g "/tmp/a b" becomes "/tmp/a%20b".
+    ///
+    /// Panics if the value contains a nul character.
+    pub fn escape_value(value: &str) -> String {
+        let v = to_c_str(value);
+        let p = unsafe { ffi::dbus_address_escape_value(v.as_ptr()) };
+        if p.is_null() { panic!("D-Bus error: dbus_address_escape_value failed") };
+        let r = c_str_to_slice(&(p as *const c_char)).unwrap().to_string();
+        unsafe { ffi::dbus_free(p as *mut c_void) };
+        r
+    }
+
+    /// Unescapes a value from an address. Returns an `ErrorKind::BadAddress` error if the value
+    /// is not properly escaped.
+    pub fn unescape_value(value: &str) -> Result<String, Error> {
+        let v = try!(CString::new(value).map_err(|_| bad_address("Value contains a nul character")));
+        let mut e = Error::empty();
+        let p = unsafe { ffi::dbus_address_unescape_value(v.as_ptr(), e.get_mut()) };
+        if p.is_null() { return Err(e) };
+        let b = unsafe { ::std::ffi::CStr::from_ptr(p) }.to_bytes().to_vec();
+        unsafe { ffi::dbus_free(p as *mut c_void) };
+        String::from_utf8(b).map_err(|_| bad_address("Value is not valid UTF-8"))
+    }
+}
+
+impl str::FromStr for Address {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Address, Error> { Address::parse(s) }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, e) in self.entries.iter().enumerate() {
+            try!(write!(f, "{}{}", if i > 0 { ";" } else { "" }, e));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn parse_address() {
+    use ErrorKind;
+
+    let a = Address::parse("unix:path=/tmp/dbus%20test,guid=1234;tcp:host=localhost,port=4711;autolaunch:").unwrap();
+    assert_eq!(a.entries().len(), 3);
+    let e = &a.entries()[0];
+    assert_eq!(e.method(), "unix");
+    assert_eq!(e.get("path"), Some("/tmp/dbus test"));
+    assert_eq!(e.get("guid"), Some("1234"));
+    assert_eq!(e.get("abstract"), None);
+    assert_eq!(a.entries()[1].pairs(), &[("host".to_string(), "localhost".to_string()), ("port".into(), "4711".into())]);
+    assert_eq!(a.entries()[2].method(), "autolaunch");
+    assert!(a.entries()[2].pairs().is_empty());
+    assert_eq!(a.to_string(), "unix:path=/tmp/dbus%20test,guid=1234;tcp:host=localhost,port=4711;autolaunch:");
+    assert_eq!(a.to_string().parse::<Address>().unwrap(), a);
+
+    for bad in &["unix", "unix:path", "unix:path=%zz", "unix:path=a\0b"] {
+        assert_eq!(Address::parse(bad).unwrap_err().kind(), ErrorKind::BadAddress, "{:?}", bad);
+    }
+
+    let mut e = AddressEntry::new("unix");
+    e.set("path", "/run/a;b,c=d");
+    e.set("path", "/run/a b");
+    assert_eq!(e.to_string(), "unix:path=/run/a%20b");
+    assert_eq!(e.remove("path"), Some("/run/a b".into()));
+    assert_eq!(e.remove("path"), None);
+    let a = Address::from_entries(vec!(AddressEntry::new("tcp"), e));
+    assert_eq!(a.to_string(), "tcp:;unix:");
+
+    assert_eq!(Address::escape_value("a;b,c=d"), "a%3bb%2cc%3dd");
+    assert_eq!(Address::unescape_value("a%3Bb").unwrap(), "a;b");
+    assert!(Address::unescape_value("a%3").is_err());
+}
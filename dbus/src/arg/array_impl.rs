@@ -29,17 +29,21 @@ impl<'a, T: Arg> Arg for &'a [T] {
     fn signature() -> Signature<'static> { Signature::from(format!("a{}", T::signature())) }
 }
 
+// Can we do append_fixed_array?
+#[inline]
+fn can_fixed_array<T: Arg>(len: usize) -> bool {
+    let a = (T::ARG_TYPE, mem::size_of::<T>());
+    (len > 1) && (len == len as i32 as usize) && FIXED_ARRAY_ALIGNMENTS.iter().any(|&v| v == a)
+}
+
 fn array_append<T: Arg, F: FnMut(&T, &mut IterAppend)>(z: &[T], i: &mut IterAppend, mut f: F) {
     let zptr = z.as_ptr();
     let zlen = z.len() as i32;
-
-    // Can we do append_fixed_array?
-    let a = (T::ARG_TYPE, mem::size_of::<T>());
-    let can_fixed_array = (zlen > 1) && (z.len() == zlen as usize) && FIXED_ARRAY_ALIGNMENTS.iter().any(|&v| v == a);
+    let can_fixed_array = can_fixed_array::<T>(z.len());
 
     i.append_container(ArgType::Array, Some(T::signature().as_cstr()), |s|
         if can_fixed_array { unsafe { check("dbus_message_iter_append_fixed_array",
-            ffi::dbus_message_iter_append_fixed_array(&mut s.0, a.0 as c_int, &zptr as *const _ as *const c_void, zlen)) }}
+            ffi::dbus_message_iter_append_fixed_array(&mut s.0, T::ARG_TYPE as c_int, &zptr as *const _ as *const c_void, zlen)) }}
         else { for arg in z { f(arg, s); }}
     );
 }
@@ -222,7 +226,8 @@ impl<T: Arg> Arg for Vec<T> {
 
 impl<T: Arg + Append> Append for Vec<T> {
     fn append(self, i: &mut IterAppend) {
-        Array::new(self).append(i);
+        if can_fixed_array::<T>(self.len()) { array_append(&self, i, |_, _| unreachable!()) }
+        else { Array::new(self).append(i) }
     }
 }
 
@@ -4,6 +4,7 @@ use super::check;
 use {Signature, Path, OwnedFd};
 use std::{ptr, any, mem};
 use std::ffi::CStr;
+use std::borrow::Cow;
 use std::os::raw::{c_void, c_char, c_int};
 
 
@@ -178,7 +179,6 @@ impl<'a> Arg for &'a str {
 
 impl<'a> Append for &'a str {
     fn append(self, i: &mut IterAppend) {
-        use std::borrow::Cow;
         let b: &[u8] = self.as_bytes();
         let v: Cow<[u8]> = if b.len() > 0 && b[b.len()-1] == 0 { Cow::Borrowed(b) }
         else {
@@ -214,6 +214,29 @@ impl<'a> Get<'a> for String {
 
 refarg_impl!(String, _i, None, Some(&_i), None, None);
 
+/// Represents a D-Bus string.
+///
+/// When reading, the string is borrowed from the message, so no allocation is needed
+/// (e g when reading a `Vec<Cow<str>>`), while still allowing owned strings to be appended.
+impl<'a> Arg for Cow<'a, str> {
+    const ARG_TYPE: ArgType = ArgType::String;
+    fn signature() -> Signature<'static> { unsafe { Signature::from_slice_unchecked(b"s\0") } }
+}
+impl<'a> Append for Cow<'a, str> {
+    fn append(self, i: &mut IterAppend) {
+        match self {
+            Cow::Borrowed(s) => s.append(i),
+            Cow::Owned(s) => s.append(i),
+        }
+    }
+}
+impl<'a> DictKey for Cow<'a, str> {}
+impl<'a> Get<'a> for Cow<'a, str> {
+    fn get(i: &mut Iter<'a>) -> Option<Cow<'a, str>> { <&str>::get(i).map(Cow::Borrowed) }
+}
+
+refarg_impl!(Cow<'static, str>, _i, None, Some(&_i), None, None);
+
 /// Represents a D-Bus string.
 impl<'a> Arg for &'a CStr {
     const ARG_TYPE: ArgType = ArgType::String;
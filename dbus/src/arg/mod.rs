@@ -63,12 +63,12 @@ mod basic_impl;
 mod variantstruct_impl;
 mod array_impl;
 
-pub use self::msgarg::{Arg, FixedArray, Get, DictKey, Append, RefArg, AppendAll, ReadAll, cast, cast_mut};
+pub use self::msgarg::{Arg, FixedArray, Get, DictKey, Append, RefArg, AppendAll, ReadAll, ArgBuilder, cast, cast_mut};
 pub use self::array_impl::{Array, Dict};
 pub use self::variantstruct_impl::Variant;
 
 use std::{fmt, mem, ptr, error};
-use {ffi, Message, Signature, Path, OwnedFd};
+use {ffi, Message, Signature, Path, OwnedFd, version};
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_void, c_int};
 
@@ -230,6 +230,34 @@ impl<'a> Iter<'a> {
         ArgType::from_i32(s as i32).unwrap()
     }
 
+    /// If the current argument is an array, returns the type of its elements.
+    ///
+    /// This works for empty arrays too, so it can be used to check the element type before
+    /// reading the array. Dicts are arrays of `ArgType::DictEntry`.
+    pub fn element_type(&mut self) -> Option<ArgType> {
+        if self.arg_type() != ArgType::Array { return None };
+        let t = unsafe { ffi::dbus_message_iter_get_element_type(&mut self.0) };
+        ArgType::from_i32(t as i32).ok()
+    }
+
+    /// If the current argument is an array, returns the number of elements, e g to preallocate
+    /// room for them.
+    ///
+    /// This is cheap for arrays of fixed size types (integers, doubles and booleans). For other
+    /// element types, the array is walked to count the elements.
+    pub fn element_count(&mut self) -> Option<usize> {
+        if self.arg_type() != ArgType::Array { return None };
+        // dbus_message_iter_get_element_count is new in libdbus 1.9.16.
+        if let Some(f) = version::optional_fn(b"dbus_message_iter_get_element_count\0") {
+            let f: extern "C" fn(*mut ffi::DBusMessageIter) -> c_int = unsafe { mem::transmute(f) };
+            return Some(f(&mut self.0) as usize);
+        }
+        let mut si = self.recurse(ArgType::Array).unwrap();
+        let mut n = 0;
+        while si.arg_type() != ArgType::Invalid { n += 1; si.next(); }
+        Some(n)
+    }
+
     /// Returns false if there are no more items.
     pub fn next(&mut self) -> bool {
         self.2 += 1;
@@ -238,6 +238,14 @@ impl ArgBuilder for () {
     fn append(self, _: &mut Message) {}
 }
 
+impl AppendAll for () {
+    fn append(self, _: &mut IterAppend) {}
+}
+
+impl ReadAll for () {
+    fn read(_: &mut Iter) -> Result<Self, TypeMismatchError> { Ok(()) }
+}
+
 macro_rules! argbuilder_impl {
     ($($n: ident $t: ident $s: ty,)+) => {
 
@@ -296,14 +304,15 @@ argbuilder_impl!(a A str, b B str, c C str, d D str, e E str, f F str, g G str,
 mod test {
     extern crate tempdir;
 
-    use {Connection, ConnectionItem, Message, BusType, Path, Signature};
+    use {ConnectionItem, Message, Path, Signature};
     use arg::{Array, Variant, Dict, Iter, ArgType, TypeMismatchError, RefArg, cast};
 
     use std::collections::HashMap;
 
     #[test]
     fn refarg() {
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
         c.register_object_path("/mooh").unwrap();
         let m = Message::new_method_call(&c.unique_name(), "/mooh", "com.example.hello", "Hello").unwrap();
 
@@ -359,9 +368,25 @@ mod test {
         }
     }
 
+    #[test]
+    fn cow_strings() {
+        use std::borrow::Cow;
+        let owned: Vec<Cow<str>> = vec!(Cow::Borrowed("Hello"), Cow::Owned(String::from("world")));
+        let mut d = HashMap::new();
+        d.insert(Cow::Borrowed("key"), 5u32);
+        let m = Message::new_signal("/cow", "com.example.cow", "Strings").unwrap().append2(owned, d);
+        let (v, d): (Vec<Cow<str>>, HashMap<Cow<str>, u32>) = m.read2().unwrap();
+        assert_eq!(v, vec!("Hello", "world"));
+        assert!(v.iter().all(|s| if let Cow::Borrowed(_) = *s { true } else { false }));
+        assert_eq!(d["key"], 5);
+        let r: &RefArg = &Cow::Borrowed("static") as &Cow<'static, str>;
+        assert_eq!(r.as_str(), Some("static"));
+    }
+
     #[test]
     fn message_types() {
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
         c.register_object_path("/hello").unwrap();
         let m = Message::new_method_call(&c.unique_name(), "/hello", "com.example.hello", "Hello").unwrap();
         let m = m.append1(2000u16);
@@ -423,4 +448,28 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn element_count_and_type() {
+        let mut d = HashMap::new();
+        d.insert("a", 1u8);
+        d.insert("b", 2u8);
+        let m = Message::new_signal("/", "com.example.dbusrs.Elements", "Elements").unwrap()
+            .append3(vec!(1i32, 2, 3), vec!("x", "yz"), d)
+            .append2(Vec::<u64>::new(), 5u8);
+        let mut i = m.iter_init();
+        assert_eq!((i.element_type(), i.element_count()), (Some(ArgType::Int32), Some(3)));
+        assert!(i.next());
+        assert_eq!((i.element_type(), i.element_count()), (Some(ArgType::String), Some(2)));
+        assert!(i.next());
+        assert_eq!((i.element_type(), i.element_count()), (Some(ArgType::DictEntry), Some(2)));
+        assert!(i.next());
+        assert_eq!((i.element_type(), i.element_count()), (Some(ArgType::UInt64), Some(0)));
+        assert!(i.next());
+        assert_eq!((i.element_type(), i.element_count()), (None, None));
+        // Reading is not affected.
+        let mut i = m.iter_init();
+        assert_eq!(i.element_count(), Some(3));
+        assert_eq!(i.read::<Vec<i32>>().unwrap(), vec!(1, 2, 3));
+    }
 }
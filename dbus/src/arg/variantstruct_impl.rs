@@ -216,22 +216,27 @@ impl RefArg for Vec<Box<RefArg>> {
     }
 }
 
+/// Panics if the item cannot be appended; use `Message::append_items` to get an error instead.
 impl Append for message::MessageItem {
     fn append(self, i: &mut IterAppend) {
-        message::append_messageitem(&mut i.0, &self)
+        if let Err(e) = message::append_messageitem(&mut i.0, &self) { panic!("{}", e) }
     }
 }
 
 impl<'a> Get<'a> for message::MessageItem {
+    /// Returns None if the argument cannot be read as a MessageItem, see `Message::get_items`.
     fn get(i: &mut Iter<'a>) -> Option<Self> {
-        message::get_messageitem(&mut i.0)
+        message::get_messageitem(&mut i.0).ok().and_then(|x| x)
     }
 }
 
+/// Appending panics if the item cannot be appended, see the `Append` impl.
 impl RefArg for message::MessageItem {
     fn arg_type(&self) -> ArgType { ArgType::from_i32(self.array_type()).unwrap() }
     fn signature(&self) -> Signature<'static> { message::MessageItem::signature(&self) }
-    fn append(&self, i: &mut IterAppend) { message::append_messageitem(&mut i.0, self) }
+    fn append(&self, i: &mut IterAppend) {
+        if let Err(e) = message::append_messageitem(&mut i.0, self) { panic!("{}", e) }
+    }
     #[inline]
     fn as_any(&self) -> &any::Any where Self: 'static { self }
     #[inline]
@@ -0,0 +1,429 @@
+use {Connection, Error, ErrorKind, BusName, Timeout};
+use arg::{AppendAll, ReadAll, RefArg, Variant};
+use std::collections::HashMap;
+
+/// Reply to `DBusProxy::start_service_by_name` and `Connection::activate_service`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum StartServiceReply {
+    /// The service was successfully started.
+    Success = 1,
+    /// A connection already owns the given name.
+    AlreadyRunning = 2,
+}
+
+/// Whether a name is available on the bus, see `DBusProxy::name_status`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum NameStatus {
+    /// The name has an owner, with this unique name.
+    Owned(BusName<'static>),
+    /// The name has no owner, but can be started by service activation.
+    Activatable,
+    /// The name has no owner, and cannot be activated.
+    Absent,
+}
+
+type StatsDict = HashMap<String, Variant<Box<RefArg>>>;
+
+fn stat(d: &StatsDict, key: &str) -> Option<u32> { d.get(key).and_then(|v| v.0.as_u64()).map(|v| v as u32) }
+
+/// Statistics about the D-Bus server, see `DBusProxy::get_stats`.
+///
+/// Fields are None if the D-Bus server did not report them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BusStats {
+    /// Increases every time the statistics are taken.
+    pub serial: Option<u32>,
+    /// Number of connections that have completed authentication.
+    pub active_connections: Option<u32>,
+    /// Number of connections that have not yet completed authentication.
+    pub incomplete_connections: Option<u32>,
+    /// Number of match rules of all connections.
+    pub match_rules: Option<u32>,
+    /// Highest number of match rules of all connections.
+    pub peak_match_rules: Option<u32>,
+    /// Highest number of match rules of a single connection.
+    pub peak_match_rules_per_connection: Option<u32>,
+    /// Number of names owned, both unique and well-known.
+    pub bus_names: Option<u32>,
+    /// Highest number of names owned.
+    pub peak_bus_names: Option<u32>,
+    /// Highest number of names owned by a single connection.
+    pub peak_bus_names_per_connection: Option<u32>,
+}
+
+impl BusStats {
+    fn from_dict(d: &StatsDict) -> Self {
+        BusStats {
+            serial: stat(d, "Serial"),
+            active_connections: stat(d, "ActiveConnections"),
+            incomplete_connections: stat(d, "IncompleteConnections"),
+            match_rules: stat(d, "MatchRules"),
+            peak_match_rules: stat(d, "PeakMatchRules"),
+            peak_match_rules_per_connection: stat(d, "PeakMatchRulesPerConnection"),
+            bus_names: stat(d, "BusNames"),
+            peak_bus_names: stat(d, "PeakBusNames"),
+            peak_bus_names_per_connection: stat(d, "PeakBusNamesPerConnection"),
+        }
+    }
+}
+
+/// Statistics about a single connection to the D-Bus server, see `DBusProxy::get_connection_stats`.
+///
+/// Incoming means messages from the connection to the D-Bus server, outgoing means messages
+/// queued for delivery to the connection. Fields are None if the D-Bus server did not report them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// Increases every time the statistics are taken.
+    pub serial: Option<u32>,
+    /// The unique name of the connection.
+    pub unique_name: Option<String>,
+    /// Number of messages in the incoming queue.
+    pub incoming_messages: Option<u32>,
+    /// Number of bytes in the incoming queue.
+    pub incoming_bytes: Option<u32>,
+    /// Number of file descriptors in the incoming queue.
+    pub incoming_fds: Option<u32>,
+    /// Highest number of bytes in the incoming queue.
+    pub peak_incoming_bytes: Option<u32>,
+    /// Highest number of file descriptors in the incoming queue.
+    pub peak_incoming_fds: Option<u32>,
+    /// Number of messages in the outgoing queue.
+    pub outgoing_messages: Option<u32>,
+    /// Number of bytes in the outgoing queue.
+    pub outgoing_bytes: Option<u32>,
+    /// Number of file descriptors in the outgoing queue.
+    pub outgoing_fds: Option<u32>,
+    /// Highest number of bytes in the outgoing queue.
+    pub peak_outgoing_bytes: Option<u32>,
+    /// Highest number of file descriptors in the outgoing queue.
+    pub peak_outgoing_fds: Option<u32>,
+    /// Number of match rules of the connection.
+    pub match_rules: Option<u32>,
+    /// Highest number of match rules of the connection.
+    pub peak_match_rules: Option<u32>,
+    /// Number of names owned by the connection.
+    pub bus_names: Option<u32>,
+    /// Highest number of names owned by the connection.
+    pub peak_bus_names: Option<u32>,
+}
+
+impl ConnectionStats {
+    fn from_dict(d: &StatsDict) -> Self {
+        ConnectionStats {
+            serial: stat(d, "Serial"),
+            unique_name: d.get("UniqueName").and_then(|v| v.0.as_str()).map(|v| v.into()),
+            incoming_messages: stat(d, "IncomingMessages"),
+            incoming_bytes: stat(d, "IncomingBytes"),
+            incoming_fds: stat(d, "IncomingFDs"),
+            peak_incoming_bytes: stat(d, "PeakIncomingBytes"),
+            peak_incoming_fds: stat(d, "PeakIncomingFDs"),
+            outgoing_messages: stat(d, "OutgoingMessages"),
+            outgoing_bytes: stat(d, "OutgoingBytes"),
+            outgoing_fds: stat(d, "OutgoingFDs"),
+            peak_outgoing_bytes: stat(d, "PeakOutgoingBytes"),
+            peak_outgoing_fds: stat(d, "PeakOutgoingFDs"),
+            match_rules: stat(d, "MatchRules"),
+            peak_match_rules: stat(d, "PeakMatchRules"),
+            bus_names: stat(d, "BusNames"),
+            peak_bus_names: stat(d, "PeakBusNames"),
+        }
+    }
+}
+
+fn u32_list(d: &StatsDict, key: &str) -> Option<Vec<u32>> {
+    d.get(key).and_then(|v| v.0.as_iter()).map(|i| i.filter_map(|x| x.as_u64()).map(|x| x as u32).collect())
+}
+
+/// Credentials of a connection to the D-Bus server, see `DBusProxy::get_connection_credentials`.
+///
+/// Fields are None if the D-Bus server did not report them, e g because they do not apply
+/// to the platform, or the server is too old to know about them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Credentials {
+    /// The Unix user id of the process owning the connection.
+    pub unix_user_id: Option<u32>,
+    /// The Unix group ids of the process owning the connection.
+    pub unix_group_ids: Option<Vec<u32>>,
+    /// The process id of the process owning the connection.
+    pub process_id: Option<u32>,
+    /// The Windows security identifier of the process owning the connection.
+    pub windows_sid: Option<String>,
+    /// The security label of the process owning the connection, as given by the Linux
+    /// security module in use (e g SELinux or AppArmor). The exact format depends on the module.
+    pub linux_security_label: Option<Vec<u8>>,
+}
+
+impl Credentials {
+    /// The security label as a string, without the trailing nul byte the D-Bus server includes.
+    ///
+    /// Returns None if there is no label, or it is not valid UTF-8.
+    pub fn security_label(&self) -> Option<&str> {
+        let l = self.linux_security_label.as_ref()?;
+        let l = if l.last() == Some(&0) { &l[..l.len()-1] } else { &l[..] };
+        ::std::str::from_utf8(l).ok()
+    }
+
+    fn from_dict(d: &StatsDict) -> Self {
+        Credentials {
+            unix_user_id: stat(d, "UnixUserID"),
+            unix_group_ids: u32_list(d, "UnixGroupIDs"),
+            process_id: stat(d, "ProcessID"),
+            windows_sid: d.get("WindowsSID").and_then(|v| v.0.as_str()).map(|v| v.into()),
+            linux_security_label: d.get("LinuxSecurityLabel").and_then(|v| v.0.as_iter())
+                .map(|i| i.filter_map(|x| x.as_u64()).map(|x| x as u8).collect()),
+        }
+    }
+}
+
+/// Typed methods of the `org.freedesktop.DBus` interface, i e the D-Bus server itself.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Connection, BusType, DBusProxy};
+///
+/// let c = Connection::get_private(BusType::Session).unwrap();
+/// let bus = DBusProxy::new(&c);
+/// assert!(bus.list_names().unwrap().iter().any(|n| n == "org.freedesktop.DBus"));
+/// assert_eq!(&*bus.get_name_owner("org.freedesktop.DBus").unwrap(), "org.freedesktop.DBus");
+/// ```
+#[derive(Debug, Clone)]
+pub struct DBusProxy<'a> {
+    conn: &'a Connection,
+    timeout: Timeout,
+}
+
+impl<'a> DBusProxy<'a> {
+    /// Creates a proxy for the server the connection is connected to, using the default timeout.
+    pub fn new(c: &'a Connection) -> Self { DBusProxy { conn: c, timeout: Timeout::Default } }
+
+    /// Sets the timeout for method calls made through this proxy.
+    pub fn with_timeout<T: Into<Timeout>>(mut self, t: T) -> Self { self.timeout = t.into(); self }
+
+    fn call<A: AppendAll, R: ReadAll>(&self, member: &str, args: A) -> Result<R, Error> {
+        self.call_iface("org.freedesktop.DBus", member, args)
+    }
+
+    fn call_iface<A: AppendAll, R: ReadAll>(&self, iface: &str, member: &str, args: A) -> Result<R, Error> {
+        let r = try!(self.conn.call_method("org.freedesktop.DBus", "/org/freedesktop/DBus", iface, member, args, self.timeout));
+        Ok(try!(R::read(&mut r.iter_init())))
+    }
+
+    /// Returns all names currently owned on the bus, both unique and well-known.
+    pub fn list_names(&self) -> Result<Vec<String>, Error> {
+        self.call("ListNames", ()).map(|(r,)| r)
+    }
+
+    /// Returns all names that can be started by service activation.
+    pub fn list_activatable_names(&self) -> Result<Vec<String>, Error> {
+        self.call("ListActivatableNames", ()).map(|(r,)| r)
+    }
+
+    /// Checks whether the name currently has an owner.
+    pub fn name_has_owner(&self, name: &str) -> Result<bool, Error> {
+        self.call("NameHasOwner", (name,)).map(|(r,)| r)
+    }
+
+    /// Returns the unique name of the owner of the name.
+    ///
+    /// Returns an `org.freedesktop.DBus.Error.NameHasNoOwner` error if the name has no owner.
+    pub fn get_name_owner(&self, name: &str) -> Result<BusName<'static>, Error> {
+        let (r,): (String,) = try!(self.call("GetNameOwner", (name,)));
+        BusName::new(r).map_err(|e| Error::new_custom("org.freedesktop.DBus.Error.InvalidArgs", &e))
+    }
+
+    /// Returns whether the name currently has an owner, can be activated, or neither.
+    pub fn name_status(&self, name: &str) -> Result<NameStatus, Error> {
+        match self.get_name_owner(name) {
+            Ok(owner) => return Ok(NameStatus::Owned(owner)),
+            Err(ref e) if e.kind() == ErrorKind::NameHasNoOwner => {},
+            Err(e) => return Err(e),
+        }
+        let a = try!(self.list_activatable_names());
+        Ok(if a.iter().any(|n| n == name) { NameStatus::Activatable } else { NameStatus::Absent })
+    }
+
+    /// Returns the Unix user id of the process owning the name.
+    pub fn get_connection_unix_user(&self, name: &str) -> Result<u32, Error> {
+        self.call("GetConnectionUnixUser", (name,)).map(|(r,)| r)
+    }
+
+    /// Returns the Unix process id of the process owning the name.
+    pub fn get_connection_unix_process_id(&self, name: &str) -> Result<u32, Error> {
+        self.call("GetConnectionUnixProcessID", (name,)).map(|(r,)| r)
+    }
+
+    /// Returns the credentials of the process owning the name, e g to make authorization decisions.
+    ///
+    /// Unlike `get_connection_unix_user` and friends, this returns everything the D-Bus server
+    /// knows in a single call.
+    pub fn get_connection_credentials(&self, name: &str) -> Result<Credentials, Error> {
+        let (d,): (StatsDict,) = try!(self.call("GetConnectionCredentials", (name,)));
+        Ok(Credentials::from_dict(&d))
+    }
+
+    /// Returns the SELinux security context of the process owning the name.
+    ///
+    /// This is the older way of getting the security label; prefer `get_connection_security_label`,
+    /// which also works for other Linux security modules, e g AppArmor. Returns an
+    /// `org.freedesktop.DBus.Error.SELinuxSecurityContextUnknown` error if SELinux is not in use.
+    pub fn get_connection_selinux_security_context(&self, name: &str) -> Result<Vec<u8>, Error> {
+        self.call("GetConnectionSELinuxSecurityContext", (name,)).map(|(r,)| r)
+    }
+
+    /// Returns the security label (e g the SELinux context or AppArmor profile) of the process
+    /// owning the name, or None if the D-Bus server does not know it.
+    ///
+    /// This uses `get_connection_credentials`, and falls back to `get_connection_selinux_security_context`
+    /// for D-Bus servers that do not report the label there. The exact format depends on the
+    /// security module; see `Credentials::security_label` for getting it as a string.
+    pub fn get_connection_security_label(&self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        match self.get_connection_credentials(name) {
+            Ok(Credentials { linux_security_label: Some(l), .. }) => return Ok(Some(l)),
+            Ok(_) => {},
+            Err(ref e) if e.kind() == ErrorKind::UnknownMethod => {},
+            Err(e) => return Err(e),
+        }
+        match self.get_connection_selinux_security_context(name) {
+            Ok(l) => Ok(Some(l)),
+            Err(ref e) if e.kind() == ErrorKind::SELinuxSecurityContextUnknown => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Adds a match rule, see `Connection::add_match`.
+    pub fn add_match(&self, rule: &str) -> Result<(), Error> {
+        self.call("AddMatch", (rule,))
+    }
+
+    /// Removes a match rule, see `Connection::remove_match`.
+    pub fn remove_match(&self, rule: &str) -> Result<(), Error> {
+        self.call("RemoveMatch", (rule,))
+    }
+
+    /// Starts the service owning the name, using service activation.
+    ///
+    /// Flags are currently unused by the D-Bus server, pass 0.
+    pub fn start_service_by_name(&self, name: &str, flags: u32) -> Result<StartServiceReply, Error> {
+        let (r,): (u32,) = try!(self.call("StartServiceByName", (name, flags)));
+        match r {
+            1 => Ok(StartServiceReply::Success),
+            2 => Ok(StartServiceReply::AlreadyRunning),
+            _ => Err(Error::new_custom("org.freedesktop.DBus.Error.Failed", &format!("Unknown StartServiceByName reply {}", r))),
+        }
+    }
+
+    /// Returns the unique id of the D-Bus server.
+    pub fn get_id(&self) -> Result<String, Error> {
+        self.call("GetId", ()).map(|(r,)| r)
+    }
+
+    /// Turns the connection into a monitor, receiving copies of all messages matching any of the rules.
+    ///
+    /// An empty list of rules means all messages. Flags are currently unused by the D-Bus server, pass 0.
+    /// After this call, the connection can no longer send messages, only receive them.
+    /// Older D-Bus servers do not support this method; use match rules with `eavesdrop` set instead.
+    /// See `MonitorConnection` for a wrapper that only allows receiving.
+    pub fn become_monitor(&self, rules: &[&str], flags: u32) -> Result<(), Error> {
+        self.call_iface("org.freedesktop.DBus.Monitoring", "BecomeMonitor", (rules, flags))
+    }
+
+    /// Returns statistics about the D-Bus server.
+    ///
+    /// This uses the `org.freedesktop.DBus.Debug.Stats` interface, which is only available if the D-Bus server
+    /// was built with statistics enabled, and might be restricted by its security policy.
+    pub fn get_stats(&self) -> Result<BusStats, Error> {
+        let (d,): (StatsDict,) = try!(self.call_iface("org.freedesktop.DBus.Debug.Stats", "GetStats", ()));
+        Ok(BusStats::from_dict(&d))
+    }
+
+    /// Returns statistics about the connection owning the name, e g its queue sizes and match rule count.
+    ///
+    /// See `get_stats` for when this is available.
+    pub fn get_connection_stats(&self, name: &str) -> Result<ConnectionStats, Error> {
+        let (d,): (StatsDict,) = try!(self.call_iface("org.freedesktop.DBus.Debug.Stats", "GetConnectionStats", (name,)));
+        Ok(ConnectionStats::from_dict(&d))
+    }
+
+    /// Returns the match rules of all connections, by unique name.
+    ///
+    /// See `get_stats` for when this is available.
+    pub fn get_all_match_rules(&self) -> Result<HashMap<String, Vec<String>>, Error> {
+        self.call_iface("org.freedesktop.DBus.Debug.Stats", "GetAllMatchRules", ()).map(|(r,)| r)
+    }
+}
+
+#[test]
+fn dbus_proxy() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let bus = DBusProxy::new(&c).with_timeout(2000);
+    let names = bus.list_names().unwrap();
+    assert!(names.iter().any(|n| *n == c.unique_name()));
+    assert!(bus.name_has_owner(&c.unique_name()).unwrap());
+    assert!(!bus.name_has_owner("com.example.dbusproxy.nonexistent").unwrap());
+    assert_eq!(&*bus.get_name_owner(&c.unique_name()).unwrap(), &*c.unique_name());
+    assert_eq!(bus.name_status(&c.unique_name()).unwrap(), NameStatus::Owned(c.unique_name().into()));
+    assert_eq!(bus.name_status("com.example.dbusproxy.nonexistent").unwrap(), NameStatus::Absent);
+    assert!(bus.list_activatable_names().unwrap().iter().any(|n| n == "org.freedesktop.DBus"));
+    let cred = bus.get_connection_credentials(&c.unique_name()).unwrap();
+    assert_eq!(cred.unix_user_id, Some(unsafe { ::libc::getuid() }));
+    assert_eq!(cred.process_id, Some(::std::process::id()));
+    if let Some(ref g) = cred.unix_group_ids { assert!(g.contains(&unsafe { ::libc::getgid() })) };
+    let label = bus.get_connection_security_label(&c.unique_name()).unwrap();
+    if cred.linux_security_label.is_some() { assert_eq!(label, cred.linux_security_label) };
+    let e = bus.get_name_owner("com.example.dbusproxy.nonexistent").unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.NameHasNoOwner"));
+    assert_eq!(bus.get_connection_unix_process_id(&c.unique_name()).unwrap(), ::std::process::id());
+    assert_eq!(bus.get_connection_unix_user(&c.unique_name()).unwrap(), unsafe { ::libc::getuid() });
+    bus.list_activatable_names().unwrap();
+    let e = bus.start_service_by_name("com.example.dbusproxy.nonexistent", 0).unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.ServiceUnknown"));
+    bus.add_match("type='signal',interface='com.example.dbusproxy'").unwrap();
+    bus.remove_match("type='signal',interface='com.example.dbusproxy'").unwrap();
+    assert_eq!(bus.get_id().unwrap().len(), 32);
+}
+
+#[test]
+fn dbus_proxy_monitor() {
+    use Message;
+    let bus = ::testing::shared_bus();
+    let m = bus.connect().unwrap();
+    DBusProxy::new(&m).become_monitor(&["type='signal',interface='com.example.dbusproxy.monitor'"], 0).unwrap();
+    let c = bus.connect().unwrap();
+    c.send(Message::new_signal("/", "com.example.dbusproxy.monitor", "Hello").unwrap()).unwrap();
+    let msg = m.incoming(2000).find(|msg| msg.member().map_or(false, |x| &*x == "Hello")).unwrap();
+    assert_eq!(msg.sender(), Some(c.unique_name().into()));
+}
+
+#[test]
+fn dbus_proxy_stats() {
+    use ErrorKind;
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let bus = DBusProxy::new(&c);
+    let rule = "type='signal',interface='com.example.dbusproxy.stats'";
+    c.add_match(rule).unwrap();
+    let s = match bus.get_stats() {
+        Ok(s) => s,
+        // The D-Bus server was built without statistics
+        Err(ref e) if e.kind() == ErrorKind::UnknownInterface || e.kind() == ErrorKind::UnknownMethod => return,
+        Err(e) => panic!("{}", e),
+    };
+    assert!(s.active_connections.unwrap() >= 1);
+    assert!(s.match_rules.unwrap() >= 1);
+    let cs = bus.get_connection_stats(&c.unique_name()).unwrap();
+    assert_eq!(cs.unique_name, Some(c.unique_name()));
+    assert_eq!(cs.match_rules, Some(1));
+    assert_eq!(bus.get_all_match_rules().unwrap()[&c.unique_name()], vec!(rule.to_string()));
+}
+
+#[test]
+fn credentials_security_label() {
+    let mut cred = Credentials::default();
+    assert_eq!(cred.security_label(), None);
+    cred.linux_security_label = Some(b"unconfined_u:unconfined_r:unconfined_t:s0\0".to_vec());
+    assert_eq!(cred.security_label(), Some("unconfined_u:unconfined_r:unconfined_t:s0"));
+    cred.linux_security_label = Some(b"unconfined".to_vec());
+    assert_eq!(cred.security_label(), Some("unconfined"));
+}
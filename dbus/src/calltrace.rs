@@ -0,0 +1,117 @@
+//! Spans around method calls, enabled with the `tracing` feature.
+
+use {Message, MessageType, Error, ErrorKind};
+use tracing::Span;
+use tracing::field::Empty;
+
+pub fn call_span(msg: &Message) -> Span {
+    if msg.msg_type() != MessageType::MethodCall { return Span::none() }
+    ::tracing::debug_span!("dbus.method_call",
+        destination = msg.destination().as_ref().map(|x| &**x).unwrap_or(""),
+        interface = msg.interface().as_ref().map(|x| &**x).unwrap_or(""),
+        member = msg.member().as_ref().map(|x| &**x).unwrap_or(""),
+        serial = Empty, outcome = Empty, error = Empty)
+}
+
+pub fn record_serial(span: &Span, serial: u32) { span.record("serial", &serial); }
+
+fn record_error(span: &Span, name: Option<&str>) {
+    let outcome = if name.map(ErrorKind::from_name) == Some(ErrorKind::NoReply) { "timeout" } else { "error" };
+    span.record("outcome", &outcome);
+    span.record("error", &name.unwrap_or(""));
+}
+
+/// Records the outcome of a call, given the reply message.
+pub fn record_reply(span: &Span, reply: &Message) {
+    if reply.msg_type() == MessageType::Error {
+        record_error(span, reply.error_name().as_ref().map(|x| &**x))
+    } else { span.record("outcome", &"reply"); }
+}
+
+/// Records the outcome of a call, given the result of a blocking call.
+pub fn record_result(span: &Span, r: &Result<Message, Error>) {
+    match *r {
+        Ok(ref m) => record_reply(span, m),
+        Err(ref e) => record_error(span, e.name()),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use tracing::{self, Subscriber, Event, Metadata, Id};
+    use tracing::span::{Attributes, Record};
+    use tracing::field::{Field, Visit};
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    /// Collects the fields of all spans.
+    #[derive(Clone, Default)]
+    pub struct SpanCollector {
+        pub spans: Arc<Mutex<Vec<HashMap<String, String>>>>,
+    }
+
+    struct Fields<'a>(&'a mut HashMap<String, String>);
+
+    impl<'a> Visit for Fields<'a> {
+        fn record_debug(&mut self, f: &Field, v: &fmt::Debug) { self.0.insert(f.name().into(), format!("{:?}", v)); }
+        fn record_str(&mut self, f: &Field, v: &str) { self.0.insert(f.name().into(), v.into()); }
+    }
+
+    impl Subscriber for SpanCollector {
+        fn enabled(&self, _: &Metadata) -> bool { true }
+        fn new_span(&self, a: &Attributes) -> Id {
+            let mut h = HashMap::new();
+            h.insert("name".into(), a.metadata().name().into());
+            a.record(&mut Fields(&mut h));
+            let mut v = self.spans.lock().unwrap();
+            v.push(h);
+            Id::from_u64(v.len() as u64)
+        }
+        fn record(&self, id: &Id, r: &Record) {
+            let mut v = self.spans.lock().unwrap();
+            r.record(&mut Fields(&mut v[id.into_u64() as usize - 1]));
+        }
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event) {}
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    impl SpanCollector {
+        /// Runs f with this collector as the thread's default subscriber.
+        ///
+        /// With a single subscriber around, tracing caches the interest of whichever thread
+        /// hits a callsite first, and other tests make calls without a subscriber. So the
+        /// call span callsite is registered here, and its interest rebuilt, before running f.
+        pub fn run<R, F: FnOnce() -> R>(&self, f: F) -> R {
+            tracing::subscriber::with_default(self.clone(), || {
+                let m = ::Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus.Peer", "Ping").unwrap();
+                drop(super::call_span(&m));
+                tracing::callsite::rebuild_interest_cache();
+                f()
+            })
+        }
+    }
+}
+
+#[test]
+fn method_call_spans() {
+    let bus = ::testing::shared_bus();
+    let col = test::SpanCollector::default();
+    col.run(|| {
+        let c = bus.connect().unwrap();
+        c.call_method("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetId", (), 2000).unwrap();
+        c.call_method("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "NoSuchMethod", (), 2000).unwrap_err();
+    });
+    let spans = col.spans.lock().unwrap();
+    let getid = spans.iter().find(|s| s.get("member").map(|x| &**x) == Some("GetId")).unwrap();
+    assert_eq!(getid["name"], "dbus.method_call");
+    assert_eq!(getid["destination"], "org.freedesktop.DBus");
+    assert_eq!(getid["interface"], "org.freedesktop.DBus");
+    assert_eq!(getid["outcome"], "reply");
+    assert!(getid["serial"].parse::<u32>().unwrap() > 0);
+    let nsm = spans.iter().find(|s| s.get("member").map(|x| &**x) == Some("NoSuchMethod")).unwrap();
+    assert_eq!(nsm["outcome"], "error");
+    assert_eq!(nsm["error"], "org.freedesktop.DBus.Error.UnknownMethod");
+}
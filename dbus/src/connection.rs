@@ -1,11 +1,15 @@
-use super::{Error, ffi, to_c_str, c_str_to_slice, Watch, Message, MessageType, BusName, Path, ConnPath};
-use super::{RequestNameReply, ReleaseNameReply, BusType};
-use super::watch::WatchList;
-use std::{fmt, mem, ptr, thread, panic, ops};
+use super::{Error, ErrorKind, ffi, to_c_str, c_str_to_slice, Timeout, Watch, Message, MessageType, BusName, Path, Interface, Member, ConnPath, PendingCall};
+use super::arg::{AppendAll, IterAppend, ArgType};
+use super::{RequestNameReply, ReleaseNameReply, BusType, SignalArgs, DBusProxy, StartServiceReply, DispatchStatus};
+use super::watch::{WatchList, TimeoutList, WatchTimeout};
+use std::{fmt, mem, ptr, thread, panic, ops, env};
 use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 use std::cell::{Cell, RefCell};
+use std::sync::{Mutex, Once};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::os::unix::io::RawFd;
-use std::os::raw::{c_void, c_char, c_int, c_uint};
+use std::os::raw::{c_void, c_char, c_int, c_uint, c_ulong, c_long};
 
 /// The type of function to use for replacing the message callback.
 ///
@@ -16,7 +20,7 @@ pub type MessageCallback = Box<FnMut(&Connection, Message) -> bool + 'static>;
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
 /// Flags to use for Connection::register_name.
 ///
-/// More than one flag can be specified, if so just add their values.
+/// More than one flag can be specified, if so combine them with `|` into a `NameFlags`.
 pub enum DBusNameFlag {
     /// Allow another service to become the primary owner if requested
     AllowReplacement = ffi::DBUS_NAME_FLAG_ALLOW_REPLACEMENT as isize,
@@ -31,6 +35,244 @@ impl DBusNameFlag {
     pub fn value(self) -> u32 { self as u32 }
 }
 
+impl ops::BitOr for DBusNameFlag {
+    type Output = NameFlags;
+    fn bitor(self, rhs: DBusNameFlag) -> NameFlags { NameFlags::from(self) | rhs }
+}
+
+/// A set of flags to use for Connection::register_name.
+///
+/// Combine flags with `|`, e g `NameFlag::AllowReplacement | NameFlag::DoNotQueue`.
+/// For backwards compatibility, a raw `u32` can be used wherever a NameFlags is expected;
+/// bits that do not correspond to a flag are ignored.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash, Default)]
+pub struct NameFlags(u32);
+
+impl NameFlags {
+    /// Allow another service to become the primary owner if requested
+    pub const ALLOW_REPLACEMENT: NameFlags = NameFlags(ffi::DBUS_NAME_FLAG_ALLOW_REPLACEMENT as u32);
+    /// Request to replace the current primary owner
+    pub const REPLACE_EXISTING: NameFlags = NameFlags(ffi::DBUS_NAME_FLAG_REPLACE_EXISTING as u32);
+    /// If we can not become the primary owner do not place us in the queue
+    pub const DO_NOT_QUEUE: NameFlags = NameFlags(ffi::DBUS_NAME_FLAG_DO_NOT_QUEUE as u32);
+
+    const ALL: u32 = (ffi::DBUS_NAME_FLAG_ALLOW_REPLACEMENT | ffi::DBUS_NAME_FLAG_REPLACE_EXISTING |
+        ffi::DBUS_NAME_FLAG_DO_NOT_QUEUE) as u32;
+
+    /// No flags set.
+    pub fn empty() -> NameFlags { NameFlags(0) }
+
+    /// Creates a set of flags from their u32 value, or returns None if unknown bits are set.
+    pub fn from_bits(bits: u32) -> Option<NameFlags> {
+        if bits & !Self::ALL == 0 { Some(NameFlags(bits)) } else { None }
+    }
+
+    /// u32 value of the flags.
+    pub fn bits(&self) -> u32 { self.0 }
+
+    /// Returns true if all flags in `other` are set.
+    pub fn contains<F: Into<NameFlags>>(&self, other: F) -> bool {
+        let o = other.into().0;
+        self.0 & o == o
+    }
+}
+
+impl From<DBusNameFlag> for NameFlags {
+    fn from(f: DBusNameFlag) -> NameFlags { NameFlags(f.value()) }
+}
+
+impl From<u32> for NameFlags {
+    fn from(bits: u32) -> NameFlags { NameFlags(bits & Self::ALL) }
+}
+
+impl<F: Into<NameFlags>> ops::BitOr<F> for NameFlags {
+    type Output = NameFlags;
+    fn bitor(self, rhs: F) -> NameFlags { NameFlags(self.0 | rhs.into().0) }
+}
+
+impl<F: Into<NameFlags>> ops::BitOrAssign<F> for NameFlags {
+    fn bitor_assign(&mut self, rhs: F) { self.0 |= rhs.into().0 }
+}
+
+// The launchd socket as an address, see `launchd_fallback`.
+#[cfg(any(target_os = "macos", test))]
+fn launchd_address(socket: &str) -> Option<String> {
+    let socket = socket.trim();
+    if socket.is_empty() { None } else { Some(format!("unix:path={}", ::Address::escape_value(socket))) }
+}
+
+// Running launchctl for every connection would be slow, and the socket does not move while we run.
+#[cfg(target_os = "macos")]
+static LAUNCHCTL_SOCKET: Mutex<Option<Option<String>>> = Mutex::new(None);
+
+#[cfg(target_os = "macos")]
+fn launchctl_socket() -> Option<String> {
+    let o = ::std::process::Command::new("launchctl").args(&["getenv", "DBUS_LAUNCHD_SESSION_BUS_SOCKET"]).output().ok()?;
+    if !o.status.success() { return None };
+    String::from_utf8(o.stdout).ok()
+}
+
+#[cfg(target_os = "macos")]
+fn launchd_session_address() -> Option<String> {
+    if let Ok(s) = env::var("DBUS_LAUNCHD_SESSION_BUS_SOCKET") { return launchd_address(&s) };
+    let mut cached = LAUNCHCTL_SOCKET.lock().unwrap();
+    if cached.is_none() { *cached = Some(launchctl_socket()) };
+    launchd_address(cached.as_ref().unwrap().as_ref()?)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn launchd_session_address() -> Option<String> { None }
+
+// On macOS, the session bus address is usually not in the environment, but published by launchd.
+// If `DBUS_SESSION_BUS_ADDRESS` is not set, the socket path is then taken from
+// `DBUS_LAUNCHD_SESSION_BUS_SOCKET`, in the environment or else from `launchctl getenv`.
+fn launchd_fallback(bus: BusType) -> Option<String> {
+    if bus != BusType::Session || env::var_os(Env::Session.var_name()).map(|v| !v.is_empty()).unwrap_or(false) { return None };
+    launchd_session_address()
+}
+
+/// The environment variables pointing to the well-known buses, see `Connection::from_env`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum Env {
+    /// The session bus, `DBUS_SESSION_BUS_ADDRESS`.
+    Session,
+    /// The system bus, `DBUS_SYSTEM_BUS_ADDRESS`.
+    System,
+    /// The bus that started this process through service activation, `DBUS_STARTER_ADDRESS`.
+    Starter,
+}
+
+impl Env {
+    /// The name of the environment variable.
+    pub fn var_name(&self) -> &'static str {
+        match *self {
+            Env::Session => "DBUS_SESSION_BUS_ADDRESS",
+            Env::System => "DBUS_SYSTEM_BUS_ADDRESS",
+            Env::Starter => "DBUS_STARTER_ADDRESS",
+        }
+    }
+
+    /// The address in the environment variable, or None if it is not set (or empty).
+    pub fn address(&self) -> Option<String> {
+        env::var(self.var_name()).ok().and_then(|a| if a.is_empty() { None } else { Some(a) })
+    }
+
+    /// The corresponding bus type.
+    pub fn bus_type(&self) -> BusType {
+        match *self {
+            Env::Session => BusType::Session,
+            Env::System => BusType::System,
+            Env::Starter => BusType::Starter,
+        }
+    }
+}
+
+/// Configures and opens a connection, see `Connection::builder`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Connection, BusType};
+///
+/// let c = Connection::builder().bus(BusType::System).max_message_size(1 << 20).build().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectionBuilder {
+    bus: Option<BusType>,
+    address: Option<String>,
+    register: bool,
+    private: bool,
+    exit_on_disconnect: bool,
+    max_message_size: Option<usize>,
+    max_received_size: Option<usize>,
+    flush_on_send: bool,
+}
+
+impl ConnectionBuilder {
+    /// Connects to a well-known bus. This is the default, with `BusType::Session`.
+    pub fn bus(mut self, bus: BusType) -> Self { self.bus = Some(bus); self.address = None; self }
+
+    /// Connects to the bus given by an environment variable, see `Connection::from_env`.
+    pub fn env(mut self, env: Env) -> Self {
+        match env.address() {
+            Some(a) => { self.address = Some(a); self.register = true; },
+            None => { self.bus = Some(env.bus_type()); self.address = None; },
+        }
+        self
+    }
+
+    /// Connects to an address, e g "unix:path=/run/example". If `register` is true, this is a bus
+    /// and we register with it (see `Connection::register`); otherwise this is a peer-to-peer
+    /// connection.
+    pub fn address(mut self, address: &str, register: bool) -> Self {
+        self.address = Some(address.into()); self.register = register; self
+    }
+
+    /// Whether to open a private connection (the default), or to use libdbus' shared connection
+    /// to the bus or address, which other code in the same process might use as well.
+    ///
+    /// A shared connection is not closed when the `Connection` is dropped. Only one `Connection`
+    /// at a time can wrap the same shared connection; building another one while the first is
+    /// still alive returns an error.
+    pub fn private(mut self, private: bool) -> Self { self.private = private; self }
+
+    /// Whether the process should exit when the connection is closed. Off by default.
+    pub fn exit_on_disconnect(mut self, exit: bool) -> Self { self.exit_on_disconnect = exit; self }
+
+    /// The largest message, in bytes, that can be received on the connection.
+    /// libdbus' default is 32 MiB.
+    pub fn max_message_size(mut self, size: usize) -> Self { self.max_message_size = Some(size); self }
+
+    /// The total number of bytes that received messages can use at once, see
+    /// `Connection::set_max_received_size`.
+    pub fn max_received_size(mut self, size: usize) -> Self { self.max_received_size = Some(size); self }
+
+    /// Whether sending a message blocks until it has been written, see `Connection::set_flush_on_send`.
+    pub fn flush_on_send(mut self, flush: bool) -> Self { self.flush_on_send = flush; self }
+
+    /// Opens the connection.
+    pub fn build(mut self) -> Result<Connection, Error> {
+        if self.address.is_none() {
+            if let Some(addr) = launchd_fallback(self.bus.unwrap_or(BusType::Session)) {
+                self.address = Some(addr);
+                self.register = true;
+            }
+        }
+        let mut e = Error::empty();
+        let conn = match (&self.address, self.private) {
+            (&Some(ref a), true) => unsafe { ffi::dbus_connection_open_private(to_c_str(a).as_ptr(), e.get_mut()) },
+            (&Some(ref a), false) => unsafe { ffi::dbus_connection_open(to_c_str(a).as_ptr(), e.get_mut()) },
+            (&None, true) => unsafe { ffi::dbus_bus_get_private(self.bus.unwrap_or(BusType::Session), e.get_mut()) },
+            (&None, false) => unsafe { ffi::dbus_bus_get(self.bus.unwrap_or(BusType::Session), e.get_mut()) },
+        };
+        if conn == ptr::null_mut() { return Err(e) }
+        let c = try!(Connection::conn_from_ptr(conn, self.private));
+        // A shared connection to an address might have been registered already.
+        if self.address.is_some() && self.register && unsafe { ffi::dbus_bus_get_unique_name(conn) }.is_null() {
+            try!(c.register());
+        }
+        unsafe { ffi::dbus_connection_set_exit_on_disconnect(conn, self.exit_on_disconnect as u32) };
+        if let Some(m) = self.max_message_size { c.set_max_message_size(m) };
+        if let Some(m) = self.max_received_size { c.set_max_received_size(m) };
+        c.set_flush_on_send(self.flush_on_send);
+        Ok(c)
+    }
+}
+
+/// What to do when the queue of incoming messages is full, see `Connection::set_max_pending_items`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum QueuePolicy {
+    /// Stop reading from the connection until there is room in the queue again.
+    ///
+    /// No messages are lost; instead they are left in the socket, so that the sender is slowed
+    /// down. Note that this also delays replies to method calls until the queue has room again.
+    Block,
+    /// Drop the oldest message in the queue to make room for the new one.
+    DropOldest,
+    /// Drop the new message.
+    DropNewest,
+}
+
 /// When listening for incoming events on the D-Bus, this enum will tell you what type
 /// of incoming event has happened.
 #[derive(Debug)]
@@ -43,12 +285,25 @@ pub enum ConnectionItem {
     Signal(Message),
     /// Incoming method return, including method return errors (mostly used for Async I/O)
     MethodReturn(Message),
+    /// The connection was closed, e g because the D-Bus server went down.
+    ///
+    /// This is the local `org.freedesktop.DBus.Local.Disconnected` signal. It is the last item;
+    /// after it, the iterator ends.
+    Disconnected,
+}
+
+pub(crate) fn is_disconnected_signal(m: &Message) -> bool {
+    m.msg_type() == MessageType::Signal &&
+        m.path().as_ref().map(|p| &**p) == Some("/org/freedesktop/DBus/Local") &&
+        m.interface().as_ref().map(|i| &**i) == Some("org.freedesktop.DBus.Local") &&
+        m.member().as_ref().map(|m| &**m) == Some("Disconnected")
 }
 
 impl From<Message> for ConnectionItem {
     fn from(m: Message) -> Self {
         let mtype = m.msg_type();
         match mtype {
+            MessageType::Signal if is_disconnected_signal(&m) => ConnectionItem::Disconnected,
             MessageType::Signal => ConnectionItem::Signal(m),
             MessageType::MethodReturn => ConnectionItem::MethodReturn(m),
             MessageType::Error => ConnectionItem::MethodReturn(m),
@@ -81,7 +336,7 @@ impl<'a> ConnectionItems<'a> {
             ConnectionItem::MethodReturn(ref msg) => msg,
             ConnectionItem::Signal(ref msg) => msg,
             ConnectionItem::MethodCall(ref msg) => msg,
-            ConnectionItem::Nothing => return false,
+            ConnectionItem::Nothing | ConnectionItem::Disconnected => return false,
         };
 
         msghandler_process(&mut self.handlers, m, &self.c)
@@ -132,7 +387,9 @@ impl<'a> Iterator for ConnectionItems<'a> {
 
             if !self.c.i.pending_items.borrow().is_empty() { continue };
             if r == ffi::DBusDispatchStatus::DataRemains { continue };
-            if r == ffi::DBusDispatchStatus::Complete { return if self.end_on_timeout { None } else { Some(ConnectionItem::Nothing) } };
+            if r == ffi::DBusDispatchStatus::Complete {
+                return if self.end_on_timeout || !self.c.is_connected() { None } else { Some(ConnectionItem::Nothing) }
+            };
             panic!("dbus_connection_dispatch failed");
         }
     }
@@ -178,6 +435,20 @@ impl<C: ops::Deref<Target = Connection>> Iterator for ConnMsgs<C> {
     }
 }
 
+// Marks the libdbus connections that a `Connection` wraps, see `conn_from_ptr`.
+static WRAPPED_SLOT_INIT: Once = Once::new();
+static WRAPPED_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+fn wrapped_slot() -> i32 {
+    WRAPPED_SLOT_INIT.call_once(|| {
+        // Never freed; libdbus keeps a pointer to the slot number.
+        let slot = Box::into_raw(Box::new(-1));
+        if unsafe { ffi::dbus_connection_allocate_data_slot(slot) } == 0 { panic!("Out of memory"); }
+        WRAPPED_SLOT.store(unsafe { *slot } as usize, Ordering::SeqCst);
+    });
+    WRAPPED_SLOT.load(Ordering::SeqCst) as i32
+}
+
 /* Since we register callbacks with userdata pointers,
    we need to make sure the connection pointer does not move around.
    Hence this extra indirection. */
@@ -185,10 +456,25 @@ struct IConnection {
     conn: Cell<*mut ffi::DBusConnection>,
     pending_items: RefCell<VecDeque<Message>>,
     watches: Option<Box<WatchList>>,
+    timeouts: Option<Box<TimeoutList>>,
     handlers: RefCell<MsgHandlerList>,
+    hooks: RefCell<Vec<Box<MsgHook>>>,
 
     filter_cb: RefCell<Option<MessageCallback>>,
     filter_cb_panic: RefCell<thread::Result<()>>,
+    max_pending: Cell<Option<(usize, QueuePolicy)>>,
+    dropped_items: Cell<u64>,
+    // The max received size libdbus had before we blocked reading, see QueuePolicy::Block.
+    blocked_max_received: Cell<Option<c_long>>,
+    flush_on_send: Cell<bool>,
+    close_on_drop: Cell<bool>,
+    // Names requested through register_name and not yet released, see close_graceful.
+    owned_names: RefCell<Vec<String>>,
+    // Paths registered through register_object_path, unregistered again by detach.
+    object_paths: RefCell<Vec<String>>,
+    dispatch_status_cb: Mutex<Option<Box<Fn(DispatchStatus) + Send>>>,
+    #[cfg(feature = "log")]
+    log_traffic: Cell<bool>,
 }
 
 /// A D-Bus connection. Start here if you want to get on the D-Bus!
@@ -196,6 +482,47 @@ pub struct Connection {
     i: Box<IConnection>,
 }
 
+impl IConnection {
+    // Returns false if a hook vetoed the message.
+    fn run_hooks(&self, m: &Message, outgoing: bool) -> bool {
+        // Take the hooks out while calling them, so that messages sent from a hook do not recurse.
+        let mut v = match self.hooks.try_borrow_mut() {
+            Ok(mut h) => mem::replace(&mut *h, vec!()),
+            Err(_) => return true,
+        };
+        if v.is_empty() { return true };
+        let r = v.iter_mut().all(|h| if outgoing { h.outgoing(m) } else { h.incoming(m) });
+        let mut v2 = self.hooks.borrow_mut();
+        v.append(&mut *v2);
+        *v2 = v;
+        r
+    }
+
+    fn pending_full(&self) -> Option<QueuePolicy> {
+        match self.max_pending.get() {
+            Some((max, policy)) if self.pending_items.borrow().len() >= max => Some(policy),
+            _ => None,
+        }
+    }
+
+    // Messages count towards libdbus' max received size for as long as they are alive,
+    // so setting it to the minimum stops reading while the queue is full.
+    fn block_reading(&self, block: bool) {
+        let conn = self.conn.get();
+        match (block, self.blocked_max_received.get()) {
+            (true, None) => {
+                self.blocked_max_received.set(Some(unsafe { ffi::dbus_connection_get_max_received_size(conn) }));
+                unsafe { ffi::dbus_connection_set_max_received_size(conn, 1) };
+            }
+            (false, Some(old)) => {
+                self.blocked_max_received.set(None);
+                unsafe { ffi::dbus_connection_set_max_received_size(conn, old) };
+            }
+            _ => {},
+        }
+    }
+}
+
 pub fn conn_handle(c: &Connection) -> *mut ffi::DBusConnection {
     c.i.conn.get()
 }
@@ -215,9 +542,13 @@ extern "C" fn filter_message_cb(conn: *mut ffi::DBusConnection, msg: *mut ffi::D
         return ffi::DBusHandlerResult::Handled;
     }
 
+    #[cfg(feature = "log")]
+    { if i.log_traffic.get() { ::wirelog::log_msg("received", &Message::from_ptr(msg, true)) } }
+
     let fcb = panic::AssertUnwindSafe(&i.filter_cb);
     let r = panic::catch_unwind(|| {
         let m = Message::from_ptr(msg, true);
+        if !connref.0.i.run_hooks(&m, false) { return true };
         let mut cb = fcb.borrow_mut().take().unwrap(); // Take the callback out while we call it.
         let r = cb(connref.0, m);
         let mut cb2 = fcb.borrow_mut(); // If the filter callback has not been replaced, put it back in.
@@ -235,9 +566,26 @@ extern "C" fn filter_message_cb(conn: *mut ffi::DBusConnection, msg: *mut ffi::D
     }
 }
 
+extern "C" fn dispatch_status_cb(_conn: *mut ffi::DBusConnection, new_status: DispatchStatus, user_data: *mut c_void) {
+    let i: &IConnection = unsafe { mem::transmute(user_data) };
+    if let Ok(f) = i.dispatch_status_cb.lock() { if let Some(ref f) = *f { f(new_status) } };
+}
+
 fn default_filter_callback(c: &Connection, m: Message) -> bool {
     let b = m.msg_type() == MessageType::Signal;
+    match c.i.pending_full() {
+        Some(QueuePolicy::DropNewest) => { c.drop_pending(&m); return true },
+        Some(QueuePolicy::DropOldest) => {
+            let d = c.i.pending_items.borrow_mut().pop_front();
+            match d {
+                Some(d) => c.drop_pending(&d),
+                None => { c.drop_pending(&m); return true },
+            }
+        }
+        _ => {},
+    }
     c.i.pending_items.borrow_mut().push_back(m);
+    if c.i.pending_full() == Some(QueuePolicy::Block) { c.i.block_reading(true) };
     b
 }
 
@@ -254,14 +602,33 @@ impl Connection {
         self.i.conn.get()
     }
 
-    fn conn_from_ptr(conn: *mut ffi::DBusConnection) -> Result<Connection, Error> {
+    // `close_on_drop` must be false for shared connections, which libdbus does not allow to be closed.
+    // Takes over the reference to `conn`, also when returning an error.
+    pub (crate) fn conn_from_ptr(conn: *mut ffi::DBusConnection, close_on_drop: bool) -> Result<Connection, Error> {
+        // Two structs installing their callbacks on the same connection would steal each other's messages.
+        if !unsafe { ffi::dbus_connection_get_data(conn, wrapped_slot()) }.is_null() {
+            unsafe { ffi::dbus_connection_unref(conn) };
+            return Err(Error::new_custom("org.freedesktop.DBus.Error.Failed", "The connection is already wrapped by another Connection"));
+        }
         let mut c = Connection { i: Box::new(IConnection {
             conn: Cell::new(conn),
             pending_items: RefCell::new(VecDeque::new()),
             watches: None,
+            timeouts: None,
             handlers: RefCell::new(vec!()),
+            hooks: RefCell::new(vec!()),
             filter_cb: RefCell::new(Some(Box::new(default_filter_callback))),
             filter_cb_panic: RefCell::new(Ok(())),
+            max_pending: Cell::new(None),
+            dropped_items: Cell::new(0),
+            blocked_max_received: Cell::new(None),
+            flush_on_send: Cell::new(true),
+            close_on_drop: Cell::new(close_on_drop),
+            owned_names: RefCell::new(vec!()),
+            object_paths: RefCell::new(vec!()),
+            dispatch_status_cb: Mutex::new(None),
+            #[cfg(feature = "log")]
+            log_traffic: Cell::new(true),
         })};
 
         /* No, we don't want our app to suddenly quit if dbus goes down */
@@ -271,17 +638,52 @@ impl Connection {
         } != 0);
 
         c.i.watches = Some(WatchList::new(&c, Box::new(|_| {})));
+        c.i.timeouts = Some(TimeoutList::new(&c, Box::new(|_| {})));
+        assert!(unsafe {
+            ffi::dbus_connection_set_data(c.conn(), wrapped_slot(), mem::transmute(&*c.i), None)
+        } != 0);
         Ok(c)
     }
 
+    /// Returns a builder for configuring a connection before opening it.
+    ///
+    /// By default, it opens a private connection to the session bus.
+    pub fn builder() -> ConnectionBuilder {
+        ConnectionBuilder { bus: None, address: None, register: false, private: true,
+            exit_on_disconnect: false, max_message_size: None, max_received_size: None, flush_on_send: true }
+    }
+
     /// Creates a new D-Bus connection.
+    ///
+    /// `BusType::Starter` connects to the bus that started this process through service
+    /// activation, as given by the `DBUS_STARTER_ADDRESS` environment variable (libdbus has
+    /// fallbacks if it is not set).
+    ///
+    /// On macOS, the session bus is looked up through launchd if `DBUS_SESSION_BUS_ADDRESS`
+    /// is not set: the socket path is taken from `DBUS_LAUNCHD_SESSION_BUS_SOCKET`, in the
+    /// environment or else from `launchctl getenv`.
     pub fn get_private(bus: BusType) -> Result<Connection, Error> {
+        if let Some(addr) = launchd_fallback(bus) { return Self::open_address(&addr) };
         let mut e = Error::empty();
         let conn = unsafe { ffi::dbus_bus_get_private(bus, e.get_mut()) };
         if conn == ptr::null_mut() {
             return Err(e)
         }
-        Self::conn_from_ptr(conn)
+        Self::conn_from_ptr(conn, true)
+    }
+
+    /// Creates a new D-Bus connection to the bus whose address is given by the environment.
+    ///
+    /// If the environment variable for the bus is set (see `Env::address`), this connects to
+    /// that address and registers with the bus. Otherwise it falls back to libdbus's own lookup
+    /// through `get_private`, e g the default path of the system bus, or launchd on macOS.
+    /// This makes it explicit which bus is connected to, e g when tests or sandboxes
+    /// point `DBUS_SESSION_BUS_ADDRESS` to a private bus.
+    pub fn from_env(env: Env) -> Result<Connection, Error> {
+        match env.address() {
+            Some(addr) => Self::open_address(&addr),
+            None => Self::get_private(env.bus_type()),
+        }
     }
 
     /// Creates a new D-Bus connection to a remote address.
@@ -293,7 +695,20 @@ impl Connection {
         if conn == ptr::null_mut() {
             return Err(e)
         }
-        Self::conn_from_ptr(conn)
+        Self::conn_from_ptr(conn, true)
+    }
+
+    /// Connects to the message bus at an address, and registers with it.
+    ///
+    /// This is `open_private` followed by `register`, for buses that are not found through the
+    /// environment, e g one bridged over TCP (`tcp:host=192.168.1.2,port=4711`). Authentication
+    /// uses the first mechanism the server accepts: EXTERNAL over unix sockets, DBUS_COOKIE_SHA1
+    /// (which needs a shared home directory) or ANONYMOUS, if the bus allows it.
+    /// File descriptors cannot be passed over TCP, see `can_send_type`.
+    pub fn open_address(address: &str) -> Result<Connection, Error> {
+        let c = try!(Self::open_private(address));
+        try!(c.register());
+        Ok(c)
     }
 
     /// Registers a new D-Bus connection with the bus.
@@ -315,27 +730,185 @@ impl Connection {
 
     /// Sends a message over the D-Bus and waits for a reply.
     /// This is usually used for method calls.
-    pub fn send_with_reply_and_block(&self, msg: Message, timeout_ms: i32) -> Result<Message, Error> {
+    ///
+    /// The timeout can be a `Timeout`, a `Duration`, or an i32 in milliseconds (-1 for the default timeout).
+    pub fn send_with_reply_and_block<T: Into<Timeout>>(&self, msg: Message, timeout: T) -> Result<Message, Error> {
+        #[cfg(feature = "tracing")]
+        let span = ::calltrace::call_span(&msg);
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+        let r = self.send_with_reply_and_block_inner(&msg, timeout.into());
+        #[cfg(feature = "tracing")]
+        {
+            ::calltrace::record_serial(&span, msg.get_serial());
+            ::calltrace::record_result(&span, &r);
+        }
+        r
+    }
+
+    /// Sends a message over the D-Bus and blocks, waiting for a reply or a timeout, like
+    /// `send_with_reply_and_block`, but classifies failures into a `CallError`.
+    ///
+    /// This lets callers tell a call that timed out, or a peer that went away without
+    /// replying, from an error reply sent by the remote side.
+    pub fn call_blocking<T: Into<Timeout>>(&self, msg: Message, timeout: T) -> Result<Message, CallError> {
+        let timeout = timeout.into();
+        let start = Instant::now();
+        self.send_with_reply_and_block(msg, timeout).map_err(|e| {
+            if e.kind() == ErrorKind::Disconnected || !self.is_connected() { return CallError::Disconnected };
+            if e.kind() != ErrorKind::NoReply { return CallError::Remote(e) };
+            // libdbus reports both a local timeout and a peer that disconnected before
+            // replying as NoReply, so tell them apart by how long we have been waiting.
+            let limit = match timeout {
+                Timeout::Default => Some(Duration::from_millis(DEFAULT_CALL_TIMEOUT_MS)),
+                Timeout::Infinite => None,
+                Timeout::Duration(d) => Some(d),
+            };
+            if limit.map_or(false, |l| start.elapsed() >= l) { CallError::Timeout } else { CallError::NoReply }
+        })
+    }
+
+    fn send_with_reply_and_block_inner(&self, msg: &Message, timeout: Timeout) -> Result<Message, Error> {
+        if !self.i.run_hooks(msg, true) { return Err(vetoed_error("Message")) };
         let mut e = Error::empty();
         let response = unsafe {
             ffi::dbus_connection_send_with_reply_and_block(self.conn(), msg.ptr(),
-                timeout_ms as c_int, e.get_mut())
+                timeout.call_ms(), e.get_mut())
         };
+        self.log_msg("sent", msg);
         if response == ptr::null_mut() {
             return Err(e);
         }
-        Ok(Message::from_ptr(response, false))
+        let r = Message::from_ptr(response, false);
+        self.log_msg("received", &r);
+        if !self.i.run_hooks(&r, false) { return Err(vetoed_error("Reply")) };
+        Ok(r)
+    }
+
+    /// Makes a method call and waits for the reply.
+    ///
+    /// This builds the method call message, appends the arguments, sends it and waits for
+    /// up to `timeout` for the reply. If the reply is an error message, it is returned as an Error.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use dbus::{Connection, BusType};
+    ///
+    /// let c = Connection::get_private(BusType::Session).unwrap();
+    /// let reply = c.call_method("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "ListNames", (), 2000).unwrap();
+    /// let names: Vec<&str> = reply.read1().unwrap();
+    /// assert!(names.contains(&"org.freedesktop.DBus"));
+    /// ```
+    pub fn call_method<'a, D, P, I, M, A, T>(&self, dest: D, path: P, iface: I, member: M, args: A, timeout: T) -> Result<Message, Error>
+    where D: Into<BusName<'a>>, P: Into<Path<'a>>, I: Into<Interface<'a>>, M: Into<Member<'a>>, A: AppendAll, T: Into<Timeout> {
+        let mut msg = Message::method_call(&dest.into(), &path.into(), &iface.into(), &member.into());
+        args.append(&mut IterAppend::new(&mut msg));
+        let mut r = try!(self.send_with_reply_and_block(msg, timeout));
+        try!(r.as_result());
+        Ok(r)
+    }
+
+    /// Checks that a peer is alive, by calling `org.freedesktop.DBus.Peer.Ping` on it.
+    ///
+    /// Returns an error if the peer does not exist, or does not answer within `timeout`.
+    pub fn ping<'a, D: Into<BusName<'a>>, T: Into<Timeout>>(&self, dest: D, timeout: T) -> Result<(), Error> {
+        self.call_method(dest, "/", "org.freedesktop.DBus.Peer", "Ping", (), timeout).map(|_| ())
+    }
+
+    /// Returns the machine id of the machine a peer is running on,
+    /// by calling `org.freedesktop.DBus.Peer.GetMachineId` on it.
+    pub fn peer_machine_id<'a, D: Into<BusName<'a>>>(&self, dest: D) -> Result<String, Error> {
+        let r = try!(self.call_method(dest, "/", "org.freedesktop.DBus.Peer", "GetMachineId", (), Timeout::Default));
+        Ok(try!(r.read1()))
+    }
+
+    /// Starts the service owning a name through service activation, unless it is already running.
+    ///
+    /// Method calls to an activatable name start the service implicitly, but doing it explicitly
+    /// is useful e g before a batch of calls, to get startup failures reported up front.
+    /// See `DBusProxy::start_service_by_name`.
+    pub fn activate_service(&self, name: &str) -> Result<StartServiceReply, Error> {
+        DBusProxy::new(self).start_service_by_name(name, 0)
+    }
+
+    /// Returns the Unix user id of the process owning a name on the bus, e g to apply
+    /// per-caller policies in a server. Asks the D-Bus server, see `DBusProxy::get_connection_unix_user`.
+    pub fn peer_unix_user(&self, name: &str) -> Result<u32, Error> {
+        DBusProxy::new(self).get_connection_unix_user(name)
+    }
+
+    /// Returns the Unix process id of the process owning a name on the bus.
+    /// Asks the D-Bus server, see `DBusProxy::get_connection_unix_process_id`.
+    pub fn peer_unix_process_id(&self, name: &str) -> Result<u32, Error> {
+        DBusProxy::new(self).get_connection_unix_process_id(name)
+    }
+
+    /// Returns the Unix user id of the process at the other end of this connection, if known.
+    ///
+    /// This is mostly useful for direct (peer-to-peer) connections accepted by a server, since
+    /// libdbus only learns the credentials of the other end when authenticating it as a server.
+    /// For a bus connection, the other end is the D-Bus server itself; use `peer_unix_user`
+    /// to ask about other connections to the bus.
+    pub fn unix_user(&self) -> Option<u32> {
+        let mut uid: c_ulong = 0;
+        let r = unsafe { ffi::dbus_connection_get_unix_user(self.conn(), &mut uid) };
+        if r == 0 { None } else { Some(uid as u32) }
+    }
+
+    /// Returns the Unix process id of the process at the other end of this connection, if known.
+    ///
+    /// See `unix_user` for direct vs bus connections.
+    pub fn unix_process_id(&self) -> Option<u32> {
+        let mut pid: c_ulong = 0;
+        let r = unsafe { ffi::dbus_connection_get_unix_process_id(self.conn(), &mut pid) };
+        if r == 0 { None } else { Some(pid as u32) }
     }
 
     /// Sends a message over the D-Bus without waiting. Useful for sending signals and method call replies.
+    ///
+    /// Unless turned off with `set_flush_on_send`, this blocks until the message is written to the socket.
     pub fn send(&self, msg: Message) -> Result<u32,()> {
+        let serial = try!(self.send_noflush(&msg));
+        self.flush_if_enabled();
+        Ok(serial)
+    }
+
+    /// Sends several messages, then flushes the outgoing queue once.
+    ///
+    /// This is faster than calling `send` for each message, since `send` waits for every
+    /// message to be written before returning. Returns the serial numbers of the messages.
+    /// If one of them fails, the error is returned, but the messages before it have been sent.
+    pub fn send_batch(&self, msgs: &[Message]) -> Result<Vec<u32>, ()> {
+        let r = msgs.iter().map(|m| self.send_noflush(m)).collect();
+        self.flush_if_enabled();
+        r
+    }
+
+    /// Sets whether `send` (and the other methods sending messages) blocks until the message has been
+    /// written to the socket. It is on by default.
+    ///
+    /// When off, messages stay in the outgoing queue until the connection reads or writes next time,
+    /// e g when iterating over `incoming`, or when `flush` is called. This improves throughput
+    /// when sending many messages.
+    pub fn set_flush_on_send(&self, enable: bool) { self.i.flush_on_send.set(enable) }
+
+    /// Returns whether `send` flushes the outgoing queue, see `set_flush_on_send`.
+    pub fn flush_on_send(&self) -> bool { self.i.flush_on_send.get() }
+
+    fn send_noflush(&self, msg: &Message) -> Result<u32, ()> {
+        if !self.i.run_hooks(msg, true) { return Err(()) };
         let mut serial = 0u32;
         let r = unsafe { ffi::dbus_connection_send(self.conn(), msg.ptr(), &mut serial) };
         if r == 0 { return Err(()); }
-        unsafe { ffi::dbus_connection_flush(self.conn()) };
+        self.log_msg("sent", msg);
         Ok(serial)
     }
 
+    fn flush_if_enabled(&self) {
+        if self.i.flush_on_send.get() { unsafe { ffi::dbus_connection_flush(self.conn()) } }
+    }
+
     /// Sends a message over the D-Bus, returning a MessageReply.
     ///
     /// Call add_handler on the result to start waiting for reply. This should be done before next call to `incoming` or `iter`.
@@ -344,11 +917,142 @@ impl Connection {
         Ok(MessageReply(Some(f), serial))
     }
 
+    /// Sends a message over the D-Bus, returning a PendingCall.
+    ///
+    /// The PendingCall can be used to check for, wait for, or cancel the reply.
+    /// If no reply has arrived within the timeout, the PendingCall completes
+    /// with an error reply. (Use -1 or `Timeout::Default` for the default timeout.)
+    pub fn send_with_pending_call<T: Into<Timeout>>(&self, msg: Message, timeout: T) -> Result<PendingCall, ()> {
+        if !self.i.run_hooks(&msg, true) { return Err(()) };
+        let mut p = ptr::null_mut();
+        let r = unsafe { ffi::dbus_connection_send_with_reply(self.conn(), msg.ptr(), &mut p, timeout.into().call_ms()) };
+        if r == 0 || p == ptr::null_mut() { return Err(()); }
+        self.log_msg("sent", &msg);
+        self.flush_if_enabled();
+        Ok(PendingCall::from_ptr(p))
+    }
+
+    /// Returns true if there are messages in the outgoing queue, not yet written to the socket.
+    pub fn has_messages_to_send(&self) -> bool {
+        unsafe { ffi::dbus_connection_has_messages_to_send(self.conn()) != 0 }
+    }
+
+    /// The approximate number of bytes in the outgoing queue.
+    pub fn outgoing_size(&self) -> usize {
+        unsafe { ffi::dbus_connection_get_outgoing_size(self.conn()) as usize }
+    }
+
+    /// Blocks until the outgoing queue is empty.
+    pub fn flush(&self) {
+        unsafe { ffi::dbus_connection_flush(self.conn()) }
+    }
+
+    /// Blocks until the outgoing queue is empty, or the timeout has passed.
+    ///
+    /// Returns true if the queue was emptied, false on timeout or if the connection was disconnected.
+    pub fn flush_timeout(&self, timeout: Duration) -> bool {
+        flush_timeout(self.conn(), timeout)
+    }
+
+    /// Returns true if dropping this struct closes the connection.
+    ///
+    /// This is the case for private connections. Shared connections (see `ConnectionBuilder::private`)
+    /// are only detached from this struct and unreferenced when dropped, since other code might
+    /// still be using them.
+    pub fn closes_on_drop(&self) -> bool { self.i.close_on_drop.get() }
+
+    /// Detaches the underlying libdbus connection from this struct and returns it, without closing it.
+    ///
+    /// Handlers, callbacks and incoming messages not yet returned from the iterators are dropped.
+    /// The caller takes over the reference held by this struct, and is responsible for calling
+    /// `dbus_connection_unref` (and for a private connection, `dbus_connection_close` first).
+    pub fn into_raw(self) -> *mut ffi::DBusConnection {
+        let p = self.conn();
+        self.detach();
+        self.i.conn.set(ptr::null_mut());
+        p
+    }
+
+    /// Wraps a connection opened by C code or other bindings to libdbus in the same process.
+    ///
+    /// If `owned` is true, the returned struct takes over the caller's reference, and closes the
+    /// connection when dropped; the connection must then be a private one. Otherwise a reference
+    /// is added and the connection is left open when dropped, as for a shared connection.
+    ///
+    /// The returned struct installs its own filter, watch and timeout functions, so the connection
+    /// must not also be dispatched or integrated into a main loop by the other code. For the same
+    /// reason, an error is returned if another `Connection` already wraps it.
+    ///
+    /// Unsafe because `ptr` must point to a valid libdbus connection.
+    pub unsafe fn from_raw(ptr: *mut ffi::DBusConnection, owned: bool) -> Result<Connection, Error> {
+        assert!(!ptr.is_null());
+        if !owned { ffi::dbus_connection_ref(ptr); }
+        Connection::conn_from_ptr(ptr, owned)
+    }
+
+    /// The underlying libdbus connection. It is only valid while this struct is alive.
+    pub fn as_raw(&self) -> *mut ffi::DBusConnection { self.conn() }
+
+    // The connection lives on after this struct is dropped, so it must no longer call into us,
+    // nor claim to handle messages for our object paths.
+    fn detach(&self) {
+        for path in self.i.object_paths.borrow_mut().drain(..) {
+            let p = to_c_str(&path);
+            if unsafe { ffi::dbus_connection_unregister_object_path(self.conn(), p.as_ptr()) } == 0 { panic!("Out of memory"); }
+        }
+        unsafe {
+            ffi::dbus_connection_set_data(self.conn(), wrapped_slot(), ptr::null_mut(), None);
+            ffi::dbus_connection_remove_filter(self.conn(), Some(filter_message_cb), mem::transmute(&*self.i));
+            ffi::dbus_connection_set_watch_functions(self.conn(), None, None, None, ptr::null_mut(), None);
+            ffi::dbus_connection_set_timeout_functions(self.conn(), None, None, None, ptr::null_mut(), None);
+            ffi::dbus_connection_set_dispatch_status_function(self.conn(), None, ptr::null_mut(), None);
+        }
+    }
+
+    /// Closes the connection after sending everything in the outgoing queue.
+    ///
+    /// Dropping a connection closes it right away, so replies and signals still in the outgoing
+    /// queue may be lost. This flushes the queue first and, if `release_names` is true, releases
+    /// the names requested through `register_name` (the bus releases them anyway when we
+    /// disconnect, but this way it happens before the connection goes away). Shared connections
+    /// cannot be closed, so for these the connection is only flushed and dropped.
+    ///
+    /// The timeout covers all of this. Returns false if it passed, or if the connection was
+    /// disconnected, before everything was sent.
+    pub fn close_graceful(self, timeout: Duration, release_names: bool) -> bool {
+        let deadline = Instant::now() + timeout;
+        let remaining = || { let now = Instant::now(); if now >= deadline { Duration::from_secs(0) } else { deadline - now } };
+        if !self.flush_timeout(remaining()) { return false };
+        if !release_names { return true };
+        let names = self.i.owned_names.borrow().clone();
+        for name in names {
+            let m = Message::new_method_call("org.freedesktop.DBus", "/org/freedesktop/DBus", "org.freedesktop.DBus", "ReleaseName")
+                .unwrap().append1(&*name);
+            if self.send_with_reply_and_block(m, Timeout::Duration(remaining())).is_err() { return false };
+            self.i.owned_names.borrow_mut().retain(|n| *n != name);
+        }
+        true
+    }
+
+    /// Returns a handle that can be used to send messages over this connection from other threads.
+    ///
+    /// See `Sender` for details.
+    pub fn sender(&self) -> Sender {
+        Sender(unsafe { ffi::dbus_connection_ref(self.conn()) })
+    }
+
+    /// Reserves space in the outgoing queue for sending one message later, see `PreallocatedSend`.
+    ///
+    /// Returns an error if out of memory.
+    pub fn preallocate_send(&self) -> Result<PreallocatedSend, ()> {
+        PreallocatedSend::new(self.conn())
+    }
+
     /// Adds a message handler to the connection.
     ///
     /// # Example
     ///
-    /// ```
+    /// ```rust,no_run
     /// use std::{cell, rc};
     /// use dbus::{Connection, Message, BusType};
     ///
@@ -380,6 +1084,22 @@ impl Connection {
         self.i.handlers.borrow_mut().pop()
     }
 
+    /// Adds a hook that is called for every message sent and received on this connection.
+    ///
+    /// Hooks are called in the order they were added, before any `MsgHandler` or message callback.
+    /// If a hook vetoes a message, later hooks are not called: an outgoing message is not sent
+    /// (and the send method returns an error), and an incoming message is dropped.
+    ///
+    /// Messages sent from within a hook, and messages sent through a `Sender`, do not pass through the hooks.
+    pub fn add_hook<H: MsgHook + 'static>(&self, h: H) {
+        self.i.hooks.borrow_mut().push(Box::new(h));
+    }
+
+    /// Removes the most recently added hook from the connection.
+    pub fn extract_hook(&self) -> Option<Box<MsgHook>> {
+        self.i.hooks.borrow_mut().pop()
+    }
+
     /// Get the connection's unique name.
     pub fn unique_name(&self) -> String {
         let c = unsafe { ffi::dbus_bus_get_unique_name(self.conn()) };
@@ -390,15 +1110,148 @@ impl Connection {
     ///
     /// If there are no incoming events, ConnectionItems::Nothing will be returned.
     /// See ConnectionItems::new if you want to customize this behaviour.
-    pub fn iter(&self, timeout_ms: i32) -> ConnectionItems {
-        ConnectionItems::new(self, Some(timeout_ms), false)
+    ///
+    /// The timeout is how long to wait for new events each time; `Timeout::Default` and
+    /// `Timeout::Infinite` (or a negative i32) both wait forever.
+    pub fn iter<T: Into<Timeout>>(&self, timeout: T) -> ConnectionItems {
+        ConnectionItems::new(self, Some(timeout.into().io_ms()), false)
     }
 
     /// Check if there are new incoming events
     ///
-    /// Supersedes "iter".
-    pub fn incoming(&self, timeout_ms: u32) -> ConnMsgs<&Self> {
-        ConnMsgs { conn: &self, timeout_ms: Some(timeout_ms) }
+    /// Supersedes "iter". The timeout is how long to wait for new messages each time, as for `iter`.
+    pub fn incoming<T: Into<Timeout>>(&self, timeout: T) -> ConnMsgs<&Self> {
+        // Waiting forever is -1, which ConnMsgs casts back from u32::MAX.
+        ConnMsgs { conn: &self, timeout_ms: Some(timeout.into().io_ms() as u32) }
+    }
+
+    /// Peeks at the next message in libdbus' incoming queue, without removing it.
+    ///
+    /// This lets a dispatcher inspect the message's headers and either take it (with
+    /// `BorrowedMessage::steal`) or leave it in the queue for another component sharing the
+    /// connection, which happens when the `BorrowedMessage` is dropped. The message has been
+    /// read from the socket but not dispatched, so message hooks and handlers have not seen it.
+    ///
+    /// This does not read from the socket; returns None if no message has been read yet.
+    /// While a message is borrowed, no messages can be dispatched on the connection, and
+    /// other threads trying to do so will block.
+    pub fn borrow_message(&self) -> Option<BorrowedMessage> {
+        let p = unsafe { ffi::dbus_connection_borrow_message(self.conn()) };
+        if p.is_null() { return None };
+        Some(BorrowedMessage { conn: self, msg: Some(Message::from_ptr(p, true)) })
+    }
+
+    /// Reads from and writes to the socket, blocking for at most `timeout` if there is nothing to do.
+    ///
+    /// Incoming messages are put in libdbus' queue, but not dispatched; call `dispatch` for that.
+    /// Together with `dispatch` and `next_dispatched`, this lets a custom main loop do socket I/O
+    /// and message processing on its own schedule, instead of both at once in `incoming`.
+    ///
+    /// Returns an error if the connection is disconnected.
+    pub fn read_write<T: Into<Timeout>>(&self, timeout: T) -> Result<(), ()> {
+        let r = unsafe { ffi::dbus_connection_read_write(self.conn(), timeout.into().io_ms()) };
+        if r == 0 { Err(()) } else { Ok(()) }
+    }
+
+    /// Dispatches one message from libdbus' queue.
+    ///
+    /// Message hooks, handlers registered with `add_handler` and the message callback are run,
+    /// and unless handled, the message ends up in the queue returned by `next_dispatched`.
+    /// Returns `DataRemains` if there are more messages to dispatch.
+    ///
+    /// # Panics
+    ///
+    /// If called from within a message callback, hook or handler.
+    pub fn dispatch(&self) -> DispatchStatus {
+        if self.i.filter_cb.borrow().is_none() { panic!("Connection::dispatch called recursively or with a MessageCallback set to None"); }
+        let r = unsafe { ffi::dbus_connection_dispatch(self.conn()) };
+        self.check_panic();
+        r
+    }
+
+    /// Returns whether there are messages waiting to be dispatched.
+    ///
+    /// libdbus might read several messages from the socket at once. If so, `DataRemains` is
+    /// returned: `dispatch` needs to be called again, even though the socket is not readable.
+    pub fn dispatch_status(&self) -> DispatchStatus {
+        unsafe { ffi::dbus_connection_get_dispatch_status(self.conn()) }
+    }
+
+    /// Sets a callback to be called when the dispatch status changes, see `dispatch_status`.
+    ///
+    /// For async I/O: when called with `DataRemains`, schedule a call to `dispatch`, as the main loop
+    /// will not otherwise be woken up by the socket becoming readable. Do not dispatch from within
+    /// the callback itself.
+    ///
+    /// The callback might be called from another thread, e g one sending through a `Sender`,
+    /// so it needs to be `Send`. Setting `None` removes the callback.
+    pub fn set_dispatch_status_callback(&self, f: Option<Box<Fn(DispatchStatus) + Send>>) {
+        let enable = f.is_some();
+        *self.i.dispatch_status_cb.lock().unwrap() = f;
+        let (cb, data): (ffi::DBusDispatchStatusFunction, *mut c_void) =
+            if enable { (Some(dispatch_status_cb), unsafe { mem::transmute(&*self.i) }) } else { (None, ptr::null_mut()) };
+        unsafe { ffi::dbus_connection_set_dispatch_status_function(self.conn(), cb, data, None) };
+    }
+
+    /// Returns the next message that has been dispatched, but not handled, without doing any I/O.
+    ///
+    /// See `read_write` and `dispatch`.
+    pub fn next_dispatched(&self) -> Option<Message> { self.next_msg() }
+
+    /// Returns the file descriptor of the underlying socket, if any.
+    ///
+    /// This is useful for simple main loops: put the fd in your select/poll set (for reading), and
+    /// call `process` when it becomes readable. If you also need to know when the socket becomes
+    /// writable, or want to handle timeouts, use `watch_fds` and `watch_timeouts` instead.
+    ///
+    /// Returns None if the connection is not connected to a unix socket.
+    pub fn unix_fd(&self) -> Option<RawFd> {
+        let mut fd: c_int = -1;
+        let r = unsafe { ffi::dbus_connection_get_unix_fd(self.conn(), &mut fd) };
+        if r == 0 || fd < 0 { None } else { Some(fd as RawFd) }
+    }
+
+    /// Returns true if values of this type can be sent over the connection.
+    ///
+    /// All types can be sent, except `ArgType::UnixFd`: passing file descriptors needs a unix
+    /// socket transport, and both sides must have agreed on it during authentication. Check this
+    /// before sending an `OwnedFd`, to be able to fall back to something else, e g over TCP.
+    pub fn can_send_type(&self, t: ArgType) -> bool {
+        unsafe { ffi::dbus_connection_can_send_type(self.conn(), t as c_int) != 0 }
+    }
+
+    /// Returns true if the connection has been authenticated.
+    pub fn is_authenticated(&self) -> bool {
+        unsafe { ffi::dbus_connection_get_is_authenticated(self.conn()) != 0 }
+    }
+
+    /// Returns true unless the peer has authenticated as a specific user.
+    ///
+    /// This is mostly useful on the server side of a peer-to-peer connection that allows
+    /// anonymous clients. On the client side, the server's user is not known, so this is true.
+    pub fn is_anonymous(&self) -> bool {
+        unsafe { ffi::dbus_connection_get_is_anonymous(self.conn()) != 0 }
+    }
+
+    /// The globally unique ID of the server we authenticated with, if known.
+    ///
+    /// This is the `guid` in the server's address.
+    pub fn server_id(&self) -> Option<String> {
+        let p = unsafe { ffi::dbus_connection_get_server_id(self.conn()) };
+        if p.is_null() { return None };
+        let r = c_str_to_slice(&(p as *const c_char)).map(|s| s.to_string());
+        unsafe { ffi::dbus_free(p as *mut c_void) };
+        r
+    }
+
+    /// Reads and writes to the socket without blocking, then returns the incoming messages.
+    ///
+    /// The returned iterator will return pending items only, never block for new events.
+    /// Call this when the fd returned from `unix_fd` is readable; make sure to exhaust the iterator,
+    /// otherwise some messages might be left in the queue until the next time the socket is readable.
+    pub fn process(&self) -> ConnMsgs<&Self> {
+        unsafe { ffi::dbus_connection_read_write(self.conn(), 0) };
+        ConnMsgs { conn: &self, timeout_ms: None }
     }
 
     /// Register an object path.
@@ -417,7 +1270,9 @@ impl Connection {
             let user_data: *mut c_void = mem::transmute(&*self.i);
             ffi::dbus_connection_try_register_object_path(self.conn(), p.as_ptr(), &vtable, user_data, e.get_mut())
         };
-        if r == 0 { Err(e) } else { Ok(()) }
+        if r == 0 { return Err(e) };
+        self.i.object_paths.borrow_mut().push(path.into());
+        Ok(())
     }
 
     /// Unregister an object path.
@@ -425,9 +1280,12 @@ impl Connection {
         let p = to_c_str(path);
         let r = unsafe { ffi::dbus_connection_unregister_object_path(self.conn(), p.as_ptr()) };
         if r == 0 { panic!("Out of memory"); }
+        self.i.object_paths.borrow_mut().retain(|x| x != path);
     }
 
     /// List registered object paths.
+    ///
+    /// Returns the names of the children of `path` (e g "b" for "/a/b"), see `list_registered`.
     pub fn list_registered_object_paths(&self, path: &str) -> Vec<String> {
         let p = to_c_str(path);
         let mut clist: *mut *mut c_char = ptr::null_mut();
@@ -448,12 +1306,56 @@ impl Connection {
         v
     }
 
+    /// Lists the direct children of `path` that have object paths registered at or below them.
+    ///
+    /// E g if "/a/b" and "/a/c/d" are registered, the children of "/a" are "/a/b" and "/a/c".
+    /// Useful for listing child nodes when introspecting `path`.
+    pub fn list_registered(&self, path: &Path) -> Vec<Path<'static>> {
+        let prefix = if path.ends_with('/') { path.to_string() } else { format!("{}/", path) };
+        self.list_registered_object_paths(path).into_iter()
+            .map(|n| Path::new(format!("{}{}", prefix, n)).unwrap())
+            .collect()
+    }
+
     /// Register a name.
-    pub fn register_name(&self, name: &str, flags: u32) -> Result<RequestNameReply, Error> {
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use dbus::{Connection, BusType, NameFlag, NameFlags, RequestNameReply};
+    ///
+    /// let c = Connection::get_private(BusType::Session).unwrap();
+    /// let r = c.register_name("com.example.dbusrs.doctest", NameFlag::AllowReplacement | NameFlag::DoNotQueue).unwrap();
+    /// assert_eq!(r, RequestNameReply::PrimaryOwner);
+    /// c.register_name("com.example.dbusrs.doctest2", NameFlags::empty()).unwrap();
+    /// ```
+    pub fn register_name<F: Into<NameFlags>>(&self, name: &str, flags: F) -> Result<RequestNameReply, Error> {
         let mut e = Error::empty();
         let n = to_c_str(name);
-        let r = unsafe { ffi::dbus_bus_request_name(self.conn(), n.as_ptr(), flags, e.get_mut()) };
-        if r == -1 { Err(e) } else { Ok(unsafe { mem::transmute(r) }) }
+        let r = unsafe { ffi::dbus_bus_request_name(self.conn(), n.as_ptr(), flags.into().bits(), e.get_mut()) };
+        if r == -1 { return Err(e) };
+        let reply = match r {
+            1 => RequestNameReply::PrimaryOwner,
+            2 => RequestNameReply::InQueue,
+            3 => RequestNameReply::Exists,
+            4 => RequestNameReply::AlreadyOwner,
+            _ => return Err(unexpected_reply("RequestName", r)),
+        };
+        if reply != RequestNameReply::Exists {
+            let mut names = self.i.owned_names.borrow_mut();
+            if !names.iter().any(|n| n == name) { names.push(name.into()) };
+        }
+        Ok(reply)
+    }
+
+    /// Register a name, and return a guard that releases it when dropped.
+    ///
+    /// This makes sure a service relinquishes its well-known name when the scope ends
+    /// (or a panic unwinds). Otherwise works like `register_name`; the reply is available
+    /// from `NameGuard::reply`.
+    pub fn register_name_guard<F: Into<NameFlags>>(&self, name: &str, flags: F) -> Result<NameGuard, Error> {
+        let r = try!(self.register_name(name, flags));
+        Ok(NameGuard { conn: self, name: Some(name.into()), reply: r })
     }
 
     /// Release a name.
@@ -461,7 +1363,14 @@ impl Connection {
         let mut e = Error::empty();
         let n = to_c_str(name);
         let r = unsafe { ffi::dbus_bus_release_name(self.conn(), n.as_ptr(), e.get_mut()) };
-        if r == -1 { Err(e) } else { Ok(unsafe { mem::transmute(r) }) }
+        if r == -1 { return Err(e) };
+        self.i.owned_names.borrow_mut().retain(|n| n != name);
+        match r {
+            1 => Ok(ReleaseNameReply::Released),
+            2 => Ok(ReleaseNameReply::NonExistent),
+            3 => Ok(ReleaseNameReply::NotOwner),
+            _ => Err(unexpected_reply("ReleaseName", r)),
+        }
     }
 
     /// Add a match rule to match messages on the message bus.
@@ -475,6 +1384,15 @@ impl Connection {
         if e.name().is_some() { Err(e) } else { Ok(()) }
     }
 
+    /// Add a match rule, and return a guard that removes it when dropped.
+    ///
+    /// This makes sure the match rule does not leak when the scope ends (or a panic unwinds),
+    /// which matters for long-running programs. Otherwise works like `add_match`.
+    pub fn add_match_guard(&self, rule: &str) -> Result<MatchGuard, Error> {
+        try!(self.add_match(rule));
+        Ok(MatchGuard { conn: self, rule: Some(rule.into()) })
+    }
+
     /// Remove a match rule to match messages on the message bus.
     pub fn remove_match(&self, rule: &str) -> Result<(), Error> {
         let mut e = Error::empty();
@@ -500,11 +1418,30 @@ impl Connection {
         ConnectionItems::new(self, None, true)
     }
 
+    /// Async I/O: Get an up-to-date list of timeouts to watch.
+    ///
+    /// Only enabled timeouts are returned.
+    pub fn watch_timeouts(&self) -> Vec<WatchTimeout> {
+        self.i.timeouts.as_ref().unwrap().get_enabled_timeouts()
+    }
+
+    /// Async I/O: Call this function when a timeout has expired, i e,
+    /// `interval_ms` milliseconds after the timeout was added or last handled.
+    /// The returned iterator will return pending items only, never block for new events.
+    ///
+    /// Calling this for a timeout that no longer exists is a no-op.
+    pub fn timeout_handle(&self, t: &WatchTimeout) -> ConnectionItems {
+        self.i.timeouts.as_ref().unwrap().timeout_handle(t.id());
+        ConnectionItems::new(self, None, true)
+    }
+
 
     /// Create a convenience struct for easier calling of many methods on the same destination and path.
-    pub fn with_path<'a, D: Into<BusName<'a>>, P: Into<Path<'a>>>(&'a self, dest: D, path: P, timeout_ms: i32) ->
+    ///
+    /// The timeout can be a `Timeout`, a `Duration`, or an i32 in milliseconds (-1 for the default timeout).
+    pub fn with_path<'a, D: Into<BusName<'a>>, P: Into<Path<'a>>, T: Into<Timeout>>(&'a self, dest: D, path: P, timeout: T) ->
         ConnPath<'a, &'a Connection> {
-        ConnPath { conn: self, dest: dest.into(), path: path.into(), timeout: timeout_ms }
+        ConnPath { conn: self, dest: dest.into(), path: path.into(), timeout: timeout.into(), allow_interactive_authorization: false }
     }
 
     /// Replace the default message callback. Returns the previously set callback.
@@ -551,7 +1488,7 @@ impl Connection {
     ///
     /// Chain our callback to filter out some messages before `iter().next()`:
     ///
-    /// ```
+    /// ```rust,no_run
     /// use dbus::{Connection, BusType, MessageType};
     /// let c = Connection::get_private(BusType::Session).unwrap();
     /// // Take the previously set callback
@@ -593,13 +1530,127 @@ impl Connection {
     /// see https://github.com/diwic/dbus-rs/issues/99 for additional info.)
     pub fn set_watch_callback(&self, f: Box<Fn(Watch) + Send>) { self.i.watches.as_ref().unwrap().set_on_update(f); }
 
+    /// Sets a callback to be called if a timeout is added, removed or toggled.
+    ///
+    /// For async I/O. The timeout changed is provided as a parameter; if it is not enabled, it should
+    /// no longer be handled. When the timeout is (re-)enabled, the interval starts counting from now.
+    ///
+    /// The same caveats as for `set_watch_callback` apply.
+    pub fn set_timeout_callback(&self, f: Box<Fn(WatchTimeout) + Send>) { self.i.timeouts.as_ref().unwrap().set_on_update(f); }
+
+    /// Turns logging of sent and received messages on or off for this connection. It is on by default.
+    ///
+    /// Every message sent through this connection and every message received is logged
+    /// at the debug level, with the target `dbus::wire`. Messages sent through a `Sender`
+    /// are not logged.
+    #[cfg(feature = "log")]
+    pub fn set_log_traffic(&self, enable: bool) { self.i.log_traffic.set(enable) }
+
+    /// Returns whether messages sent and received on this connection are logged, see `set_log_traffic`.
+    #[cfg(feature = "log")]
+    pub fn log_traffic(&self) -> bool { self.i.log_traffic.get() }
+
+    #[cfg(feature = "log")]
+    fn log_msg(&self, dir: &str, m: &Message) { if self.i.log_traffic.get() { ::wirelog::log_msg(dir, m) } }
+
+    #[cfg(not(feature = "log"))]
+    #[inline(always)]
+    fn log_msg(&self, _: &str, _: &Message) {}
+
+    /// Limits the number of incoming messages queued up, waiting to be returned from the iterators.
+    ///
+    /// By default the queue is unbounded, so a long-running process that stops consuming
+    /// messages while signals keep coming in will grow without limit. With a capacity set,
+    /// `policy` decides what happens to the messages that do not fit. Method calls that are
+    /// dropped get a `LimitsExceeded` error reply, so that the caller does not have to wait
+    /// for a timeout.
+    ///
+    /// Setting `None` removes the limit. The limit only applies to messages queued by the
+    /// default message callback, see `replace_message_callback`.
+    pub fn set_max_pending_items(&self, max: Option<usize>, policy: QueuePolicy) {
+        self.i.max_pending.set(max.map(|m| (m, policy)));
+        if self.i.pending_full() != Some(QueuePolicy::Block) { self.i.block_reading(false) };
+    }
+
+    /// The capacity and policy of the incoming message queue, see `set_max_pending_items`.
+    pub fn max_pending_items(&self) -> Option<(usize, QueuePolicy)> { self.i.max_pending.get() }
+
+    /// Sets the largest message, in bytes, that can be received on the connection.
+    ///
+    /// If a peer sends a larger message, the connection is disconnected. libdbus' default is 32 MiB,
+    /// which is more than a service handling untrusted peers (e g a peer-to-peer server) might want
+    /// to allocate for each of them.
+    pub fn set_max_message_size(&self, size: usize) {
+        unsafe { ffi::dbus_connection_set_max_message_size(self.conn(), size as c_long) }
+    }
+
+    /// The largest message, in bytes, that can be received, see `set_max_message_size`.
+    pub fn max_message_size(&self) -> usize {
+        unsafe { ffi::dbus_connection_get_max_message_size(self.conn()) as usize }
+    }
+
+    /// Sets the largest number of unix file descriptors that a received message can contain.
+    pub fn set_max_message_unix_fds(&self, n: usize) {
+        unsafe { ffi::dbus_connection_set_max_message_unix_fds(self.conn(), n as c_long) }
+    }
+
+    /// The largest number of unix file descriptors a received message can contain.
+    pub fn max_message_unix_fds(&self) -> usize {
+        unsafe { ffi::dbus_connection_get_max_message_unix_fds(self.conn()) as usize }
+    }
+
+    /// Sets the total number of bytes that received messages can use at once.
+    ///
+    /// Messages count towards the limit until they are dropped; while it is exceeded, no more
+    /// messages are read from the socket. This does not disconnect the peer, it just slows it down.
+    /// libdbus' default is 63 MiB.
+    pub fn set_max_received_size(&self, size: usize) {
+        // While QueuePolicy::Block has stopped reading, the limit is restored when there is room again.
+        if self.i.blocked_max_received.get().is_some() {
+            self.i.blocked_max_received.set(Some(size as c_long));
+        } else {
+            unsafe { ffi::dbus_connection_set_max_received_size(self.conn(), size as c_long) }
+        }
+    }
+
+    /// The total number of bytes received messages can use at once, see `set_max_received_size`.
+    pub fn max_received_size(&self) -> usize {
+        match self.i.blocked_max_received.get() {
+            Some(size) => size as usize,
+            None => unsafe { ffi::dbus_connection_get_max_received_size(self.conn()) as usize },
+        }
+    }
+
+    /// Sets the total number of unix file descriptors that received messages can hold at once.
+    pub fn set_max_received_unix_fds(&self, n: usize) {
+        unsafe { ffi::dbus_connection_set_max_received_unix_fds(self.conn(), n as c_long) }
+    }
+
+    /// The total number of unix file descriptors received messages can hold at once.
+    pub fn max_received_unix_fds(&self) -> usize {
+        unsafe { ffi::dbus_connection_get_max_received_unix_fds(self.conn()) as usize }
+    }
+
+    /// The number of incoming messages that have been dropped because the queue was full.
+    pub fn dropped_items(&self) -> u64 { self.i.dropped_items.get() }
+
+    fn drop_pending(&self, m: &Message) {
+        self.i.dropped_items.set(self.i.dropped_items.get() + 1);
+        if m.msg_type() != MessageType::MethodCall || m.get_no_reply() { return };
+        if let Some(r) = Message::new_error(m, "org.freedesktop.DBus.Error.LimitsExceeded", "Incoming message queue is full") {
+            let _ = self.send(r);
+        }
+    }
+
     fn check_panic(&self) {
         let p = mem::replace(&mut *self.i.filter_cb_panic.borrow_mut(), Ok(()));
         if let Err(perr) = p { panic::resume_unwind(perr); }
     }
 
     fn next_msg(&self) -> Option<Message> {
-        while let Some(msg) = self.i.pending_items.borrow_mut().pop_front() {
+        loop {
+            let msg = match self.i.pending_items.borrow_mut().pop_front() { Some(m) => m, None => break };
+            if self.i.pending_full().is_none() { self.i.block_reading(false) };
             let mut v: MsgHandlerList = mem::replace(&mut *self.i.handlers.borrow_mut(), vec!());
             let b = msghandler_process(&mut v, &msg, self);
             let mut v2 = self.i.handlers.borrow_mut();
@@ -612,12 +1663,257 @@ impl Connection {
 
 }
 
+/// A match rule added to the message bus, which is removed when dropped.
+///
+/// You get one by calling `Connection::add_match_guard`.
+#[derive(Debug)]
+pub struct MatchGuard<'a> {
+    conn: &'a Connection,
+    rule: Option<String>,
+}
+
+impl<'a> MatchGuard<'a> {
+    /// The match rule.
+    pub fn rule(&self) -> &str { self.rule.as_ref().unwrap() }
+
+    /// Consumes the guard without removing the match rule, returning the rule.
+    pub fn into_rule(mut self) -> String { self.rule.take().unwrap() }
+}
+
+impl<'a> Drop for MatchGuard<'a> {
+    fn drop(&mut self) {
+        // Nothing sensible to do with an error here.
+        if let Some(ref rule) = self.rule { let _ = self.conn.remove_match(rule); }
+    }
+}
+
+/// A name registered on the message bus, which is released when dropped.
+///
+/// You get one by calling `Connection::register_name_guard`.
+#[derive(Debug)]
+pub struct NameGuard<'a> {
+    conn: &'a Connection,
+    name: Option<String>,
+    reply: RequestNameReply,
+}
+
+impl<'a> NameGuard<'a> {
+    /// The registered name.
+    pub fn name(&self) -> &str { self.name.as_ref().unwrap() }
+
+    /// The reply to the name request, e g whether we became the primary owner or were queued.
+    pub fn reply(&self) -> RequestNameReply { self.reply }
+
+    /// Consumes the guard without releasing the name, returning the name.
+    pub fn into_name(mut self) -> String { self.name.take().unwrap() }
+}
+
+impl<'a> Drop for NameGuard<'a> {
+    fn drop(&mut self) {
+        // Nothing sensible to do with an error here.
+        if let Some(ref name) = self.name { let _ = self.conn.release_name(name); }
+    }
+}
+
+fn flush_timeout(conn: *mut ffi::DBusConnection, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while unsafe { ffi::dbus_connection_has_messages_to_send(conn) != 0 } {
+        let now = Instant::now();
+        if now >= deadline { return false };
+        // Round up, so that we don't spin on a zero timeout for the last fraction of a millisecond.
+        let ms = Timeout::Duration(deadline - now + Duration::from_millis(1)).io_ms();
+        if unsafe { ffi::dbus_connection_read_write(conn, ms) } == 0 { return false };
+    }
+    true
+}
+
+/// A message peeked at in the incoming queue, see `Connection::borrow_message`.
+///
+/// Dereferences to the message. Unless stolen, it is put back first in the queue when dropped.
+pub struct BorrowedMessage<'a> {
+    conn: &'a Connection,
+    // Holds a reference of its own; the queue's reference is owned by the connection until stolen.
+    msg: Option<Message>,
+}
+
+impl<'a> BorrowedMessage<'a> {
+    /// Removes the message from the incoming queue and takes ownership of it.
+    pub fn steal(mut self) -> Message {
+        let m = self.msg.take().unwrap();
+        unsafe { ffi::dbus_connection_steal_borrowed_message(self.conn.conn(), m.ptr()) };
+        // We now own the queue's reference as well as our own.
+        Message::from_ptr(m.ptr(), false)
+    }
+}
+
+impl<'a> ops::Deref for BorrowedMessage<'a> {
+    type Target = Message;
+    fn deref(&self) -> &Message { self.msg.as_ref().unwrap() }
+}
+
+impl<'a> Drop for BorrowedMessage<'a> {
+    fn drop(&mut self) {
+        if let Some(m) = self.msg.take() {
+            let p = m.ptr();
+            drop(m);
+            unsafe { ffi::dbus_connection_return_message(self.conn.conn(), p) };
+        }
+    }
+}
+
+impl<'a> fmt::Debug for BorrowedMessage<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "BorrowedMessage({:?})", self.msg.as_ref().unwrap())
+    }
+}
+
+/// A handle for sending messages over a connection, from any thread.
+///
+/// You get one by calling `Connection::sender`. It can be cloned and sent to other threads,
+/// e g worker threads that need to emit signals.
+///
+/// Sending only puts the message in the outgoing queue. The message is written
+/// the next time the connection reads or writes (e g when iterating over `Connection::incoming`),
+/// or when `flush` is called.
+///
+/// If the connection has been dropped, messages are silently discarded.
+pub struct Sender(*mut ffi::DBusConnection);
+
+// libdbus connections are thread safe, since dbus_threads_init_default has been called.
+unsafe impl Send for Sender {}
+unsafe impl Sync for Sender {}
+
+impl Sender {
+    /// Puts a message into the outgoing queue.
+    ///
+    /// Returns a serial number that can be used to match against a reply.
+    pub fn send(&self, msg: Message) -> Result<u32, ()> {
+        let mut serial = 0u32;
+        let r = unsafe { ffi::dbus_connection_send(self.0, msg.ptr(), &mut serial) };
+        if r == 0 { return Err(()); }
+        Ok(serial)
+    }
+
+    /// Emits a signal.
+    pub fn emit<S: SignalArgs>(&self, path: &Path, signal: &S) -> Result<u32, ()> {
+        self.send(signal.to_emit_message(path))
+    }
+
+    /// Blocks until the outgoing queue is empty.
+    ///
+    /// Note: If another thread is currently blocking on the connection, e g waiting for
+    /// incoming messages, this might block until that thread returns.
+    pub fn flush(&self) {
+        unsafe { ffi::dbus_connection_flush(self.0) }
+    }
+
+    /// Blocks until the outgoing queue is empty, or the timeout has passed.
+    ///
+    /// Returns true if the queue was emptied, false on timeout or if the connection was disconnected.
+    pub fn flush_timeout(&self, timeout: Duration) -> bool {
+        flush_timeout(self.0, timeout)
+    }
+
+    /// Returns true if there are messages in the outgoing queue, not yet written to the socket.
+    pub fn has_messages_to_send(&self) -> bool {
+        unsafe { ffi::dbus_connection_has_messages_to_send(self.0) != 0 }
+    }
+
+    /// The approximate number of bytes in the outgoing queue.
+    pub fn outgoing_size(&self) -> usize {
+        unsafe { ffi::dbus_connection_get_outgoing_size(self.0) as usize }
+    }
+
+    /// Reserves space in the outgoing queue for sending one message later, see `PreallocatedSend`.
+    ///
+    /// Returns an error if out of memory.
+    pub fn preallocate_send(&self) -> Result<PreallocatedSend, ()> {
+        PreallocatedSend::new(self.0)
+    }
+}
+
+/// Space reserved in the outgoing queue of a connection, for sending one message.
+///
+/// Sending a message normally allocates memory, and can therefore fail. Preallocate the
+/// space up front (with `Connection::preallocate_send` or `Sender::preallocate_send`), and
+/// sending is guaranteed to succeed, which is useful when emitting signals from a
+/// latency-critical path. The message itself should also be created up front.
+///
+/// Like with `Sender`, message hooks are not run and the message is not logged.
+/// If the reserved space is not used, it is released when this is dropped.
+pub struct PreallocatedSend {
+    conn: *mut ffi::DBusConnection,
+    p: *mut ffi::DBusPreallocatedSend,
+}
+
+// Only the connection it was allocated for can use it, and libdbus connections are thread safe.
+unsafe impl Send for PreallocatedSend {}
+
+impl PreallocatedSend {
+    fn new(conn: *mut ffi::DBusConnection) -> Result<PreallocatedSend, ()> {
+        let p = unsafe { ffi::dbus_connection_preallocate_send(conn) };
+        if p.is_null() { return Err(()) };
+        Ok(PreallocatedSend { conn: unsafe { ffi::dbus_connection_ref(conn) }, p: p })
+    }
+
+    /// Puts a message into the outgoing queue, using the reserved space. This cannot fail.
+    ///
+    /// Returns a serial number that can be used to match against a reply.
+    pub fn send(mut self, msg: Message) -> u32 {
+        let mut serial = 0u32;
+        let p = mem::replace(&mut self.p, ptr::null_mut());
+        unsafe { ffi::dbus_connection_send_preallocated(self.conn, p, msg.ptr(), &mut serial) };
+        serial
+    }
+}
+
+impl Drop for PreallocatedSend {
+    fn drop(&mut self) {
+        if !self.p.is_null() { unsafe { ffi::dbus_connection_free_preallocated_send(self.conn, self.p) } };
+        unsafe { ffi::dbus_connection_unref(self.conn) }
+    }
+}
+
+impl fmt::Debug for PreallocatedSend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "D-Bus PreallocatedSend")
+    }
+}
+
+impl Clone for Sender {
+    fn clone(&self) -> Self {
+        Sender(unsafe { ffi::dbus_connection_ref(self.0) })
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        unsafe { ffi::dbus_connection_unref(self.0) }
+    }
+}
+
+impl fmt::Debug for Sender {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "D-Bus Sender")
+    }
+}
+
 impl Drop for Connection {
     fn drop(&mut self) {
+        // Detached by into_raw.
+        if self.conn().is_null() { return };
+        // Closing changes the dispatch status, and someone else might still hold a reference
+        // to the connection; either way the callback must not outlive us.
         unsafe {
-            ffi::dbus_connection_close(self.conn());
-            ffi::dbus_connection_unref(self.conn());
+            ffi::dbus_connection_set_dispatch_status_function(self.conn(), None, ptr::null_mut(), None);
+            ffi::dbus_connection_set_data(self.conn(), wrapped_slot(), ptr::null_mut(), None);
         }
+        if self.i.close_on_drop.get() {
+            unsafe { ffi::dbus_connection_close(self.conn()) };
+        } else {
+            self.detach();
+        }
+        unsafe { ffi::dbus_connection_unref(self.conn()) };
     }
 }
 
@@ -665,6 +1961,115 @@ pub trait MsgHandler {
     fn handle_msg(&mut self, _msg: &Message) -> Option<MsgHandlerResult> { None }
 }
 
+/// A message handler that calls a closure for each incoming signal it recognizes.
+///
+/// Signals are recognized, and turned into events of type `E`, by a parse function. This is how
+/// `ServiceWatcher`, `NameTracker` and `PropSubscription` make their handlers, but it can be used
+/// for any signal. Add the handler to the connection with `Connection::add_handler`.
+pub struct SignalHandler<E, F> {
+    parse: Box<FnMut(&Message) -> Option<E>>,
+    consume: bool,
+    f: F,
+}
+
+impl<E, F: FnMut(E)> SignalHandler<E, F> {
+    /// Creates a handler that calls `f` with everything `parse` returns.
+    ///
+    /// If `consume` is true, the signals that `parse` recognizes are no longer returned
+    /// from the connection's iterators, nor passed on to handlers added after this one.
+    pub fn new<P: FnMut(&Message) -> Option<E> + 'static>(parse: P, consume: bool, f: F) -> Self {
+        SignalHandler { parse: Box::new(parse), consume: consume, f: f }
+    }
+}
+
+impl<E, F> fmt::Debug for SignalHandler<E, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "SignalHandler {{ consume: {} }}", self.consume)
+    }
+}
+
+impl<E, F: FnMut(E)> MsgHandler for SignalHandler<E, F> {
+    fn handler_type(&self) -> MsgHandlerType { MsgHandlerType::MsgType(MessageType::Signal) }
+    fn handle_msg(&mut self, msg: &Message) -> Option<MsgHandlerResult> {
+        let e = (self.parse)(msg)?;
+        (self.f)(e);
+        if self.consume { Some(MsgHandlerResult { handled: true, done: false, reply: vec!() }) } else { None }
+    }
+}
+
+/// A hook observing all messages sent and received on a connection, see `Connection::add_hook`.
+///
+/// Both methods return whether the message may pass; return false to veto it.
+/// The default implementations let all messages pass.
+pub trait MsgHook {
+    /// Called for every message about to be sent.
+    fn outgoing(&mut self, _msg: &Message) -> bool { true }
+
+    /// Called for every message received, including replies to blocking method calls.
+    fn incoming(&mut self, _msg: &Message) -> bool { true }
+}
+
+// The timeout libdbus uses for method calls with DBUS_TIMEOUT_USE_DEFAULT.
+const DEFAULT_CALL_TIMEOUT_MS: u64 = 25000;
+
+/// The ways a blocking method call can fail, as returned by `Connection::call_blocking`.
+#[derive(Debug)]
+pub enum CallError {
+    /// No reply was received before the timeout expired.
+    Timeout,
+    /// The peer went away (e g disconnected from the bus) without replying.
+    NoReply,
+    /// Our connection is disconnected.
+    Disconnected,
+    /// Any other error, usually an error reply sent by the remote side.
+    Remote(Error),
+}
+
+impl CallError {
+    /// Returns true for failures where retrying the same call might succeed,
+    /// i e `Timeout` and `NoReply`.
+    pub fn is_transient(&self) -> bool {
+        match *self {
+            CallError::Timeout | CallError::NoReply => true,
+            CallError::Disconnected | CallError::Remote(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CallError::Timeout => write!(f, "Method call timed out"),
+            CallError::NoReply => write!(f, "Peer did not send a reply"),
+            CallError::Disconnected => write!(f, "Connection is disconnected"),
+            CallError::Remote(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl ::std::error::Error for CallError {
+    fn description(&self) -> &str { "D-Bus method call failed" }
+}
+
+impl From<CallError> for Error {
+    fn from(e: CallError) -> Error {
+        let name = match e {
+            CallError::Timeout | CallError::NoReply => "org.freedesktop.DBus.Error.NoReply",
+            CallError::Disconnected => "org.freedesktop.DBus.Error.Disconnected",
+            CallError::Remote(e) => return e,
+        };
+        Error::new_custom(name, &e.to_string())
+    }
+}
+
+fn unexpected_reply(method: &str, r: c_int) -> Error {
+    Error::new_custom("org.freedesktop.DBus.Error.Failed", &format!("Unexpected {} reply {}", method, r))
+}
+
+pub (crate) fn vetoed_error(what: &str) -> Error {
+    Error::new_custom("org.freedesktop.DBus.Error.Failed", &format!("{} was vetoed by a hook", what))
+}
+
 /// The result from MsgHandler::handle.
 #[derive(Debug, Default)]
 pub struct MsgHandlerResult {
@@ -717,7 +2122,8 @@ impl<'a, F: FnOnce(Result<&Message, Error>) + 'a> MsgHandler for MessageReply<F>
 #[test]
 fn message_reply() {
     use std::{cell, rc};
-    let c = Connection::get_private(BusType::Session).unwrap();
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
     assert!(c.is_connected());
     let m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "ListNames").unwrap();
     let quit = rc::Rc::new(cell::Cell::new(false));
@@ -731,3 +2137,670 @@ fn message_reply() {
     assert!(false);
 }
 
+
+#[test]
+fn unix_fd_process() {
+    use libc;
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let fd = c.unix_fd().unwrap();
+    let m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetId").unwrap();
+    let serial = c.send(m).unwrap();
+    loop {
+        let mut pfd = libc::pollfd { fd: fd, events: libc::POLLIN, revents: 0 };
+        assert_eq!(unsafe { libc::poll(&mut pfd, 1, 1000) }, 1);
+        for msg in c.process() {
+            if msg.get_reply_serial() == Some(serial) { return; }
+        }
+    }
+}
+
+#[test]
+fn sender_thread() {
+    use std::thread;
+    use stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged as PC;
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let c2 = bus.connect().unwrap();
+    c2.add_match(&PC::match_str(Some(&c.unique_name().into()), None)).unwrap();
+
+    let sender = c.sender();
+    thread::spawn(move || {
+        let mut pc = PC::default();
+        pc.interface_name = "com.example.sendertest".into();
+        let s = sender.clone();
+        s.emit(&"/sendertest".into(), &pc).unwrap();
+        s.flush();
+    }).join().unwrap();
+
+    for m in c2.incoming(1000) {
+        if let Some(pc) = PC::from_message(&m) {
+            assert_eq!(&*m.path().unwrap(), "/sendertest");
+            assert_eq!(pc.interface_name, "com.example.sendertest");
+            return;
+        }
+    }
+    panic!("signal not received");
+}
+
+#[test]
+fn match_guard() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let c2 = bus.connect().unwrap();
+    let rule = format!("type='signal',sender='{}',interface='com.example.matchguard'", c.unique_name());
+    let emit = || {
+        let m = Message::new_signal("/matchguard", "com.example.matchguard", "Ping").unwrap();
+        c.send(m).unwrap();
+    };
+    let received = || c2.incoming(300).any(|m| m.interface().map(|i| &*i == "com.example.matchguard").unwrap_or(false));
+    {
+        let g = c2.add_match_guard(&rule).unwrap();
+        assert_eq!(g.rule(), rule);
+        emit();
+        assert!(received());
+    }
+    emit();
+    assert!(!received());
+}
+
+#[test]
+fn name_guard() {
+    use DBusProxy;
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let bus = DBusProxy::new(&c);
+    let n = "com.example.dbusrs.nameguard";
+    {
+        let g = c.register_name_guard(n, 0).unwrap();
+        assert_eq!(g.name(), n);
+        assert_eq!(g.reply(), RequestNameReply::PrimaryOwner);
+        assert!(bus.name_has_owner(n).unwrap());
+    }
+    assert!(!bus.name_has_owner(n).unwrap());
+    let g = c.register_name_guard(n, 0).unwrap();
+    assert_eq!(g.into_name(), n);
+    assert!(bus.name_has_owner(n).unwrap());
+    assert_eq!(c.release_name(n).unwrap(), ReleaseNameReply::Released);
+}
+
+#[test]
+fn activate_service() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    // The D-Bus server only activates names that have a .service file, even if already owned.
+    let e = c.activate_service("com.example.dbusrs.nonexistent").unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::ServiceUnknown);
+}
+
+#[test]
+fn connection_from_env() {
+    assert_eq!(Env::Session.address(), ::std::env::var("DBUS_SESSION_BUS_ADDRESS").ok());
+    assert_eq!(Env::Starter.bus_type(), BusType::Starter);
+    assert_eq!(Env::System.var_name(), "DBUS_SYSTEM_BUS_ADDRESS");
+    // This is about finding the session bus, so it cannot use a test bus.
+    if Env::Session.address().is_none() { return };
+    let c = Connection::from_env(Env::Session).unwrap();
+    assert!(c.unique_name().starts_with(":"));
+}
+
+#[test]
+fn peer_credentials() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let uid = unsafe { libc::getuid() };
+    assert_eq!(c.peer_unix_user(&c.unique_name()).unwrap(), uid);
+    assert_eq!(c.peer_unix_process_id(&c.unique_name()).unwrap(), ::std::process::id());
+    assert!(c.peer_unix_user("com.example.dbusrs.nonexistent").is_err());
+    // As a client, we typically do not know the credentials of the D-Bus server.
+    if let Some(u) = c.unix_user() { assert_eq!(u, uid) };
+}
+
+#[test]
+fn peer_ping() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    c.ping("org.freedesktop.DBus", 2000).unwrap();
+    let e = c.ping("com.example.peerping.nonexistent", 2000).unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.ServiceUnknown"));
+    let id = c.peer_machine_id("org.freedesktop.DBus").unwrap();
+    assert_eq!(id.len(), 32);
+    assert!(id.chars().all(|c| c.is_digit(16)));
+}
+
+#[test]
+fn msg_hooks() {
+    use std::rc::Rc;
+    let bus = ::testing::shared_bus();
+    struct Recorder(&'static str, Rc<RefCell<Vec<String>>>);
+    impl MsgHook for Recorder {
+        fn outgoing(&mut self, m: &Message) -> bool {
+            self.1.borrow_mut().push(format!("{} out {}", self.0, m.member().map(|x| x.to_string()).unwrap_or_default()));
+            m.member().map_or(true, |x| &*x != "Forbidden")
+        }
+        fn incoming(&mut self, m: &Message) -> bool {
+            if m.msg_type() != MessageType::Signal { return true };
+            self.1.borrow_mut().push(format!("{} in {}", self.0, m.member().map(|x| x.to_string()).unwrap_or_default()));
+            m.member().map_or(true, |x| &*x != "Dropped")
+        }
+    }
+
+    let c = bus.connect().unwrap();
+    c.add_match("type='signal',interface='com.example.hooktest'").unwrap();
+    let log = Rc::new(RefCell::new(vec!()));
+    c.add_hook(Recorder("first", log.clone()));
+    c.add_hook(Recorder("second", log.clone()));
+
+    c.call_method("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetId", (), 2000).unwrap();
+    assert_eq!(*log.borrow(), vec!("first out GetId", "second out GetId"));
+    log.borrow_mut().clear();
+
+    let e = c.call_method("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "Forbidden", (), 2000).unwrap_err();
+    assert_eq!(e.message(), Some("Message was vetoed by a hook"));
+    assert!(c.send(Message::new_signal("/", "com.example.hooktest", "Forbidden").unwrap()).is_err());
+    assert_eq!(*log.borrow(), vec!("first out Forbidden", "first out Forbidden"));
+    log.borrow_mut().clear();
+
+    c.send(Message::new_signal("/", "com.example.hooktest", "Dropped").unwrap()).unwrap();
+    c.send(Message::new_signal("/", "com.example.hooktest", "Kept").unwrap()).unwrap();
+    // The iterator ends when a read only brought the dropped message, so keep iterating.
+    let mut received = vec!();
+    'outer: for _ in 0..10 {
+        for m in c.incoming(200) {
+            if m.interface().map_or(false, |x| &*x == "com.example.hooktest") {
+                received.push(m.member().unwrap().to_string());
+                break 'outer;
+            }
+        }
+    }
+    assert_eq!(received, vec!("Kept"));
+    assert!(log.borrow().contains(&"first in Dropped".to_string()));
+    assert!(!log.borrow().contains(&"second in Dropped".to_string()));
+    assert!(log.borrow().contains(&"second in Kept".to_string()));
+
+    assert!(c.extract_hook().is_some());
+    assert!(c.extract_hook().is_some());
+    assert!(c.extract_hook().is_none());
+}
+
+#[test]
+fn name_replies() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let c2 = bus.connect().unwrap();
+    let n = "com.example.dbusrs.namereplies";
+    assert_eq!(c.release_name(n).unwrap(), ReleaseNameReply::NonExistent);
+    assert_eq!(c.register_name(n, DBusNameFlag::DoNotQueue).unwrap(), RequestNameReply::PrimaryOwner);
+    assert_eq!(c.register_name(n, 0).unwrap(), RequestNameReply::AlreadyOwner);
+    assert_eq!(c2.register_name(n, DBusNameFlag::DoNotQueue).unwrap(), RequestNameReply::Exists);
+    assert_eq!(c2.register_name(n, 0).unwrap(), RequestNameReply::InQueue);
+    assert_eq!(c2.release_name(n).unwrap(), ReleaseNameReply::Released);
+    assert_eq!(c2.release_name(n).unwrap(), ReleaseNameReply::NotOwner);
+    assert_eq!(c.release_name(n).unwrap(), ReleaseNameReply::Released);
+}
+
+#[test]
+fn call_errors() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let m = Message::new_method_call("com.example.dbusrs.nonexistent", "/", "com.example", "Nothing").unwrap();
+    match c.call_blocking(m, 2000) {
+        Err(CallError::Remote(ref e)) if e.kind() == ErrorKind::ServiceUnknown => {},
+        x => panic!("{:?}", x),
+    }
+
+    // Nobody handles calls to ourselves while we are blocking.
+    let m = Message::new_method_call(&*c.unique_name(), "/", "com.example", "Nothing").unwrap();
+    let e = c.call_blocking(m, Duration::from_millis(100)).unwrap_err();
+    assert!(if let CallError::Timeout = e { true } else { false }, "{:?}", e);
+    assert!(e.is_transient());
+    assert_eq!(Error::from(e).kind(), ErrorKind::NoReply);
+
+    let c2 = bus.connect().unwrap();
+    let name = c2.unique_name();
+    let t = thread::spawn(move || {
+        let c = bus.connect().unwrap();
+        let m = Message::new_method_call(&*name, "/", "com.example", "Nothing").unwrap();
+        c.call_blocking(m, 10000).map(|_| ())
+    });
+    // Give the bus time to forward the call to c2, then disconnect c2 without replying.
+    thread::sleep(Duration::from_millis(300));
+    drop(c2);
+    match t.join().unwrap() {
+        Err(CallError::NoReply) => {},
+        x => panic!("{:?}", x),
+    }
+}
+
+#[test]
+fn pending_queue_limit() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    c.incoming(200).count(); // NameAcquired
+    let send_signals = |n: u32| {
+        let dropped = c.dropped_items();
+        for i in 0..n {
+            let mut m = Message::new_signal("/queue", "com.example.dbusrs.Queue", "Item").unwrap().append1(i);
+            m.set_destination(Some(c.unique_name().into()));
+            c.send(m).unwrap();
+        }
+        // Dispatch everything into the queue without consuming it.
+        for _ in 0..10 {
+            unsafe { ffi::dbus_connection_read_write(c.conn(), 100) };
+            while unsafe { ffi::dbus_connection_dispatch(c.conn()) } == ffi::DBusDispatchStatus::DataRemains {};
+            if (c.i.pending_items.borrow().len() as u64) + c.dropped_items() - dropped >= n as u64 { break };
+        }
+        // With QueuePolicy::Block, the rest is only read once there is room in the queue.
+        c.incoming(200).filter_map(|m| m.read1::<u32>().ok()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(c.max_pending_items(), None);
+    c.set_max_pending_items(Some(2), QueuePolicy::DropOldest);
+    assert_eq!(c.max_pending_items(), Some((2, QueuePolicy::DropOldest)));
+    assert_eq!(send_signals(4), vec!(2, 3));
+    assert_eq!(c.dropped_items(), 2);
+
+    c.set_max_pending_items(Some(2), QueuePolicy::DropNewest);
+    assert_eq!(send_signals(4), vec!(0, 1));
+    assert_eq!(c.dropped_items(), 4);
+
+    let max_received = unsafe { ffi::dbus_connection_get_max_received_size(c.conn()) };
+    c.set_max_pending_items(Some(1), QueuePolicy::Block);
+    assert_eq!(send_signals(3), vec!(0, 1, 2));
+    assert_eq!(c.dropped_items(), 4);
+    assert_eq!(unsafe { ffi::dbus_connection_get_max_received_size(c.conn()) }, max_received);
+
+    // Method calls that do not fit get an error reply.
+    c.set_max_pending_items(Some(0), QueuePolicy::DropNewest);
+    let c2 = bus.connect().unwrap();
+    let m = Message::new_method_call(&*c.unique_name(), "/", "com.example", "Nothing").unwrap();
+    let pc = c2.send_with_pending_call(m, 5000).unwrap();
+    for _ in 0..10 {
+        if c.dropped_items() == 5 { break };
+        c.incoming(200).count();
+    }
+    pc.block();
+    let r = pc.steal_reply().unwrap();
+    assert_eq!(r.error_name().as_ref().map(|s| &**s), Some("org.freedesktop.DBus.Error.LimitsExceeded"));
+    c.set_max_pending_items(None, QueuePolicy::Block);
+}
+
+#[test]
+fn preallocated_send() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    c.add_match("interface=com.example.dbusrs.Prealloc,member=Hot").unwrap();
+    let unused = c.preallocate_send().unwrap();
+    drop(unused);
+
+    let p1 = c.preallocate_send().unwrap();
+    let p2 = c.sender().preallocate_send().unwrap();
+    let m1 = Message::new_signal("/hot", "com.example.dbusrs.Prealloc", "Hot").unwrap().append1(1u32);
+    let m2 = Message::new_signal("/hot", "com.example.dbusrs.Prealloc", "Hot").unwrap().append1(2u32);
+    let t = thread::spawn(move || p2.send(m2));
+    let s1 = p1.send(m1);
+    let s2 = t.join().unwrap();
+    assert!(s1 != 0 && s2 != 0 && s1 != s2);
+
+    let mut v = vec!();
+    for m in c.incoming(1000) {
+        if m.member().as_ref().map(|s| &**s) == Some("Hot") { v.push(m.read1::<u32>().unwrap()) };
+        if v.len() == 2 { break };
+    }
+    v.sort();
+    assert_eq!(v, vec!(1, 2));
+}
+
+#[test]
+fn borrow_message() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    c.incoming(200).count(); // NameAcquired
+    assert!(c.borrow_message().is_none());
+    for i in 0..2u32 {
+        let mut m = Message::new_signal("/borrow", "com.example.dbusrs.Borrow", "Item").unwrap().append1(i);
+        m.set_destination(Some(c.unique_name().into()));
+        c.send(m).unwrap();
+    }
+    for _ in 0..10 {
+        unsafe { ffi::dbus_connection_read_write(c.conn(), 100) };
+        if unsafe { ffi::dbus_connection_get_dispatch_status(c.conn()) } == ffi::DBusDispatchStatus::DataRemains { break };
+    }
+
+    // Put back when dropped.
+    let b = c.borrow_message().unwrap();
+    assert_eq!(b.read1::<u32>().unwrap(), 0);
+    drop(b);
+    let b = c.borrow_message().unwrap();
+    assert_eq!(b.read1::<u32>().unwrap(), 0);
+    let m = b.steal();
+    assert_eq!(m.read1::<u32>().unwrap(), 0);
+
+    let v: Vec<u32> = c.incoming(200).filter_map(|m| m.read1().ok()).collect();
+    assert_eq!(v, vec!(1));
+    assert_eq!(&*m.member().unwrap(), "Item");
+}
+
+#[test]
+fn read_write_dispatch() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    c.incoming(200).count(); // NameAcquired
+    assert_eq!(c.dispatch(), DispatchStatus::Complete);
+    assert!(c.next_dispatched().is_none());
+    for i in 0..3u32 {
+        let mut m = Message::new_signal("/rw", "com.example.dbusrs.ReadWrite", "Item").unwrap().append1(i);
+        m.set_destination(Some(c.unique_name().into()));
+        c.send(m).unwrap();
+    }
+    let mut v = vec!();
+    for _ in 0..20 {
+        c.read_write(Duration::from_millis(100)).unwrap();
+        while c.dispatch() == DispatchStatus::DataRemains {};
+        while let Some(m) = c.next_dispatched() { v.push(m.read1::<u32>().unwrap()) };
+        if v.len() == 3 { break };
+    }
+    assert_eq!(v, vec!(0, 1, 2));
+}
+
+#[test]
+fn outgoing_queue() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let s = c.sender();
+    assert!(!c.has_messages_to_send());
+    assert_eq!(c.outgoing_size(), 0);
+    // Sending through a Sender does not flush, but libdbus writes as much as the socket takes
+    // right away, so whether anything is left in the queue depends on the bus.
+    for _ in 0..100 {
+        s.send(Message::new_signal("/out", "com.example.dbusrs.Outgoing", "Queued").unwrap().append1(vec!(0u8; 100000))).unwrap();
+    }
+    assert_eq!(c.has_messages_to_send(), s.has_messages_to_send());
+    assert_eq!(c.has_messages_to_send(), c.outgoing_size() > 0);
+    assert!(c.flush_timeout(Duration::from_secs(10)));
+    assert!(!c.has_messages_to_send());
+    assert_eq!(c.outgoing_size(), 0);
+    s.send(Message::new_signal("/out", "com.example.dbusrs.Outgoing", "Queued").unwrap()).unwrap();
+    c.flush();
+    assert!(!s.has_messages_to_send());
+    assert!(s.flush_timeout(Duration::from_millis(0)));
+}
+
+#[test]
+fn send_batch() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    c.add_match("interface=com.example.dbusrs.Batch").unwrap();
+    let msgs: Vec<_> = (0..5u32).map(|i| Message::new_signal("/batch", "com.example.dbusrs.Batch", "Item").unwrap().append1(i)).collect();
+    let serials = c.send_batch(&msgs).unwrap();
+    assert_eq!(serials.len(), 5);
+    assert!(!c.has_messages_to_send());
+
+    assert!(c.flush_on_send());
+    c.set_flush_on_send(false);
+    assert!(!c.flush_on_send());
+    c.send(Message::new_signal("/batch", "com.example.dbusrs.Batch", "Item").unwrap().append1(5u32)).unwrap();
+    let mut v = vec!();
+    for m in c.incoming(1000) {
+        if let Ok(i) = m.read1::<u32>() { v.push(i) };
+        if v.len() == 6 { break };
+    }
+    assert_eq!(v, vec!(0, 1, 2, 3, 4, 5));
+}
+
+#[test]
+fn connection_builder() {
+    let bus = ::testing::shared_bus();
+    let c = bus.builder().build().unwrap();
+    assert!(c.flush_on_send());
+    let c2 = bus.builder().flush_on_send(false).max_message_size(4096).build().unwrap();
+    assert!(!c2.flush_on_send());
+    assert_eq!(c2.max_message_size(), 4096);
+    assert!(c.unique_name() != c2.unique_name());
+
+    let c3 = Connection::builder().address(bus.address(), true).build().unwrap();
+    c3.ping("org.freedesktop.DBus", 2000).unwrap();
+    let c4 = Connection::builder().address(bus.address(), false).build().unwrap();
+    assert!(c4.is_connected());
+
+    // Shared connections survive being dropped.
+    let shared = bus.builder().private(false).build().unwrap();
+    let name = shared.unique_name();
+    drop(shared);
+    let shared = bus.builder().private(false).build().unwrap();
+    assert_eq!(shared.unique_name(), name);
+    shared.ping("org.freedesktop.DBus", 2000).unwrap();
+    assert!(bus.builder().private(false).build().is_err());
+}
+
+#[test]
+fn into_raw() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    assert!(c.closes_on_drop());
+    assert!(!bus.builder().private(false).build().unwrap().closes_on_drop());
+
+    let name = c.unique_name();
+    c.register_object_path("/com/example/dbusrs/intoraw").unwrap();
+    let p = c.into_raw();
+    unsafe {
+        assert!(ffi::dbus_connection_get_is_connected(p) != 0);
+        let m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus.Peer", "Ping").unwrap();
+        let mut e = Error::empty();
+        let r = ffi::dbus_connection_send_with_reply_and_block(p, m.as_raw(), 2000, e.get_mut());
+        let r = Message::from_raw(r, false);
+        assert_eq!(r.msg_type(), MessageType::MethodReturn);
+
+        // Borrowed, so it stays open when dropped.
+        let c = Connection::from_raw(p, false).unwrap();
+        assert!(Connection::from_raw(p, false).is_err());
+        assert!(c.list_registered_object_paths("/com/example/dbusrs").is_empty());
+        assert_eq!(c.as_raw(), p);
+        assert_eq!(c.unique_name(), name);
+        assert!(!c.closes_on_drop());
+        drop(c);
+        assert!(ffi::dbus_connection_get_is_connected(p) != 0);
+
+        let c = Connection::from_raw(p, true).unwrap();
+        c.ping("org.freedesktop.DBus", 2000).unwrap();
+        assert!(c.closes_on_drop());
+    }
+}
+
+#[test]
+fn size_limits() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    c.set_max_message_unix_fds(4);
+    assert_eq!(c.max_message_unix_fds(), 4);
+    c.set_max_received_unix_fds(16);
+    assert_eq!(c.max_received_unix_fds(), 16);
+    c.set_max_received_size(1 << 20);
+    assert_eq!(c.max_received_size(), 1 << 20);
+
+    // A peer sending a message larger than the limit gets us disconnected.
+    c.set_max_message_size(1000);
+    assert_eq!(c.max_message_size(), 1000);
+    let mut m = Message::new_signal("/limits", "com.example.dbusrs.Limits", "Large").unwrap().append1(vec!(0u8; 2000));
+    m.set_destination(Some(c.unique_name().into()));
+    let c2 = bus.connect().unwrap();
+    c2.send(m).unwrap();
+    for _ in 0..20 {
+        if !c.is_connected() { break };
+        c.incoming(100).count();
+    }
+    assert!(!c.is_connected());
+}
+
+#[test]
+fn transport_features() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    assert!(c.can_send_type(ArgType::String));
+    assert!(c.can_send_type(ArgType::Array));
+    if c.can_send_type(ArgType::UnixFd) { assert!(c.unix_fd().is_some()) };
+    assert!(c.is_authenticated());
+    assert!(c.is_anonymous());
+    let id = c.server_id().unwrap();
+    assert_eq!(id.len(), 32);
+    assert!(bus.address().contains(&*id));
+}
+
+#[test]
+fn dispatch_status() {
+    use std::sync::Arc;
+    use WatchEvent;
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    c.incoming(200).count(); // NameAcquired
+    assert_eq!(c.dispatch_status(), DispatchStatus::Complete);
+    let statuses = Arc::new(Mutex::new(vec!()));
+    let s2 = statuses.clone();
+    c.set_dispatch_status_callback(Some(Box::new(move |s| s2.lock().unwrap().push(s))));
+    let last = || statuses.lock().unwrap().last().cloned();
+
+    let mut m = Message::new_signal("/status", "com.example.dbusrs.Status", "Item").unwrap();
+    m.set_destination(Some(c.unique_name().into()));
+    c.send(m).unwrap();
+    // Simulate a main loop telling us that the socket is readable.
+    let fd = c.unix_fd().unwrap();
+    for _ in 0..20 {
+        c.watch_handle(fd, WatchEvent::Readable as c_uint);
+        if c.dispatch_status() == DispatchStatus::DataRemains { break };
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(c.dispatch_status(), DispatchStatus::DataRemains);
+    assert_eq!(last(), Some(DispatchStatus::DataRemains));
+    while c.dispatch() == DispatchStatus::DataRemains {};
+    assert_eq!(last(), Some(DispatchStatus::Complete));
+    assert!(c.next_dispatched().is_some());
+
+    c.set_dispatch_status_callback(None);
+    let n = statuses.lock().unwrap().len();
+    c.ping("org.freedesktop.DBus", 2000).unwrap();
+    assert_eq!(statuses.lock().unwrap().len(), n);
+}
+
+#[test]
+fn disconnected_item() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    // Make the connection drop by sending it a message larger than it accepts.
+    c.set_max_message_size(1000);
+    let mut m = Message::new_signal("/disconnect", "com.example.dbusrs.Disconnect", "Large").unwrap().append1(vec!(0u8; 2000));
+    m.set_destination(Some(c.unique_name().into()));
+    let c2 = bus.connect().unwrap();
+    c2.send(m).unwrap();
+
+    let mut items = 0;
+    let mut disconnected = false;
+    for ci in c.iter(100) {
+        if let ConnectionItem::Disconnected = ci { disconnected = true };
+        items += 1;
+        assert!(items < 1000, "Iterator did not end after disconnection");
+    }
+    assert!(disconnected);
+    assert!(!c.is_connected());
+    assert!(c.iter(100).next().is_none());
+}
+
+#[test]
+fn close_graceful() {
+    let bus = ::testing::shared_bus();
+    let name = "com.example.dbusrs.closegraceful";
+    let c = bus.connect().unwrap();
+    assert_eq!(c.register_name(name, 0).unwrap(), RequestNameReply::PrimaryOwner);
+    let c2 = bus.connect().unwrap();
+    c2.add_match("interface=com.example.dbusrs.Graceful").unwrap();
+    c.set_flush_on_send(false);
+    for i in 0..20u32 {
+        c.send(Message::new_signal("/", "com.example.dbusrs.Graceful", "Bye").unwrap().append1(i)).unwrap();
+    }
+    assert!(c.close_graceful(Duration::from_secs(5), true));
+
+    let mut got = vec!();
+    for _ in 0..10 {
+        for m in c2.incoming(200) {
+            if m.interface().as_ref().map(|s| &**s) == Some("com.example.dbusrs.Graceful") { got.push(m.read1::<u32>().unwrap()) };
+        }
+        if got.len() == 20 { break };
+    }
+    assert_eq!(got, (0..20).collect::<Vec<_>>());
+    let e = ::DBusProxy::new(&c2).get_name_owner(name).unwrap_err();
+    assert_eq!(e.kind(), ::ErrorKind::NameHasNoOwner);
+}
+
+#[test]
+fn list_registered() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    for p in &["/lr/a", "/lr/b/c", "/lr/b/d", "/other"] { c.register_object_path(p).unwrap() };
+    let mut v = c.list_registered(&"/lr".into());
+    v.sort();
+    assert_eq!(v, vec!(Path::from("/lr/a"), Path::from("/lr/b")));
+    assert_eq!(c.list_registered(&"/lr/b".into()).len(), 2);
+    assert!(c.list_registered(&"/lr/a".into()).is_empty());
+    let root = c.list_registered(&"/".into());
+    assert!(root.contains(&Path::from("/lr")) && root.contains(&Path::from("/other")));
+}
+
+#[test]
+fn tcp_transport() {
+    let configs = [("tcp-cookie", ""), ("tcp-anonymous", "<auth>ANONYMOUS</auth><allow_anonymous/>")];
+    for &(name, config) in &configs {
+        let bus = match ::testing::TestBus::with_config("tcp:host=127.0.0.1,port=0", config) {
+            Ok(b) => b,
+            Err(ref e) if e.kind() == ::ErrorKind::SpawnFailed => return, // No dbus-daemon available
+            Err(e) => panic!("{}", e),
+        };
+        let addr = bus.address().to_string();
+        assert!(addr.starts_with("tcp:"));
+
+        let c = Connection::open_address(&addr).unwrap();
+        assert!(c.is_authenticated());
+        assert!(c.unique_name().starts_with(":"));
+        assert!(!c.can_send_type(ArgType::UnixFd));
+        assert!(c.unix_fd().is_some());
+        c.register_object_path("/tcp").unwrap();
+
+        // Over the bus, and with the builder.
+        let c2 = Connection::builder().address(&addr, true).build().unwrap();
+        c2.ping("org.freedesktop.DBus", 2000).unwrap();
+        let m = Message::new_method_call(&*c.unique_name(), "/tcp", "com.example.dbusrs.Tcp", "Echo").unwrap().append1(name);
+        let pending = c2.send_with_pending_call(m, 5000).unwrap();
+        'outer: for _ in 0..10 {
+            for m in c.incoming(200) {
+                if m.msg_type() != MessageType::MethodCall { continue };
+                c.send(m.method_return().append1(m.read1::<&str>().unwrap())).unwrap();
+                break 'outer;
+            }
+        }
+        pending.block();
+        assert_eq!(pending.steal_reply().unwrap().read1::<&str>().unwrap(), name);
+    }
+}
+
+#[test]
+fn launchd_session() {
+    assert_eq!(launchd_address("/private/tmp/com.apple.launchd.x/unix_domain_listener\n"),
+        Some("unix:path=/private/tmp/com.apple.launchd.x/unix_domain_listener".into()));
+    assert_eq!(launchd_address("/tmp/a b"), Some("unix:path=/tmp/a%20b".into()));
+    assert_eq!(launchd_address("\n"), None);
+    if cfg!(not(target_os = "macos")) { assert_eq!(launchd_fallback(BusType::Session), None) };
+    assert_eq!(launchd_fallback(BusType::System), None);
+}
+
+#[test]
+fn name_flags() {
+    let f = DBusNameFlag::AllowReplacement | DBusNameFlag::DoNotQueue;
+    assert_eq!(f.bits(), 5);
+    assert!(f.contains(NameFlags::DO_NOT_QUEUE));
+    assert!(!f.contains(DBusNameFlag::ReplaceExisting));
+    assert_eq!(NameFlags::from(DBusNameFlag::ReplaceExisting as u32), NameFlags::REPLACE_EXISTING);
+    assert_eq!(NameFlags::from_bits(0x100), None);
+    assert_eq!(NameFlags::from(0x104).bits(), 4);
+    let mut g = NameFlags::empty();
+    g |= NameFlags::ALLOW_REPLACEMENT;
+    g |= DBusNameFlag::DoNotQueue;
+    assert_eq!(f, g);
+}
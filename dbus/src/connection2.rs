@@ -1,5 +1,9 @@
-use crate::{BusType, Error, Message, to_c_str, Watch};
-use std::{ptr, str};
+use crate::{BusType, Error, Message, Timeout, to_c_str, Watch, MsgHook};
+use crate::connection::vetoed_error;
+use std::{ptr, str, ops, fmt};
+use std::sync::Mutex;
+#[cfg(feature = "log")]
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::ffi::CStr;
 use std::os::raw::{c_void};
 
@@ -174,18 +178,151 @@ impl TxRx {
     }
 }
 
+/// A thread-safe connection, for making blocking method calls from several threads at once.
+///
+/// This struct is Send + Sync, so you can put it in an Arc and share it between threads.
+/// Every thread can call `send_with_reply_and_block` concurrently; the calls are multiplexed
+/// over the same socket, and every reply is routed to the thread waiting for it.
+///
+/// All methods of TxRx are available too. Note that other incoming messages (e g signals) are
+/// put in the incoming queue, so call `pop_message` once in a while if you expect any.
+pub struct SyncConnection {
+    txrx: TxRx,
+    hooks: Mutex<Vec<Box<MsgHook + Send>>>,
+    #[cfg(feature = "log")]
+    log_traffic: AtomicBool,
+}
+
+impl SyncConnection {
+    /// Creates a new D-Bus connection.
+    ///
+    /// Blocking: until the connection is up and running.
+    pub fn get_private(bus: BusType) -> Result<SyncConnection, Error> {
+        TxRx::get_private(bus).map(SyncConnection::from)
+    }
+
+    /// Creates a new D-Bus connection to a remote address.
+    ///
+    /// Note: for all common cases (System / Session bus) you probably want "get_private" instead.
+    /// You probably also want to call `register` on the result.
+    ///
+    /// Blocking: until the connection is established.
+    pub fn open_private(address: &str) -> Result<SyncConnection, Error> {
+        TxRx::open_private(address).map(SyncConnection::from)
+    }
+
+    /// Registers a new D-Bus connection with the bus.
+    ///
+    /// Blocking: until a "Hello" response is received from the server.
+    pub fn register(&mut self) -> Result<(), Error> { self.txrx.register() }
+
+    /// Sends a message over the D-Bus and waits for a reply.
+    ///
+    /// Blocking: until a reply is received, or until the timeout (a `Timeout`, a `Duration`, or an i32 in milliseconds) has passed.
+    pub fn send_with_reply_and_block<T: Into<Timeout>>(&self, msg: Message, timeout: T) -> Result<Message, Error> {
+        #[cfg(feature = "tracing")]
+        let span = ::calltrace::call_span(&msg);
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+        let r = self.send_with_reply_and_block_inner(&msg, timeout.into());
+        #[cfg(feature = "tracing")]
+        {
+            ::calltrace::record_serial(&span, msg.get_serial());
+            ::calltrace::record_result(&span, &r);
+        }
+        r
+    }
+
+    fn send_with_reply_and_block_inner(&self, msg: &Message, timeout: Timeout) -> Result<Message, Error> {
+        if !self.run_hooks(msg, true) { return Err(vetoed_error("Message")) };
+        let mut e = Error::empty();
+        let response = unsafe {
+            ffi::dbus_connection_send_with_reply_and_block(self.txrx.conn(), msg.ptr(), timeout.call_ms(), e.get_mut())
+        };
+        self.log_msg("sent", msg);
+        if response == ptr::null_mut() {
+            return Err(e);
+        }
+        let r = Message::from_ptr(response, false);
+        self.log_msg("received", &r);
+        if !self.run_hooks(&r, false) { return Err(vetoed_error("Reply")) };
+        Ok(r)
+    }
+
+    /// Adds a hook that is called for every message sent, and every reply received,
+    /// by `send_with_reply_and_block`. See `Connection::add_hook`.
+    ///
+    /// The hooks are called with a lock held, so calls from other threads wait for them,
+    /// and a hook must not make calls on this connection.
+    pub fn add_hook<H: MsgHook + Send + 'static>(&self, h: H) {
+        self.hooks.lock().unwrap().push(Box::new(h));
+    }
+
+    /// Removes the most recently added hook from the connection.
+    pub fn extract_hook(&self) -> Option<Box<MsgHook + Send>> {
+        self.hooks.lock().unwrap().pop()
+    }
+
+    // Returns false if a hook vetoed the message.
+    fn run_hooks(&self, m: &Message, outgoing: bool) -> bool {
+        let mut v = self.hooks.lock().unwrap();
+        v.iter_mut().all(|h| if outgoing { h.outgoing(m) } else { h.incoming(m) })
+    }
+
+    /// Turns logging of sent and received messages on or off for this connection, see
+    /// `Connection::set_log_traffic`. It is on by default.
+    #[cfg(feature = "log")]
+    pub fn set_log_traffic(&self, enable: bool) { self.log_traffic.store(enable, Ordering::Relaxed) }
+
+    /// Returns whether messages sent and received on this connection are logged, see `set_log_traffic`.
+    #[cfg(feature = "log")]
+    pub fn log_traffic(&self) -> bool { self.log_traffic.load(Ordering::Relaxed) }
+
+    #[cfg(feature = "log")]
+    fn log_msg(&self, dir: &str, m: &Message) { if self.log_traffic() { ::wirelog::log_msg(dir, m) } }
+
+    #[cfg(not(feature = "log"))]
+    #[inline(always)]
+    fn log_msg(&self, _: &str, _: &Message) {}
+}
+
+impl From<TxRx> for SyncConnection {
+    fn from(txrx: TxRx) -> Self {
+        SyncConnection {
+            txrx,
+            hooks: Mutex::new(vec!()),
+            #[cfg(feature = "log")]
+            log_traffic: AtomicBool::new(true),
+        }
+    }
+}
+
+impl fmt::Debug for SyncConnection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "D-Bus SyncConnection({:?})", self.txrx.unique_name())
+    }
+}
+
+impl ops::Deref for SyncConnection {
+    type Target = TxRx;
+    fn deref(&self) -> &TxRx { &self.txrx }
+}
+
 #[test]
 fn test_txrx_send_sync() {
     fn is_send<T: Send>(_: &T) {}
     fn is_sync<T: Sync>(_: &T) {}
-    let c = TxRx::get_private(BusType::Session).unwrap();
+    let bus = ::testing::shared_bus();
+    let c = TxRx::open_private(bus.address()).unwrap();
     is_send(&c);
     is_sync(&c);
 }
 
 #[test]
 fn txrx_simple_test() {
-    let mut c = TxRx::get_private(BusType::Session).unwrap();
+    let bus = ::testing::shared_bus();
+    let mut c = TxRx::open_private(bus.address()).unwrap();
+    c.register().unwrap();
     assert!(c.is_connected());
     let fds = c.watch_fds().unwrap();
     println!("{:?}", fds);
@@ -212,3 +349,57 @@ fn txrx_simple_test() {
     }
 }
 
+
+#[test]
+fn sync_connection_threads() {
+    use std::sync::Arc;
+    use std::thread;
+    let bus = ::testing::shared_bus();
+    let mut c = SyncConnection::open_private(bus.address()).unwrap();
+    c.register().unwrap();
+    let c = Arc::new(c);
+    let threads: Vec<_> = (0..4).map(|_| {
+        let c = c.clone();
+        thread::spawn(move || {
+            for _ in 0..10 {
+                let m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetNameOwner").unwrap()
+                    .append1(c.unique_name().unwrap());
+                let r = c.send_with_reply_and_block(m, 2000).unwrap();
+                assert_eq!(r.read1::<&str>().unwrap(), c.unique_name().unwrap());
+            }
+        })
+    }).collect();
+    for t in threads { t.join().unwrap() }
+}
+
+#[test]
+fn sync_connection_hooks() {
+    use std::sync::Arc;
+    let bus = ::testing::shared_bus();
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+    impl MsgHook for Recorder {
+        fn outgoing(&mut self, m: &Message) -> bool {
+            self.0.lock().unwrap().push(format!("out {}", m.member().map(|x| x.to_string()).unwrap_or_default()));
+            m.member().map_or(true, |x| &*x != "Forbidden")
+        }
+        fn incoming(&mut self, m: &Message) -> bool {
+            self.0.lock().unwrap().push(format!("in {:?}", m.msg_type()));
+            true
+        }
+    }
+
+    let mut c = SyncConnection::open_private(bus.address()).unwrap();
+    c.register().unwrap();
+    let log = Arc::new(Mutex::new(vec!()));
+    c.add_hook(Recorder(log.clone()));
+    let m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetId").unwrap();
+    c.send_with_reply_and_block(m, 2000).unwrap();
+    assert_eq!(*log.lock().unwrap(), vec!("out GetId", "in MethodReturn"));
+    log.lock().unwrap().clear();
+
+    let m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "Forbidden").unwrap();
+    let e = c.send_with_reply_and_block(m, 2000).unwrap_err();
+    assert_eq!(e.message(), Some("Message was vetoed by a hook"));
+    assert_eq!(*log.lock().unwrap(), vec!("out Forbidden"));
+    assert!(c.extract_hook().is_some());
+}
@@ -0,0 +1,136 @@
+use {Connection, ffi};
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{fmt, ptr};
+
+// Tells data of a dropped slot apart from data of a new slot that libdbus gave the same number.
+static NEXT_SLOT_SERIAL: AtomicUsize = AtomicUsize::new(1);
+
+/// A slot for attaching data of type `T` to connections, see `Connection::set_data`.
+///
+/// This lets crates built on top of this one keep per-connection state, without having to
+/// maintain a map keyed by connection. Each slot can hold one value per connection; create
+/// one slot per kind of data, typically once at startup.
+///
+/// The data lives as long as the underlying libdbus connection, which can outlive the
+/// `Connection` struct (e g if a `Sender` still refers to it), and it is then dropped in
+/// whatever thread releases the last reference. That is why it needs to be `Send + Sync`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Connection, BusType, DataSlot};
+///
+/// let slot: DataSlot<String> = DataSlot::new().unwrap();
+/// let c = Connection::get_private(BusType::Session).unwrap();
+/// c.set_data(&slot, Some("Hello".into())).unwrap();
+/// assert_eq!(&*c.get_data(&slot).unwrap(), "Hello");
+/// ```
+pub struct DataSlot<T> {
+    // libdbus keeps a pointer to the slot number, so it must not move.
+    id: Box<i32>,
+    serial: usize,
+    _t: PhantomData<fn(T) -> T>,
+}
+
+impl<T: Send + Sync + 'static> DataSlot<T> {
+    /// Allocates a new slot. Returns an error if out of memory.
+    pub fn new() -> Result<DataSlot<T>, ()> {
+        let mut id = Box::new(-1);
+        if unsafe { ffi::dbus_connection_allocate_data_slot(&mut *id) } == 0 { return Err(()) };
+        Ok(DataSlot { id: id, serial: NEXT_SLOT_SERIAL.fetch_add(1, Ordering::Relaxed), _t: PhantomData })
+    }
+}
+
+impl<T> Drop for DataSlot<T> {
+    /// Frees the slot. Data already attached to connections is dropped together with the connections.
+    fn drop(&mut self) {
+        unsafe { ffi::dbus_connection_free_data_slot(&mut *self.id) };
+    }
+}
+
+impl<T> fmt::Debug for DataSlot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "DataSlot({})", self.id)
+    }
+}
+
+// `get_data` reads `serial` before it knows what `T` is, so it must be at the same offset for every `T`.
+#[repr(C)]
+struct SlotData<T> {
+    serial: usize,
+    data: Arc<T>,
+}
+
+extern "C" fn free_slot_data<T>(p: *mut c_void) {
+    drop(unsafe { Box::from_raw(p as *mut SlotData<T>) });
+}
+
+impl Connection {
+    /// Attaches data to this connection, replacing (and dropping) what was in the slot before.
+    ///
+    /// `None` removes the data. Returns an error if out of memory.
+    pub fn set_data<T: Send + Sync + 'static>(&self, slot: &DataSlot<T>, data: Option<T>) -> Result<(), ()> {
+        let (p, f) = match data {
+            Some(d) => {
+                let b = Box::new(SlotData { serial: slot.serial, data: Arc::new(d) });
+                (Box::into_raw(b) as *mut c_void, Some(free_slot_data::<T> as extern "C" fn(*mut c_void)))
+            }
+            None => (ptr::null_mut(), None),
+        };
+        if unsafe { ffi::dbus_connection_set_data(self.as_raw(), *slot.id, p, f) } == 0 {
+            if let Some(f) = f { f(p) };
+            return Err(());
+        }
+        Ok(())
+    }
+
+    /// Returns the data attached to this connection in the slot, if any.
+    pub fn get_data<T: Send + Sync + 'static>(&self, slot: &DataSlot<T>) -> Option<Arc<T>> {
+        let p = unsafe { ffi::dbus_connection_get_data(self.as_raw(), *slot.id) } as *const SlotData<T>;
+        if p.is_null() { return None };
+        // Only a slot with the same serial, and hence of the same type, can have stored this.
+        let d = unsafe { &*p };
+        if d.serial != slot.serial { return None };
+        Some(d.data.clone())
+    }
+}
+
+#[test]
+fn data_slots() {
+    let bus = ::testing::shared_bus();
+
+    struct Counted(Arc<AtomicUsize>);
+    impl Drop for Counted {
+        fn drop(&mut self) { self.0.fetch_add(1, Ordering::SeqCst); }
+    }
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let names: DataSlot<String> = DataSlot::new().unwrap();
+    let counted: DataSlot<Counted> = DataSlot::new().unwrap();
+    let c = bus.connect().unwrap();
+    assert!(c.get_data(&names).is_none());
+
+    c.set_data(&names, Some("Hello".into())).unwrap();
+    c.set_data(&counted, Some(Counted(drops.clone()))).unwrap();
+    assert_eq!(&*c.get_data(&names).unwrap(), "Hello");
+    c.set_data(&names, Some("World".into())).unwrap();
+    assert_eq!(&*c.get_data(&names).unwrap(), "World");
+    c.set_data(&names, None).unwrap();
+    assert!(c.get_data(&names).is_none());
+
+    // A new slot might get the same number as a dropped one, but not its data.
+    let c2 = bus.connect().unwrap();
+    c2.set_data(&names, Some("Old".into())).unwrap();
+    drop(names);
+    let numbers: DataSlot<u32> = DataSlot::new().unwrap();
+    assert!(c2.get_data(&numbers).is_none());
+
+    let kept = c.get_data(&counted).unwrap();
+    drop(c);
+    assert_eq!(drops.load(Ordering::SeqCst), 0);
+    drop(kept);
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+}
@@ -1,5 +1,6 @@
-use crate::{Message, MessageType, Error, to_c_str, c_str_to_slice};
-use std::ptr;
+use crate::{Message, MessageType, Error, Version, to_c_str, c_str_to_slice, version};
+use std::{mem, ptr};
+use std::os::raw::c_char;
 
 use std::collections::HashMap;
 
@@ -103,10 +104,16 @@ impl<C: MessageDispatcherConfig> MessageDispatcher<C> {
             if let Some(method) = m.member() {
                 if &*method == "Ping" { return Some(m.method_return()) }
                 if &*method == "GetMachineId" {
+                    // dbus_try_get_local_machine_id is new in libdbus 1.12.
+                    let f = match version::require_fn(b"dbus_try_get_local_machine_id\0", Version::new(1, 12, 0), "GetMachineId") {
+                        Ok(f) => f,
+                        Err(e) => return Some(m.error(&e.name().unwrap().into(), &to_c_str(e.message().unwrap()))),
+                    };
+                    let f: extern "C" fn(*mut ffi::DBusError) -> *mut c_char = unsafe { mem::transmute(f) };
                     let mut r = m.method_return();
                     let mut e = Error::empty();
                     unsafe {
-                        let id = ffi::dbus_try_get_local_machine_id(e.get_mut());
+                        let id = f(e.get_mut());
                         if id != ptr::null_mut() {
                             r = r.append1(c_str_to_slice(&(id as *const _)).unwrap());
                             ffi::dbus_free(id as *mut _);
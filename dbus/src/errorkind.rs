@@ -0,0 +1,129 @@
+use std::fmt;
+
+macro_rules! error_kinds {
+    ($($(#[$attr: meta])* $v: ident => $n: expr,)*) => {
+
+/// The standard `org.freedesktop.DBus.Error.*` error names, as returned by `Error::kind`.
+///
+/// Errors with any other name (or no name at all) have the kind `Other`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    $($(#[$attr])* $v,)*
+    /// An error name that is not one of the standard ones.
+    Other,
+}
+
+impl ErrorKind {
+    /// Looks up the kind of an error name, e g "org.freedesktop.DBus.Error.NoReply".
+    pub fn from_name(name: &str) -> ErrorKind {
+        match name {
+            $($n => ErrorKind::$v,)*
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// The full error name, or None for `Other`.
+    pub fn name(&self) -> Option<&'static str> {
+        match *self {
+            $(ErrorKind::$v => Some($n),)*
+            ErrorKind::Other => None,
+        }
+    }
+}
+
+    }
+}
+
+error_kinds! {
+    /// A generic error; "something went wrong".
+    Failed => "org.freedesktop.DBus.Error.Failed",
+    /// There was not enough memory to complete an operation.
+    NoMemory => "org.freedesktop.DBus.Error.NoMemory",
+    /// The bus doesn't know how to launch a service to supply the bus name you wanted.
+    ServiceUnknown => "org.freedesktop.DBus.Error.ServiceUnknown",
+    /// The bus name you referenced doesn't exist (i.e. no application owns it).
+    NameHasNoOwner => "org.freedesktop.DBus.Error.NameHasNoOwner",
+    /// No reply to a message expecting one, usually means a timeout occurred.
+    NoReply => "org.freedesktop.DBus.Error.NoReply",
+    /// Something went wrong reading or writing to a socket, for example.
+    IOError => "org.freedesktop.DBus.Error.IOError",
+    /// A D-Bus bus address was malformed.
+    BadAddress => "org.freedesktop.DBus.Error.BadAddress",
+    /// Requested operation isn't supported (like connecting to a transport that isn't available).
+    NotSupported => "org.freedesktop.DBus.Error.NotSupported",
+    /// Some limited resource is exhausted.
+    LimitsExceeded => "org.freedesktop.DBus.Error.LimitsExceeded",
+    /// Security restrictions don't allow doing what you're trying to do.
+    AccessDenied => "org.freedesktop.DBus.Error.AccessDenied",
+    /// Authentication didn't work.
+    AuthFailed => "org.freedesktop.DBus.Error.AuthFailed",
+    /// Unable to connect to server (probably caused by ECONNREFUSED on a socket).
+    NoServer => "org.freedesktop.DBus.Error.NoServer",
+    /// Certain timeout errors, possibly ETIMEDOUT on a socket.
+    Timeout => "org.freedesktop.DBus.Error.Timeout",
+    /// No network access (probably ENETUNREACH on a socket).
+    NoNetwork => "org.freedesktop.DBus.Error.NoNetwork",
+    /// Can't bind a socket since its address is in use (i.e. EADDRINUSE).
+    AddressInUse => "org.freedesktop.DBus.Error.AddressInUse",
+    /// The connection is disconnected and you're trying to use it.
+    Disconnected => "org.freedesktop.DBus.Error.Disconnected",
+    /// Invalid arguments passed to a method call.
+    InvalidArgs => "org.freedesktop.DBus.Error.InvalidArgs",
+    /// Missing file.
+    FileNotFound => "org.freedesktop.DBus.Error.FileNotFound",
+    /// Existing file and the operation you're using does not silently overwrite.
+    FileExists => "org.freedesktop.DBus.Error.FileExists",
+    /// Method name you invoked isn't known by the object you invoked it on.
+    UnknownMethod => "org.freedesktop.DBus.Error.UnknownMethod",
+    /// Object you invoked a method on isn't known.
+    UnknownObject => "org.freedesktop.DBus.Error.UnknownObject",
+    /// Interface you invoked a method on isn't known by the object.
+    UnknownInterface => "org.freedesktop.DBus.Error.UnknownInterface",
+    /// Property you tried to access isn't known by the object.
+    UnknownProperty => "org.freedesktop.DBus.Error.UnknownProperty",
+    /// Property you tried to set is read-only.
+    PropertyReadOnly => "org.freedesktop.DBus.Error.PropertyReadOnly",
+    /// Certain timeout errors, e.g. while starting a service.
+    TimedOut => "org.freedesktop.DBus.Error.TimedOut",
+    /// Tried to remove or modify a match rule that didn't exist.
+    MatchRuleNotFound => "org.freedesktop.DBus.Error.MatchRuleNotFound",
+    /// The match rule isn't syntactically valid.
+    MatchRuleInvalid => "org.freedesktop.DBus.Error.MatchRuleInvalid",
+    /// While starting a new process, something went wrong.
+    SpawnFailed => "org.freedesktop.DBus.Error.Spawn.Failed",
+    /// Tried to get a UNIX process ID and it wasn't available.
+    UnixProcessIdUnknown => "org.freedesktop.DBus.Error.UnixProcessIdUnknown",
+    /// A type signature is not valid.
+    InvalidSignature => "org.freedesktop.DBus.Error.InvalidSignature",
+    /// A file contains invalid syntax or is otherwise broken.
+    InvalidFileContent => "org.freedesktop.DBus.Error.InvalidFileContent",
+    /// Asked for SELinux security context and it wasn't available.
+    SELinuxSecurityContextUnknown => "org.freedesktop.DBus.Error.SELinuxSecurityContextUnknown",
+    /// Asked for ADT audit data and it wasn't available.
+    AdtAuditDataUnknown => "org.freedesktop.DBus.Error.AdtAuditDataUnknown",
+    /// There's already an object with the requested object path.
+    ObjectPathInUse => "org.freedesktop.DBus.Error.ObjectPathInUse",
+    /// The message meta data does not match the payload.
+    InconsistentMessage => "org.freedesktop.DBus.Error.InconsistentMessage",
+    /// The message is not allowed without performing interactive authorization,
+    /// but could have succeeded if an interactive authorization step was allowed.
+    InteractiveAuthorizationRequired => "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired",
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name().unwrap_or("(other)"))
+    }
+}
+
+#[test]
+fn error_kinds() {
+    use Error;
+    assert_eq!(ErrorKind::from_name("org.freedesktop.DBus.Error.NoReply"), ErrorKind::NoReply);
+    assert_eq!(ErrorKind::from_name("com.example.Error"), ErrorKind::Other);
+    assert_eq!(ErrorKind::UnknownMethod.name(), Some("org.freedesktop.DBus.Error.UnknownMethod"));
+    assert_eq!(ErrorKind::Other.name(), None);
+    let e = Error::new_custom("org.freedesktop.DBus.Error.AccessDenied", "Go away");
+    assert_eq!(e.kind(), ErrorKind::AccessDenied);
+    assert_eq!(Error::new_custom("com.example.Error", "Custom").kind(), ErrorKind::Other);
+}
@@ -0,0 +1,117 @@
+use {Message, MsgHook};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A message sent or received on a connection, as kept by a `FlightRecorder`.
+#[derive(Debug)]
+pub struct FlightRecord {
+    /// When the message was sent or received.
+    pub time: SystemTime,
+    /// True if the message was sent, false if it was received.
+    pub outgoing: bool,
+    /// The message itself.
+    pub message: Message,
+}
+
+impl FlightRecord {
+    /// Formats the record with a timestamp (in seconds since the Unix epoch), a direction and the message
+    /// in the format of `Message::pretty_print`.
+    pub fn pretty_print(&self) -> String {
+        let t = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        format!("{}.{:06} {} {}", t.as_secs(), t.subsec_micros(), if self.outgoing { ">>" } else { "<<" }, self.message.pretty_print())
+    }
+}
+
+/// Keeps the last N messages sent and received on a connection, e g to report what D-Bus traffic
+/// preceded a failure.
+///
+/// Add a clone of the recorder to a connection with `Connection::add_hook`. The recorder can be
+/// shared between threads, so it can be dumped from a panic hook. Messages are kept by reference,
+/// not copied, so recording is cheap until the records are dumped.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Connection, BusType, FlightRecorder};
+///
+/// let c = Connection::get_private(BusType::Session).unwrap();
+/// let fr = FlightRecorder::new(50);
+/// c.add_hook(fr.clone());
+/// c.call_method("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetId", (), 2000).unwrap();
+/// println!("{}", fr.dump());
+/// ```
+#[derive(Debug, Clone)]
+pub struct FlightRecorder {
+    capacity: usize,
+    records: Arc<Mutex<VecDeque<FlightRecord>>>,
+}
+
+impl FlightRecorder {
+    /// Creates a recorder keeping at most `capacity` messages.
+    pub fn new(capacity: usize) -> Self {
+        FlightRecorder { capacity: capacity, records: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))) }
+    }
+
+    /// The maximum number of messages kept.
+    pub fn capacity(&self) -> usize { self.capacity }
+
+    /// The number of messages currently kept.
+    pub fn len(&self) -> usize { self.lock().len() }
+
+    /// Returns true if no messages are kept.
+    pub fn is_empty(&self) -> bool { self.lock().is_empty() }
+
+    /// Removes all records.
+    pub fn clear(&self) { self.lock().clear() }
+
+    /// Removes and returns all records, oldest first.
+    pub fn take(&self) -> Vec<FlightRecord> { self.lock().drain(..).collect() }
+
+    /// Formats all records, oldest first, see `FlightRecord::pretty_print`.
+    pub fn dump(&self) -> String {
+        self.lock().iter().map(|r| r.pretty_print()).collect()
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<VecDeque<FlightRecord>> {
+        // A panic while holding the lock does not make the records invalid, and a dump is most useful after a panic.
+        self.records.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record(&self, m: &Message, outgoing: bool) {
+        if self.capacity == 0 { return }
+        let r = FlightRecord { time: SystemTime::now(), outgoing: outgoing, message: Message::from_ptr(m.ptr(), true) };
+        let mut v = self.lock();
+        if v.len() >= self.capacity { v.pop_front(); }
+        v.push_back(r);
+    }
+}
+
+impl MsgHook for FlightRecorder {
+    fn outgoing(&mut self, m: &Message) -> bool { self.record(m, true); true }
+    fn incoming(&mut self, m: &Message) -> bool { self.record(m, false); true }
+}
+
+#[test]
+fn flight_recorder() {
+    use MessageType;
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let fr = FlightRecorder::new(3);
+    c.add_hook(fr.clone());
+    c.call_method("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetId", (), 2000).unwrap();
+    assert_eq!(fr.len(), 2);
+    let d = fr.dump();
+    assert!(d.contains(" >> method call "));
+    assert!(d.contains("member=GetId"));
+    assert!(d.contains(" << method return "));
+
+    for _ in 0..3 { c.call_method("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "ListNames", (), 2000).unwrap(); }
+    let r = fr.take();
+    assert_eq!(r.len(), 3);
+    assert_eq!(r[2].message.msg_type(), MessageType::MethodReturn);
+    assert!(!r[2].outgoing);
+    assert!(r[1].outgoing);
+    assert_eq!(r[2].message.get_reply_serial(), Some(r[1].message.get_serial()));
+    assert_eq!(fr.len(), 0);
+}
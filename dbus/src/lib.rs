@@ -16,19 +16,49 @@
 #![warn(missing_docs)]
 
 extern crate libc;
+#[cfg(feature = "mio")]
+extern crate mio as mio_crate;
+#[cfg(feature = "nonblock")]
+extern crate futures;
+#[cfg(all(feature = "nonblock", feature = "tokio"))]
+extern crate tokio;
+#[cfg(all(feature = "nonblock", feature = "async-io"))]
+extern crate async_io;
+#[cfg(feature = "log")]
+#[macro_use]
+extern crate log;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 
 pub use ffi::DBusBusType as BusType;
 pub use connection::DBusNameFlag as NameFlag;
+pub use connection::{NameFlags, Env, QueuePolicy, ConnectionBuilder};
 pub use ffi::DBusRequestNameReply as RequestNameReply;
 pub use ffi::DBusReleaseNameReply as ReleaseNameReply;
 pub use ffi::DBusMessageType as MessageType;
+pub use ffi::DBusDispatchStatus as DispatchStatus;
 
-pub use message::{Message, MessageItem, MessageItemArray, FromMessageItem, OwnedFd, ArrayError, ConnPath};
-pub use connection::{Connection, ConnectionItems, ConnectionItem, ConnMsgs, MsgHandler, MsgHandlerResult, MsgHandlerType, MessageCallback};
+pub use message::{Message, MessageItem, MessageItemArray, FromMessageItem, OwnedFd, ArrayError, ParseError, MAX_NESTING_DEPTH, MAX_SIGNATURE_LENGTH, ConnPath};
+pub use connection::{Connection, Sender, PreallocatedSend, BorrowedMessage, MatchGuard, NameGuard, ConnectionItems, ConnectionItem, ConnMsgs, MsgHandler, MsgHandlerResult, MsgHandlerType, SignalHandler, MsgHook, MessageCallback, CallError};
 pub use prop::PropHandler;
-pub use prop::Props;
-pub use watch::{Watch, WatchEvent};
+pub use prop::{Props, PropError, PropSubscription, PropChanged, PropChangedHandler};
+pub use watch::{Watch, WatchEvent, WatchTimeout};
 pub use signalargs::SignalArgs;
+pub use pendingcall::PendingCall;
+pub use timeout::Timeout;
+pub use busproxy::{DBusProxy, StartServiceReply, NameStatus, BusStats, ConnectionStats, Credentials};
+pub use servicewatcher::{ServiceWatcher, ServiceEvent, ServiceHandler};
+pub use nametracker::{NameTracker, NameEvent, NameHandler};
+pub use nameresolver::NameResolver;
+pub use flightrecorder::{FlightRecorder, FlightRecord};
+pub use errorkind::ErrorKind;
+pub use msgpool::MessagePool;
+pub use reconnect::{ReconnectingConnection, ReconnectingMsgs, ReconnectEvent};
+pub use version::{version, Version};
+pub use dataslot::DataSlot;
+pub use address::{Address, AddressEntry};
+pub use server::Server;
+pub use monitor::{MonitorConnection, MonitorMsgs};
 
 /// A TypeSig describes the type of a MessageItem.
 #[deprecated(note="Use Signature instead")]
@@ -45,10 +75,29 @@ mod prop;
 mod watch;
 mod connection;
 mod signalargs;
+mod pendingcall;
+mod timeout;
+mod busproxy;
+mod servicewatcher;
+mod nametracker;
+mod nameresolver;
+mod flightrecorder;
+mod errorkind;
+mod msgpool;
+mod reconnect;
+mod version;
+mod dataslot;
+mod address;
+mod server;
+mod monitor;
+#[cfg(feature = "log")]
+mod wirelog;
+#[cfg(feature = "tracing")]
+mod calltrace;
 
 mod connection2;
 mod dispatcher;
-pub use connection2::TxRx;
+pub use connection2::{TxRx, SyncConnection};
 pub use dispatcher::{MessageDispatcher, MessageDispatcherConfig};
 // pub mod crossroads;
 
@@ -62,8 +111,16 @@ pub mod arg;
 
 pub mod stdintf;
 
+pub mod testing;
+
 pub mod tree;
 
+#[cfg(feature = "mio")]
+pub mod mio;
+
+#[cfg(feature = "nonblock")]
+pub mod nonblock;
+
 static INITDBUS: std::sync::Once = std::sync::ONCE_INIT;
 
 fn init_dbus() {
@@ -128,6 +185,13 @@ impl Error {
         c_str_to_slice(&self.e.message)
     }
 
+    /// The kind of error, if it is one of the standard D-Bus errors.
+    ///
+    /// Prefer this over comparing `name()` with the standard error name strings.
+    pub fn kind(&self) -> ErrorKind {
+        self.name().map(ErrorKind::from_name).unwrap_or(ErrorKind::Other)
+    }
+
     fn get_mut(&mut self) -> &mut ffi::DBusError { &mut self.e }
 }
 
@@ -162,20 +226,52 @@ impl From<arg::TypeMismatchError> for Error {
     }
 }
 
+impl From<message::ParseError> for Error {
+    fn from(t: message::ParseError) -> Error {
+        Error::new_custom("org.freedesktop.DBus.Error.InvalidArgs", &format!("{}", t))
+    }
+}
+
 impl From<tree::MethodErr> for Error {
     fn from(t: tree::MethodErr) -> Error {
         Error::new_custom(t.errorname(), t.description())
     }
 }
 
+impl From<Error> for std::io::Error {
+    /// Maps the standard D-Bus errors to the closest `io::ErrorKind`. The original
+    /// error is kept as the inner error of the `io::Error`.
+    fn from(e: Error) -> std::io::Error {
+        use std::io::ErrorKind as K;
+        let kind = match e.kind() {
+            ErrorKind::NoReply | ErrorKind::Timeout | ErrorKind::TimedOut => K::TimedOut,
+            ErrorKind::AccessDenied | ErrorKind::AuthFailed |
+                ErrorKind::InteractiveAuthorizationRequired => K::PermissionDenied,
+            ErrorKind::ServiceUnknown | ErrorKind::NameHasNoOwner | ErrorKind::UnknownMethod |
+                ErrorKind::UnknownObject | ErrorKind::UnknownInterface | ErrorKind::UnknownProperty |
+                ErrorKind::FileNotFound | ErrorKind::MatchRuleNotFound => K::NotFound,
+            ErrorKind::InvalidArgs | ErrorKind::InvalidSignature | ErrorKind::MatchRuleInvalid |
+                ErrorKind::BadAddress => K::InvalidInput,
+            ErrorKind::InconsistentMessage | ErrorKind::InvalidFileContent => K::InvalidData,
+            ErrorKind::FileExists | ErrorKind::ObjectPathInUse => K::AlreadyExists,
+            ErrorKind::Disconnected => K::NotConnected,
+            ErrorKind::NoServer => K::ConnectionRefused,
+            ErrorKind::AddressInUse => K::AddrInUse,
+            _ => K::Other,
+        };
+        std::io::Error::new(kind, e)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::{Connection, Message, BusType, MessageItem, ConnectionItem, NameFlag,
+    use super::{Message, MessageItem, ConnectionItem, NameFlag,
         RequestNameReply, ReleaseNameReply};
 
     #[test]
     fn connection() {
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
         let n = c.unique_name();
         assert!(n.starts_with(":1."));
         println!("Connected to DBus, unique name: {}", n);
@@ -183,15 +279,33 @@ mod test {
 
     #[test]
     fn invalid_message() {
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
         let m = Message::new_method_call("foo.bar", "/", "foo.bar", "FooBar").unwrap();
         let e = c.send_with_reply_and_block(m, 2000).err().unwrap();
         assert!(e.name().unwrap() == "org.freedesktop.DBus.Error.ServiceUnknown");
     }
 
+    #[test]
+    fn io_error() {
+        use std::io;
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
+        let m = Message::new_method_call("foo.bar", "/", "foo.bar", "FooBar").unwrap();
+        let e: io::Error = c.send_with_reply_and_block(m, 2000).unwrap_err().into();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        let e: io::Error = super::Error::new_custom("org.freedesktop.DBus.Error.NoReply", "No reply").into();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        let e: io::Error = super::Error::new_custom("com.example.Error", "Custom").into();
+        assert_eq!(e.kind(), io::ErrorKind::Other);
+        let inner = e.into_inner().unwrap().downcast::<super::Error>().unwrap();
+        assert_eq!(inner.name(), Some("com.example.Error"));
+    }
+
     #[test]
     fn message_listnames() {
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
         let m = Message::method_call(&"org.freedesktop.DBus".into(), &"/".into(),
             &"org.freedesktop.DBus".into(), &"ListNames".into());
         let r = c.send_with_reply_and_block(m, 2000).unwrap();
@@ -201,11 +315,12 @@ mod test {
 
     #[test]
     fn message_namehasowner() {
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
         let mut m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "NameHasOwner").unwrap();
-        m.append_items(&[MessageItem::Str("org.freedesktop.DBus".to_string())]);
+        m.append_items(&[MessageItem::Str("org.freedesktop.DBus".to_string())]).unwrap();
         let r = c.send_with_reply_and_block(m, 2000).unwrap();
-        let reply = r.get_items();
+        let reply = r.get_items().unwrap();
         println!("{:?}", reply);
         assert_eq!(reply, vec!(MessageItem::Bool(true)));
     }
@@ -213,9 +328,11 @@ mod test {
     #[test]
     fn object_path() {
         use  std::sync::mpsc;
+        let bus = ::testing::shared_bus();
         let (tx, rx) = mpsc::channel();
+        let thread_bus = bus.clone();
         let thread = ::std::thread::spawn(move || {
-            let c = Connection::get_private(BusType::Session).unwrap();
+            let c = thread_bus.connect().unwrap();
             c.register_object_path("/hello").unwrap();
             // println!("Waiting...");
             tx.send(c.unique_name()).unwrap();
@@ -233,7 +350,7 @@ mod test {
             c.unregister_object_path("/hello");
         });
 
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let c = bus.connect().unwrap();
         let n = rx.recv().unwrap();
         let m = Message::new_method_call(&n, "/hello", "com.example.hello", "Hello").unwrap();
         println!("Sending...");
@@ -246,15 +363,17 @@ mod test {
 
     #[test]
     fn register_name() {
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
         let n = format!("com.example.hello.test.register_name");
-        assert_eq!(c.register_name(&n, NameFlag::ReplaceExisting as u32).unwrap(), RequestNameReply::PrimaryOwner);
+        assert_eq!(c.register_name(&n, NameFlag::ReplaceExisting).unwrap(), RequestNameReply::PrimaryOwner);
         assert_eq!(c.release_name(&n).unwrap(), ReleaseNameReply::Released);
     }
 
     #[test]
     fn signal() {
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
         let iface = "com.example.signaltest";
         let mstr = format!("interface='{}',member='ThisIsASignal'", iface);
         c.add_match(&mstr).unwrap();
@@ -282,7 +401,8 @@ mod test {
 
     #[test]
     fn watch() {
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
         let d = c.watch_fds();
         assert!(d.len() > 0);
         println!("Fds to watch: {:?}", d);
@@ -1,4 +1,6 @@
 use crate::{Message, MessageType, BusName, Path, Interface, Member};
+use crate::arg::ArgType;
+use std::borrow::Cow;
 
 
 #[derive(Clone, Debug, Default)]
@@ -17,6 +19,23 @@ pub struct MatchRule<'a> {
     pub interface: Option<Interface<'a>>,
     /// Match on message member (signal or method name)
     pub member: Option<Member<'a>>,
+    /// Match on message object path, and all object paths below it
+    pub path_namespace: Option<Path<'a>>,
+    /// Match on string arguments: the argument with index N (0 - 63) must be a string equal to the value
+    pub args: Vec<(u8, Cow<'a, str>)>,
+    /// Match on the first argument being a string that is a bus name or interface name
+    /// equal to, or within, the namespace (e g "com.example" matches "com.example.Foo")
+    pub arg0namespace: Option<Cow<'a, str>>,
+    /// Match on message destination. The D-Bus server requires this to be a unique name
+    /// (e g ":1.42"), since it matches on the connection the message is delivered to.
+    pub destination: Option<BusName<'a>>,
+    /// Also receive messages that are addressed to other connections.
+    ///
+    /// This is the older way of capturing bus traffic, for servers without `BecomeMonitor`.
+    /// The server's security policy may still refuse to deliver such messages. Note that messages
+    /// sent to a well-known name are seen with that name as destination, whereas the server
+    /// matches a `destination` filter against the unique name of the receiving connection.
+    pub eavesdrop: bool,
     _more_fields_may_come: (),
 }
 
@@ -31,6 +50,23 @@ fn msg_type_str(m: MessageType) -> &'static str {
     }
 }
 
+// Quoting according to the D-Bus specification: the only escape is for an apostrophe, which needs to be outside quotes.
+fn quote(s: &str) -> String {
+    s.split('\'').map(|x| format!("'{}'", x)).collect::<Vec<_>>().join("\\'")
+}
+
+fn in_namespace(s: &str, ns: &str, sep: char) -> bool {
+    if ns.ends_with(sep) { return s.starts_with(ns) } // The root path namespace
+    s.starts_with(ns) && (s.len() == ns.len() || s[ns.len()..].starts_with(sep))
+}
+
+fn str_arg(msg: &Message, n: u8) -> Option<&str> {
+    let mut i = msg.iter_init();
+    for _ in 0..n { if !i.next() { return None } }
+    if i.arg_type() != ArgType::String { return None }
+    i.get()
+}
+
 
 impl<'a> MatchRule<'a> {
     /// Make a string which you can use in the call to "add_match".
@@ -43,26 +79,55 @@ impl<'a> MatchRule<'a> {
         if let Some(ref x) = self.path { v.push(("path", &x)) };
         if let Some(ref x) = self.interface { v.push(("interface", &x)) };
         if let Some(ref x) = self.member { v.push(("member", &x)) };
+        if let Some(ref x) = self.path_namespace { v.push(("path_namespace", &x)) };
+        if let Some(ref x) = self.arg0namespace { v.push(("arg0namespace", &x)) };
+        if let Some(ref x) = self.destination { v.push(("destination", &x)) };
+        if self.eavesdrop { v.push(("eavesdrop", "true")) };
 
-        // For now we don't need to worry about internal quotes in strings as those are not valid names. 
-        // If we start matching against arguments, we need to worry.
-        let v: Vec<_> = v.into_iter().map(|(k, v)| format!("{}='{}'", k, v)).collect();
+        // Names and paths cannot contain quotes, but arguments can.
+        let mut v: Vec<_> = v.into_iter().map(|(k, v)| format!("{}={}", k, quote(v))).collect();
+        v.extend(self.args.iter().map(|&(n, ref a)| format!("arg{}={}", n, quote(a))));
         v.join(",")
     }
 
     /// Returns whether or not the message matches the rule.
+    ///
+    /// Note: messages carry the unique name of their sender, so a rule with a well-known name as
+    /// `sender` does not match here. Use `NameResolver::matches` for such rules.
     pub fn matches(&self, msg: &Message) -> bool {
-        if let Some(x) = self.msg_type { if x != msg.msg_type() { return false; }};
         if self.sender.is_some() && msg.sender() != self.sender { return false };
+        self.matches_except_sender(msg)
+    }
+
+    pub (crate) fn matches_except_sender(&self, msg: &Message) -> bool {
+        if let Some(x) = self.msg_type { if x != msg.msg_type() { return false; }};
         if self.path.is_some() && msg.path() != self.path { return false };
         if self.interface.is_some() && msg.interface() != self.interface { return false };
         if self.member.is_some() && msg.member() != self.member { return false };
-        true
+        if self.destination.is_some() && msg.destination() != self.destination { return false };
+        if let Some(ref x) = self.path_namespace {
+            match msg.path() {
+                Some(ref p) if in_namespace(p, x, '/') => {},
+                _ => return false,
+            }
+        }
+        if let Some(ref x) = self.arg0namespace {
+            match str_arg(msg, 0) {
+                Some(a) if in_namespace(a, x, '.') => {},
+                _ => return false,
+            }
+        }
+        self.args.iter().all(|&(n, ref a)| str_arg(msg, n) == Some(&**a))
     }
 
     /// Create a new struct which matches every message.
     pub fn new() -> Self { Default::default() }
 
+    /// Create a new struct which matches every message, including those addressed to other connections.
+    ///
+    /// See the `eavesdrop` field for details.
+    pub fn new_eavesdrop() -> Self { MatchRule { eavesdrop: true, .. Default::default() } }
+
     /// Returns a clone with no static references
     pub fn into_static(&self) -> MatchRule<'static> {
         MatchRule {
@@ -71,7 +136,64 @@ impl<'a> MatchRule<'a> {
             path: self.path.as_ref().map(|x| x.clone().into_static()),
             interface: self.interface.as_ref().map(|x| x.clone().into_static()),
             member: self.member.as_ref().map(|x| x.clone().into_static()),
+            path_namespace: self.path_namespace.as_ref().map(|x| x.clone().into_static()),
+            args: self.args.iter().map(|&(n, ref a)| (n, Cow::Owned(a.clone().into_owned()))).collect(),
+            arg0namespace: self.arg0namespace.as_ref().map(|x| Cow::Owned(x.clone().into_owned())),
+            destination: self.destination.as_ref().map(|x| x.clone().into_static()),
+            eavesdrop: self.eavesdrop,
             _more_fields_may_come: (),
         }
     }
 } 
+
+#[test]
+fn match_rule_args() {
+    let bus = ::testing::shared_bus();
+    let mut mr = MatchRule::new();
+    mr.member = Some("NameOwnerChanged".into());
+    mr.args.push((0, "com.example.it's".into()));
+    mr.path_namespace = Some("/com/example".into());
+    assert_eq!(mr.match_str(), r"member='NameOwnerChanged',path_namespace='/com/example',arg0='com.example.it'\''s'");
+
+    let m = |p: &str, a: &str| Message::new_signal(p, "org.freedesktop.DBus", "NameOwnerChanged").unwrap().append3(a, "", ":1.1");
+    assert!(mr.matches(&m("/com/example", "com.example.it's")));
+    assert!(mr.matches(&m("/com/example/sub", "com.example.it's")));
+    assert!(!mr.matches(&m("/com/examples", "com.example.it's")));
+    assert!(!mr.matches(&m("/com/example", "com.example.its")));
+
+    mr.args.clear();
+    mr.arg0namespace = Some("com.example".into());
+    assert!(mr.matches(&m("/com/example", "com.example")));
+    assert!(mr.matches(&m("/com/example", "com.example.Foo")));
+    assert!(!mr.matches(&m("/com/example", "com.examples")));
+
+    mr.path_namespace = Some("/".into());
+    assert!(mr.matches(&m("/org", "com.example.Foo")));
+    assert!(!mr.matches(&Message::new_signal("/org", "com.example", "Bar").unwrap().append1(5u32)));
+    assert_eq!(mr.into_static().match_str(), mr.match_str());
+
+    // Check that the D-Bus server accepts the syntax
+    let c = bus.connect().unwrap();
+    mr.args.push((2, "it's".into()));
+    c.add_match(&mr.match_str()).unwrap();
+}
+
+#[test]
+fn match_rule_eavesdrop() {
+    let bus = ::testing::shared_bus();
+    let mut mr = MatchRule::new_eavesdrop();
+    mr.msg_type = Some(MessageType::MethodCall);
+    assert_eq!(mr.match_str(), "type='method_call',eavesdrop='true'");
+    assert_eq!(mr.into_static().match_str(), mr.match_str());
+
+    let c = bus.connect().unwrap();
+    c.add_match(&mr.match_str()).unwrap();
+    c.remove_match(&mr.match_str()).unwrap();
+
+    mr.destination = Some(c.unique_name().into());
+    assert_eq!(mr.match_str(), format!("type='method_call',destination='{}',eavesdrop='true'", c.unique_name()));
+    let m = Message::new_method_call(&*c.unique_name(), "/", "com.example", "Foo").unwrap();
+    assert!(mr.matches(&m));
+    assert!(!mr.matches(&Message::new_method_call("com.example.other", "/", "com.example", "Foo").unwrap()));
+    c.add_match(&mr.match_str()).unwrap();
+}
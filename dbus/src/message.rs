@@ -1,12 +1,12 @@
 use std::borrow::Cow;
 use std::{fmt, mem, ptr, ops};
-use super::{ffi, Error, MessageType, Signature, libc, to_c_str, c_str_to_slice, init_dbus};
-use super::{BusName, Path, Interface, Member, ErrorName, Connection, SignalArgs};
+use super::{ffi, Error, MessageType, Signature, Version, libc, to_c_str, c_str_to_slice, init_dbus, version};
+use super::{BusName, Path, Interface, Member, ErrorName, Connection, SignalArgs, MatchGuard, Timeout};
 use std::os::unix::io::{RawFd, AsRawFd};
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::os::raw::{c_void, c_char, c_int};
 
-use super::arg::{Append, IterAppend, Get, Iter, Arg, RefArg, TypeMismatchError};
+use super::arg::{Append, AppendAll, ReadAll, IterAppend, Get, Iter, Arg, RefArg, TypeMismatchError};
 
 #[derive(Debug,Copy,Clone)]
 /// Errors that can happen when creating a MessageItem::Array.
@@ -19,6 +19,76 @@ pub enum ArrayError {
     InvalidSignature,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Errors that can happen when reading MessageItems from a message.
+pub enum ParseError {
+    /// A dict entry did not contain exactly one key and one value.
+    DictEntry,
+    /// A variant did not contain exactly one value.
+    Variant,
+    /// A string was not valid UTF-8.
+    InvalidString,
+    /// An object path was not valid.
+    InvalidObjectPath,
+    /// The argument has a type (given as its D-Bus type code) that cannot be represented as a MessageItem.
+    UnsupportedType(char),
+    /// Containers (arrays, structs, dict entries and variants) were nested deeper than allowed.
+    TooDeep,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::DictEntry => write!(f, "D-Bus dict entry does not contain a key and a value"),
+            ParseError::Variant => write!(f, "D-Bus variant does not contain a single value"),
+            ParseError::InvalidString => write!(f, "D-Bus string is not valid UTF-8"),
+            ParseError::InvalidObjectPath => write!(f, "D-Bus object path is not valid"),
+            ParseError::UnsupportedType(c) => write!(f, "D-Bus type '{}' cannot be read as a MessageItem", c),
+            ParseError::TooDeep => write!(f, "D-Bus containers are nested too deep"),
+        }
+    }
+}
+
+impl ::std::error::Error for ParseError {
+    fn description(&self) -> &str { "D-Bus message parse error" }
+}
+
+/// The default limit of container nesting when reading MessageItems, which is the maximum
+/// nesting depth allowed by the D-Bus specification.
+pub const MAX_NESTING_DEPTH: usize = 64;
+
+/// The maximum length of a message signature (the concatenated signature of all arguments),
+/// as given by the D-Bus specification.
+pub const MAX_SIGNATURE_LENGTH: usize = 255;
+
+// The maximum nesting of arrays, and of structs and dict entries, within a single signature.
+const MAX_ARRAY_DEPTH: usize = 32;
+const MAX_STRUCT_DEPTH: usize = 32;
+
+// Checks the limits the D-Bus specification puts on a signature. Unlike Signature::new, this
+// is done even with the no-string-validation feature, since libdbus aborts on violations.
+fn check_signature_limits(s: &str) -> Result<(), String> {
+    if s.len() > MAX_SIGNATURE_LENGTH {
+        return Err(format!("signature is longer than {} characters", MAX_SIGNATURE_LENGTH));
+    }
+    let (mut open, mut arrays, mut structs) = (vec!(), 0, 0);
+    for c in s.bytes() {
+        match c {
+            b'a' => { open.push(c); arrays += 1; continue },
+            b'(' | b'{' => { open.push(c); structs += 1; },
+            b')' | b'}' => { open.pop(); structs -= 1; },
+            _ => {},
+        }
+        if arrays > MAX_ARRAY_DEPTH { return Err(format!("arrays are nested more than {} levels deep", MAX_ARRAY_DEPTH)) }
+        if structs > MAX_STRUCT_DEPTH { return Err(format!("structs are nested more than {} levels deep", MAX_STRUCT_DEPTH)) }
+        // A complete type ends all arrays it is the element type of.
+        if c != b'(' && c != b'{' {
+            while open.last() == Some(&b'a') { open.pop(); arrays -= 1; }
+        }
+    }
+    Ok(())
+}
+
 fn new_dbus_message_iter() -> ffi::DBusMessageIter { unsafe { mem::zeroed() }}
 
 
@@ -78,14 +148,11 @@ impl MessageItemArray {
         if a.sig.as_bytes()[0] != ffi::DBUS_TYPE_ARRAY as u8 { return Err(ArrayError::InvalidSignature) }
         {
             let esig = a.element_signature();
+            let mut s = vec!();
             for i in &a.v {
-                let b = if let MessageItem::DictEntry(ref k, ref v) = *i {
-                     let s = format!("{{{}{}}}", k.signature(), v.signature());
-                     s.as_bytes() == esig.to_bytes()
-                } else {
-                     i.signature().as_cstr() == esig
-                };
-                if !b { return Err(ArrayError::DifferentElementTypes) }
+                s.clear();
+                i.write_signature(&mut s);
+                if &*s != esig.to_bytes() { return Err(ArrayError::DifferentElementTypes) }
             }
         }
         Ok(a)
@@ -97,11 +164,9 @@ impl MessageItemArray {
     }
 
     fn make_sig(m: &MessageItem) -> Signature<'static> {
-        if let MessageItem::DictEntry(ref k, ref v) = *m {
-            Signature::new(format!("a{{{}{}}}", k.signature(), v.signature())).unwrap()
-        } else {
-            Signature::new(format!("a{}", m.signature())).unwrap()
-        }
+        let mut s = vec!(b'a');
+        m.write_signature(&mut s);
+        Signature::new(s).unwrap()
     }
 
     /// Signature of array (full array signature)
@@ -123,7 +188,11 @@ impl ops::Deref for MessageItemArray {
 /// Note that the newer generic design (see `arg` module) is both faster
 /// and less error prone than MessageItem, and should be your first hand choice
 /// whenever applicable.
+///
+/// New variants may be added in the future, so matches must have a wildcard arm.
+/// (This is a breaking change from versions before `Unknown` was added.)
 #[derive(Debug, PartialEq, PartialOrd, Clone)]
+#[non_exhaustive]
 pub enum MessageItem {
     /// A D-Bus array requires all elements to be of the same type.
     /// All elements must match the Signature.
@@ -162,6 +231,16 @@ pub enum MessageItem {
     /// D-Bus allows for sending file descriptors, which can be used to
     /// set up SHM, unix pipes, or other communication channels.
     UnixFd(OwnedFd),
+    /// An argument of a type that MessageItem cannot represent, e g a signature.
+    ///
+    /// Its value is not available, but the rest of the message can still be read.
+    /// Such an item cannot be appended to a message.
+    Unknown {
+        /// The D-Bus type code of the argument.
+        type_code: char,
+        /// The complete signature of the argument.
+        signature: Signature<'static>,
+    },
 }
 
 fn iter_get_basic<T>(i: &mut ffi::DBusMessageIter) -> T {
@@ -173,43 +252,105 @@ fn iter_get_basic<T>(i: &mut ffi::DBusMessageIter) -> T {
     }
 }
 
-fn iter_append_array(i: &mut ffi::DBusMessageIter, a: &[MessageItem], t: &CStr) {
+fn no_memory() -> Error { Error::new_custom("org.freedesktop.DBus.Error.NoMemory", "Out of memory when appending to message") }
+
+fn check_oom(r: u32) -> Result<(), Error> { if r == 0 { Err(no_memory()) } else { Ok(()) } }
+
+fn iter_append_container<F>(i: &mut ffi::DBusMessageIter, t: c_int, sig: *const c_char, f: F) -> Result<(), Error>
+where F: FnOnce(&mut ffi::DBusMessageIter) -> Result<(), Error> {
     let mut subiter = new_dbus_message_iter();
+    try!(check_oom(unsafe { ffi::dbus_message_iter_open_container(i, t, sig, &mut subiter) }));
+    if let Err(e) = f(&mut subiter) {
+        // Releases what libdbus allocated for the container, which would otherwise be leaked.
+        unsafe { ffi::dbus_message_iter_abandon_container(i, &mut subiter) };
+        return Err(e);
+    }
+    check_oom(unsafe { ffi::dbus_message_iter_close_container(i, &mut subiter) })
+}
 
-    assert!(unsafe { ffi::dbus_message_iter_open_container(i, ffi::DBUS_TYPE_ARRAY, t.as_ptr(), &mut subiter) } != 0);
-    for item in a.iter() {
-//        assert!(item.type_sig() == t);
-        item.iter_append(&mut subiter);
+fn iter_append_array(i: &mut ffi::DBusMessageIter, a: &[MessageItem], t: &CStr) -> Result<(), Error> {
+    iter_append_container(i, ffi::DBUS_TYPE_ARRAY, t.as_ptr(), |subiter| {
+        if let Some(r) = iter_append_fixed_array(subiter, a) { return r };
+        MessageItem::copy_to_iter(subiter, a)
+    })
+}
+
+// Appends arrays of integers, doubles and booleans with a single copy, instead of one
+// FFI call per element. Returns None if the items are not of a fixed size type.
+fn iter_append_fixed_array(i: &mut ffi::DBusMessageIter, a: &[MessageItem]) -> Option<Result<(), Error>> {
+    macro_rules! fixed_array {
+        ($variant: ident, $t: expr, $conv: expr) => {{
+            let v: Vec<_> = a.iter().filter_map(|x| if let MessageItem::$variant(b) = *x { Some($conv(b)) } else { None }).collect();
+            if v.len() != a.len() { return None }
+            let p = v.as_ptr();
+            Some(check_oom(unsafe { ffi::dbus_message_iter_append_fixed_array(i, $t, &p as *const _ as *const c_void, v.len() as c_int) }))
+        }}
+    }
+    match *a.first()? {
+        MessageItem::Bool(_) => fixed_array!(Bool, ffi::DBUS_TYPE_BOOLEAN, |b| if b { 1u32 } else { 0u32 }),
+        MessageItem::Byte(_) => fixed_array!(Byte, ffi::DBUS_TYPE_BYTE, |b: u8| b),
+        MessageItem::Int16(_) => fixed_array!(Int16, ffi::DBUS_TYPE_INT16, |b: i16| b),
+        MessageItem::Int32(_) => fixed_array!(Int32, ffi::DBUS_TYPE_INT32, |b: i32| b),
+        MessageItem::Int64(_) => fixed_array!(Int64, ffi::DBUS_TYPE_INT64, |b: i64| b),
+        MessageItem::UInt16(_) => fixed_array!(UInt16, ffi::DBUS_TYPE_UINT16, |b: u16| b),
+        MessageItem::UInt32(_) => fixed_array!(UInt32, ffi::DBUS_TYPE_UINT32, |b: u32| b),
+        MessageItem::UInt64(_) => fixed_array!(UInt64, ffi::DBUS_TYPE_UINT64, |b: u64| b),
+        MessageItem::Double(_) => fixed_array!(Double, ffi::DBUS_TYPE_DOUBLE, |b: f64| b),
+        _ => None,
     }
-    assert!(unsafe { ffi::dbus_message_iter_close_container(i, &mut subiter) } != 0);
 }
 
-fn iter_append_struct(i: &mut ffi::DBusMessageIter, a: &[MessageItem]) {
-    let mut subiter = new_dbus_message_iter();
-    let res = unsafe { ffi::dbus_message_iter_open_container(i, ffi::DBUS_TYPE_STRUCT, ptr::null(), &mut subiter) };
-    assert!(res != 0);
-    for item in a.iter() {
-        item.iter_append(&mut subiter);
+// Reads arrays of integers, doubles and booleans with a single FFI call.
+// Returns None if the element type is not of a fixed size type.
+fn iter_get_fixed_array(i: &mut ffi::DBusMessageIter, t: c_int) -> Option<Vec<MessageItem>> {
+    fn get<T: Copy>(i: &mut ffi::DBusMessageIter) -> &[T] {
+        let mut p: *const T = ptr::null();
+        let mut n: c_int = 0;
+        unsafe {
+            ffi::dbus_message_iter_get_fixed_array(i, &mut p as *mut _ as *mut c_void, &mut n);
+            if n <= 0 || p.is_null() { &[] } else { ::std::slice::from_raw_parts(p, n as usize) }
+        }
     }
-    let res2 = unsafe { ffi::dbus_message_iter_close_container(i, &mut subiter) };
-    assert!(res2 != 0);
+    Some(match t {
+        ffi::DBUS_TYPE_BOOLEAN => get::<u32>(i).iter().map(|&b| MessageItem::Bool(b != 0)).collect(),
+        ffi::DBUS_TYPE_BYTE => get(i).iter().map(|&b| MessageItem::Byte(b)).collect(),
+        ffi::DBUS_TYPE_INT16 => get(i).iter().map(|&b| MessageItem::Int16(b)).collect(),
+        ffi::DBUS_TYPE_INT32 => get(i).iter().map(|&b| MessageItem::Int32(b)).collect(),
+        ffi::DBUS_TYPE_INT64 => get(i).iter().map(|&b| MessageItem::Int64(b)).collect(),
+        ffi::DBUS_TYPE_UINT16 => get(i).iter().map(|&b| MessageItem::UInt16(b)).collect(),
+        ffi::DBUS_TYPE_UINT32 => get(i).iter().map(|&b| MessageItem::UInt32(b)).collect(),
+        ffi::DBUS_TYPE_UINT64 => get(i).iter().map(|&b| MessageItem::UInt64(b)).collect(),
+        ffi::DBUS_TYPE_DOUBLE => get(i).iter().map(|&b| MessageItem::Double(b)).collect(),
+        _ => return None,
+    })
 }
 
-fn iter_append_variant(i: &mut ffi::DBusMessageIter, a: &MessageItem) {
-    let mut subiter = new_dbus_message_iter();
-    let asig = a.signature();
-    let atype = asig.as_cstr();
-    assert!(unsafe { ffi::dbus_message_iter_open_container(i, ffi::DBUS_TYPE_VARIANT, atype.as_ptr(), &mut subiter) } != 0);
-    a.iter_append(&mut subiter);
-    assert!(unsafe { ffi::dbus_message_iter_close_container(i, &mut subiter) } != 0);
+fn iter_append_struct(i: &mut ffi::DBusMessageIter, a: &[MessageItem]) -> Result<(), Error> {
+    iter_append_container(i, ffi::DBUS_TYPE_STRUCT, ptr::null(), |subiter| MessageItem::copy_to_iter(subiter, a))
 }
 
-fn iter_append_dict(i: &mut ffi::DBusMessageIter, k: &MessageItem, v: &MessageItem) {
-    let mut subiter = new_dbus_message_iter();
-    assert!(unsafe { ffi::dbus_message_iter_open_container(i, ffi::DBUS_TYPE_DICT_ENTRY, ptr::null(), &mut subiter) } != 0);
-    k.iter_append(&mut subiter);
-    v.iter_append(&mut subiter);
-    assert!(unsafe { ffi::dbus_message_iter_close_container(i, &mut subiter) } != 0);
+fn iter_append_variant(i: &mut ffi::DBusMessageIter, a: &MessageItem) -> Result<(), Error> {
+    // Borrow the signature of arrays instead of cloning it, basic types have static signatures.
+    // Struct signatures have already been validated by check_append, so skip validating them again.
+    let (owned, owned_struct);
+    let asig = match *a {
+        MessageItem::Array(ref arr) => arr.sig.as_cstr(),
+        MessageItem::Struct(_) => {
+            let mut s = vec!();
+            a.write_signature(&mut s);
+            owned_struct = unsafe { CString::from_vec_unchecked(s) };
+            &*owned_struct
+        },
+        _ => { owned = a.signature(); owned.as_cstr() },
+    };
+    iter_append_container(i, ffi::DBUS_TYPE_VARIANT, asig.as_ptr(), |subiter| a.iter_append(subiter))
+}
+
+fn iter_append_dict(i: &mut ffi::DBusMessageIter, k: &MessageItem, v: &MessageItem) -> Result<(), Error> {
+    iter_append_container(i, ffi::DBUS_TYPE_DICT_ENTRY, ptr::null(), |subiter| {
+        try!(k.iter_append(subiter));
+        v.iter_append(subiter)
+    })
 }
 
 impl MessageItem {
@@ -230,11 +371,36 @@ impl MessageItem {
             MessageItem::UInt64(_) => <u64 as Arg>::signature(),
             MessageItem::Double(_) => <f64 as Arg>::signature(),
             MessageItem::Array(ref a) => a.sig.clone(),
-            MessageItem::Struct(ref s) => Signature::new(format!("({})", s.iter().fold(String::new(), |s, i| s + &*i.signature()))).unwrap(),
+            MessageItem::Struct(_) => {
+                let mut s = vec!();
+                self.write_signature(&mut s);
+                Signature::new(s).unwrap()
+            },
             MessageItem::Variant(_) => <Variant<u8> as Arg>::signature(),
             MessageItem::DictEntry(_, _) => { panic!("Dict entries are only valid inside arrays, and therefore has no signature on their own") },
             MessageItem::ObjectPath(_) => <Path as Arg>::signature(),
             MessageItem::UnixFd(_) => <OwnedFd as Arg>::signature(),
+            MessageItem::Unknown { ref signature, .. } => signature.clone(),
+        }
+    }
+
+    // Writes the signature without allocating and validating a Signature for every
+    // nested container. Unlike signature(), dict entries are written as "{kv}".
+    fn write_signature(&self, s: &mut Vec<u8>) {
+        match *self {
+            MessageItem::Array(ref a) => s.extend_from_slice(a.sig.as_cstr().to_bytes()),
+            MessageItem::Struct(ref v) => {
+                s.push(b'(');
+                for i in v { i.write_signature(s) };
+                s.push(b')');
+            },
+            MessageItem::DictEntry(ref k, ref v) => {
+                s.push(b'{');
+                k.write_signature(s);
+                v.write_signature(s);
+                s.push(b'}');
+            },
+            _ => s.extend_from_slice(self.signature().as_cstr().to_bytes()),
         }
     }
 
@@ -264,6 +430,7 @@ impl MessageItem {
             &MessageItem::DictEntry(_,_) => ffi::DBUS_TYPE_DICT_ENTRY,
             &MessageItem::ObjectPath(_) => ffi::DBUS_TYPE_OBJECT_PATH,
             &MessageItem::UnixFd(_) => ffi::DBUS_TYPE_UNIX_FD,
+            &MessageItem::Unknown { type_code, .. } => type_code as c_int,
         };
         s as i32
     }
@@ -307,42 +474,52 @@ impl MessageItem {
         MessageItem::new_array2(i.map(|ii| ii.clone()))
     }
 
-    fn from_iter_single(i: &mut ffi::DBusMessageIter) -> Option<MessageItem> {
+    // Depth is the number of container levels still allowed.
+    fn from_iter_single(i: &mut ffi::DBusMessageIter, depth: usize) -> Result<Option<MessageItem>, ParseError> {
         let t = unsafe { ffi::dbus_message_iter_get_arg_type(i) };
         match t {
-            ffi::DBUS_TYPE_INVALID => { None },
+            ffi::DBUS_TYPE_DICT_ENTRY | ffi::DBUS_TYPE_VARIANT | ffi::DBUS_TYPE_ARRAY | ffi::DBUS_TYPE_STRUCT
+                if depth == 0 => return Err(ParseError::TooDeep),
+            _ => {},
+        }
+        Ok(Some(match t {
+            ffi::DBUS_TYPE_INVALID => { return Ok(None) },
             ffi::DBUS_TYPE_DICT_ENTRY => {
                 let mut subiter = new_dbus_message_iter();
                 unsafe { ffi::dbus_message_iter_recurse(i, &mut subiter) };
-                let a = MessageItem::from_iter(&mut subiter);
-                if a.len() != 2 { panic!("D-Bus dict entry error"); }
+                let a = try!(MessageItem::from_iter(&mut subiter, depth - 1));
+                if a.len() != 2 { return Err(ParseError::DictEntry) }
                 let mut a = a.into_iter();
                 let key = Box::new(a.next().unwrap());
                 let value = Box::new(a.next().unwrap());
-                Some(MessageItem::DictEntry(key, value))
+                MessageItem::DictEntry(key, value)
             }
             ffi::DBUS_TYPE_VARIANT => {
                 let mut subiter = new_dbus_message_iter();
                 unsafe { ffi::dbus_message_iter_recurse(i, &mut subiter) };
-                let a = MessageItem::from_iter(&mut subiter);
-                if a.len() != 1 { panic!("D-Bus variant error"); }
-                Some(MessageItem::Variant(Box::new(a.into_iter().next().unwrap())))
+                let a = try!(MessageItem::from_iter(&mut subiter, depth - 1));
+                if a.len() != 1 { return Err(ParseError::Variant) }
+                MessageItem::Variant(Box::new(a.into_iter().next().unwrap()))
             }
             ffi::DBUS_TYPE_ARRAY => {
                 let mut subiter = new_dbus_message_iter();
                 unsafe { ffi::dbus_message_iter_recurse(i, &mut subiter) };
                 let c = unsafe { ffi::dbus_message_iter_get_signature(&mut subiter) };
-                let s = format!("a{}", c_str_to_slice(&(c as *const c_char)).unwrap());
+                let s = c_str_to_slice(&(c as *const c_char)).map(|s| format!("a{}", s));
                 unsafe { ffi::dbus_free(c as *mut c_void) };
-                let t = Signature::new(s).unwrap();
+                let t = try!(s.and_then(|s| Signature::new(s).ok()).ok_or(ParseError::UnsupportedType('a')));
 
-                let a = MessageItem::from_iter(&mut subiter);
-                Some(MessageItem::Array(MessageItemArray { v: a, sig: t }))
+                let et = unsafe { ffi::dbus_message_iter_get_element_type(i) };
+                let a = match iter_get_fixed_array(&mut subiter, et) {
+                    Some(a) => a,
+                    None => try!(MessageItem::from_iter(&mut subiter, depth - 1)),
+                };
+                MessageItem::Array(MessageItemArray { v: a, sig: t })
             },
             ffi::DBUS_TYPE_STRUCT => {
                 let mut subiter = new_dbus_message_iter();
                 unsafe { ffi::dbus_message_iter_recurse(i, &mut subiter) };
-                Some(MessageItem::Struct(MessageItem::from_iter(&mut subiter)))
+                MessageItem::Struct(try!(MessageItem::from_iter(&mut subiter, depth - 1)))
             },
             ffi::DBUS_TYPE_STRING => {
                 let mut c: *const c_char = ptr::null();
@@ -350,7 +527,7 @@ impl MessageItem {
                     let p: *mut c_void = mem::transmute(&mut c);
                     ffi::dbus_message_iter_get_basic(i, p);
                 };
-                Some(MessageItem::Str(c_str_to_slice(&c).expect("D-Bus string error").to_string()))
+                MessageItem::Str(try!(c_str_to_slice(&c).ok_or(ParseError::InvalidString)).to_string())
             },
             ffi::DBUS_TYPE_OBJECT_PATH => {
                 let mut c: *const c_char = ptr::null();
@@ -358,46 +535,52 @@ impl MessageItem {
                     let p: *mut c_void = mem::transmute(&mut c);
                     ffi::dbus_message_iter_get_basic(i, p);
                 };
-                let o = Path::new(c_str_to_slice(&c).expect("D-Bus object path error")).ok().expect("D-Bus object path error");
-                Some(MessageItem::ObjectPath(o))
+                let o = try!(c_str_to_slice(&c).and_then(|s| Path::new(s).ok()).ok_or(ParseError::InvalidObjectPath));
+                MessageItem::ObjectPath(o)
             },
-            ffi::DBUS_TYPE_UNIX_FD => Some(MessageItem::UnixFd(OwnedFd::new(iter_get_basic(i)))),
-            ffi::DBUS_TYPE_BOOLEAN => Some(MessageItem::Bool(iter_get_basic::<u32>(i) != 0)),
-            ffi::DBUS_TYPE_BYTE => Some(MessageItem::Byte(iter_get_basic(i))),
-            ffi::DBUS_TYPE_INT16 => Some(MessageItem::Int16(iter_get_basic(i))),
-            ffi::DBUS_TYPE_INT32 => Some(MessageItem::Int32(iter_get_basic(i))),
-            ffi::DBUS_TYPE_INT64 => Some(MessageItem::Int64(iter_get_basic(i))),
-            ffi::DBUS_TYPE_UINT16 => Some(MessageItem::UInt16(iter_get_basic(i))),
-            ffi::DBUS_TYPE_UINT32 => Some(MessageItem::UInt32(iter_get_basic(i))),
-            ffi::DBUS_TYPE_UINT64 => Some(MessageItem::UInt64(iter_get_basic(i))),
-            ffi::DBUS_TYPE_DOUBLE => Some(MessageItem::Double(iter_get_basic(i))),
-            _ => { None /* Only the new msgarg module supports signatures */ }
-        }
+            ffi::DBUS_TYPE_UNIX_FD => MessageItem::UnixFd(OwnedFd::new(iter_get_basic(i))),
+            ffi::DBUS_TYPE_BOOLEAN => MessageItem::Bool(iter_get_basic::<u32>(i) != 0),
+            ffi::DBUS_TYPE_BYTE => MessageItem::Byte(iter_get_basic(i)),
+            ffi::DBUS_TYPE_INT16 => MessageItem::Int16(iter_get_basic(i)),
+            ffi::DBUS_TYPE_INT32 => MessageItem::Int32(iter_get_basic(i)),
+            ffi::DBUS_TYPE_INT64 => MessageItem::Int64(iter_get_basic(i)),
+            ffi::DBUS_TYPE_UINT16 => MessageItem::UInt16(iter_get_basic(i)),
+            ffi::DBUS_TYPE_UINT32 => MessageItem::UInt32(iter_get_basic(i)),
+            ffi::DBUS_TYPE_UINT64 => MessageItem::UInt64(iter_get_basic(i)),
+            ffi::DBUS_TYPE_DOUBLE => MessageItem::Double(iter_get_basic(i)),
+            // Only the new msgarg module supports signatures
+            _ => {
+                let c = unsafe { ffi::dbus_message_iter_get_signature(i) };
+                let s = c_str_to_slice(&(c as *const c_char)).and_then(|s| Signature::new(s).ok());
+                unsafe { ffi::dbus_free(c as *mut c_void) };
+                let type_code = (t as u8) as char;
+                MessageItem::Unknown { type_code: type_code, signature: try!(s.ok_or(ParseError::UnsupportedType(type_code))) }
+            }
+        }))
     }
 
-    fn from_iter(i: &mut ffi::DBusMessageIter) -> Vec<MessageItem> {
+    fn from_iter(i: &mut ffi::DBusMessageIter, depth: usize) -> Result<Vec<MessageItem>, ParseError> {
         let mut v = Vec::new();
-        while let Some(m) = Self::from_iter_single(i) {
+        while let Some(m) = try!(Self::from_iter_single(i, depth)) {
             v.push(m);
             unsafe { ffi::dbus_message_iter_next(i) };
         }
-        v
+        Ok(v)
     }
 
-    fn iter_append_basic<T>(&self, i: &mut ffi::DBusMessageIter, v: T) {
+    fn iter_append_basic<T>(&self, i: &mut ffi::DBusMessageIter, v: T) -> Result<(), Error> {
         let t = self.array_type() as c_int;
         let p = &v as *const _ as *const c_void;
-        unsafe {
-            ffi::dbus_message_iter_append_basic(i, t, p);
-        }
+        check_oom(unsafe { ffi::dbus_message_iter_append_basic(i, t, p) })
     }
 
-    fn iter_append(&self, i: &mut ffi::DBusMessageIter) {
+    fn iter_append(&self, i: &mut ffi::DBusMessageIter) -> Result<(), Error> {
         match self {
-            &MessageItem::Str(ref s) => unsafe {
-                let c = to_c_str(s);
-                let p = mem::transmute(&c);
-                ffi::dbus_message_iter_append_basic(i, ffi::DBUS_TYPE_STRING, p);
+            &MessageItem::Str(ref s) => {
+                let c = try!(CString::new(s.as_bytes()).map_err(|_|
+                    Error::new_custom("org.freedesktop.DBus.Error.InvalidArgs", "String contains a nul character")));
+                let p = c.as_ptr();
+                check_oom(unsafe { ffi::dbus_message_iter_append_basic(i, ffi::DBUS_TYPE_STRING, &p as *const _ as *const c_void) })
             },
             &MessageItem::Bool(b) => self.iter_append_basic(i, if b { 1u32 } else { 0u32 }),
             &MessageItem::Byte(b) => self.iter_append_basic(i, b),
@@ -413,18 +596,71 @@ impl MessageItem {
             &MessageItem::Struct(ref v) => iter_append_struct(i, &**v),
             &MessageItem::Variant(ref b) => iter_append_variant(i, &**b),
             &MessageItem::DictEntry(ref k, ref v) => iter_append_dict(i, &**k, &**v),
-            &MessageItem::ObjectPath(ref s) => unsafe {
+            &MessageItem::ObjectPath(ref s) => {
                 let c: *const libc::c_char = s.as_ref().as_ptr();
-                let p = mem::transmute(&c);
-                ffi::dbus_message_iter_append_basic(i, ffi::DBUS_TYPE_OBJECT_PATH, p);
-            }
+                check_oom(unsafe { ffi::dbus_message_iter_append_basic(i, ffi::DBUS_TYPE_OBJECT_PATH, &c as *const _ as *const c_void) })
+            },
+            &MessageItem::Unknown { ref signature, .. } => Err(Error::new_custom("org.freedesktop.DBus.Error.InvalidArgs",
+                &format!("Cannot append a MessageItem of unknown type ({})", signature))),
         }
     }
 
-    fn copy_to_iter(i: &mut ffi::DBusMessageIter, v: &[MessageItem]) {
+    fn copy_to_iter(i: &mut ffi::DBusMessageIter, v: &[MessageItem]) -> Result<(), Error> {
         for item in v.iter() {
-            item.iter_append(i);
+            try!(item.iter_append(i));
+        }
+        Ok(())
+    }
+
+    // Checks that this item can be appended without libdbus complaining, and
+    // pushes its signature onto sig. Unlike signature(), this never panics.
+    // `depth` is the number of containers (including variants) this item is inside of.
+    fn check_append(&self, in_array: bool, depth: usize, sig: &mut String) -> Result<(), String> {
+        let is_container = match *self {
+            MessageItem::Array(_) | MessageItem::Struct(_) | MessageItem::Variant(_) | MessageItem::DictEntry(_, _) => true,
+            _ => false,
+        };
+        if is_container && depth >= MAX_NESTING_DEPTH {
+            return Err(format!("containers are nested more than {} levels deep", MAX_NESTING_DEPTH));
+        }
+        match *self {
+            MessageItem::Str(ref s) => {
+                if s.as_bytes().contains(&0) { return Err("string contains a nul character".into()) }
+                sig.push('s');
+            },
+            MessageItem::Array(ref a) => {
+                sig.push_str(&a.sig);
+                for e in &a.v { try!(e.check_append(true, depth + 1, &mut String::new())) }
+            },
+            MessageItem::Struct(ref v) => {
+                if v.is_empty() { return Err("structs must have at least one field".into()) }
+                sig.push('(');
+                for e in v { try!(e.check_append(false, depth + 1, sig)) }
+                sig.push(')');
+            },
+            MessageItem::Variant(ref b) => {
+                let mut s = String::new();
+                try!(b.check_append(false, depth + 1, &mut s));
+                try!(check_signature_limits(&s).map_err(|e| format!("variant contents has invalid signature \"{}\": {}", s, e)));
+                try!(Signature::new(&*s).map_err(|e| format!("variant contents has invalid signature \"{}\": {}", s, e)));
+                sig.push('v');
+            },
+            MessageItem::DictEntry(ref k, ref v) => {
+                if !in_array { return Err("dict entries are only valid inside arrays".into()) }
+                match **k {
+                    MessageItem::Array(_) | MessageItem::Struct(_) | MessageItem::Variant(_) |
+                    MessageItem::DictEntry(_, _) => return Err("dict entry keys must be of a basic type".into()),
+                    _ => {},
+                }
+                sig.push('{');
+                try!(k.check_append(false, depth + 1, sig));
+                try!(v.check_append(false, depth + 1, sig));
+                sig.push('}');
+            },
+            MessageItem::Unknown { ref signature, .. } => return Err(format!("cannot append a value of unknown type ({})", signature)),
+            _ => sig.push_str(&self.signature()),
         }
+        Ok(())
     }
 
     /// Conveniently get the inner value of a `MessageItem`
@@ -443,13 +679,18 @@ impl MessageItem {
 
 
 // For use by the msgarg module
-pub fn append_messageitem(i: &mut ffi::DBusMessageIter, m: &MessageItem) {
+pub fn append_messageitem(i: &mut ffi::DBusMessageIter, m: &MessageItem) -> Result<(), Error> {
+    let mut s = String::new();
+    if let Err(e) = m.check_append(false, 0, &mut s).and_then(|_| check_signature_limits(&s))
+        .and_then(|_| Signature::new(&*s).map(|_| ())) {
+        return Err(Error::new_custom("org.freedesktop.DBus.Error.InvalidArgs", &format!("Cannot append MessageItem: {}", e)));
+    }
     m.iter_append(i)
 }
 
 // For use by the msgarg module
-pub fn get_messageitem(i: &mut ffi::DBusMessageIter) -> Option<MessageItem> {
-    MessageItem::from_iter_single(i)
+pub fn get_messageitem(i: &mut ffi::DBusMessageIter) -> Result<Option<MessageItem>, ParseError> {
+    MessageItem::from_iter_single(i, MAX_NESTING_DEPTH)
 }
 
 
@@ -642,15 +883,25 @@ impl Message {
         Message { msg: ptr}
     }
 
-    /// Get the MessageItems that make up the message.
+    /// Get the MessageItems that make up the message, or an error if an argument cannot be read as a MessageItem.
+    ///
+    /// Arguments of types that MessageItem cannot represent are returned as `MessageItem::Unknown`.
+    /// Containers can be nested up to `MAX_NESTING_DEPTH` levels.
     ///
     /// Note: use `iter_init` or `get1`/`get2`/etc instead for faster access to the arguments.
     /// This method is provided for backwards compatibility.
-    pub fn get_items(&self) -> Vec<MessageItem> {
+    pub fn get_items(&self) -> Result<Vec<MessageItem>, ParseError> {
+        self.get_items_limited(MAX_NESTING_DEPTH)
+    }
+
+    /// Like `get_items`, but with a custom limit of how deep containers can be nested.
+    ///
+    /// Returns `ParseError::TooDeep` if the limit is exceeded. A limit of 0 allows no containers at all.
+    pub fn get_items_limited(&self, max_depth: usize) -> Result<Vec<MessageItem>, ParseError> {
         let mut i = new_dbus_message_iter();
         match unsafe { ffi::dbus_message_iter_init(self.msg, &mut i) } {
-            0 => Vec::new(),
-            _ => MessageItem::from_iter(&mut i)
+            0 => Ok(Vec::new()),
+            _ => MessageItem::from_iter(&mut i, max_depth)
         }
     }
 
@@ -685,25 +936,83 @@ impl Message {
         unsafe { ffi::dbus_message_set_auto_start(self.msg, if v { 1 } else { 0 }) }
     }
 
+    /// Returns true if the caller is prepared to wait for interactive authorization, see
+    /// `set_allow_interactive_authorization`.
+    ///
+    /// Always false with libdbus older than 1.8.10.
+    pub fn get_allow_interactive_authorization(&self) -> bool {
+        let f = match version::optional_fn(b"dbus_message_get_allow_interactive_authorization\0") {
+            Some(f) => f,
+            None => return false,
+        };
+        let f: extern "C" fn(*mut ffi::DBusMessage) -> u32 = unsafe { mem::transmute(f) };
+        f(self.msg) != 0
+    }
+
+    /// Sets whether the caller is prepared to wait for interactive authorization.
+    ///
+    /// Services guarded by e g polkit can then ask the user for a password (or similar) before
+    /// carrying out the method call, which might take a long time; use a long enough timeout.
+    /// Otherwise, they reply with an `ErrorKind::InteractiveAuthorizationRequired` error.
+    ///
+    /// Defaults to false. Returns an `ErrorKind::NotSupported` error with libdbus older than 1.8.10,
+    /// or if the libdbus function cannot be found.
+    pub fn set_allow_interactive_authorization(&self, v: bool) -> Result<(), Error> {
+        let f = try!(version::require_fn(b"dbus_message_set_allow_interactive_authorization\0",
+            Version::new(1, 8, 10), "Interactive authorization"));
+        let f: extern "C" fn(*mut ffi::DBusMessage, u32) = unsafe { mem::transmute(f) };
+        f(self.msg, if v { 1 } else { 0 });
+        Ok(())
+    }
+
     /// Add one or more MessageItems to this Message.
     ///
+    /// The items are validated before anything is appended: if an item cannot be appended
+    /// (e g a string containing a nul character, a `MessageItem::Unknown`, a dict entry outside
+    /// an array, or a signature that is too long or nested too deep), an InvalidArgs error naming
+    /// the index of the offending item is returned and the message is left untouched.
+    ///
+    /// If libdbus runs out of memory, an error is returned as well. In that case the partly
+    /// appended item is abandoned, which releases what libdbus allocated for it, but the message
+    /// must be discarded.
+    ///
     /// Note: using `append1`, `append2` or `append3` might be faster, especially for large arrays.
     /// This method is provided for backwards compatibility.
-    pub fn append_items(&mut self, v: &[MessageItem]) {
+    pub fn append_items(&mut self, v: &[MessageItem]) -> Result<(), Error> {
+        try!(self.check_items(v));
         let mut i = new_dbus_message_iter();
         unsafe { ffi::dbus_message_iter_init_append(self.msg, &mut i) };
-        MessageItem::copy_to_iter(&mut i, v);
+        MessageItem::copy_to_iter(&mut i, v)
+    }
+
+    // Validates the items before anything is handed to libdbus, which would otherwise
+    // print a warning and abort the append (or the process) on an invalid signature.
+    fn check_items(&self, v: &[MessageItem]) -> Result<(), Error> {
+        let mut total = String::from(&*self.signature());
+        for (idx, item) in v.iter().enumerate() {
+            let invalid = |msg: &str| Error::new_custom("org.freedesktop.DBus.Error.InvalidArgs",
+                &format!("Argument {}: {}", idx, msg));
+            let mut s = String::new();
+            try!(item.check_append(false, 0, &mut s).map_err(|e| invalid(&e)));
+            try!(check_signature_limits(&s).map_err(|e| invalid(&format!("invalid signature \"{}\": {}", s, e))));
+            try!(Signature::new(&*s).map_err(|e| invalid(&format!("invalid signature \"{}\": {}", s, e))));
+            total.push_str(&s);
+            if total.len() > MAX_SIGNATURE_LENGTH {
+                return Err(invalid(&format!("message signature would exceed the maximum length of {}", MAX_SIGNATURE_LENGTH)));
+            }
+        }
+        Ok(())
     }
 
     /// Appends one MessageItem to a message.
     /// Use in builder style: e g `m.method_return().append(7i32)`
     ///
+    /// Panics if the item cannot be appended, see `append_items`.
+    ///
     /// Note: using `append1`, `append2` or `append3` might be faster, especially for large arrays.
     /// This method is provided for backwards compatibility.
-    pub fn append<I: Into<MessageItem>>(self, v: I) -> Self {
-        let mut i = new_dbus_message_iter();
-        unsafe { ffi::dbus_message_iter_init_append(self.msg, &mut i) };
-        MessageItem::copy_to_iter(&mut i, &[v.into()]);
+    pub fn append<I: Into<MessageItem>>(mut self, v: I) -> Self {
+        self.append_items(&[v.into()]).unwrap();
         self
     }
 
@@ -853,8 +1162,16 @@ impl Message {
     pub fn iter_init<'a>(&'a self) -> Iter<'a> { Iter::new(&self) }
 
     /// Gets the MessageType of the Message.
+    ///
+    /// Returns `MessageType::Invalid` for unknown message types.
     pub fn msg_type(&self) -> MessageType {
-        unsafe { mem::transmute(ffi::dbus_message_get_type(self.msg)) }
+        match unsafe { ffi::dbus_message_get_type(self.msg) } {
+            1 => MessageType::MethodCall,
+            2 => MessageType::MethodReturn,
+            3 => MessageType::Error,
+            4 => MessageType::Signal,
+            _ => MessageType::Invalid,
+        }
     }
 
     fn msg_internal_str<'a>(&'a self, c: *const libc::c_char) -> Option<&'a [u8]> {
@@ -899,6 +1216,15 @@ impl Message {
         assert!(unsafe { ffi::dbus_message_set_destination(self.msg, c_dest) } != 0);
     }
 
+    /// Sets the sender of this Message
+    ///
+    /// The message bus overwrites the sender of all messages it forwards, so this is mostly
+    /// useful for peer-to-peer connections and for testing message handlers.
+    pub fn set_sender(&mut self, sender: Option<BusName>) {
+        let c_sender = sender.as_ref().map(|d| d.as_cstr().as_ptr()).unwrap_or(ptr::null());
+        assert!(unsafe { ffi::dbus_message_set_sender(self.msg, c_sender) } != 0);
+    }
+
     /// Gets the interface this Message is being sent to.
     pub fn interface<'a>(&'a self) -> Option<Interface<'a>> {
         self.msg_internal_str(unsafe { ffi::dbus_message_get_interface(self.msg) })
@@ -911,6 +1237,19 @@ impl Message {
             .map(|s| unsafe { Member::from_slice_unchecked(s) })
     }
 
+    /// Gets the signature of all the arguments of the message.
+    pub fn signature<'a>(&'a self) -> Signature<'a> {
+        self.msg_internal_str(unsafe { ffi::dbus_message_get_signature(self.msg) })
+            .map(|s| unsafe { Signature::from_slice_unchecked(s) })
+            .unwrap_or_else(|| Signature::from(""))
+    }
+
+    /// Gets the error name, if this is an error message.
+    pub fn error_name<'a>(&'a self) -> Option<ErrorName<'a>> {
+        self.msg_internal_str(unsafe { ffi::dbus_message_get_error_name(self.msg) })
+            .map(|s| unsafe { ErrorName::from_slice_unchecked(s) })
+    }
+
     /// When the remote end returns an error, the message itself is
     /// correct but its contents is an error. This method will
     /// transform such an error to a D-Bus Error or otherwise return
@@ -927,6 +1266,29 @@ impl Message {
 
     pub (crate) fn ptr(&self) -> *mut ffi::DBusMessage { self.msg }
 
+    /// Wraps a message created by C code or other bindings to libdbus in the same process.
+    ///
+    /// If `add_ref` is true, a reference is added, so the caller keeps its own. Otherwise the
+    /// returned struct takes over the caller's reference.
+    ///
+    /// Unsafe because `ptr` must point to a valid libdbus message.
+    pub unsafe fn from_raw(ptr: *mut ffi::DBusMessage, add_ref: bool) -> Message {
+        assert!(!ptr.is_null());
+        Message::from_ptr(ptr, add_ref)
+    }
+
+    /// The underlying libdbus message. It is only valid while this struct is alive.
+    pub fn as_raw(&self) -> *mut ffi::DBusMessage { self.msg }
+
+    /// Consumes the message and returns the underlying libdbus message, without unreferencing it.
+    ///
+    /// The caller takes over the reference, and is responsible for calling `dbus_message_unref`.
+    pub fn into_raw(self) -> *mut ffi::DBusMessage {
+        let p = self.msg;
+        mem::forget(self);
+        p
+    }
+
     pub (crate) fn from_ptr(ptr: *mut ffi::DBusMessage, add_ref: bool) -> Message {
         if add_ref {
             unsafe { ffi::dbus_message_ref(ptr) };
@@ -950,9 +1312,126 @@ impl fmt::Debug for Message {
     }
 }
 
+fn pretty_str(s: &str) -> String { format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")) }
+
+fn pretty_args(i: &mut Iter, depth: usize, out: &mut String) {
+    use arg::ArgType;
+    loop {
+        let t = i.arg_type();
+        if t == ArgType::Invalid { break };
+        out.push_str(&"   ".repeat(depth));
+        pretty_arg(i, t, depth, out);
+        out.push('\n');
+        i.next();
+    }
+}
+
+fn pretty_arg(i: &mut Iter, t: ::arg::ArgType, depth: usize, out: &mut String) {
+    use arg::ArgType;
+    let indent = "   ".repeat(depth);
+    let s = match t {
+        ArgType::Byte => format!("byte {}", i.get::<u8>().unwrap()),
+        ArgType::Boolean => format!("boolean {}", i.get::<bool>().unwrap()),
+        ArgType::Int16 => format!("int16 {}", i.get::<i16>().unwrap()),
+        ArgType::UInt16 => format!("uint16 {}", i.get::<u16>().unwrap()),
+        ArgType::Int32 => format!("int32 {}", i.get::<i32>().unwrap()),
+        ArgType::UInt32 => format!("uint32 {}", i.get::<u32>().unwrap()),
+        ArgType::Int64 => format!("int64 {}", i.get::<i64>().unwrap()),
+        ArgType::UInt64 => format!("uint64 {}", i.get::<u64>().unwrap()),
+        ArgType::Double => format!("double {}", i.get::<f64>().unwrap()),
+        ArgType::String => format!("string {}", pretty_str(i.get::<&str>().unwrap())),
+        ArgType::ObjectPath => format!("object path {}", pretty_str(&i.get::<Path>().unwrap())),
+        ArgType::Signature => format!("signature {}", pretty_str(&i.get::<Signature>().unwrap())),
+        ArgType::UnixFd => "file descriptor".into(),
+        ArgType::Variant => {
+            let mut si = i.recurse(t).unwrap();
+            let st = si.arg_type();
+            out.push_str("variant ");
+            pretty_arg(&mut si, st, depth, out);
+            return;
+        }
+        ArgType::Array | ArgType::Struct | ArgType::DictEntry => {
+            let (open, close) = match t {
+                ArgType::Array => ("array [", "]"),
+                ArgType::Struct => ("struct {", "}"),
+                _ => ("dict entry(", ")"),
+            };
+            let mut si = i.recurse(t).unwrap();
+            out.push_str(open);
+            out.push('\n');
+            pretty_args(&mut si, depth + 1, out);
+            out.push_str(&indent);
+            out.push_str(close);
+            return;
+        }
+        ArgType::Invalid => unreachable!(),
+    };
+    out.push_str(&s);
+}
+
+impl Message {
+    /// Formats the message the way the `dbus-monitor` tool does.
+    ///
+    /// The first line contains the message type and headers, followed by one line per argument
+    /// (more for containers), indented and prefixed with the argument type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dbus::Message;
+    ///
+    /// let m = Message::new_signal("/hello", "com.example.Hello", "Greeting").unwrap().append2("Hi", 5u32);
+    /// assert_eq!(m.pretty_print(), "signal sender=(null) -> destination=(null destination) serial=0 \
+    ///     path=/hello; interface=com.example.Hello; member=Greeting\n   string \"Hi\"\n   uint32 5\n");
+    /// ```
+    pub fn pretty_print(&self) -> String {
+        let sender = self.sender().map(|s| s.to_string()).unwrap_or("(null)".into());
+        let dest = self.destination().map(|s| s.to_string()).unwrap_or("(null destination)".into());
+        let mut out = match self.msg_type() {
+            MessageType::MethodCall => "method call",
+            MessageType::MethodReturn => "method return",
+            MessageType::Signal => "signal",
+            MessageType::Error => "error",
+            MessageType::Invalid => "invalid",
+        }.to_string();
+        out.push_str(&format!(" sender={} -> destination={} serial={}", sender, dest, self.get_serial()));
+        match self.msg_type() {
+            MessageType::MethodCall | MessageType::Signal => {
+                let (_, p, i, m) = self.headers();
+                out.push_str(&format!(" path={}; interface={}; member={}", p.unwrap_or("(null)".into()),
+                    i.unwrap_or("(null)".into()), m.unwrap_or("(null)".into())));
+            },
+            MessageType::Error => {
+                let e = self.error_name().map(|e| e.to_string()).unwrap_or("(null)".into());
+                out.push_str(&format!(" error_name={}", e));
+            }
+            _ => {},
+        }
+        if let Some(r) = self.get_reply_serial() { out.push_str(&format!(" reply_serial={}", r)) };
+        out.push('\n');
+        pretty_args(&mut self.iter_init(), 1, &mut out);
+        out
+    }
+}
+
 /// A convenience struct that wraps connection, destination and path.
 ///
 /// Useful if you want to make many method calls to the same destination path.
+/// Methods of the standard interfaces (e g `introspect`, property getters and setters) are available
+/// through the traits in the `stdintf` module.
+///
+/// Create it with `Connection::with_path`. It has a private field now, so it can no longer be
+/// built with a struct literal.
+///
+/// # Example
+/// ```rust,no_run
+/// use dbus::{Connection, BusType};
+///
+/// let c = Connection::get_private(BusType::Session).unwrap();
+/// let p = c.with_path("org.freedesktop.DBus", "/", 2000);
+/// let (has_owner,): (bool,) = p.method_call("org.freedesktop.DBus", "NameHasOwner", ("org.freedesktop.DBus",)).unwrap();
+/// assert!(has_owner);
+/// ```
 #[derive(Clone, Debug)]
 pub struct ConnPath<'a, C> {
     /// Some way to access the connection, e g a &Connection or Rc<Connection>
@@ -961,18 +1440,48 @@ pub struct ConnPath<'a, C> {
     pub dest: BusName<'a>,
     /// Object path on the destination
     pub path: Path<'a>,
-    /// Timeout in milliseconds for blocking method calls
-    pub timeout: i32,
+    /// Timeout for blocking method calls
+    ///
+    /// This was an i32 in milliseconds before; use `Timeout::from(ms)` where such a value is at hand.
+    pub timeout: Timeout,
+    pub (crate) allow_interactive_authorization: bool,
 }
 
 impl<'a, C: ::std::ops::Deref<Target=Connection>> ConnPath<'a, C> {
+    /// Whether method calls allow interactive authorization, see
+    /// `Message::set_allow_interactive_authorization`. Defaults to false.
+    pub fn allow_interactive_authorization(mut self, allow: bool) -> Self { self.allow_interactive_authorization = allow; self }
+
     /// Make a D-Bus method call, where you can append arguments inside the closure.
     pub fn method_call_with_args<F: FnOnce(&mut Message)>(&self, i: &Interface, m: &Member, f: F) -> Result<Message, Error> {
         let mut msg = Message::method_call(&self.dest, &self.path, i, m);
         f(&mut msg);
+        if self.allow_interactive_authorization { try!(msg.set_allow_interactive_authorization(true)) };
         self.conn.send_with_reply_and_block(msg, self.timeout)
     }
 
+    /// Make a D-Bus method call, with the arguments given as a tuple, and read the reply arguments.
+    ///
+    /// Error replies are turned into errors.
+    pub fn method_call<'i, 'm, I, M, A, R>(&self, i: I, m: M, args: A) -> Result<R, Error>
+    where I: Into<Interface<'i>>, M: Into<Member<'m>>, A: AppendAll, R: ReadAll {
+        let mut msg = Message::method_call(&self.dest, &self.path, &i.into(), &m.into());
+        args.append(&mut IterAppend::new(&mut msg));
+        if self.allow_interactive_authorization { try!(msg.set_allow_interactive_authorization(true)) };
+        let mut r = try!(self.conn.send_with_reply_and_block(msg, self.timeout));
+        try!(r.as_result());
+        Ok(try!(R::read(&mut r.iter_init())))
+    }
+
+    /// Adds a match rule for a signal sent from this destination and path.
+    ///
+    /// The match rule is removed when the returned guard is dropped. Incoming signals are returned from
+    /// `Connection::iter` as usual, use `SignalArgs::from_message` to parse them.
+    pub fn match_signal<S: SignalArgs>(&self) -> Result<MatchGuard, Error> {
+        let rule = S::match_rule(Some(&self.dest), Some(&self.path)).match_str();
+        self.conn.add_match_guard(&rule)
+    }
+
     /// Emit a D-Bus signal, where you can append arguments inside the closure.
     pub fn signal_with_args<F: FnOnce(&mut Message)>(&self, i: &Interface, m: &Member, f: F) -> Result<u32, Error> {
         let mut msg = Message::signal(&self.path, i, m);
@@ -997,7 +1506,7 @@ pub (crate) fn message_set_serial(m: &mut Message, s: u32) {
 mod test {
     extern crate tempdir;
 
-    use super::super::{Connection, Message, MessageType, BusType, MessageItem, OwnedFd, libc, Path, BusName};
+    use super::super::{Message, MessageType, MessageItem, OwnedFd, libc, Path, BusName};
 
     #[test]
     fn unix_fd() {
@@ -1005,8 +1514,9 @@ mod test {
         use std::io::SeekFrom;
         use std::fs::OpenOptions;
         use std::os::unix::io::AsRawFd;
+        let bus = ::testing::shared_bus();
 
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let c = bus.connect().unwrap();
         c.register_object_path("/hello").unwrap();
         let mut m = Message::new_method_call(&c.unique_name(), "/hello", "com.example.hello", "Hello").unwrap();
         let tempdir = tempdir::TempDir::new("dbus-rs-test").unwrap();
@@ -1017,7 +1527,7 @@ mod test {
         file.write_all(b"z").unwrap();
         file.seek(SeekFrom::Start(0)).unwrap();
         let ofd = OwnedFd::new(file.as_raw_fd());
-        m.append_items(&[MessageItem::UnixFd(ofd.clone())]);
+        m.append_items(&[MessageItem::UnixFd(ofd.clone())]).unwrap();
         println!("Sending {:?}", m.get_items());
         c.send(m).unwrap();
 
@@ -1037,7 +1547,8 @@ mod test {
 
     #[test]
     fn message_types() {
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
         c.register_object_path("/hello").unwrap();
         let mut m = Message::new_method_call(&c.unique_name(), "/hello", "com.example.hello", "Hello").unwrap();
         m.append_items(&[
@@ -1051,7 +1562,7 @@ mod test {
             MessageItem::Struct(vec!(256i16.into())),
             Path::new("/some/path").unwrap().into(),
             MessageItem::new_array(vec!((123543u32.into(), true.into()).into())).unwrap()
-        ]);
+        ]).unwrap();
         let sending = format!("{:?}", m.get_items());
         println!("Sending {}", sending);
         c.send(m).unwrap();
@@ -1071,6 +1582,7 @@ mod test {
     #[test]
     fn dict_of_dicts() {
         use std::collections::BTreeMap;
+        let bus = ::testing::shared_bus();
 
         let officeactions: BTreeMap<&'static str, MessageItem> = BTreeMap::new();
         let mut officethings = BTreeMap::new();
@@ -1102,10 +1614,10 @@ mod test {
         println!("As MessageItem: {:?}", m);
         assert_eq!(&*m.signature(), "a{oa{sa{sv}}}");
 
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let c = bus.connect().unwrap();
         c.register_object_path("/hello").unwrap();
         let mut msg = Message::new_method_call(&c.unique_name(), "/hello", "org.freedesktop.DBusObjectManager", "GetManagedObjects").unwrap();
-        msg.append_items(&[m]);
+        msg.append_items(&[m]).unwrap();
         let sending = format!("{:?}", msg.get_items());
         println!("Sending {}", sending);
         c.send(msg).unwrap();
@@ -1124,7 +1636,8 @@ mod test {
 
     #[test]
     fn issue24() {
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
         let mut m = Message::new_method_call("org.test.rust", "/", "org.test.rust", "Test").unwrap();
 
         let a = MessageItem::from("test".to_string());
@@ -1135,7 +1648,7 @@ mod test {
         let args = [MessageItem::new_array(vec!(foo, bar)).unwrap()];
         println!("{:?}", args);
 
-        m.append_items(&args);
+        m.append_items(&args).unwrap();
         c.send(m).unwrap();
     }
 
@@ -1149,4 +1662,236 @@ mod test {
         m.set_no_reply(true);
         assert!(m.get_no_reply());
     }
+
+    #[test]
+    fn connpath_signal() {
+        use stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged as PPC;
+        use {ConnectionItem, SignalArgs};
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
+        let p = c.with_path(c.unique_name(), "/connpath/signal", 2000);
+        let _guard = p.match_signal::<PPC>().unwrap();
+        p.emit(&PPC { interface_name: "com.example.test".into(), ..Default::default() }).unwrap();
+        for n in c.iter(1000) {
+            // The bus also sends NameAcquired to us, regardless of match rules.
+            if let Some(ppc) = match n { ConnectionItem::Signal(s) => PPC::from_message(&s), _ => None } {
+                assert_eq!(&*ppc.interface_name, "com.example.test");
+                return;
+            }
+        }
+        panic!("Signal not received");
+    }
+
+    #[test]
+    fn pretty_print() {
+        use std::collections::HashMap;
+        use arg::Variant;
+        let mut d = HashMap::new();
+        d.insert("Key", Variant(5u32));
+        let mut m = Message::new_method_call("com.example.pretty", "/pretty", "com.example.Pretty", "Print").unwrap()
+            .append3(vec!("a\"b"), d, (Path::from("/p"), 1.5f64, true));
+        super::message_set_serial(&mut m, 7);
+        assert_eq!(m.pretty_print(), r#"method call sender=(null) -> destination=com.example.pretty serial=7 path=/pretty; interface=com.example.Pretty; member=Print
+   array [
+      string "a\"b"
+   ]
+   array [
+      dict entry(
+         string "Key"
+         variant uint32 5
+      )
+   ]
+   struct {
+      object path "/p"
+      double 1.5
+      boolean true
+   }
+"#);
+        let e = m.error(&"com.example.Error".into(), &::std::ffi::CString::new("Oops").unwrap());
+        assert_eq!(e.error_name(), Some("com.example.Error".into()));
+        assert_eq!(e.pretty_print(), "error sender=(null) -> destination=(null destination) serial=0 error_name=com.example.Error reply_serial=7\n   string \"Oops\"\n");
+    }
+
+    #[test]
+    fn parse_errors() {
+        use std::collections::HashMap;
+        use arg::Variant;
+        use Signature;
+        let m = Message::new_signal("/parse", "com.example.Parse", "Errors").unwrap()
+            .append3("Hello", Variant(Signature::from("s")), 5u32);
+        let unknown = MessageItem::Unknown { type_code: 'g', signature: "g".into() };
+        assert_eq!(m.get_items(), Ok(vec!(MessageItem::Str("Hello".into()), MessageItem::Variant(Box::new(unknown.clone())),
+            MessageItem::UInt32(5))));
+
+        let mut d = HashMap::new();
+        d.insert("Key", Signature::from("s"));
+        let m = Message::new_signal("/parse", "com.example.Parse", "Errors").unwrap().append1(d);
+        let items = m.get_items().unwrap();
+        assert_eq!(items[0].signature(), "a{sg}".into());
+        match items[0] {
+            MessageItem::Array(ref a) => assert_eq!(a[0], MessageItem::DictEntry(Box::new("Key".into()), Box::new(unknown))),
+            _ => panic!(),
+        }
+
+        let m = Message::new_signal("/parse", "com.example.Parse", "Errors").unwrap().append2(5u8, Path::from("/p"));
+        assert_eq!(m.get_items(), Ok(vec!(MessageItem::Byte(5), MessageItem::ObjectPath("/p".into()))));
+    }
+
+    #[test]
+    fn parse_depth_limit() {
+        use arg::Variant;
+        use super::{ParseError, MAX_NESTING_DEPTH};
+        let m = Message::new_signal("/parse", "com.example.Parse", "Depth").unwrap()
+            .append2(5u8, Variant(vec!((Variant(1u32),))));
+        assert_eq!(m.get_items_limited(4).unwrap().len(), 2);
+        assert_eq!(m.get_items_limited(3), Err(ParseError::TooDeep));
+        assert_eq!(m.get_items_limited(0), Err(ParseError::TooDeep));
+
+        // Build a message with variants nested as deep as libdbus allows.
+        let mut m = MessageItem::UInt32(7);
+        for _ in 0..MAX_NESTING_DEPTH - 1 { m = MessageItem::Variant(Box::new(m)) };
+        let mut msg = Message::new_signal("/parse", "com.example.Parse", "Depth").unwrap();
+        msg.append_items(&[m]).unwrap();
+        assert_eq!(msg.get_items().unwrap().len(), 1);
+        assert_eq!(msg.get_items_limited(10), Err(ParseError::TooDeep));
+    }
+
+    #[test]
+    fn append_errors() {
+        let mut m = Message::new_signal("/append", "com.example.Append", "Errors").unwrap();
+        let e = m.append_items(&["Hello\0world".into()]).unwrap_err();
+        assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.InvalidArgs"));
+        let unknown = MessageItem::Unknown { type_code: 'g', signature: "g".into() };
+        assert!(m.append_items(&[MessageItem::Struct(vec!(5u8.into(), unknown))]).is_err());
+        m.append_items(&[5u8.into(), "Hello".into()]).unwrap();
+    }
+
+    #[test]
+    fn nested_signatures() {
+        let inner = MessageItem::Struct(vec!(5u8.into(), MessageItem::new_array(vec!("a".into())).unwrap()));
+        let entry = MessageItem::DictEntry(Box::new("k".into()), Box::new(MessageItem::Variant(Box::new(inner.clone()))));
+        let outer = MessageItem::Struct(vec!(inner.clone(), MessageItem::new_array(vec!(entry)).unwrap()));
+        assert_eq!(&*outer.signature(), "((yas)a{sv})");
+        let mut m = Message::new_signal("/sig", "com.example.Sig", "Nested").unwrap();
+        m.append_items(&[outer.clone(), MessageItem::Variant(Box::new(outer.clone()))]).unwrap();
+        assert_eq!(&*m.signature(), "((yas)a{sv})v");
+        assert_eq!(m.get_items(), Ok(vec!(outer.clone(), MessageItem::Variant(Box::new(outer)))));
+    }
+
+    #[test]
+    fn fixed_arrays() {
+        let items = vec!(
+            MessageItem::new_array(vec!(true.into(), false.into(), true.into())).unwrap(),
+            MessageItem::new_array((0..300).map(|i| MessageItem::Byte(i as u8)).collect()).unwrap(),
+            MessageItem::new_array(vec!((-5i16).into(), 7i16.into())).unwrap(),
+            MessageItem::new_array(vec!(1u64.into(), ::std::u64::MAX.into())).unwrap(),
+            MessageItem::new_array(vec!(1.5f64.into(), (-2.25f64).into())).unwrap(),
+            MessageItem::new_array(vec!(MessageItem::Variant(Box::new(5i32.into())))).unwrap(),
+            MessageItem::Array(super::MessageItemArray::new(vec!(), ::Signature::new("ai").unwrap()).unwrap()),
+        );
+        let mut m = Message::new_signal("/fixed", "com.example.Fixed", "Arrays").unwrap();
+        m.append_items(&items).unwrap();
+        assert_eq!(&*m.signature(), "abayanatadavai");
+        assert_eq!(m.get_items(), Ok(items));
+        let (b, y): (Vec<bool>, Vec<u8>) = m.read2().unwrap();
+        assert_eq!(b, vec!(true, false, true));
+        assert_eq!(y.len(), 300);
+    }
+
+    #[test]
+    fn abandon_failed_append() {
+        let mut m = Message::new_signal("/", "com.example.dbusrs.Abandon", "Abandon").unwrap().append1(5u8);
+        let mut i = super::new_dbus_message_iter();
+        unsafe { ffi::dbus_message_iter_init_append(m.ptr(), &mut i) };
+        let r = super::iter_append_container(&mut i, ffi::DBUS_TYPE_ARRAY, b"s\0".as_ptr() as *const _, |sub| {
+            try!(MessageItem::Str("Hello".into()).iter_append(sub));
+            Err(super::no_memory())
+        });
+        assert!(r.is_err());
+        assert_eq!(&*m.signature(), "y");
+        assert_eq!(m.read1::<u8>().unwrap(), 5);
+    }
+
+    #[test]
+    fn interactive_authorization() {
+        use std::thread;
+        use ::Version;
+        let bus = ::testing::shared_bus();
+        if ::version() < Version::new(1, 8, 10) {
+            let m = Message::new_signal("/", "com.example.dbusrs.Auth", "Auth").unwrap();
+            assert!(m.set_allow_interactive_authorization(true).is_err());
+            return;
+        }
+        let m = Message::new_method_call("com.example.dbusrs", "/", "com.example.dbusrs.Auth", "Auth").unwrap();
+        assert!(!m.get_allow_interactive_authorization());
+        m.set_allow_interactive_authorization(true).unwrap();
+        assert!(m.get_allow_interactive_authorization());
+
+        let c = bus.connect().unwrap();
+        c.register_object_path("/auth").unwrap();
+        let name = c.unique_name();
+        let t = thread::spawn(move || {
+            let c2 = bus.connect().unwrap();
+            let p = c2.with_path(name, "/auth", 5000).allow_interactive_authorization(true);
+            let r: bool = p.method_call("com.example.dbusrs.Auth", "Auth", ()).map(|(r,)| r).unwrap();
+            r
+        });
+        'outer: loop { for m in c.incoming(1000) {
+            if m.msg_type() != MessageType::MethodCall { continue };
+            c.send(m.method_return().append1(m.get_allow_interactive_authorization())).unwrap();
+            break 'outer;
+        }}
+        assert!(t.join().unwrap());
+    }
+
+    #[test]
+    fn raw_message() {
+        let m = Message::new_signal("/", "com.example.dbusrs.Raw", "Raw").unwrap().append1(5u8);
+        let p = m.into_raw();
+        let m2 = unsafe { Message::from_raw(p, true) };
+        let m = unsafe { Message::from_raw(p, false) };
+        assert_eq!(m.as_raw(), m2.as_raw());
+        drop(m2);
+        assert_eq!(m.read1::<u8>().unwrap(), 5);
+    }
+
+    #[test]
+    fn signature_limits() {
+        use super::check_signature_limits;
+        assert!(check_signature_limits("a{sv}(ia(ay))").is_ok());
+        let arrays = format!("{}i", "a".repeat(32));
+        assert!(check_signature_limits(&arrays).is_ok());
+        assert!(check_signature_limits(&format!("a{}", arrays)).is_err());
+        assert!(check_signature_limits(&format!("({}i{})", "(".repeat(31), ")".repeat(31))).is_ok());
+        assert!(check_signature_limits(&format!("({}i{})", "(".repeat(32), ")".repeat(32))).is_err());
+        // Arrays end with their element type, so these do not add up.
+        assert!(check_signature_limits(&format!("{}i{}i", "a".repeat(20), "a".repeat(20))).is_ok());
+        assert!(check_signature_limits(&"i".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn append_validation() {
+        let mut m = Message::new_signal("/append", "com.example.Append", "Validation").unwrap();
+        let check = |m: &mut Message, v: &[MessageItem], idx: usize| {
+            let e = m.append_items(v).unwrap_err();
+            assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.InvalidArgs"));
+            assert!(e.message().unwrap().starts_with(&format!("Argument {}:", idx)), "{:?}", e);
+        };
+        let entry = MessageItem::DictEntry(Box::new(1u8.into()), Box::new(2u8.into()));
+        check(&mut m, &[5u8.into(), entry.clone()], 1);
+        check(&mut m, &[MessageItem::Struct(vec!())], 0);
+        check(&mut m, &[MessageItem::Variant(Box::new(entry))], 0);
+        let mut deep = MessageItem::Byte(1);
+        for _ in 0..40 { deep = MessageItem::Struct(vec!(deep)) };
+        check(&mut m, &[deep], 0);
+        let mut deep = MessageItem::Byte(1);
+        for _ in 0..super::MAX_NESTING_DEPTH + 1 { deep = MessageItem::Variant(Box::new(deep)) };
+        check(&mut m, &[deep], 0);
+        let long: Vec<MessageItem> = (0..300).map(|i| MessageItem::Int32(i)).collect();
+        check(&mut m, &long, 255);
+        // Nothing should have been appended by the failed attempts.
+        assert_eq!(&*m.signature(), "");
+        m.append_items(&long[..255]).unwrap();
+        check(&mut m, &[1u8.into()], 0);
+    }
 }
@@ -369,7 +369,7 @@ impl<M: MethodType> Property<M> {
 
     /// Helper method to verify and extract a MessageItem from a Set message
     pub fn verify_remote_set(&self, m: &Message) -> Result<MessageItem, MethodErr> {
-        let items = m.get_items();
+        let items = try!(m.get_items().map_err(|e| MethodErr::invalid_arg(&e)));
         let s: &MessageItem = try!(items.get(2).ok_or_else(|| MethodErr::no_arg())
             .and_then(|i| i.inner().map_err(|_| MethodErr::invalid_arg(&i))));
 
@@ -970,7 +970,7 @@ fn prop_server() {
     super::message::message_set_serial(&mut msg, 10);
     let r = tree.handle(&msg).unwrap();
     let r1 = r.get(0).unwrap();
-    let ii = r1.get_items();
+    let ii = r1.get_items().unwrap();
     let vv: &MessageItem = ii.get(0).unwrap().inner().unwrap();
     let v: i32 = vv.inner().unwrap();
     assert_eq!(v, 0);
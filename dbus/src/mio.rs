@@ -0,0 +1,132 @@
+//! Integration with [mio](https://docs.rs/mio/0.6).
+//!
+//! Enable the `mio` feature to use this module. It makes it possible to register a `Connection`
+//! (or individual `Watch`es) with a `mio::Poll`, so D-Bus can be multiplexed with sockets and timers
+//! in the same event loop.
+//!
+//! The fds are always registered as level triggered, because libdbus might leave data
+//! in the socket after a call to `watch_handle`.
+//!
+//! # Example
+//! ```rust,no_run
+//! extern crate mio;
+//! extern crate dbus;
+//! fn main() {
+//!     use dbus::{Connection, BusType};
+//!     let c = Connection::get_private(BusType::Session).unwrap();
+//!     let poll = mio::Poll::new().unwrap();
+//!     poll.register(&c, mio::Token(0), mio::Ready::readable(), mio::PollOpt::level()).unwrap();
+//!
+//!     let mut events = mio::Events::with_capacity(16);
+//!     poll.poll(&mut events, Some(::std::time::Duration::from_millis(100))).unwrap();
+//!     for event in events.iter() {
+//!         for item in dbus::mio::handle_ready(&c, event.readiness()) {
+//!             println!("Received ConnectionItem: {:?}", item);
+//!         }
+//!     }
+//! }
+//! ```
+
+use mio_crate::{Evented, Poll, Token, Ready, PollOpt};
+use mio_crate::unix::{EventedFd, UnixReady};
+use super::{Connection, ConnectionItems, Watch, WatchEvent};
+use std::io;
+use std::os::raw::c_uint;
+
+/// Returns the readiness to register for, given what libdbus currently wants to watch the fd for.
+pub fn interest(w: &Watch) -> Ready {
+    let mut r = Ready::from(UnixReady::hup() | UnixReady::error());
+    if w.readable() { r.insert(Ready::readable()) };
+    if w.writable() { r.insert(Ready::writable()) };
+    r
+}
+
+/// Transforms mio readiness into flags you can send into `Connection::watch_handle`.
+pub fn ready_to_flags(r: Ready) -> c_uint {
+    let ur = UnixReady::from(r);
+    0 +
+    if ur.is_readable() { WatchEvent::Readable as c_uint } else { 0 } +
+    if ur.is_writable() { WatchEvent::Writable as c_uint } else { 0 } +
+    if ur.is_error() { WatchEvent::Error as c_uint } else { 0 } +
+    if ur.is_hup() { WatchEvent::Hangup as c_uint } else { 0 }
+}
+
+/// Call this when mio reports readiness on the token the connection was registered with.
+///
+/// All fds of the connection are handled, and the returned iterator will return
+/// pending items only, never block for new events.
+pub fn handle_ready(c: &Connection, r: Ready) -> ConnectionItems {
+    let flags = ready_to_flags(r);
+    for w in c.watch_fds() {
+        // Only the watch side effect is wanted here, the items are returned below.
+        let _ = c.watch_handle(w.fd(), flags);
+    }
+    ConnectionItems::new(c, None, true)
+}
+
+fn level(mut opts: PollOpt) -> PollOpt {
+    opts.remove(PollOpt::edge());
+    opts.insert(PollOpt::level());
+    opts
+}
+
+impl Evented for Watch {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        EventedFd(&self.fd()).register(poll, token, interest & self::interest(self), level(opts))
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        EventedFd(&self.fd()).reregister(poll, token, interest & self::interest(self), level(opts))
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        EventedFd(&self.fd()).deregister(poll)
+    }
+}
+
+/// Registers all fds the connection currently watches, with the same token.
+///
+/// If libdbus later adds or toggles watches (see `Connection::set_watch_callback`),
+/// call `Poll::reregister` to update the registration.
+impl Evented for Connection {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        for w in self.watch_fds() { try!(w.register(poll, token, interest, opts)) }
+        Ok(())
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        for w in self.watch_fds() { try!(w.reregister(poll, token, interest, opts)) }
+        Ok(())
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        for w in self.watch_fds() { try!(w.deregister(poll)) }
+        Ok(())
+    }
+}
+
+#[test]
+fn mio_method_call() {
+    use super::Message;
+    use std::time::Duration;
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let poll = Poll::new().unwrap();
+    poll.register(&c, Token(5), Ready::readable(), PollOpt::level()).unwrap();
+
+    let m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetId").unwrap();
+    let serial = c.send(m).unwrap();
+    let mut events = ::mio_crate::Events::with_capacity(16);
+    loop {
+        poll.poll(&mut events, Some(Duration::from_millis(1000))).unwrap();
+        assert!(!events.is_empty());
+        for event in events.iter() {
+            assert_eq!(event.token(), Token(5));
+            for item in handle_ready(&c, event.readiness()) {
+                if let ::ConnectionItem::MethodReturn(r) = item {
+                    if r.get_reply_serial() == Some(serial) { return; }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use {BusType, ConnMsgs, Connection, DBusProxy, Error, Message, MessageType};
+use std::fmt;
+use std::os::unix::io::RawFd;
+
+/// A connection that has become a monitor, using the `org.freedesktop.DBus.Monitoring` interface.
+///
+/// A monitor receives copies of the messages on the bus that match its rules, including method
+/// calls, replies and errors between other connections. In exchange, the D-Bus server no longer
+/// lets it send anything: not even replies, and not even calls to the bus itself (the server
+/// disconnects monitors that try). This type only allows receiving, so that cannot happen by
+/// accident.
+///
+/// The server must support `BecomeMonitor` (dbus-daemon 1.9.10 or later), and its security
+/// policy usually restricts it to root, or to the owner of a session bus. For older servers,
+/// use match rules with `eavesdrop` set instead, see `MatchRule`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{BusType, MonitorConnection};
+///
+/// let m = MonitorConnection::open(BusType::Session, &["type='signal'"]).unwrap();
+/// loop {
+///     for msg in m.incoming(1000) { println!("{}", msg.pretty_print()); }
+/// }
+/// ```
+pub struct MonitorConnection {
+    conn: Connection,
+    unique_name: String,
+}
+
+impl MonitorConnection {
+    /// Turns a connection into a monitor, receiving copies of all messages matching any of the rules.
+    ///
+    /// An empty list of rules means all messages. The connection is consumed, since it can no longer
+    /// be used for sending. Messages it already received before are still returned by `incoming`.
+    pub fn new(conn: Connection, rules: &[&str]) -> Result<MonitorConnection, Error> {
+        // Method calls that are not handled get an automatic error reply from libdbus,
+        // which would make the server disconnect us.
+        if let Some(mut old_cb) = conn.replace_message_callback(None) {
+            conn.replace_message_callback(Some(Box::new(move |c, m| { old_cb(c, m); true })));
+        }
+        try!(DBusProxy::new(&conn).become_monitor(rules, 0));
+        let n = conn.unique_name();
+        Ok(MonitorConnection { conn: conn, unique_name: n })
+    }
+
+    /// Opens a new connection to the bus and turns it into a monitor. See `new`.
+    pub fn open(bus: BusType, rules: &[&str]) -> Result<MonitorConnection, Error> {
+        MonitorConnection::new(try!(Connection::get_private(bus)), rules)
+    }
+
+    /// The unique name the connection had before it became a monitor.
+    pub fn unique_name(&self) -> &str { &self.unique_name }
+
+    /// Returns false once the connection to the bus has been lost.
+    pub fn is_connected(&self) -> bool { self.conn.is_connected() }
+
+    /// The file descriptor of the underlying socket, to poll for incoming messages.
+    pub fn unix_fd(&self) -> Option<RawFd> { self.conn.unix_fd() }
+
+    /// Returns an iterator over the monitored messages, which ends when no message arrived within the timeout.
+    ///
+    /// The `NameLost` signal the server sends when a connection becomes a monitor is filtered out.
+    pub fn incoming(&self, timeout_ms: u32) -> MonitorMsgs {
+        MonitorMsgs { msgs: self.conn.incoming(timeout_ms), unique_name: &self.unique_name }
+    }
+}
+
+impl fmt::Debug for MonitorConnection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "MonitorConnection({})", self.unique_name)
+    }
+}
+
+/// Iterator over monitored messages, see `MonitorConnection::incoming`.
+pub struct MonitorMsgs<'a> {
+    msgs: ConnMsgs<&'a Connection>,
+    unique_name: &'a str,
+}
+
+impl<'a> fmt::Debug for MonitorMsgs<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "MonitorMsgs({})", self.unique_name)
+    }
+}
+
+fn is_own_name_lost(m: &Message, unique_name: &str) -> bool {
+    m.msg_type() == MessageType::Signal && m.destination().as_ref().map(|d| &**d) == Some(unique_name) &&
+        m.interface().as_ref().map(|i| &**i) == Some("org.freedesktop.DBus") &&
+        m.member().as_ref().map(|x| &**x) == Some("NameLost") && m.get1::<&str>() == Some(unique_name)
+}
+
+impl<'a> Iterator for MonitorMsgs<'a> {
+    type Item = Message;
+    fn next(&mut self) -> Option<Message> {
+        loop {
+            let m = self.msgs.next()?;
+            if !is_own_name_lost(&m, self.unique_name) { return Some(m) };
+        }
+    }
+}
+
+#[test]
+fn monitor_connection() {
+    use ErrorKind;
+    use testing::TestBus;
+
+    let bus = match TestBus::new() {
+        Ok(b) => b,
+        Err(ref e) if e.kind() == ErrorKind::SpawnFailed => return, // No dbus-daemon available
+        Err(e) => panic!("{}", e),
+    };
+    let m = match MonitorConnection::new(bus.connect().unwrap(), &["interface='com.example.dbusrs.Monitor'"]) {
+        Ok(m) => m,
+        Err(ref e) if e.kind() == ErrorKind::UnknownInterface || e.kind() == ErrorKind::UnknownMethod => return, // Old server
+        Err(e) => panic!("{}", e),
+    };
+    assert!(m.unique_name().starts_with(":"));
+    assert!(m.unix_fd().is_some());
+
+    // Method calls between other connections are seen, but not our own NameLost.
+    let a = bus.connect().unwrap();
+    let b = bus.connect().unwrap();
+    let call = Message::new_method_call(&*b.unique_name(), "/", "com.example.dbusrs.Monitor", "Hello").unwrap();
+    a.send(call).unwrap();
+    let msgs: Vec<_> = m.incoming(1000).collect();
+    assert!(!msgs.iter().any(|x| x.member().map_or(false, |x| &*x == "NameLost")));
+    let seen = msgs.iter().find(|x| x.member().map_or(false, |x| &*x == "Hello")).unwrap();
+    assert_eq!(seen.msg_type(), MessageType::MethodCall);
+    assert_eq!(seen.sender().unwrap(), a.unique_name().into());
+    assert!(m.is_connected());
+}
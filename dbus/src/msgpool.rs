@@ -0,0 +1,121 @@
+use {Message, MessageType, ffi};
+use std::os::raw::c_int;
+use std::ptr;
+
+/// A pool of preallocated method return messages, for servers answering many method calls.
+///
+/// Creating a reply with `Message::method_return` allocates a new message every time. A pool
+/// allocates empty replies up front (and whenever `fill` is called, e g when the server is idle),
+/// so that answering a call only fills in the reply serial and destination.
+///
+/// libdbus cannot reuse a message once it has been sent, so a message taken from the pool is
+/// not put back; call `fill` to replenish it. If the pool is empty, `method_return` falls back
+/// to allocating a new message.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Connection, BusType, MessagePool, MessageType};
+///
+/// let c = Connection::get_private(BusType::Session).unwrap();
+/// let mut pool = MessagePool::new(64);
+/// loop {
+///     for msg in c.incoming(1000) {
+///         if msg.msg_type() == MessageType::MethodCall {
+///             c.send(pool.method_return(&msg).append1("Hello")).unwrap();
+///         }
+///     }
+///     pool.fill();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MessagePool {
+    free: Vec<Message>,
+    capacity: usize,
+}
+
+impl MessagePool {
+    /// Creates a pool and fills it with `capacity` messages.
+    pub fn new(capacity: usize) -> MessagePool {
+        let mut p = MessagePool { free: Vec::with_capacity(capacity), capacity: capacity };
+        p.fill();
+        p
+    }
+
+    /// Allocates messages until the pool is full again.
+    pub fn fill(&mut self) {
+        while self.free.len() < self.capacity {
+            let p = unsafe { ffi::dbus_message_new(MessageType::MethodReturn as c_int) };
+            if p == ptr::null_mut() { return };
+            let m = Message::from_ptr(p, false);
+            unsafe { ffi::dbus_message_set_no_reply(m.ptr(), 1) };
+            self.free.push(m);
+        }
+    }
+
+    /// The number of messages currently in the pool.
+    pub fn len(&self) -> usize { self.free.len() }
+
+    /// Returns true if the pool is empty.
+    pub fn is_empty(&self) -> bool { self.free.is_empty() }
+
+    /// The number of messages the pool holds when full.
+    pub fn capacity(&self) -> usize { self.capacity }
+
+    /// Creates a method return (reply) for a method call, taking a message from the pool if possible.
+    ///
+    /// The result is the same as `m.method_return()`.
+    pub fn method_return(&mut self, m: &Message) -> Message {
+        let r = match self.free.pop() {
+            Some(r) => r,
+            None => return m.method_return(),
+        };
+        unsafe {
+            if ffi::dbus_message_set_reply_serial(r.ptr(), ffi::dbus_message_get_serial(m.ptr())) == 0 {
+                panic!("D-Bus error: dbus_message_set_reply_serial failed")
+            }
+            let sender = ffi::dbus_message_get_sender(m.ptr());
+            if sender != ptr::null() && ffi::dbus_message_set_destination(r.ptr(), sender) == 0 {
+                panic!("D-Bus error: dbus_message_set_destination failed")
+            }
+        }
+        r
+    }
+}
+
+#[test]
+fn message_pool() {
+    let bus = ::testing::shared_bus();
+
+    let mut pool = MessagePool::new(2);
+    assert_eq!((pool.len(), pool.capacity()), (2, 2));
+
+    let c = bus.connect().unwrap();
+    c.register_object_path("/").unwrap();
+    let c2 = bus.connect().unwrap();
+    let mut replies = vec!();
+    for i in 0..3u32 {
+        let m = Message::new_method_call(&*c.unique_name(), "/", "com.example.dbusrs.Pool", "Get").unwrap().append1(i);
+        replies.push(c2.send_with_pending_call(m, 5000).unwrap());
+    }
+    let mut n = 0;
+    for _ in 0..10 {
+        for m in c.incoming(200) {
+            if m.msg_type() != MessageType::MethodCall { continue };
+            let r = pool.method_return(&m);
+            let r2 = m.method_return();
+            assert_eq!(r.get_reply_serial(), r2.get_reply_serial());
+            assert_eq!(r.destination(), r2.destination());
+            assert_eq!(r.get_no_reply(), r2.get_no_reply());
+            c.send(r.append1(m.read1::<u32>().unwrap() * 2)).unwrap();
+            n += 1;
+        }
+        if n == 3 { break };
+    }
+    assert!(pool.is_empty());
+    pool.fill();
+    assert_eq!(pool.len(), 2);
+
+    let v: Vec<u32> = replies.iter().map(|p| { p.block(); p.steal_reply().unwrap().read1().unwrap() }).collect();
+    assert_eq!(v, vec!(0, 2, 4));
+}
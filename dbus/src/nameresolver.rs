@@ -0,0 +1,153 @@
+use {Connection, Error, ErrorKind, Message, MessageType, MatchRule, MatchGuard, BusName, SignalArgs, DBusProxy};
+use stdintf::org_freedesktop_dbus::DBusNameOwnerChanged;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+fn match_rule(name: &str) -> MatchRule<'static> {
+    let mut m = MatchRule::new();
+    m.msg_type = Some(MessageType::Signal);
+    m.sender = Some("org.freedesktop.DBus".into());
+    m.path = Some("/org/freedesktop/DBus".into());
+    m.interface = Some(DBusNameOwnerChanged::INTERFACE.into());
+    m.member = Some(DBusNameOwnerChanged::NAME.into());
+    m.args.push((0, Cow::Owned(name.into())));
+    m
+}
+
+#[derive(Debug)]
+struct Entry<'a> {
+    owner: Option<BusName<'static>>,
+    _guard: MatchGuard<'a>,
+}
+
+/// Maps well-known names to the unique names of their current owners.
+///
+/// Messages carry the unique name of their sender (e g ":1.42"), so filtering signals on a
+/// well-known name (e g "org.freedesktop.Notifications") requires knowing who currently owns it.
+/// The resolver looks up a name with `GetNameOwner` the first time it is needed, and adds a match
+/// rule for its `NameOwnerChanged` signal to keep the cached owner fresh. The cache is only
+/// as fresh as the last message passed to `update`, so call it for every incoming message.
+/// The match rules are removed when the name is forgotten or the resolver is dropped.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Connection, BusType, MatchRule, NameResolver};
+///
+/// let c = Connection::get_private(BusType::Session).unwrap();
+/// let r = NameResolver::new(&c);
+/// let mut rule = MatchRule::new();
+/// rule.member = Some("NotificationClosed".into());
+/// rule.sender = Some("org.freedesktop.Notifications".into());
+/// c.add_match(&rule.match_str()).unwrap();
+/// for msg in c.incoming(1000) {
+///     r.update(&msg);
+///     if r.matches(&rule, &msg) { println!("Notification closed") };
+/// }
+/// ```
+#[derive(Debug)]
+pub struct NameResolver<'a> {
+    conn: &'a Connection,
+    names: RefCell<HashMap<String, Entry<'a>>>,
+}
+
+impl<'a> NameResolver<'a> {
+    /// Creates a resolver with an empty cache.
+    pub fn new(c: &'a Connection) -> Self {
+        NameResolver { conn: c, names: RefCell::new(HashMap::new()) }
+    }
+
+    /// Returns the unique name of the current owner of `name`, or None if it has no owner.
+    ///
+    /// Unique names resolve to themselves. Other names are looked up on the bus the first time,
+    /// and then answered from the cache.
+    pub fn resolve(&self, name: &str) -> Result<Option<BusName<'static>>, Error> {
+        if name.starts_with(':') { return BusName::new(name).map(Some).map_err(|e|
+            Error::new_custom("org.freedesktop.DBus.Error.InvalidArgs", &e)) };
+        if let Some(e) = self.names.borrow().get(name) { return Ok(e.owner.clone()) };
+
+        // Add the match rule first, so that no ownership change is missed.
+        let guard = try!(self.conn.add_match_guard(&match_rule(name).match_str()));
+        let owner = match DBusProxy::new(self.conn).get_name_owner(name) {
+            Ok(owner) => Some(owner),
+            Err(ref e) if e.kind() == ErrorKind::NameHasNoOwner => None,
+            Err(e) => return Err(e),
+        };
+        self.names.borrow_mut().insert(name.into(), Entry { owner: owner.clone(), _guard: guard });
+        Ok(owner)
+    }
+
+    /// Removes the name from the cache, and its match rule from the bus.
+    pub fn forget(&self, name: &str) {
+        self.names.borrow_mut().remove(name);
+    }
+
+    /// Updates the cache if the message is a `NameOwnerChanged` signal for a cached name.
+    ///
+    /// Returns true if the cache was updated.
+    pub fn update(&self, msg: &Message) -> bool {
+        if msg.sender().as_ref().map(|s| &**s) != Some("org.freedesktop.DBus") { return false }
+        let s = match DBusNameOwnerChanged::from_message(msg) { Some(s) => s, None => return false };
+        let mut names = self.names.borrow_mut();
+        let e = match names.get_mut(&s.name) { Some(e) => e, None => return false };
+        e.owner = if s.new_owner.is_empty() { None } else { BusName::new(s.new_owner).ok() };
+        true
+    }
+
+    /// Returns true if the message was sent by the current owner of `name`.
+    ///
+    /// Lookup errors are treated as the name not matching.
+    pub fn is_sender(&self, name: &str, msg: &Message) -> bool {
+        let sender = match msg.sender() { Some(s) => s, None => return false };
+        if &*sender == name { return true };
+        match self.resolve(name) {
+            Ok(Some(owner)) => owner == sender,
+            _ => false,
+        }
+    }
+
+    /// Like `MatchRule::matches`, but resolves a well-known name in the rule's `sender` to its
+    /// current owner before comparing it with the message's sender.
+    pub fn matches(&self, rule: &MatchRule, msg: &Message) -> bool {
+        if let Some(ref sender) = rule.sender {
+            if !self.is_sender(sender, msg) { return false };
+        }
+        rule.matches_except_sender(msg)
+    }
+}
+
+#[test]
+fn name_resolver() {
+    let bus = ::testing::shared_bus();
+
+    let name = "com.example.dbusrs.nameresolver";
+    let c = bus.connect().unwrap();
+    let r = NameResolver::new(&c);
+    assert_eq!(r.resolve(name).unwrap(), None);
+    assert_eq!(r.resolve(&c.unique_name()).unwrap(), Some(c.unique_name().into()));
+
+    let c2 = bus.connect().unwrap();
+    c2.register_name(name, 0).unwrap();
+    let mut rule = MatchRule::new();
+    rule.interface = Some("com.example.dbusrs.NameResolver".into());
+    rule.sender = Some(name.into());
+    c.add_match(&rule.match_str()).unwrap();
+    c2.send(Message::new_signal("/", "com.example.dbusrs.NameResolver", "Ping").unwrap()).unwrap();
+
+    let (mut updated, mut matched) = (false, false);
+    for msg in c.incoming(1000) {
+        if r.update(&msg) { updated = true };
+        if r.matches(&rule, &msg) {
+            assert!(!rule.matches(&msg));
+            matched = true;
+        }
+        if updated && matched { break };
+    }
+    assert_eq!(r.resolve(name).unwrap(), Some(c2.unique_name().into()));
+
+    c2.release_name(name).unwrap();
+    assert!(c.incoming(1000).any(|msg| r.update(&msg)));
+    assert_eq!(r.resolve(name).unwrap(), None);
+    r.forget(name);
+}
@@ -0,0 +1,136 @@
+use {Connection, Error, ErrorKind, Message, MessageType, BusName, DBusProxy, NameFlags, RequestNameReply};
+use connection::SignalHandler;
+
+/// A change of ownership of a name we requested, see `NameTracker`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NameEvent {
+    /// We are now the primary owner of the name.
+    Acquired,
+    /// We are no longer the primary owner of the name.
+    Lost,
+}
+
+fn parse_event(name: &BusName, msg: &Message) -> Option<NameEvent> {
+    if msg.msg_type() != MessageType::Signal { return None }
+    if msg.sender().as_ref().map(|s| &**s) != Some("org.freedesktop.DBus") { return None }
+    if msg.interface().as_ref().map(|s| &**s) != Some("org.freedesktop.DBus") { return None }
+    let e = match msg.member().as_ref().map(|s| &**s) {
+        Some("NameAcquired") => NameEvent::Acquired,
+        Some("NameLost") => NameEvent::Lost,
+        _ => return None,
+    };
+    let n: &str = msg.read1().ok()?;
+    if n == &**name { Some(e) } else { None }
+}
+
+/// Tracks whether we are the primary owner of a name on the bus.
+///
+/// If `RequestName` returns `InQueue`, or if we allowed another connection to replace us,
+/// ownership can change at any time. The bus tells us with the `NameAcquired` and `NameLost`
+/// signals. Unlike most signals, these are sent to us directly, so the tracker needs no match
+/// rule and nothing is set up on the bus: it is only a filter for those signals. Pass incoming
+/// messages to `event`, or let `handler` call a closure.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Connection, BusType, NameTracker, NameEvent, RequestNameReply};
+///
+/// let c = Connection::get_private(BusType::Session).unwrap();
+/// let t = NameTracker::new(&c, "com.example.dbusrs.tracked");
+/// if t.request(0).unwrap() == RequestNameReply::InQueue {
+///     println!("Waiting for the name");
+/// }
+/// for msg in c.incoming(1000) {
+///     match t.event(&msg) {
+///         Some(NameEvent::Acquired) => println!("We own the name"),
+///         Some(NameEvent::Lost) => println!("We lost the name"),
+///         None => {},
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct NameTracker<'a> {
+    conn: &'a Connection,
+    name: BusName<'static>,
+}
+
+impl<'a> NameTracker<'a> {
+    /// Starts tracking the name. This does not request the name, see `request`.
+    pub fn new<'b, N: Into<BusName<'b>>>(c: &'a Connection, name: N) -> Self {
+        NameTracker { conn: c, name: name.into().into_static() }
+    }
+
+    /// The tracked name.
+    pub fn name(&self) -> &BusName<'static> { &self.name }
+
+    /// Requests the name, same as `Connection::register_name`.
+    pub fn request<F: Into<NameFlags>>(&self, flags: F) -> Result<RequestNameReply, Error> {
+        self.conn.register_name(&self.name, flags)
+    }
+
+    /// Asks the D-Bus server whether we are currently the primary owner of the name.
+    pub fn current(&self) -> Result<NameEvent, Error> {
+        match DBusProxy::new(self.conn).get_name_owner(&self.name) {
+            Ok(ref owner) if **owner == *self.conn.unique_name() => Ok(NameEvent::Acquired),
+            Ok(_) => Ok(NameEvent::Lost),
+            Err(ref e) if e.kind() == ErrorKind::NameHasNoOwner => Ok(NameEvent::Lost),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// If the message tells us that we acquired or lost the tracked name, returns which.
+    pub fn event(&self, msg: &Message) -> Option<NameEvent> { parse_event(&self.name, msg) }
+
+    /// Returns a message handler that calls `f` whenever we acquire or lose the name.
+    ///
+    /// Add it to the connection with `Connection::add_handler`. The `NameAcquired` and `NameLost`
+    /// signals for the name are consumed by the handler. Since there is no match rule, the
+    /// handler keeps working after the tracker is dropped, until it is removed from the connection.
+    pub fn handler<F: FnMut(NameEvent)>(&self, f: F) -> NameHandler<F> {
+        let name = self.name.clone();
+        SignalHandler::new(move |msg| parse_event(&name, msg), true, f)
+    }
+}
+
+/// The handler returned by `NameTracker::handler`, calling a closure whenever we acquire or lose
+/// a name.
+pub type NameHandler<F> = SignalHandler<NameEvent, F>;
+
+#[test]
+fn name_tracker() {
+    use NameFlag;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    let bus = ::testing::shared_bus();
+
+    let name = "com.example.dbusrs.nametracker";
+    let c = bus.connect().unwrap();
+    let t = NameTracker::new(&c, name);
+    assert_eq!(&**t.name(), name);
+    assert_eq!(t.current().unwrap(), NameEvent::Lost);
+
+    let c2 = bus.connect().unwrap();
+    c2.register_name(name, NameFlag::AllowReplacement).unwrap();
+    assert_eq!(t.request(0).unwrap(), RequestNameReply::InQueue);
+    assert_eq!(t.current().unwrap(), NameEvent::Lost);
+    c2.release_name(name).unwrap();
+
+    let mut events = vec!();
+    for msg in c.incoming(1000) {
+        if let Some(e) = t.event(&msg) { events.push(e) };
+        if events.len() == 1 { break };
+    }
+    assert_eq!(events, vec!(NameEvent::Acquired));
+    assert_eq!(t.current().unwrap(), NameEvent::Acquired);
+
+    let events = Rc::new(RefCell::new(vec!()));
+    let e2 = events.clone();
+    c.add_handler(t.handler(move |e| e2.borrow_mut().push(e)));
+    c.release_name(name).unwrap();
+    for _ in 0..10 {
+        if events.borrow().len() == 1 { break };
+        c.incoming(200).count();
+    }
+    assert_eq!(*events.borrow(), vec!(NameEvent::Lost));
+}
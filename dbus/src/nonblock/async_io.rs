@@ -0,0 +1,87 @@
+//! I/O resource for driving a nonblocking connection with `async-io`.
+//!
+//! Enable the `async-io` feature (as well as `nonblock`) to use this module. This works with any
+//! executor, e g smol or async-std, without pulling in Tokio.
+//!
+//! # Example
+//! ```rust,no_run
+//! extern crate async_io;
+//! extern crate dbus;
+//! fn main() {
+//!     use dbus::nonblock;
+//!     let (resource, conn) = nonblock::async_io::new_session().unwrap();
+//!
+//!     // The I/O resource needs to be polled by some executor, here we use a separate thread.
+//!     std::thread::spawn(move || async_io::block_on(resource));
+//!
+//!     let reply = conn.method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "ListNames", ());
+//!     let (names,): (Vec<String>,) = async_io::block_on(reply).unwrap();
+//!     assert!(names.iter().any(|n| n == "org.freedesktop.DBus"));
+//! }
+//! ```
+
+use crate::{Error, TxRx, BusType};
+use super::{Connection, FdReadiness, io_error};
+use async_io::Async;
+use std::future::Future;
+use std::os::unix::io::RawFd;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The I/O resource, which reads from and writes to the connection's file descriptor.
+///
+/// Poll it on any executor. It finishes (with an error) only if the connection is closed.
+pub struct IoResource {
+    conn: Connection,
+    fd: Async<RawFd>,
+}
+
+impl IoResource {
+    /// Creates an I/O resource for an existing connection.
+    pub fn new(conn: Connection) -> Result<Self, Error> {
+        let fd = try!(conn.unix_fd());
+        let fd = try!(Async::new(fd).map_err(io_error));
+        Ok(IoResource { conn: conn, fd: fd })
+    }
+}
+
+impl FdReadiness for Async<RawFd> {
+    fn poll_readable(&self, cx: &mut Context) -> Poll<::std::io::Result<()>> { Async::poll_readable(self, cx) }
+    fn poll_writable(&self, cx: &mut Context) -> Poll<::std::io::Result<()>> { Async::poll_writable(self, cx) }
+}
+
+impl Future for IoResource {
+    type Output = Error;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Error> { self.conn.poll_io(&self.fd, cx) }
+}
+
+/// Connects to a bus, and returns the I/O resource and a handle to the connection.
+///
+/// Blocking: until the connection is up and running.
+pub fn new(bus: BusType) -> Result<(IoResource, Connection), Error> {
+    let conn = Connection::new(try!(TxRx::get_private(bus)));
+    Ok((try!(IoResource::new(conn.clone())), conn))
+}
+
+/// Connects to the session bus, see `new`.
+pub fn new_session() -> Result<(IoResource, Connection), Error> { new(BusType::Session) }
+
+/// Connects to the system bus, see `new`.
+pub fn new_system() -> Result<(IoResource, Connection), Error> { new(BusType::System) }
+
+#[test]
+fn async_io_method_call() {
+    use std::thread;
+    let bus = ::testing::shared_bus();
+    let conn = super::test_connection(&bus);
+    let resource = IoResource::new(conn.clone()).unwrap();
+    thread::spawn(move || ::async_io::block_on(resource));
+
+    let reply = conn.method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetNameOwner", ("org.freedesktop.DBus",));
+    let (owner,): (String,) = ::async_io::block_on(reply).unwrap();
+    assert_eq!(owner, "org.freedesktop.DBus");
+
+    let reply = conn.method_call::<_, _, _, _, _, ()>("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetNameOwner", ("com.example.doesnotexist",));
+    let e = ::async_io::block_on(reply).unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.NameHasNoOwner"));
+}
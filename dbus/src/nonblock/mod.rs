@@ -0,0 +1,497 @@
+//! Async version of the connection, built on std futures. [unstable / experimental]
+//!
+//! Enable the `nonblock` feature to use this module. The `Connection` here is a cheaply cloneable
+//! handle which is `Send + Sync`; method calls return futures, and incoming messages matching a
+//! `MatchRule` can be received as a stream.
+//!
+//! Nothing happens unless the connection is driven by an I/O resource, which owns the
+//! connection's file descriptor and reads/writes from it when it becomes ready.
+//! Enable the `tokio` feature for an I/O resource for Tokio (see the `tokio` submodule), or
+//! the `async-io` feature for an executor-agnostic one (see the `async_io` submodule).
+//!
+//! Incoming method calls are answered with default replies (see `MessageDispatcher::default_dispatch`),
+//! i e, this module is for the client side only, for now.
+
+use crate::{Message, MessageType, Error, TxRx, MatchRule, BusName, Path, Interface, Member,
+    MessageDispatcher, MessageDispatcherConfig, SignalArgs};
+use crate::arg::{AppendAll, ReadAll, IterAppend, Arg, Get, ArgType, Variant, TypeMismatchError};
+use crate::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged;
+use futures::channel::{oneshot, mpsc};
+use futures::task::AtomicWaker;
+use futures::{Future, Stream, TryFutureExt};
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+#[cfg(feature = "async-io")]
+pub mod async_io;
+
+pub mod stdintf;
+
+struct DispatcherCfg {
+    txrx: Arc<TxRx>,
+    streams: Vec<(usize, MatchRule<'static>, mpsc::UnboundedSender<Message>)>,
+}
+
+impl MessageDispatcherConfig for DispatcherCfg {
+    type Reply = oneshot::Sender<Message>;
+    fn on_reply(r: Self::Reply, msg: Message, _: &mut MessageDispatcher<Self>) {
+        // If the receiver has been dropped, nobody is interested in the reply.
+        let _ = r.send(msg);
+    }
+    fn on_signal(msg: Message, d: &mut MessageDispatcher<Self>) {
+        for &(_, ref rule, ref sender) in &d.inner().streams {
+            if rule.matches(&msg) {
+                // Incoming messages are locked, so sharing the underlying message is fine.
+                let _ = sender.unbounded_send(Message::from_ptr(msg.ptr(), true));
+            }
+        }
+    }
+    fn on_send(msg: Message, d: &mut MessageDispatcher<Self>) {
+        let _ = d.inner().txrx.send(msg);
+    }
+}
+
+struct Inner {
+    txrx: Arc<TxRx>,
+    dispatcher: Mutex<MessageDispatcher<DispatcherCfg>>,
+    // Wakes up the I/O resource, e g when there is something to write.
+    waker: AtomicWaker,
+    next_stream_id: AtomicUsize,
+}
+
+/// A cheaply cloneable handle to an async D-Bus connection.
+///
+/// See the `tokio` submodule for an example.
+#[derive(Clone)]
+pub struct Connection(Arc<Inner>);
+
+impl Connection {
+    /// Wraps a TxRx into an async connection.
+    ///
+    /// You probably want to use a constructor in an I/O resource submodule instead (e g `tokio::new_session`),
+    /// which also sets up the I/O resource that drives the connection.
+    pub fn new(txrx: TxRx) -> Self {
+        let txrx = Arc::new(txrx);
+        Connection(Arc::new(Inner {
+            dispatcher: Mutex::new(MessageDispatcher::new(DispatcherCfg { txrx: txrx.clone(), streams: vec!() })),
+            txrx: txrx,
+            waker: AtomicWaker::new(),
+            next_stream_id: AtomicUsize::new(1),
+        }))
+    }
+
+    /// Get the connection's unique name.
+    ///
+    /// It's usually something like ":1.54"
+    pub fn unique_name(&self) -> Option<&str> { self.0.txrx.unique_name() }
+
+    /// Puts a message into libdbus out queue, and wakes up the I/O resource to send it.
+    ///
+    /// Returns a serial number than can be used to match against a reply.
+    pub fn send(&self, msg: Message) -> Result<u32, ()> {
+        let r = self.0.txrx.send(msg);
+        self.0.waker.wake();
+        r
+    }
+
+    /// Sends a message and returns a future that resolves to the reply.
+    ///
+    /// Error replies are turned into errors.
+    pub fn send_with_reply(&self, msg: Message) -> MethodReply<Message> {
+        self.send_with_reply_parse(msg, |m| Ok(m))
+    }
+
+    /// Makes a method call, and returns a future that resolves to the reply arguments.
+    pub fn method_call<'a, D, P, I, M, A, R>(&self, dest: D, path: P, iface: I, member: M, args: A) -> MethodReply<R>
+    where D: Into<BusName<'a>>, P: Into<Path<'a>>, I: Into<Interface<'a>>, M: Into<Member<'a>>, A: AppendAll, R: ReadAll {
+        let mut msg = Message::method_call(&dest.into(), &path.into(), &iface.into(), &member.into());
+        args.append(&mut IterAppend::new(&mut msg));
+        self.send_with_reply_parse(msg, |m| Ok(try!(R::read(&mut m.iter_init()))))
+    }
+
+    /// Returns a proxy for making method calls to a specific destination and object path.
+    pub fn with_proxy<'a, D: Into<BusName<'a>>, P: Into<Path<'a>>>(&self, dest: D, path: P) -> Proxy<'a> {
+        Proxy { destination: dest.into(), path: path.into(), connection: self.clone(), allow_interactive_authorization: false }
+    }
+
+    /// Adds a match rule to the D-Bus server, and returns a future resolving to a stream of
+    /// messages matching the rule.
+    ///
+    /// Incoming messages are matched against the rule locally too. Messages carry the unique name
+    /// of their sender, so if the rule has a sender, it must be a unique name to match anything.
+    ///
+    /// When the stream is dropped, the match rule is removed from the server.
+    pub fn add_match(&self, rule: MatchRule<'static>) -> AddMatch {
+        self.add_match_local(&rule, rule.clone())
+    }
+
+    // Adds the rule to the D-Bus server, but matches incoming messages against a different rule.
+    fn add_match_local(&self, rule: &MatchRule, local: MatchRule<'static>) -> AddMatch {
+        let match_str = rule.match_str();
+        let (s, r) = mpsc::unbounded();
+        let id = self.0.next_stream_id.fetch_add(1, Ordering::Relaxed);
+        self.0.dispatcher.lock().unwrap().inner_mut().streams.push((id, local, s));
+        let reply = self.method_call("org.freedesktop.DBus", "/org/freedesktop/DBus", "org.freedesktop.DBus", "AddMatch", (&*match_str,));
+        let stream = MsgStream { conn: self.clone(), id: id, recv: r, match_str: None };
+        AddMatch { reply: reply, stream: Some((stream, match_str)) }
+    }
+
+    fn send_with_reply_parse<T>(&self, msg: Message, parse: fn(Message) -> Result<T, Error>) -> MethodReply<T> {
+        let (s, r) = oneshot::channel();
+        #[cfg(feature = "tracing")]
+        let span = crate::calltrace::call_span(&msg);
+        // Hold the lock while sending, so that the reply cannot be dispatched before it is added.
+        let recv = {
+            let mut d = self.0.dispatcher.lock().unwrap();
+            self.0.txrx.send(msg).map(|serial| { d.add_reply(serial, s); (serial, r) })
+        };
+        self.0.waker.wake();
+        #[cfg(feature = "tracing")]
+        match recv {
+            Ok((serial, _)) => crate::calltrace::record_serial(&span, serial),
+            Err(_) => { span.record("outcome", &"error"); },
+        }
+        MethodReply { recv: recv.map(|(_, r)| r).map_err(|_| Some(Error::new_custom("org.freedesktop.DBus.Error.Failed", "Sending message failed"))),
+            parse: parse, #[cfg(feature = "tracing")] span: span }
+    }
+
+    /// The connection's file descriptor, for I/O resources to watch.
+    #[cfg(any(feature = "tokio", feature = "async-io"))]
+    pub (crate) fn unix_fd(&self) -> Result<::std::os::unix::io::RawFd, Error> {
+        let mut fd = -1;
+        if unsafe { crate::ffi::dbus_connection_get_unix_fd(self.0.txrx.conn(), &mut fd) } == 0 || fd < 0 {
+            return Err(Error::new_custom("org.freedesktop.DBus.Error.Failed", "Connection has no unix fd"));
+        }
+        Ok(fd)
+    }
+
+    /// Reads and writes without blocking, and dispatches all incoming messages.
+    ///
+    /// Called by I/O resources when the fd is ready, or they have been woken up.
+    /// Returns an error if the connection has been disconnected.
+    #[cfg(any(feature = "tokio", feature = "async-io"))]
+    pub (crate) fn process(&self, cx: &mut Context) -> Result<(), Error> {
+        self.0.waker.register(cx.waker());
+        loop {
+            if self.0.txrx.read_write(Some(0)).is_err() || !self.0.txrx.is_connected() {
+                // Drop all senders, so that all waiting futures and streams finish.
+                let mut d = self.0.dispatcher.lock().unwrap();
+                *d = MessageDispatcher::new(DispatcherCfg { txrx: self.0.txrx.clone(), streams: vec!() });
+                return Err(Error::new_custom("org.freedesktop.DBus.Error.Disconnected", "Connection was closed"));
+            }
+            {
+                let mut d = self.0.dispatcher.lock().unwrap();
+                while let Some(msg) = self.0.txrx.pop_message() { d.dispatch(msg) };
+            }
+            // libdbus reads a limited amount of data per call, so make sure the socket is drained
+            // before the I/O resource starts waiting for it to become readable again.
+            let mut pfd = ::libc::pollfd { fd: try!(self.unix_fd()), events: ::libc::POLLIN, revents: 0 };
+            if unsafe { ::libc::poll(&mut pfd, 1, 0) } <= 0 { return Ok(()) }
+        }
+    }
+
+    /// Whether or not there are messages in libdbus out queue.
+    #[cfg(any(feature = "tokio", feature = "async-io"))]
+    pub (crate) fn has_messages_to_send(&self) -> bool {
+        unsafe { crate::ffi::dbus_connection_has_messages_to_send(self.0.txrx.conn()) != 0 }
+    }
+
+    /// Processes the connection until its fd is neither readable, nor writable while there is
+    /// something to send. This is the poll function of the I/O resources, which only provide
+    /// the readiness of the fd. Returns (ready) with an error once the connection is closed.
+    #[cfg(any(feature = "tokio", feature = "async-io"))]
+    pub (crate) fn poll_io<F: FdReadiness>(&self, fd: &F, cx: &mut Context) -> Poll<Error> {
+        loop {
+            if let Err(e) = self.process(cx) { return Poll::Ready(e) };
+            let mut ready = false;
+            match fd.poll_readable(cx) {
+                Poll::Ready(Ok(())) => { ready = true; },
+                Poll::Ready(Err(e)) => return Poll::Ready(io_error(e)),
+                Poll::Pending => {},
+            }
+            if self.has_messages_to_send() {
+                match fd.poll_writable(cx) {
+                    Poll::Ready(Ok(())) => { ready = true; },
+                    Poll::Ready(Err(e)) => return Poll::Ready(io_error(e)),
+                    Poll::Pending => {},
+                }
+            }
+            if !ready { return Poll::Pending }
+        }
+    }
+}
+
+/// Readiness of the connection's fd, as reported by the reactor of an I/O resource.
+///
+/// Ready means that the reactor will not report it again until the fd has been used.
+#[cfg(any(feature = "tokio", feature = "async-io"))]
+pub (crate) trait FdReadiness {
+    fn poll_readable(&self, cx: &mut Context) -> Poll<::std::io::Result<()>>;
+    fn poll_writable(&self, cx: &mut Context) -> Poll<::std::io::Result<()>>;
+}
+
+#[cfg(any(feature = "tokio", feature = "async-io"))]
+pub (crate) fn io_error(e: ::std::io::Error) -> Error {
+    Error::new_custom("org.freedesktop.DBus.Error.IOError", &e.to_string())
+}
+
+/// A connection, together with a destination and an object path.
+///
+/// Method calls return futures, just like `Connection::method_call`. The standard interfaces
+/// can be called through the traits in the `stdintf` submodule.
+///
+/// Create it with `Connection::with_proxy`.
+#[derive(Clone)]
+pub struct Proxy<'a> {
+    /// Destination, i e what D-Bus service you're communicating with
+    pub destination: BusName<'a>,
+    /// Object path on the destination
+    pub path: Path<'a>,
+    /// The connection method calls are made on
+    pub connection: Connection,
+    allow_interactive_authorization: bool,
+}
+
+impl<'a> Proxy<'a> {
+    /// Whether method calls allow interactive authorization, see
+    /// `Message::set_allow_interactive_authorization`. Defaults to false.
+    pub fn allow_interactive_authorization(mut self, allow: bool) -> Self { self.allow_interactive_authorization = allow; self }
+
+    /// Makes a method call, and returns a future that resolves to the reply arguments.
+    pub fn method_call<'i, 'm, I, M, A, R>(&self, i: I, m: M, args: A) -> MethodReply<R>
+    where I: Into<Interface<'i>>, M: Into<Member<'m>>, A: AppendAll, R: ReadAll {
+        self.method_call_parse(i, m, args, |m| Ok(try!(R::read(&mut m.iter_init()))))
+    }
+
+    /// Returns a future resolving to a stream of new values of a property of this object.
+    ///
+    /// The future resolves once the D-Bus server has accepted the match rule for the
+    /// `PropertiesChanged` signal. See `PropertyStream` for details.
+    pub fn property_stream<T: Arg + for<'b> Get<'b>>(&self, interface: &str, name: &str)
+        -> impl Future<Output=Result<PropertyStream<T>, Error>> {
+        let mut rule = MatchRule::new();
+        rule.msg_type = Some(MessageType::Signal);
+        rule.sender = Some(self.destination.clone().into_static());
+        rule.path = Some(self.path.clone().into_static());
+        rule.interface = Some(PropertiesPropertiesChanged::INTERFACE.into());
+        rule.member = Some(PropertiesPropertiesChanged::NAME.into());
+        rule.args.push((0, Cow::Owned(interface.into())));
+        // Signals are sent from the unique name of the owner, so a well-known name never matches locally.
+        // The D-Bus server still filters on the sender.
+        let mut local = rule.clone();
+        if !local.sender.as_ref().unwrap().starts_with(':') { local.sender = None; }
+        let (interface, name) = (interface.to_string(), name.to_string());
+        self.connection.add_match_local(&rule, local).map_ok(move |msgs| PropertyStream { msgs: msgs, interface: interface, name: name, _t: PhantomData })
+    }
+
+    fn method_call_parse<'i, 'm, I, M, A, R>(&self, i: I, m: M, args: A, parse: fn(Message) -> Result<R, Error>) -> MethodReply<R>
+    where I: Into<Interface<'i>>, M: Into<Member<'m>>, A: AppendAll {
+        let mut msg = Message::method_call(&self.destination, &self.path, &i.into(), &m.into());
+        args.append(&mut IterAppend::new(&mut msg));
+        if self.allow_interactive_authorization {
+            if let Err(e) = msg.set_allow_interactive_authorization(true) {
+                return MethodReply { recv: Err(Some(e)), parse: parse, #[cfg(feature = "tracing")] span: ::tracing::Span::none() }
+            }
+        }
+        self.connection.send_with_reply_parse(msg, parse)
+    }
+}
+
+// Returns the new value of the property, if the message is a PropertiesChanged signal that contains it.
+fn changed_value<T: Arg + for<'b> Get<'b>>(msg: &Message, interface: &str, name: &str) -> Option<Result<T, TypeMismatchError>> {
+    let mut i = msg.iter_init();
+    let iface: &str = i.read().ok()?;
+    if iface != interface { return None }
+    let mut a = i.recurse(ArgType::Array)?;
+    loop {
+        let mut e = a.recurse(ArgType::DictEntry)?;
+        let key: &str = e.read().ok()?;
+        if key == name { return Some(e.read::<Variant<T>>().map(|v| v.0)) }
+        if !a.next() { return None }
+    }
+}
+
+/// A stream of new values of a property, see `Proxy::property_stream`.
+///
+/// A value is yielded every time a `PropertiesChanged` signal includes the property.
+/// If the property is only listed as invalidated, its new value is not known and nothing is
+/// yielded; fetch it with `Properties::get` if needed. If the new value has a different type
+/// than `T`, an error is yielded.
+///
+/// When the stream is dropped, the match rule is removed from the server.
+pub struct PropertyStream<T> {
+    msgs: MsgStream,
+    interface: String,
+    name: String,
+    _t: PhantomData<fn() -> T>,
+}
+
+impl<T: Arg + for<'b> Get<'b>> Stream for PropertyStream<T> {
+    type Item = Result<T, Error>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            match Pin::new(&mut this.msgs).poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Ready(Some(m)) => if let Some(r) = changed_value(&m, &this.interface, &this.name) {
+                    return Poll::Ready(Some(r.map_err(From::from)))
+                },
+            }
+        }
+    }
+}
+
+/// A future that resolves to the reply of a method call.
+#[derive(Debug)]
+pub struct MethodReply<T> {
+    recv: Result<oneshot::Receiver<Message>, Option<Error>>,
+    parse: fn(Message) -> Result<T, Error>,
+    #[cfg(feature = "tracing")]
+    span: ::tracing::Span,
+}
+
+impl<T> Future for MethodReply<T> {
+    type Output = Result<T, Error>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let r = match self.recv {
+            Err(ref mut e) => return Poll::Ready(Err(e.take().expect("MethodReply polled after completion"))),
+            Ok(ref mut recv) => match Pin::new(recv).poll(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(r) => r,
+            }
+        };
+        #[cfg(feature = "tracing")]
+        match r {
+            Ok(ref m) => crate::calltrace::record_reply(&self.span, m),
+            Err(_) => { self.span.record("outcome", &"error"); },
+        }
+        Poll::Ready(match r {
+            Err(_) => Err(Error::new_custom("org.freedesktop.DBus.Error.Disconnected", "Connection was closed")),
+            Ok(mut m) => m.as_result().map(|_| ()).and_then(|_| (self.parse)(m)),
+        })
+    }
+}
+
+/// A future that resolves to a MsgStream, once the D-Bus server has accepted the match rule.
+pub struct AddMatch {
+    reply: MethodReply<()>,
+    stream: Option<(MsgStream, String)>,
+}
+
+impl Future for AddMatch {
+    type Output = Result<MsgStream, Error>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match Pin::new(&mut self.reply).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(r) => {
+                let (mut stream, match_str) = self.stream.take().expect("AddMatch polled after completion");
+                Poll::Ready(r.map(|_| { stream.match_str = Some(match_str); stream }))
+            }
+        }
+    }
+}
+
+/// A stream of incoming messages matching a match rule.
+///
+/// Use e g `SignalArgs::from_message` to parse signals.
+pub struct MsgStream {
+    conn: Connection,
+    id: usize,
+    recv: mpsc::UnboundedReceiver<Message>,
+    match_str: Option<String>,
+}
+
+impl Stream for MsgStream {
+    type Item = Message;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Message>> {
+        Pin::new(&mut self.recv).poll_next(cx)
+    }
+}
+
+impl Drop for MsgStream {
+    fn drop(&mut self) {
+        let id = self.id;
+        self.conn.0.dispatcher.lock().unwrap().inner_mut().streams.retain(|&(i, _, _)| i != id);
+        if let Some(ref match_str) = self.match_str {
+            let msg = Message::method_call(&"org.freedesktop.DBus".into(), &"/org/freedesktop/DBus".into(),
+                &"org.freedesktop.DBus".into(), &"RemoveMatch".into()).append1(&**match_str);
+            msg.set_no_reply(true);
+            let _ = self.conn.send(msg);
+        }
+    }
+}
+
+// A connection to the bus shared by the tests, see `testing::shared_bus`.
+#[cfg(test)]
+fn test_connection(bus: &::testing::TestBus) -> Connection {
+    let mut c = TxRx::open_private(bus.address()).unwrap();
+    c.register().unwrap();
+    Connection::new(c)
+}
+
+#[test]
+fn nonblock_send_sync() {
+    fn is_send_sync<T: Send + Sync>(_: &T) {}
+    let bus = ::testing::shared_bus();
+    let c = test_connection(&bus);
+    is_send_sync(&c);
+}
+
+#[cfg(feature = "async-io")]
+#[test]
+fn nonblock_property_stream() {
+    use futures::StreamExt;
+    use crate::arg::RefArg;
+    use std::collections::HashMap;
+    let bus = ::testing::shared_bus();
+    let conn = test_connection(&bus);
+    let resource = async_io::IoResource::new(conn.clone()).unwrap();
+    ::std::thread::spawn(move || ::async_io::block_on(resource));
+
+    let p = conn.with_proxy(conn.unique_name().unwrap().to_string(), "/propstream");
+    let mut s = ::async_io::block_on(p.property_stream::<u32>("com.example.propstream", "Brightness")).unwrap();
+    let emit = |iface: &str, name: &str, v: Box<RefArg>, invalidated: Vec<String>| {
+        let mut changed = HashMap::new();
+        changed.insert(name.to_string(), Variant(v));
+        let sig = PropertiesPropertiesChanged { interface_name: iface.into(), changed_properties: changed,
+            invalidated_properties: invalidated };
+        conn.send(sig.to_emit_message(&"/propstream".into())).unwrap();
+    };
+    emit("com.example.other", "Brightness", Box::new(1u32), vec!());
+    emit("com.example.propstream", "Other", Box::new(2u32), vec!("Brightness".into()));
+    emit("com.example.propstream", "Brightness", Box::new(3u32), vec!());
+    emit("com.example.propstream", "Brightness", Box::new("four".to_string()), vec!());
+    assert_eq!(::async_io::block_on(s.next()).unwrap().unwrap(), 3);
+    assert!(::async_io::block_on(s.next()).unwrap().is_err());
+}
+
+#[cfg(all(feature = "async-io", feature = "tracing"))]
+#[test]
+fn nonblock_method_call_spans() {
+    let col = crate::calltrace::test::SpanCollector::default();
+    let bus = ::testing::shared_bus();
+    let conn = test_connection(&bus);
+    let resource = async_io::IoResource::new(conn.clone()).unwrap();
+    ::std::thread::spawn(move || ::async_io::block_on(resource));
+    col.run(|| {
+        let r: MethodReply<(String,)> = conn.method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetId", ());
+        ::async_io::block_on(r).unwrap();
+        let r: MethodReply<()> = conn.method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "NoSuchMethod", ());
+        ::async_io::block_on(r).unwrap_err();
+    });
+    let spans = col.spans.lock().unwrap();
+    let getid = spans.iter().find(|s| s.get("member").map(|x| &**x) == Some("GetId")).unwrap();
+    assert_eq!(getid["outcome"], "reply");
+    assert!(getid["serial"].parse::<u32>().unwrap() > 0);
+    let nsm = spans.iter().find(|s| s.get("member").map(|x| &**x) == Some("NoSuchMethod")).unwrap();
+    assert_eq!(nsm["outcome"], "error");
+    assert_eq!(nsm["error"], "org.freedesktop.DBus.Error.UnknownMethod");
+}
@@ -0,0 +1,118 @@
+//! The standard interfaces, for use with a nonblocking `Proxy`.
+//!
+//! These are the async counterparts of the traits in `dbus::stdintf`: every method returns a
+//! future that resolves to the reply.
+//!
+//! # Example
+//! ```rust,no_run
+//! extern crate async_io;
+//! extern crate dbus;
+//! fn main() {
+//!     use dbus::nonblock;
+//!     use dbus::nonblock::stdintf::org_freedesktop_dbus::Introspectable;
+//!     let (resource, conn) = nonblock::async_io::new_session().unwrap();
+//!     std::thread::spawn(move || async_io::block_on(resource));
+//!
+//!     let p = conn.with_proxy("org.freedesktop.DBus", "/");
+//!     println!("Introspection XML: {}", async_io::block_on(p.introspect()).unwrap());
+//! }
+//! ```
+
+#![allow(missing_docs)]
+
+pub use self::org_freedesktop_dbus::Peer as OrgFreedesktopDBusPeer;
+
+pub use self::org_freedesktop_dbus::Introspectable as OrgFreedesktopDBusIntrospectable;
+
+pub use self::org_freedesktop_dbus::Properties as OrgFreedesktopDBusProperties;
+
+pub use self::org_freedesktop_dbus::ObjectManager as OrgFreedesktopDBusObjectManager;
+
+pub mod org_freedesktop_dbus {
+
+use crate::{arg, Path};
+use super::super::{Proxy, MethodReply};
+use std::collections::HashMap;
+
+/// Method of the [org.freedesktop.DBus.Introspectable](https://dbus.freedesktop.org/doc/dbus-specification.html#standard-interfaces-introspectable) interface.
+pub trait Introspectable {
+    fn introspect(&self) -> MethodReply<String>;
+}
+
+impl<'a> Introspectable for Proxy<'a> {
+    fn introspect(&self) -> MethodReply<String> {
+        self.method_call_parse("org.freedesktop.DBus.Introspectable", "Introspect", (), |m| Ok(try!(m.read1())))
+    }
+}
+
+/// Methods of the [org.freedesktop.DBus.Properties](https://dbus.freedesktop.org/doc/dbus-specification.html#standard-interfaces-properties) interface.
+pub trait Properties {
+    fn get<R0: for<'b> arg::Get<'b>>(&self, interface_name: &str, property_name: &str) -> MethodReply<R0>;
+    fn get_all(&self, interface_name: &str) -> MethodReply<HashMap<String, arg::Variant<Box<arg::RefArg>>>>;
+    fn set<I2: arg::Arg + arg::Append>(&self, interface_name: &str, property_name: &str, value: I2) -> MethodReply<()>;
+}
+
+impl<'a> Properties for Proxy<'a> {
+    fn get<R0: for<'b> arg::Get<'b>>(&self, interface_name: &str, property_name: &str) -> MethodReply<R0> {
+        self.method_call_parse("org.freedesktop.DBus.Properties", "Get", (interface_name, property_name), |m| {
+            let value: arg::Variant<R0> = try!(m.iter_init().read());
+            Ok(value.0)
+        })
+    }
+
+    fn get_all(&self, interface_name: &str) -> MethodReply<HashMap<String, arg::Variant<Box<arg::RefArg>>>> {
+        self.method_call_parse("org.freedesktop.DBus.Properties", "GetAll", (interface_name,), |m| Ok(try!(m.read1())))
+    }
+
+    fn set<I2: arg::Arg + arg::Append>(&self, interface_name: &str, property_name: &str, value: I2) -> MethodReply<()> {
+        self.method_call("org.freedesktop.DBus.Properties", "Set", (interface_name, property_name, arg::Variant(value)))
+    }
+}
+
+/// Method of the [org.freedesktop.DBus.ObjectManager](https://dbus.freedesktop.org/doc/dbus-specification.html#standard-interfaces-objectmanager) interface.
+pub trait ObjectManager {
+    fn get_managed_objects(&self) -> MethodReply<HashMap<Path<'static>, HashMap<String, HashMap<String, arg::Variant<Box<arg::RefArg>>>>>>;
+}
+
+impl<'a> ObjectManager for Proxy<'a> {
+    fn get_managed_objects(&self) -> MethodReply<HashMap<Path<'static>, HashMap<String, HashMap<String, arg::Variant<Box<arg::RefArg>>>>>> {
+        self.method_call_parse("org.freedesktop.DBus.ObjectManager", "GetManagedObjects", (), |m| Ok(try!(m.read1())))
+    }
+}
+
+/// Methods of the [org.freedesktop.DBus.Peer](https://dbus.freedesktop.org/doc/dbus-specification.html#standard-interfaces-peer) interface.
+pub trait Peer {
+    fn ping(&self) -> MethodReply<()>;
+    fn get_machine_id(&self) -> MethodReply<String>;
+}
+
+impl<'a> Peer for Proxy<'a> {
+    fn ping(&self) -> MethodReply<()> {
+        self.method_call("org.freedesktop.DBus.Peer", "Ping", ())
+    }
+
+    fn get_machine_id(&self) -> MethodReply<String> {
+        self.method_call_parse("org.freedesktop.DBus.Peer", "GetMachineId", (), |m| Ok(try!(m.read1())))
+    }
+}
+
+}
+
+#[cfg(feature = "async-io")]
+#[test]
+fn nonblock_stdintf() {
+    use self::org_freedesktop_dbus::{Introspectable, Peer, Properties};
+    let bus = ::testing::shared_bus();
+    let conn = super::test_connection(&bus);
+    let resource = super::async_io::IoResource::new(conn.clone()).unwrap();
+    ::std::thread::spawn(move || ::async_io::block_on(resource));
+
+    let p = conn.with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus");
+    let xml = ::async_io::block_on(p.introspect()).unwrap();
+    assert!(xml.contains("org.freedesktop.DBus.Peer"));
+    ::async_io::block_on(p.ping()).unwrap();
+    let id = ::async_io::block_on(p.get_machine_id()).unwrap();
+    assert_eq!(id.len(), 32);
+    let e = ::async_io::block_on(p.get::<String>("org.freedesktop.DBus", "NoSuchProperty")).unwrap_err();
+    assert!(e.name().is_some());
+}
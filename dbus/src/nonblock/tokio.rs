@@ -0,0 +1,102 @@
+//! I/O resource for driving a nonblocking connection on a Tokio runtime.
+//!
+//! Enable the `tokio` feature (as well as `nonblock`) to use this module.
+//!
+//! # Example
+//! ```rust,no_run
+//! extern crate tokio;
+//! extern crate futures;
+//! extern crate dbus;
+//! fn main() {
+//!     use dbus::nonblock;
+//!     let rt = tokio::runtime::Runtime::new().unwrap();
+//!
+//!     // The I/O resource must be created inside the runtime, and then spawned onto it.
+//!     let (resource, conn) = rt.block_on(futures::future::lazy(|_| nonblock::tokio::new_session())).unwrap();
+//!     rt.spawn(resource);
+//!
+//!     let reply = conn.method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "ListNames", ());
+//!     let (names,): (Vec<String>,) = rt.block_on(reply).unwrap();
+//!     assert!(names.iter().any(|n| n == "org.freedesktop.DBus"));
+//! }
+//! ```
+
+use crate::{Error, TxRx, BusType};
+use super::{Connection, FdReadiness, io_error};
+use tokio::io::unix::AsyncFd;
+use std::future::Future;
+use std::os::unix::io::RawFd;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The I/O resource, which reads from and writes to the connection's file descriptor.
+///
+/// Spawn it onto the runtime. It finishes (with an error) only if the connection is closed.
+pub struct IoResource {
+    conn: Connection,
+    fd: AsyncFd<RawFd>,
+}
+
+impl IoResource {
+    /// Creates an I/O resource for an existing connection.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn new(conn: Connection) -> Result<Self, Error> {
+        let fd = try!(conn.unix_fd());
+        let fd = try!(AsyncFd::new(fd).map_err(io_error));
+        Ok(IoResource { conn: conn, fd: fd })
+    }
+}
+
+impl FdReadiness for AsyncFd<RawFd> {
+    fn poll_readable(&self, cx: &mut Context) -> Poll<::std::io::Result<()>> {
+        self.poll_read_ready(cx).map_ok(|mut guard| guard.clear_ready())
+    }
+    fn poll_writable(&self, cx: &mut Context) -> Poll<::std::io::Result<()>> {
+        self.poll_write_ready(cx).map_ok(|mut guard| guard.clear_ready())
+    }
+}
+
+impl Future for IoResource {
+    type Output = Error;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Error> { self.conn.poll_io(&self.fd, cx) }
+}
+
+/// Connects to a bus, and returns the I/O resource and a handle to the connection.
+///
+/// Must be called from within a Tokio runtime. Blocking: until the connection is up and running.
+pub fn new(bus: BusType) -> Result<(IoResource, Connection), Error> {
+    let conn = Connection::new(try!(TxRx::get_private(bus)));
+    Ok((try!(IoResource::new(conn.clone())), conn))
+}
+
+/// Connects to the session bus, see `new`.
+pub fn new_session() -> Result<(IoResource, Connection), Error> { new(BusType::Session) }
+
+/// Connects to the system bus, see `new`.
+pub fn new_system() -> Result<(IoResource, Connection), Error> { new(BusType::System) }
+
+#[test]
+fn tokio_signal_stream() {
+    use futures::StreamExt;
+    use crate::{MatchRule, Message, MessageType};
+    let rt = ::tokio::runtime::Runtime::new().unwrap();
+    let bus = ::testing::shared_bus();
+    let conn = super::test_connection(&bus);
+    let resource = rt.block_on(::futures::future::lazy(|_| IoResource::new(conn.clone()))).unwrap();
+    rt.spawn(resource);
+
+    let mut rule = MatchRule::new();
+    rule.msg_type = Some(MessageType::Signal);
+    rule.interface = Some("com.example.nonblocktest".into());
+    let mut stream = rt.block_on(conn.add_match(rule)).unwrap();
+
+    let m = Message::signal(&"/hello".into(), &"com.example.nonblocktest".into(), &"Ping".into()).append1(5u32);
+    conn.send(m).unwrap();
+    let m = rt.block_on(stream.next()).unwrap();
+    assert_eq!(m.member().unwrap(), "Ping".into());
+    assert_eq!(m.read1::<u32>().unwrap(), 5);
+
+    let e = rt.block_on(conn.method_call::<_, _, _, _, _, ()>("com.example.nonblocktest.nonexistent", "/", "com.example", "Foo", ())).unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.ServiceUnknown"));
+}
@@ -263,7 +263,7 @@ impl<'a> IObjectPath<'a> {
     }
 
     fn property_get(&self, msg: &mut Message) -> MethodResult {
-        let items = msg.get_items();
+        let items = try!(msg.get_items().map_err(|e| ("org.freedesktop.DBus.Error.InvalidArgs", e.to_string())));
         let iface_name = try!(parse_msg_str(items.get(0)));
         let prop_name = try!(parse_msg_str(items.get(1)));
 
@@ -283,7 +283,7 @@ impl<'a> IObjectPath<'a> {
     }
 
     fn property_getall(&self, msg: &mut Message) -> MethodResult {
-        let items = msg.get_items();
+        let items = try!(msg.get_items().map_err(|e| ("org.freedesktop.DBus.Error.InvalidArgs", e.to_string())));
         let iface_name = try!(parse_msg_str(items.get(0)));
 
         let is = self.interfaces.borrow();
@@ -302,7 +302,7 @@ impl<'a> IObjectPath<'a> {
     }
 
     fn property_set(&self, msg: &mut Message) -> MethodResult {
-        let items = msg.get_items();
+        let items = try!(msg.get_items().map_err(|e| ("org.freedesktop.DBus.Error.InvalidArgs", e.to_string())));
         let iface_name = try!(parse_msg_str(items.get(0)));
         let prop_name = try!(parse_msg_str(items.get(1)));
         let value = try!(parse_msg_variant(items.get(2)));
@@ -458,14 +458,15 @@ fn make_objpath<'a>(c: &'a Connection) -> ObjectPath<'a> {
 
 #[test]
 fn test_objpath() {
-    let c = Connection::get_private(super::BusType::Session).unwrap();
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
     let mut o = make_objpath(&c);
     o.set_registered(true).unwrap();
     let busname = format!("com.example.objpath.test.test_objpath");
-    assert_eq!(c.register_name(&busname, super::NameFlag::ReplaceExisting as u32).unwrap(), super::RequestNameReply::PrimaryOwner);
+    assert_eq!(c.register_name(&busname, super::NameFlag::ReplaceExisting).unwrap(), super::RequestNameReply::PrimaryOwner);
 
     let thread = ::std::thread::spawn(move || {
-        let c = Connection::get_private(super::BusType::Session).unwrap();
+        let c = bus.connect().unwrap();
         let pr = super::Props::new(&c, &*busname, "/echo", "com.example.echo", 5000);
         assert_eq!(pr.get("EchoCount").unwrap(), 7i32.into());
         let m = pr.get_all().unwrap();
@@ -492,7 +493,8 @@ fn test_objpath() {
 /*
 #[test]
 fn test_refcount() {
-    let c = Connection::get_private(super::BusType::Session).unwrap();
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
     let i = {
         let o = make_objpath(&c);
         o.i.clone()
@@ -503,7 +505,8 @@ fn test_refcount() {
 
 #[test]
 fn test_introspect() {
-    let c = Connection::get_private(super::BusType::Session).unwrap();
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
     let mut o = make_objpath(&c);
     o.set_registered(true).unwrap();
     let mut o2 = ObjectPath::new(&c, "/echo/subpath", true);
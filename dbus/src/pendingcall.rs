@@ -0,0 +1,140 @@
+use super::{ffi, Message};
+use std::{panic, process};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::os::raw::c_void;
+
+/// A method call that has been sent, but not yet replied to.
+///
+/// You get one by calling `Connection::send_with_pending_call`. The reply is delivered to
+/// the PendingCall rather than to the connection's message queue, so it will not show up in
+/// `Connection::iter` or `Connection::incoming`, unless the call is cancelled first.
+///
+/// Note: The reply is only read from the socket when the connection is processed
+/// (e g by iterating over `Connection::incoming`), or when calling `block`.
+pub struct PendingCall {
+    p: *mut ffi::DBusPendingCall,
+    // libdbus asserts if the reply is stolen twice, so keep track of that here.
+    stolen: Rc<Cell<bool>>,
+}
+
+impl PendingCall {
+    pub (crate) fn from_ptr(p: *mut ffi::DBusPendingCall) -> PendingCall {
+        PendingCall { p: p, stolen: Default::default() }
+    }
+
+    /// Returns true if a reply (or a timeout error) has been received.
+    pub fn completed(&self) -> bool {
+        unsafe { ffi::dbus_pending_call_get_completed(self.p) != 0 }
+    }
+
+    /// Stops waiting for the reply.
+    ///
+    /// If the reply arrives later, it will be handled like any other incoming message.
+    /// The notify callback (if any) will not be called.
+    pub fn cancel(&self) {
+        unsafe { ffi::dbus_pending_call_cancel(self.p) }
+    }
+
+    /// Blocks until the call has completed.
+    ///
+    /// Note: this reads and writes to the connection, but does not dispatch any other messages.
+    pub fn block(&self) {
+        unsafe { ffi::dbus_pending_call_block(self.p) }
+    }
+
+    /// Takes the reply out of the PendingCall.
+    ///
+    /// Returns None if the call has not completed yet, or if the reply has already been stolen.
+    /// The reply might be an error message (e g in case of timeout), so you probably want to
+    /// call `as_result` on it.
+    pub fn steal_reply(&self) -> Option<Message> {
+        if !self.completed() { return None; }
+        steal_reply(self.p, &self.stolen)
+    }
+
+    /// Sets a callback to be called with the reply when the call has completed.
+    ///
+    /// The reply is stolen before the callback is called, so `steal_reply` will return None afterwards.
+    /// The callback is called from inside libdbus, while the connection is being processed,
+    /// so it must not panic (the process will abort if it does).
+    pub fn set_notify<F: FnOnce(Message) + 'static>(&self, f: F) -> Result<(), ()> {
+        let data: Box<NotifyData<F>> = Box::new((Some(f), self.stolen.clone()));
+        let r = unsafe { ffi::dbus_pending_call_set_notify(self.p, Some(pending_notify_cb::<F>),
+            Box::into_raw(data) as *mut c_void, Some(pending_free_cb::<F>)) };
+        if r == 0 { return Err(()) }
+        Ok(())
+    }
+}
+
+type NotifyData<F> = (Option<F>, Rc<Cell<bool>>);
+
+fn steal_reply(p: *mut ffi::DBusPendingCall, stolen: &Cell<bool>) -> Option<Message> {
+    if stolen.replace(true) { return None; }
+    let m = unsafe { ffi::dbus_pending_call_steal_reply(p) };
+    if m.is_null() { None } else { Some(Message::from_ptr(m, false)) }
+}
+
+extern "C" fn pending_notify_cb<F: FnOnce(Message)>(pending: *mut ffi::DBusPendingCall, user_data: *mut c_void) {
+    let data: &mut NotifyData<F> = unsafe { &mut *(user_data as *mut NotifyData<F>) };
+    let f = match data.0.take() { Some(f) => f, None => return };
+    let m = match steal_reply(pending, &data.1) { Some(m) => m, None => return };
+    let r = panic::catch_unwind(panic::AssertUnwindSafe(|| f(m)));
+    if r.is_err() { process::abort() }
+}
+
+extern "C" fn pending_free_cb<F>(user_data: *mut c_void) {
+    let _: Box<NotifyData<F>> = unsafe { Box::from_raw(user_data as *mut NotifyData<F>) };
+}
+
+impl Drop for PendingCall {
+    fn drop(&mut self) {
+        unsafe { ffi::dbus_pending_call_unref(self.p) }
+    }
+}
+
+impl ::std::fmt::Debug for PendingCall {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "PendingCall {{ completed: {} }}", self.completed())
+    }
+}
+
+#[test]
+fn pending_call_notify() {
+    use std::{cell, rc};
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "ListNames").unwrap();
+    let p = c.send_with_pending_call(m, 2000).unwrap();
+    assert!(p.steal_reply().is_none());
+
+    let done: rc::Rc<cell::Cell<bool>> = Default::default();
+    let done2 = done.clone();
+    let uname = c.unique_name();
+    p.set_notify(move |mut r| {
+        let names: Vec<&str> = r.as_result().unwrap().read1().unwrap();
+        assert!(names.contains(&&*uname));
+        done2.set(true)
+    }).unwrap();
+    while !done.get() { c.incoming(100).next(); }
+
+    assert!(p.completed());
+    assert!(p.steal_reply().is_none());
+}
+
+#[test]
+fn pending_call_block_and_cancel() {
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetId").unwrap();
+    let p = c.send_with_pending_call(m, 2000).unwrap();
+    p.block();
+    assert!(p.completed());
+    let mut r = p.steal_reply().unwrap();
+    let _: &str = r.as_result().unwrap().read1().unwrap();
+
+    let m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetId").unwrap();
+    let p = c.send_with_pending_call(m, 2000).unwrap();
+    p.cancel();
+    assert!(!p.completed());
+}
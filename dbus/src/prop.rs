@@ -1,35 +1,177 @@
-use super::{Connection, Message, MessageItem, Error, Path, Interface, BusName};
+use super::{Connection, Message, MessageItem, MessageType, Error, ErrorKind, Path, Interface, BusName, MatchRule, MatchGuard, SignalArgs, Timeout};
+use connection::SignalHandler;
+use arg::{Arg, Append, Get, Variant};
+use stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged;
+use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::{error, fmt};
+
+// Parses an a{sv} MessageItem into a map of names and (unwrapped) values.
+fn parse_prop_dict(a: &MessageItem) -> Result<BTreeMap<String, MessageItem>, ()> {
+    let mut t = BTreeMap::new();
+    let a: &[MessageItem] = try!(a.inner());
+    for p in a.iter() {
+        let (k, v) = try!(p.inner());
+        let (k, v): (&String, &MessageItem) = (try!(k.inner()), try!(v.inner()));
+        t.insert(k.clone(), v.clone());
+    }
+    Ok(t)
+}
+
+// Returns the names of the interfaces in introspection XML, skipping those of child nodes.
+fn introspected_interfaces(xml: &str) -> Vec<String> {
+    let mut xml = xml.to_string();
+    while let Some(i) = xml.find("<!--") {
+        let j = xml[i..].find("-->").map(|j| i + j + 3).unwrap_or(xml.len());
+        xml.drain(i..j);
+    }
+    let mut r = vec!();
+    let mut depth = 0;
+    for tag in xml.split('<').skip(1) {
+        let tag = match tag.find('>') { Some(i) => &tag[..i], None => continue };
+        if tag.starts_with('!') || tag.starts_with('?') { continue }
+        let selfclosing = tag.ends_with('/');
+        let mut words = tag.trim_end_matches('/').split_whitespace();
+        match words.next() {
+            Some("node") if !selfclosing => depth += 1,
+            Some("/node") => depth -= 1,
+            Some("interface") if depth == 1 => {
+                let attrs = &tag["interface".len()..];
+                let name = attrs.find("name=").and_then(|i| {
+                    let v = &attrs[i+5..];
+                    let q = v.chars().next()?;
+                    if q != '"' && q != '\'' { return None }
+                    v[1..].find(q).map(|j| &v[1..j+1])
+                });
+                if let Some(n) = name { r.push(n.into()) };
+            }
+            _ => {},
+        }
+    }
+    r
+}
+
+/// An error from a Properties method call, sorted by error name.
+///
+/// Convert the errors returned by `Props` (or any other `Error`) with `PropError::from`,
+/// to branch on what went wrong without comparing error names.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Connection, BusType, Props, PropError};
+///
+/// let c = Connection::get_private(BusType::Session).unwrap();
+/// let p = Props::new(&c, "com.example.dbustest", "/hello", "com.example.dbustest", 1000);
+/// match p.set_as("Brightness", 50u32).map_err(PropError::from) {
+///     Ok(()) => {},
+///     Err(PropError::PropertyReadOnly(_)) => println!("Brightness cannot be changed"),
+///     Err(e) => panic!("{}", e),
+/// }
+/// ```
+#[derive(Debug)]
+pub enum PropError {
+    /// org.freedesktop.DBus.Error.UnknownProperty
+    UnknownProperty(Error),
+    /// org.freedesktop.DBus.Error.UnknownInterface
+    UnknownInterface(Error),
+    /// org.freedesktop.DBus.Error.InvalidArgs, e g the value has the wrong type or the property is write only
+    InvalidArgs(Error),
+    /// org.freedesktop.DBus.Error.PropertyReadOnly
+    PropertyReadOnly(Error),
+    /// org.freedesktop.DBus.Error.AccessDenied
+    AccessDenied(Error),
+    /// Any other error
+    Other(Error),
+}
+
+impl PropError {
+    /// The underlying D-Bus error.
+    pub fn error(&self) -> &Error {
+        match *self {
+            PropError::UnknownProperty(ref e) | PropError::UnknownInterface(ref e) | PropError::InvalidArgs(ref e) |
+            PropError::PropertyReadOnly(ref e) | PropError::AccessDenied(ref e) | PropError::Other(ref e) => e,
+        }
+    }
+
+    /// Converts into the underlying D-Bus error.
+    pub fn into_error(self) -> Error {
+        match self {
+            PropError::UnknownProperty(e) | PropError::UnknownInterface(e) | PropError::InvalidArgs(e) |
+            PropError::PropertyReadOnly(e) | PropError::AccessDenied(e) | PropError::Other(e) => e,
+        }
+    }
+}
+
+impl From<Error> for PropError {
+    fn from(e: Error) -> PropError {
+        match e.kind() {
+            ErrorKind::UnknownProperty => PropError::UnknownProperty(e),
+            ErrorKind::UnknownInterface => PropError::UnknownInterface(e),
+            ErrorKind::InvalidArgs => PropError::InvalidArgs(e),
+            ErrorKind::PropertyReadOnly => PropError::PropertyReadOnly(e),
+            ErrorKind::AccessDenied => PropError::AccessDenied(e),
+            _ => PropError::Other(e),
+        }
+    }
+}
+
+impl From<PropError> for Error {
+    fn from(e: PropError) -> Error { e.into_error() }
+}
+
+impl fmt::Display for PropError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.error().message().unwrap_or(""), self.error().name().unwrap_or(""))
+    }
+}
+
+impl error::Error for PropError {
+    fn description(&self) -> &str { "D-Bus property error" }
+}
 
 /// Client side properties - get and set properties on a remote application.
-pub struct Props<'a> {
+///
+/// The connection is only borrowed (or referenced through e g an `Rc<Connection>`), so any number
+/// of Props can share the same connection.
+pub struct Props<'a, C = &'a Connection> {
     name: BusName<'a>,
     path: Path<'a>,
     interface: Interface<'a>,
-    timeout_ms: i32,
-    conn: &'a Connection,
+    timeout: Timeout,
+    conn: C,
 }
 
-impl<'a> Props<'a> {
+impl<'a, C: Deref<Target=Connection>> Props<'a, C> {
     /// Create a new Props.
-    pub fn new<N, P, I>(conn: &'a Connection, name: N, path: P, interface: I, timeout_ms: i32) -> Props<'a>
-    where N: Into<BusName<'a>>, P: Into<Path<'a>>, I: Into<Interface<'a>> {
+    ///
+    /// `conn` is some way to access the connection, e g a `&Connection` or `Rc<Connection>`.
+    /// The timeout for method calls can be a `Duration`, a `Timeout`, or an i32 in milliseconds.
+    pub fn new<N, P, I, T>(conn: C, name: N, path: P, interface: I, timeout: T) -> Props<'a, C>
+    where N: Into<BusName<'a>>, P: Into<Path<'a>>, I: Into<Interface<'a>>, T: Into<Timeout> {
         Props {
             name: name.into(),
             path: path.into(),
             interface: interface.into(),
-            timeout_ms: timeout_ms,
+            timeout: timeout.into(),
             conn: conn,
         }
     }
 
+    /// The timeout for method calls.
+    pub fn timeout(&self) -> Timeout { self.timeout }
+
+    /// Changes the timeout for method calls.
+    pub fn set_timeout<T: Into<Timeout>>(&mut self, timeout: T) { self.timeout = timeout.into() }
+
     /// Get a single property's value.
     pub fn get(&self, propname: &str) -> Result<MessageItem, Error> {
         let mut m = Message::method_call(&self.name, &self.path,
             &"org.freedesktop.DBus.Properties".into(), &"Get".into());
-        m.append_items(&[self.interface.to_string().into(), propname.to_string().into()]);
-        let mut r = try!(self.conn.send_with_reply_and_block(m, self.timeout_ms));
-        let reply = try!(r.as_result()).get_items();
+        try!(m.append_items(&[self.interface.to_string().into(), propname.to_string().into()]));
+        let mut r = try!(self.conn.send_with_reply_and_block(m, self.timeout));
+        let reply = try!(try!(r.as_result()).get_items());
         if reply.len() == 1 {
             if let &MessageItem::Variant(ref v) = &reply[0] {
                 return Ok((**v).clone())
@@ -40,49 +182,258 @@ impl<'a> Props<'a> {
     }
 
     /// Set a single property's value.
+    ///
+    /// The value is wrapped in a variant before sending, with the signature taken from the
+    /// value itself, so container values (arrays, dicts and structs) work as well.
+    /// A value that already is a variant is sent as it is.
     pub fn set(&self, propname: &str, value: MessageItem) -> Result<(), Error> {
+        let value = match value {
+            MessageItem::Variant(_) => value,
+            _ => MessageItem::Variant(Box::new(value)),
+        };
         let mut m = Message::method_call(&self.name, &self.path,
             &"org.freedesktop.DBus.Properties".into(), &"Set".into());
-        m.append_items(&[self.interface.to_string().into(), propname.to_string().into(), Box::new(value).into()]);
-        let mut r = try!(self.conn.send_with_reply_and_block(m, self.timeout_ms));
+        try!(m.append_items(&[self.interface.to_string().into(), propname.to_string().into(), value]));
+        let mut r = try!(self.conn.send_with_reply_and_block(m, self.timeout));
+        try!(r.as_result());
+        Ok(())
+    }
+
+    /// Get a single property's value, as a specific type.
+    ///
+    /// Returns an error if the property does not have this type.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use dbus::{Connection, BusType, Props};
+    ///
+    /// let c = Connection::get_private(BusType::System).unwrap();
+    /// let p = Props::new(&c, "org.freedesktop.UPower", "/org/freedesktop/UPower/devices/DisplayDevice",
+    ///     "org.freedesktop.UPower.Device", 10000);
+    /// let percentage: f64 = p.get_as("Percentage").unwrap();
+    /// println!("Battery level: {}%", percentage);
+    /// ```
+    pub fn get_as<R: for<'b> Get<'b> + Arg>(&self, propname: &str) -> Result<R, Error> {
+        let m = Message::method_call(&self.name, &self.path,
+            &"org.freedesktop.DBus.Properties".into(), &"Get".into()).append2(&*self.interface, propname);
+        let mut r = try!(self.conn.send_with_reply_and_block(m, self.timeout));
+        try!(r.as_result());
+        let v: Variant<R> = try!(r.read1());
+        Ok(v.0)
+    }
+
+    /// Set a single property's value, from any type that can be appended to a message.
+    ///
+    /// The value is wrapped in a variant before sending.
+    pub fn set_as<T: Arg + Append>(&self, propname: &str, value: T) -> Result<(), Error> {
+        let m = Message::method_call(&self.name, &self.path,
+            &"org.freedesktop.DBus.Properties".into(), &"Set".into()).append3(&*self.interface, propname, Variant(value));
+        let mut r = try!(self.conn.send_with_reply_and_block(m, self.timeout));
         try!(r.as_result());
         Ok(())
     }
 
     /// Get a map of all the properties' names and their values.
+    ///
+    /// This makes a single `GetAll` call, which is a lot faster than getting the properties one by one.
     pub fn get_all(&self) -> Result<BTreeMap<String, MessageItem>, Error> {
         let mut m = Message::method_call(&self.name, &self.path,
             &"org.freedesktop.DBus.Properties".into(), &"GetAll".into());
-        m.append_items(&[self.interface.to_string().into()]);
-        let mut r = try!(self.conn.send_with_reply_and_block(m, self.timeout_ms));
-        let reply = try!(r.as_result()).get_items();
+        try!(m.append_items(&[self.interface.to_string().into()]));
+        let mut r = try!(self.conn.send_with_reply_and_block(m, self.timeout));
+        let reply = try!(try!(r.as_result()).get_items());
 
         (|| {
             if reply.len() != 1 { return Err(()) };
-            let mut t = BTreeMap::new();
-            let a: &[MessageItem] = try!(reply[0].inner());
-            for p in a.iter() {
-                let (k, v) = try!(p.inner());
-                let (k, v): (&String, &MessageItem) = (try!(k.inner()), try!(v.inner()));
-                t.insert(k.clone(), v.clone());
-            }
-            Ok(t)
+            parse_prop_dict(&reply[0])
         })().map_err(|_| {
             let f = format!("Invalid reply for property GetAll: '{:?}'", reply);
             Error::new_custom("InvalidReply", &f)
         })
     }
+
+    /// Get the properties of all interfaces of an object.
+    ///
+    /// The object is introspected to find its interfaces, then their properties are fetched
+    /// with one `GetAll` call per interface. Returns a map of interface names to maps of
+    /// property names and values. Interfaces without properties are left out, and so are the
+    /// standard `org.freedesktop.DBus.*` interfaces, and interfaces for which `GetAll` fails
+    /// with UnknownInterface or UnknownMethod (which some services return for interfaces that
+    /// they introspect but do not handle properties for).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use dbus::{Connection, BusType, Props};
+    ///
+    /// let c = Connection::get_private(BusType::System).unwrap();
+    /// let all = Props::get_all_interfaces(&c, "org.freedesktop.UPower",
+    ///     "/org/freedesktop/UPower/devices/DisplayDevice", 10000).unwrap();
+    /// for (iface, props) in all {
+    ///     println!("{}: {:?}", iface, props);
+    /// }
+    /// ```
+    pub fn get_all_interfaces<N, P, T>(conn: C, name: N, path: P, timeout: T)
+        -> Result<BTreeMap<String, BTreeMap<String, MessageItem>>, Error>
+    where C: Clone, N: Into<BusName<'a>>, P: Into<Path<'a>>, T: Into<Timeout> {
+        let timeout = timeout.into();
+        let (name, path) = (name.into(), path.into());
+        let m = Message::method_call(&name, &path, &"org.freedesktop.DBus.Introspectable".into(), &"Introspect".into());
+        let mut r = try!(conn.send_with_reply_and_block(m, timeout));
+        try!(r.as_result());
+        let xml: &str = try!(r.read1());
+
+        let mut t = BTreeMap::new();
+        for iface in introspected_interfaces(xml) {
+            if iface.starts_with("org.freedesktop.DBus.") { continue };
+            let iname = try!(Interface::new(iface).map_err(|e| Error::new_custom("org.freedesktop.DBus.Error.InvalidArgs", &e)));
+            let props = match Props::new(conn.clone(), name.clone(), path.clone(), iname.clone(), timeout).get_all() {
+                Ok(props) => props,
+                Err(ref e) if e.kind() == ErrorKind::UnknownInterface || e.kind() == ErrorKind::UnknownMethod => continue,
+                Err(e) => return Err(e),
+            };
+            if !props.is_empty() { t.insert(iname.to_string(), props); }
+        }
+        Ok(t)
+    }
+
+    /// Subscribes to changes of the properties of this interface.
+    ///
+    /// See `PropSubscription` for details.
+    pub fn subscribe(&self) -> Result<PropSubscription, Error> {
+        PropSubscription::new(&*self.conn, self.name.clone(), self.path.clone(), Some(self.interface.clone()))
+    }
+}
+
+/// The contents of a `PropertiesChanged` signal, see `PropSubscription`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropChanged {
+    /// The interface the properties belong to
+    pub interface: String,
+    /// Properties that changed, with their new values
+    pub changed: BTreeMap<String, MessageItem>,
+    /// Properties that changed, but whose new values were not sent along
+    pub invalidated: Vec<String>,
+}
+
+impl PropChanged {
+    /// Keeps only the given properties, both in the changed map and the invalidated list.
+    ///
+    /// Returns false if none of them changed.
+    pub fn retain(&mut self, names: &[&str]) -> bool {
+        let keep = |n: &String| names.iter().any(|x| x == n);
+        let changed = ::std::mem::replace(&mut self.changed, BTreeMap::new());
+        self.changed = changed.into_iter().filter(|&(ref k, _)| keep(k)).collect();
+        self.invalidated.retain(|n| keep(n));
+        !self.changed.is_empty() || !self.invalidated.is_empty()
+    }
+}
+
+fn parse_prop_changed(rule: &MatchRule, msg: &Message) -> Option<PropChanged> {
+    if !rule.matches(msg) { return None }
+    let items = msg.get_items().ok()?;
+    if items.len() != 3 { return None }
+    let interface: &String = items[0].inner().ok()?;
+    let changed = parse_prop_dict(&items[1]).ok()?;
+    let invalidated: &[MessageItem] = items[2].inner().ok()?;
+    let invalidated: Option<Vec<String>> = invalidated.iter().map(|i| i.inner().ok().map(|s: &String| s.clone())).collect();
+    Some(PropChanged { interface: interface.clone(), changed: changed, invalidated: invalidated? })
 }
 
+/// A subscription to the `PropertiesChanged` signals of a remote object.
+///
+/// The subscription owns a match rule for the object's `PropertiesChanged` signals, optionally
+/// limited to one interface, and removes it when dropped. Only properties whose EmitsChangedSignal
+/// annotation is true or invalidates are covered; each signal lists the new values of some
+/// properties, and the names of others that changed without their values being sent along.
+/// Use `event` on incoming messages to get at these, or `handler` or `handler_for` for a callback.
+///
+/// If the destination is a well-known name, the D-Bus server only routes signals from the
+/// current owner of the name, but `event` cannot check the sender itself.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Connection, BusType, Props};
+///
+/// let c = Connection::get_private(BusType::System).unwrap();
+/// let p = Props::new(&c, "org.freedesktop.UPower", "/org/freedesktop/UPower/devices/DisplayDevice",
+///     "org.freedesktop.UPower.Device", 10000);
+/// let s = p.subscribe().unwrap();
+/// for msg in c.incoming(1000) {
+///     if let Some(e) = s.event(&msg) {
+///         println!("Changed: {:?}, invalidated: {:?}", e.changed, e.invalidated);
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PropSubscription<'a> {
+    rule: MatchRule<'static>,
+    _guard: MatchGuard<'a>,
+}
+
+impl<'a> PropSubscription<'a> {
+    /// Subscribes to changes of the properties of an object.
+    ///
+    /// If interface is None, changes of the properties of all interfaces are delivered.
+    pub fn new<'b>(c: &'a Connection, dest: BusName<'b>, path: Path<'b>, interface: Option<Interface<'b>>) -> Result<Self, Error> {
+        let mut rule = MatchRule::new();
+        rule.msg_type = Some(MessageType::Signal);
+        rule.sender = Some(dest.into_static());
+        rule.path = Some(path.into_static());
+        rule.interface = Some(PropertiesPropertiesChanged::INTERFACE.into());
+        rule.member = Some(PropertiesPropertiesChanged::NAME.into());
+        if let Some(i) = interface { rule.args.push((0, Cow::Owned(i.to_string()))) };
+        let guard = try!(c.add_match_guard(&rule.match_str()));
+
+        // Signals are sent from the unique name of the owner, so a well-known name never matches locally.
+        if !rule.sender.as_ref().unwrap().starts_with(':') { rule.sender = None; }
+        Ok(PropSubscription { rule: rule, _guard: guard })
+    }
+
+    /// If the message is a matching `PropertiesChanged` signal, returns its contents.
+    pub fn event(&self, msg: &Message) -> Option<PropChanged> { parse_prop_changed(&self.rule, msg) }
+
+    /// Returns a message handler that calls `f` with every `PropertiesChanged` signal of the object.
+    ///
+    /// Add it to the connection with `Connection::add_handler`. The signals are consumed by the
+    /// handler; use `handler_for` to have several handlers for the same object. No more signals
+    /// arrive once the subscription is dropped.
+    pub fn handler<F: FnMut(PropChanged)>(&self, f: F) -> PropChangedHandler<F> {
+        let rule = self.rule.clone();
+        SignalHandler::new(move |msg| parse_prop_changed(&rule, msg), true, f)
+    }
+
+    /// Returns a message handler that calls `f` whenever one of the named properties changes.
+    ///
+    /// `f` only gets the named properties, see `PropChanged::retain`. Unlike `handler`, this
+    /// handler does not consume the signals, so several handlers for different properties
+    /// of the same object can be added.
+    pub fn handler_for<F: FnMut(PropChanged)>(&self, names: &[&str], f: F) -> PropChangedHandler<F> {
+        let rule = self.rule.clone();
+        let names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        SignalHandler::new(move |msg| {
+            let mut e = parse_prop_changed(&rule, msg)?;
+            let names: Vec<&str> = names.iter().map(|n| &**n).collect();
+            if e.retain(&names) { Some(e) } else { None }
+        }, false, f)
+    }
+}
+
+/// The handler returned by `PropSubscription::handler` and `handler_for`, calling a closure with
+/// the changed properties of an object.
+pub type PropChangedHandler<F> = SignalHandler<PropChanged, F>;
+
 /// Wrapper around Props that keeps a map of fetched properties.
-pub struct PropHandler<'a> {
-    p: Props<'a>,
+pub struct PropHandler<'a, C = &'a Connection> {
+    p: Props<'a, C>,
     map: BTreeMap<String, MessageItem>,
 }
 
-impl<'a> PropHandler<'a> {
+impl<'a, C: Deref<Target=Connection>> PropHandler<'a, C> {
     /// Create a new PropHandler from a Props.
-    pub fn new(p: Props) -> PropHandler {
+    pub fn new(p: Props<'a, C>) -> PropHandler<'a, C> {
         PropHandler { p: p, map: BTreeMap::new() }
     }
 
@@ -108,6 +459,7 @@ impl<'a> PropHandler<'a> {
     /// Set a single property's value.
     pub fn set(&mut self, propname: &str, value: MessageItem) -> Result<(), Error> {
         try!(self.p.set(propname, value.clone()));
+        let value = match value { MessageItem::Variant(v) => *v, v => v };
         self.map.insert(propname.to_string(), value);
         Ok(())
     }
@@ -134,3 +486,256 @@ fn test_get_policykit_version() {
     };
 }
 
+
+// Runs a server with some properties on the bus, until its "Quit" method is called.
+#[cfg(test)]
+fn test_server(bus: &::std::sync::Arc<::testing::TestBus>) -> (::std::thread::JoinHandle<()>, String) {
+    use tree::{Factory, Access};
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    let (tx, rx) = mpsc::channel();
+    let bus = bus.clone();
+    let t = ::std::thread::spawn(move || {
+        let c = bus.connect().unwrap();
+        let quit = Rc::new(Cell::new(false));
+        let quit2 = quit.clone();
+        let brightness = Rc::new(Cell::new(20u32));
+        let (b1, b2) = (brightness.clone(), brightness.clone());
+        let list = Rc::new(RefCell::new(vec!("first".to_string())));
+        let (l1, l2) = (list.clone(), list.clone());
+
+        let f = Factory::new_fn::<()>();
+        let tree = f.tree(()).add(f.object_path("/proptest", ()).introspectable()
+            .add(f.interface("com.example.dbusrs.proptest", ())
+                .add_m(f.method("Quit", (), move |m| { quit2.set(true); Ok(vec!(m.msg.method_return())) }))
+                .add_p(f.property::<u32,_>("Percentage", ())
+                    .on_get(|i, _| { i.append(75u32); Ok(()) }))
+                .add_p(f.property::<u32,_>("Brightness", ())
+                    .access(Access::ReadWrite)
+                    .on_get(move |i, _| { i.append(b1.get()); Ok(()) })
+                    .on_set(move |i, _| { b2.set(try!(i.read())); Ok(()) }))
+                .add_p(f.property::<Vec<String>,_>("List", ())
+                    .access(Access::ReadWrite)
+                    .on_get(move |i, _| { i.append(&*l1.borrow()); Ok(()) })
+                    .on_set(move |i, _| { *l2.borrow_mut() = try!(i.read()); Ok(()) }))
+            )
+        );
+        tree.set_registered(&c, true).unwrap();
+        c.add_handler(tree);
+        tx.send(c.unique_name()).unwrap();
+        while !quit.get() { c.incoming(100).next(); }
+    });
+    (t, rx.recv().unwrap())
+}
+
+#[cfg(test)]
+fn test_server_quit(c: &Connection, t: ::std::thread::JoinHandle<()>, name: &str) {
+    c.call_method(name, "/proptest", "com.example.dbusrs.proptest", "Quit", (), 2000).unwrap();
+    t.join().unwrap();
+}
+
+#[test]
+fn props_get_all() {
+    let bus = ::testing::shared_bus();
+    let (t, name) = test_server(&bus);
+    let c = bus.connect().unwrap();
+    let p = Props::new(&c, &*name, "/proptest", "com.example.dbusrs.proptest", 2000);
+    let all = p.get_all().unwrap();
+    assert_eq!(all.len(), 3);
+    assert_eq!(all["Percentage"], MessageItem::UInt32(75));
+    assert_eq!(all["Brightness"], p.get("Brightness").unwrap());
+    assert_eq!(all["List"], p.get("List").unwrap());
+
+    let mut ph = PropHandler::new(p);
+    ph.get_all().unwrap();
+    assert_eq!(ph.map(), &all);
+
+    let e = Props::new(&c, &*name, "/proptest", "com.example.dbusrs.nonexistent", 2000).get_all().unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.UnknownInterface"));
+    test_server_quit(&c, t, &name);
+}
+
+#[test]
+fn props_set_variant() {
+    let bus = ::testing::shared_bus();
+    let (t, name) = test_server(&bus);
+    let c = bus.connect().unwrap();
+    let p = Props::new(&c, &*name, "/proptest", "com.example.dbusrs.proptest", 2000);
+    p.set("Brightness", MessageItem::UInt32(30)).unwrap();
+    assert_eq!(p.get("Brightness").unwrap(), MessageItem::UInt32(30));
+    p.set("Brightness", MessageItem::Variant(Box::new(MessageItem::UInt32(40)))).unwrap();
+    assert_eq!(p.get("Brightness").unwrap(), MessageItem::UInt32(40));
+    let v = MessageItem::new_array(vec!("a".into(), "b".into())).unwrap();
+    p.set("List", v.clone()).unwrap();
+    assert_eq!(p.get("List").unwrap(), v);
+    let e = p.set("Brightness", "fifty".into()).unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.InvalidArgs"));
+    test_server_quit(&c, t, &name);
+}
+
+#[test]
+fn props_typed() {
+    let bus = ::testing::shared_bus();
+    let (t, name) = test_server(&bus);
+    let c = bus.connect().unwrap();
+    let p = Props::new(&c, &*name, "/proptest", "com.example.dbusrs.proptest", 2000);
+    assert_eq!(p.get_as::<u32>("Percentage").unwrap(), 75);
+    p.set_as("Brightness", 50u32).unwrap();
+    assert_eq!(p.get_as::<u32>("Brightness").unwrap(), 50);
+    p.set_as("List", vec!("x", "y")).unwrap();
+    assert_eq!(p.get_as::<Vec<String>>("List").unwrap(), vec!("x", "y"));
+
+    let e = p.get_as::<String>("Percentage").unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.Failed"));
+    let e = p.set_as("Brightness", "fifty").unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.InvalidArgs"));
+    test_server_quit(&c, t, &name);
+}
+
+#[test]
+fn props_errors() {
+    let bus = ::testing::shared_bus();
+    let (t, name) = test_server(&bus);
+    let c = bus.connect().unwrap();
+    let p = Props::new(&c, &*name, "/proptest", "com.example.dbusrs.proptest", 2000);
+    match PropError::from(p.set_as("Percentage", 5u32).unwrap_err()) {
+        PropError::PropertyReadOnly(ref e) => assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.PropertyReadOnly")),
+        e => panic!("{:?}", e),
+    }
+    match PropError::from(p.get("Nonexistent").unwrap_err()) { PropError::UnknownProperty(_) => {}, e => panic!("{:?}", e) }
+    match PropError::from(p.set_as("Brightness", "fifty").unwrap_err()) { PropError::InvalidArgs(_) => {}, e => panic!("{:?}", e) }
+    let p2 = Props::new(&c, &*name, "/proptest", "com.example.dbusrs.nonexistent", 2000);
+    match PropError::from(p2.get_all().unwrap_err()) { PropError::UnknownInterface(_) => {}, e => panic!("{:?}", e) }
+    let e = PropError::from(Error::new_custom("com.example.Error", "Something else"));
+    assert_eq!(e.to_string(), "Something else (com.example.Error)");
+    match e { PropError::Other(_) => {}, e => panic!("{:?}", e) }
+    test_server_quit(&c, t, &name);
+}
+
+#[test]
+fn props_subscribe() {
+    use arg::{Variant, RefArg};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    let bus = ::testing::shared_bus();
+
+    let c = bus.connect().unwrap();
+    let name = c.unique_name();
+    let p = Props::new(&c, &*name, "/propsubscribe", "com.example.dbusrs.propsubscribe", 2000);
+    let s = p.subscribe().unwrap();
+
+    let emit = |iface: &str| {
+        let mut changed: HashMap<String, Variant<Box<RefArg>>> = HashMap::new();
+        changed.insert("Brightness".into(), Variant(Box::new(50u32)));
+        let sig = PropertiesPropertiesChanged { interface_name: iface.into(), changed_properties: changed,
+            invalidated_properties: vec!("List".into()) };
+        c.send(sig.to_emit_message(&"/propsubscribe".into())).unwrap();
+    };
+    let mut expected = PropChanged { interface: "com.example.dbusrs.propsubscribe".into(), changed: BTreeMap::new(),
+        invalidated: vec!("List".into()) };
+    expected.changed.insert("Brightness".into(), MessageItem::UInt32(50));
+
+    emit("com.example.dbusrs.otherinterface");
+    emit("com.example.dbusrs.propsubscribe");
+    let events: Vec<_> = c.incoming(300).filter_map(|m| s.event(&m)).collect();
+    assert_eq!(events, vec!(expected.clone()));
+
+    let events = Rc::new(RefCell::new(vec!()));
+    let e2 = events.clone();
+    c.add_handler(s.handler(move |e| e2.borrow_mut().push(e)));
+    emit("com.example.dbusrs.propsubscribe");
+    c.incoming(300).count();
+    assert_eq!(*events.borrow(), vec!(expected));
+}
+
+#[test]
+fn props_all_interfaces() {
+    let bus = ::testing::shared_bus();
+    let (t, name) = test_server(&bus);
+    let c = bus.connect().unwrap();
+    let all = Props::get_all_interfaces(&c, &*name, "/proptest", 2000).unwrap();
+    assert_eq!(all.len(), 1);
+    let p = &all["com.example.dbusrs.proptest"];
+    assert_eq!(p["Percentage"], MessageItem::UInt32(75));
+    assert_eq!(p.len(), 3);
+    test_server_quit(&c, t, &name);
+}
+
+#[test]
+fn props_shared_connection() {
+    use std::rc::Rc;
+    use std::time::Duration;
+    let bus = ::testing::shared_bus();
+    let (t, name) = test_server(&bus);
+    let c = Rc::new(bus.connect().unwrap());
+    let mut p1 = Props::new(c.clone(), &*name, "/proptest", "com.example.dbusrs.proptest", Duration::from_secs(2));
+    let p2 = Props::new(&*c, &*name, "/proptest", "com.example.dbusrs.proptest", 2000);
+    assert_eq!(p1.timeout(), Timeout::Duration(Duration::from_secs(2)));
+    p1.set_timeout(Timeout::Default);
+    p1.set_as("Brightness", 10u32).unwrap();
+    assert_eq!(p2.get_as::<u32>("Brightness").unwrap(), 10);
+    let all = Props::get_all_interfaces(c.clone(), &*name, "/proptest", Duration::from_secs(2)).unwrap();
+    assert_eq!(all["com.example.dbusrs.proptest"]["Brightness"], MessageItem::UInt32(10));
+    let mut ph = PropHandler::new(p1);
+    assert_eq!(ph.get("Brightness").unwrap(), &MessageItem::UInt32(10));
+    test_server_quit(&c, t, &name);
+}
+
+#[test]
+fn introspect_interface_names() {
+    let xml = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node name="/com/example">
+  <!-- <interface name="com.example.Comment"/> -->
+  <interface name="com.example.First">
+    <property name="Foo" type="s" access="read"/>
+  </interface>
+  <interface name='com.example.Second'/>
+  <node name="child"/>
+  <node name="inline"><interface name="com.example.Child"/></node>
+</node>"#;
+    assert_eq!(introspected_interfaces(xml), vec!("com.example.First", "com.example.Second"));
+}
+
+#[test]
+fn props_changed_filter() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    let bus = ::testing::shared_bus();
+
+    let c = bus.connect().unwrap();
+    let name = c.unique_name();
+    let s = PropSubscription::new(&c, (&*name).into(), "/propfilter".into(), None).unwrap();
+    let emit = |changed: &[(&str, u32)], invalidated: &[&str]| {
+        let items: Vec<MessageItem> = changed.iter().map(|&(k, v)|
+            MessageItem::DictEntry(Box::new(k.into()), Box::new(MessageItem::Variant(Box::new(v.into()))))).collect();
+        let m = Message::signal(&"/propfilter".into(), &PropertiesPropertiesChanged::INTERFACE.into(), &PropertiesPropertiesChanged::NAME.into())
+            .append1("com.example.dbusrs.propfilter")
+            .append1(MessageItem::Array(::MessageItemArray::new(items, "a{sv}".into()).unwrap()))
+            .append1(invalidated);
+        c.send(m).unwrap();
+    };
+
+    let (a, b) = (Rc::new(RefCell::new(vec!())), Rc::new(RefCell::new(vec!())));
+    let (a2, b2) = (a.clone(), b.clone());
+    c.add_handler(s.handler_for(&["Brightness"], move |e| a2.borrow_mut().push(e)));
+    c.add_handler(s.handler_for(&["Percentage", "List"], move |e| b2.borrow_mut().push(e)));
+    emit(&[("Brightness", 1), ("Percentage", 2)], &[]);
+    emit(&[("Other", 3)], &["List"]);
+    emit(&[("Other", 4)], &[]);
+    let unhandled = c.incoming(300).filter(|m| s.event(m).is_some()).count();
+    assert_eq!(unhandled, 3);
+
+    let a = a.borrow();
+    assert_eq!(a.len(), 1);
+    assert_eq!(a[0].changed.keys().collect::<Vec<_>>(), vec!("Brightness"));
+    assert!(a[0].invalidated.is_empty());
+    let b = b.borrow();
+    assert_eq!(b.len(), 2);
+    assert_eq!(b[0].changed.keys().collect::<Vec<_>>(), vec!("Percentage"));
+    assert!(b[1].changed.is_empty());
+    assert_eq!(b[1].invalidated, vec!("List"));
+}
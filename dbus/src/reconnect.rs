@@ -0,0 +1,284 @@
+use {Connection, ConnectionBuilder, Error, Message, NameFlags, RequestNameReply, ReleaseNameReply};
+use connection::is_disconnected_signal;
+use std::{fmt, thread};
+use std::time::{Duration, Instant};
+
+/// A change of state of a `ReconnectingConnection`.
+#[derive(Debug)]
+pub enum ReconnectEvent {
+    /// The connection was lost.
+    Disconnected,
+    /// A new connection has been opened, and names, match rules and object paths restored.
+    Reconnected,
+    /// Opening a new connection (or restoring its state) failed. It is retried after the retry interval.
+    ReconnectFailed(Error),
+    /// After reconnecting, these names could not be requested again, because someone else owns
+    /// them now. It follows `Reconnected`. Names that were put in the queue (see `NameFlag`) are
+    /// included as well; if they are granted later, a `NameAcquired` signal arrives.
+    NamesLost(Vec<String>),
+}
+
+/// A connection that transparently reconnects when the D-Bus server goes away, e g when
+/// the dbus-daemon is restarted.
+///
+/// Names, match rules and object paths are recorded when they are set up through this struct,
+/// and restored on every new connection. Everything else set on the `Connection` itself (such as
+/// handlers, hooks and callbacks) is lost when reconnecting; restore it from the event callback,
+/// which is called with the new connection on `ReconnectEvent::Reconnected`.
+///
+/// Reconnecting happens while iterating over `incoming`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Connection, BusType, ReconnectingConnection, ReconnectEvent, NameFlag};
+///
+/// let mut c = ReconnectingConnection::new(Connection::builder().bus(BusType::System)).unwrap();
+/// c.register_name("com.example.dbusrs.daemon", NameFlag::DoNotQueue).unwrap();
+/// c.set_event_callback(Box::new(|_, e| println!("Connection state changed: {:?}", e)));
+/// loop {
+///     for msg in c.incoming(1000) {
+///         println!("Got message: {:?}", msg);
+///     }
+/// }
+/// ```
+pub struct ReconnectingConnection {
+    conn: Connection,
+    builder: ConnectionBuilder,
+    names: Vec<(String, NameFlags)>,
+    matches: Vec<String>,
+    paths: Vec<String>,
+    retry_interval: Duration,
+    last_attempt: Option<Instant>,
+    disconnected: bool,
+    on_event: Option<Box<FnMut(&Connection, &ReconnectEvent)>>,
+}
+
+impl ReconnectingConnection {
+    /// Opens the first connection. The builder is kept, to open new connections when reconnecting.
+    pub fn new(builder: ConnectionBuilder) -> Result<ReconnectingConnection, Error> {
+        let c = try!(builder.clone().build());
+        Ok(ReconnectingConnection { conn: c, builder: builder, names: vec!(), matches: vec!(), paths: vec!(),
+            retry_interval: Duration::from_secs(1), last_attempt: None, disconnected: false, on_event: None })
+    }
+
+    /// The current connection. Note that this is replaced when reconnecting.
+    pub fn connection(&self) -> &Connection { &self.conn }
+
+    /// Returns true if we are currently connected.
+    pub fn is_connected(&self) -> bool { self.conn.is_connected() }
+
+    /// Sets the minimum time between attempts to reconnect. The default is one second.
+    pub fn set_retry_interval(&mut self, d: Duration) { self.retry_interval = d }
+
+    /// Sets a callback to be called with the current connection when the state changes.
+    pub fn set_event_callback(&mut self, f: Box<FnMut(&Connection, &ReconnectEvent)>) { self.on_event = Some(f) }
+
+    /// Requests a name, and requests it again after reconnecting. See `Connection::register_name`.
+    ///
+    /// If someone else has taken the name by the time we reconnect, `ReconnectEvent::NamesLost` is emitted.
+    pub fn register_name<F: Into<NameFlags>>(&mut self, name: &str, flags: F) -> Result<RequestNameReply, Error> {
+        let flags = flags.into();
+        let r = try!(self.conn.register_name(name, flags));
+        self.names.retain(|&(ref n, _)| n != name);
+        self.names.push((name.into(), flags));
+        Ok(r)
+    }
+
+    /// Releases a name, and stops requesting it after reconnecting.
+    pub fn release_name(&mut self, name: &str) -> Result<ReleaseNameReply, Error> {
+        self.names.retain(|&(ref n, _)| n != name);
+        self.conn.release_name(name)
+    }
+
+    /// Adds a match rule, and adds it again after reconnecting. See `Connection::add_match`.
+    pub fn add_match(&mut self, rule: &str) -> Result<(), Error> {
+        try!(self.conn.add_match(rule));
+        self.matches.push(rule.into());
+        Ok(())
+    }
+
+    /// Removes a match rule, and stops adding it after reconnecting.
+    pub fn remove_match(&mut self, rule: &str) -> Result<(), Error> {
+        if let Some(i) = self.matches.iter().position(|r| r == rule) { self.matches.remove(i); }
+        self.conn.remove_match(rule)
+    }
+
+    /// Registers an object path, and registers it again after reconnecting.
+    /// See `Connection::register_object_path`.
+    pub fn register_object_path(&mut self, path: &str) -> Result<(), Error> {
+        try!(self.conn.register_object_path(path));
+        self.paths.push(path.into());
+        Ok(())
+    }
+
+    /// Unregisters an object path, and stops registering it after reconnecting.
+    pub fn unregister_object_path(&mut self, path: &str) {
+        self.paths.retain(|p| p != path);
+        self.conn.unregister_object_path(path)
+    }
+
+    fn emit(&mut self, e: ReconnectEvent) {
+        if let Some(ref mut f) = self.on_event { f(&self.conn, &e) };
+    }
+
+    // Returns the names that we did not get back.
+    fn restore(&self, c: &Connection) -> Result<Vec<String>, Error> {
+        for p in &self.paths { try!(c.register_object_path(p)) };
+        for r in &self.matches { try!(c.add_match(r)) };
+        let mut lost = vec!();
+        for &(ref n, f) in &self.names {
+            match try!(c.register_name(n, f)) {
+                RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => {},
+                RequestNameReply::InQueue | RequestNameReply::Exists => lost.push(n.clone()),
+            }
+        };
+        Ok(lost)
+    }
+
+    /// Opens a new connection and restores names, match rules and object paths, if disconnected.
+    ///
+    /// This is done automatically by `incoming`, but can be called to reconnect right away,
+    /// regardless of the retry interval. Returns true if a new connection was opened.
+    pub fn reconnect(&mut self) -> Result<bool, Error> {
+        if self.conn.is_connected() { return Ok(false) };
+        if !self.disconnected {
+            self.disconnected = true;
+            self.emit(ReconnectEvent::Disconnected);
+        }
+        self.last_attempt = Some(Instant::now());
+        let r = self.builder.clone().build().and_then(|c| { let lost = try!(self.restore(&c)); Ok((c, lost)) });
+        match r {
+            Ok((c, lost)) => {
+                self.conn = c;
+                self.disconnected = false;
+                self.emit(ReconnectEvent::Reconnected);
+                if !lost.is_empty() { self.emit(ReconnectEvent::NamesLost(lost)) };
+                Ok(true)
+            }
+            Err(e) => {
+                self.emit(ReconnectEvent::ReconnectFailed(Error::new_custom(e.name().unwrap_or("org.freedesktop.DBus.Error.Failed"), e.message().unwrap_or(""))));
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns an iterator over incoming messages, reconnecting if needed.
+    ///
+    /// Like `Connection::incoming`, the iterator ends when no message has arrived within the
+    /// timeout. It also ends if reconnecting failed; iterate again to retry.
+    pub fn incoming(&mut self, timeout_ms: u32) -> ReconnectingMsgs {
+        ReconnectingMsgs { c: self, timeout_ms: timeout_ms }
+    }
+}
+
+impl fmt::Debug for ReconnectingConnection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "ReconnectingConnection({:?}, names: {:?}, matches: {:?}, paths: {:?})",
+            self.conn, self.names, self.matches, self.paths)
+    }
+}
+
+/// Iterator over incoming messages, see `ReconnectingConnection::incoming`.
+#[derive(Debug)]
+pub struct ReconnectingMsgs<'a> {
+    c: &'a mut ReconnectingConnection,
+    timeout_ms: u32,
+}
+
+impl<'a> Iterator for ReconnectingMsgs<'a> {
+    type Item = Message;
+    fn next(&mut self) -> Option<Message> {
+        loop {
+            if self.c.conn.is_connected() {
+                match self.c.conn.incoming(self.timeout_ms).next() {
+                    Some(ref m) if is_disconnected_signal(m) => {},
+                    Some(m) => return Some(m),
+                    None => if self.c.conn.is_connected() { return None },
+                }
+                continue;
+            }
+            if let Some(t) = self.c.last_attempt {
+                let next = t + self.c.retry_interval;
+                let now = Instant::now();
+                if next > now {
+                    thread::sleep(::std::cmp::min(next - now, Duration::from_millis(self.timeout_ms as u64)));
+                    if Instant::now() < next { return None };
+                }
+            }
+            if self.c.reconnect().is_err() { return None };
+        }
+    }
+}
+
+#[test]
+fn reconnecting_connection() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use {DBusProxy, ErrorKind, NameFlag};
+    use testing::TestBus;
+
+    let path = format!("/tmp/dbus-rs-reconnect-{}", ::std::process::id());
+    let addr = format!("unix:path={}", path);
+    let spawn = || -> Result<TestBus, Error> {
+        let _ = ::std::fs::remove_file(&path);
+        TestBus::with_config(&addr, "")
+    };
+    let daemon = match spawn() {
+        Ok(d) => d,
+        Err(ref e) if e.kind() == ErrorKind::SpawnFailed => return, // No dbus-daemon available
+        Err(e) => panic!("{}", e),
+    };
+
+    let mut c = ReconnectingConnection::new(Connection::builder().address(&addr, true)).unwrap();
+    c.set_retry_interval(Duration::from_millis(50));
+    c.register_name("com.example.dbusrs.reconnect", NameFlag::DoNotQueue).unwrap();
+    c.register_name("com.example.dbusrs.reconnect.taken", NameFlag::DoNotQueue).unwrap();
+    c.add_match("interface=com.example.dbusrs.Reconnect").unwrap();
+    c.register_object_path("/reconnect").unwrap();
+    let events = Rc::new(RefCell::new(vec!()));
+    let e2 = events.clone();
+    let lost = Rc::new(RefCell::new(vec!()));
+    let l2 = lost.clone();
+    c.set_event_callback(Box::new(move |_, e| {
+        if let ReconnectEvent::NamesLost(ref n) = *e { *l2.borrow_mut() = n.clone() };
+        e2.borrow_mut().push(format!("{:?}", e).split('(').next().unwrap().to_string())
+    }));
+    let old_id = c.connection().server_id();
+
+    drop(daemon);
+    // While the daemon is down, reconnecting fails.
+    for _ in 0..10 {
+        c.incoming(100).count();
+        if events.borrow().len() >= 2 { break };
+    }
+    assert_eq!(&events.borrow()[..2], &["Disconnected", "ReconnectFailed"]);
+    assert!(!c.is_connected());
+
+    // Someone else gets to one of our names first.
+    let daemon = spawn().unwrap();
+    let thief = Connection::builder().address(&addr, true).build().unwrap();
+    thief.register_name("com.example.dbusrs.reconnect.taken", NameFlag::DoNotQueue).unwrap();
+    for _ in 0..20 {
+        c.incoming(100).count();
+        if c.is_connected() { break };
+    }
+    assert!(c.is_connected());
+    assert_eq!(&events.borrow()[events.borrow().len()-2..], &["Reconnected", "NamesLost"]);
+    assert_eq!(lost.borrow().as_slice(), &["com.example.dbusrs.reconnect.taken"]);
+    assert!(c.connection().server_id() != old_id);
+    let owner = DBusProxy::new(c.connection()).get_name_owner("com.example.dbusrs.reconnect").unwrap();
+    assert_eq!(&*owner, &*c.connection().unique_name());
+
+    // Match rules are restored as well.
+    let sender = Connection::builder().address(&addr, true).build().unwrap();
+    sender.send(Message::new_signal("/", "com.example.dbusrs.Reconnect", "Hello").unwrap()).unwrap();
+    let mut got = false;
+    for m in c.incoming(1000) {
+        if m.member().as_ref().map(|s| &**s) == Some("Hello") { got = true; break };
+    }
+    assert!(got);
+
+    drop(daemon);
+    let _ = ::std::fs::remove_file(&path);
+}
@@ -0,0 +1,287 @@
+use {Connection, Error, Watch, WatchEvent, ffi, init_dbus, libc, to_c_str, c_str_to_slice};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_uint, c_void};
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+use std::{fmt, ptr};
+
+struct IServer {
+    server: *mut ffi::DBusServer,
+    watches: RefCell<Vec<*mut ffi::DBusWatch>>,
+    pending: RefCell<VecDeque<*mut ffi::DBusConnection>>,
+}
+
+/// A server listening for peer-to-peer D-Bus connections.
+///
+/// This is for private endpoints that clients connect to directly, without a bus in between.
+/// Each accepted client becomes a `Connection`, which is used like any other, except that
+/// there is no bus to register with, request names from or add match rules to.
+///
+/// Supported addresses include `unix:path=`, `unix:tmpdir=`, `tcp:` and, on Linux,
+/// `unix:abstract=`, which does not leave a socket file behind.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Server, MessageType};
+///
+/// let s = Server::listen("unix:abstract=/tmp/dbus-rs-example").unwrap();
+/// println!("Listening on {}", s.address());
+/// loop {
+///     let c = match s.accept(1000) { Some(c) => c, None => continue };
+///     for m in c.incoming(1000) {
+///         if m.msg_type() == MessageType::MethodCall { c.send(m.method_return()).unwrap(); }
+///     }
+/// }
+/// ```
+pub struct Server {
+    i: Box<IServer>,
+}
+
+impl Server {
+    /// Starts listening on an address. If it has several entries, the first one that works is used.
+    pub fn listen(address: &str) -> Result<Server, Error> {
+        init_dbus();
+        let mut e = Error::empty();
+        let server = unsafe { ffi::dbus_server_listen(to_c_str(address).as_ptr(), e.get_mut()) };
+        if server.is_null() { return Err(e) };
+        let s = Server { i: Box::new(IServer { server: server, watches: RefCell::new(vec!()), pending: RefCell::new(VecDeque::new()) }) };
+        let data = &*s.i as *const IServer as *mut c_void;
+        unsafe {
+            ffi::dbus_server_set_new_connection_function(server, Some(new_connection_cb), data, None);
+            if ffi::dbus_server_set_watch_functions(server, Some(add_watch_cb), Some(remove_watch_cb), Some(toggled_watch_cb), data, None) == 0 ||
+               ffi::dbus_server_set_timeout_functions(server, Some(add_timeout_cb), Some(remove_timeout_cb), Some(remove_timeout_cb), data, None) == 0 {
+                return Err(Error::new_custom("org.freedesktop.DBus.Error.NoMemory", "Out of memory when setting up server"));
+            }
+        }
+        Ok(s)
+    }
+
+    /// Adopts the listening sockets passed in by systemd socket activation (or a compatible
+    /// service manager), so the service can be started on demand.
+    ///
+    /// This follows `sd_listen_fds` semantics: the sockets are the file descriptors starting
+    /// at 3, their number is in `LISTEN_FDS`, and `LISTEN_PID` must be the pid of this process.
+    /// These environment variables are unset afterwards. A socket unit for this would contain e g
+    /// `ListenStream=/run/example.socket`; clients connect to `unix:path=/run/example.socket`.
+    ///
+    /// This is the same as `Server::listen("systemd:")`. Adopting an arbitrary listening socket
+    /// (a `from_fd(RawFd)`) is not supported, because libdbus has no way to do it: it only takes
+    /// sockets in the way described above. A socket that is not at fd 3 can only be used by
+    /// passing it to a child process at fd 3, with `LISTEN_FDS` and `LISTEN_PID` set accordingly.
+    pub fn from_systemd() -> Result<Server, Error> { Server::listen("systemd:") }
+
+    /// The address clients should connect to, including the server id (guid).
+    ///
+    /// For `unix:tmpdir=` and `tcp:` with port 0, this contains the actual socket path or port.
+    pub fn address(&self) -> String { self.get_string(ffi::dbus_server_get_address) }
+
+    /// The unique id (guid) of the server.
+    pub fn id(&self) -> String { self.get_string(ffi::dbus_server_get_id) }
+
+    fn get_string(&self, f: unsafe extern "C" fn(*mut ffi::DBusServer) -> *mut c_char) -> String {
+        let p = unsafe { f(self.i.server) };
+        if p.is_null() { panic!("D-Bus error: out of memory") };
+        let r = c_str_to_slice(&(p as *const c_char)).unwrap_or("").to_string();
+        unsafe { ffi::dbus_free(p as *mut c_void) };
+        r
+    }
+
+    /// Returns true until the server is disconnected.
+    pub fn is_connected(&self) -> bool { unsafe { ffi::dbus_server_get_is_connected(self.i.server) != 0 } }
+
+    /// Restricts the authentication mechanisms clients may use, e g `&["EXTERNAL"]`.
+    ///
+    /// By default all mechanisms libdbus supports are allowed.
+    pub fn set_auth_mechanisms(&self, mechanisms: &[&str]) -> Result<(), Error> {
+        let m: Vec<CString> = mechanisms.iter().map(|m| to_c_str(m)).collect();
+        let mut p: Vec<*const c_char> = m.iter().map(|m| m.as_ptr()).collect();
+        p.push(ptr::null());
+        if unsafe { ffi::dbus_server_set_auth_mechanisms(self.i.server, p.as_mut_ptr()) } == 0 {
+            return Err(Error::new_custom("org.freedesktop.DBus.Error.NoMemory", "Out of memory when setting auth mechanisms"));
+        }
+        Ok(())
+    }
+
+    /// Gets the file descriptors to poll for new clients, for integrating with a main loop.
+    ///
+    /// Call `watch_handle` when one of them is ready, and then `accept(0)`.
+    pub fn watch_fds(&self) -> Vec<Watch> {
+        self.i.watches.borrow().iter().map(|&w| unsafe { Watch::from_raw(w) })
+            .filter(|w| w.readable() || w.writable()).collect()
+    }
+
+    /// Handles events on a file descriptor returned by `watch_fds`. See `WatchEvent::from_revents`.
+    pub fn watch_handle(&self, fd: RawFd, flags: c_uint) {
+        // Handling a watch can add or remove watches.
+        let w: Vec<_> = self.i.watches.borrow().clone();
+        for q in w {
+            if unsafe { ffi::dbus_watch_get_unix_fd(q) } != fd || !self.i.watches.borrow().contains(&q) { continue };
+            unsafe { ffi::dbus_watch_handle(q, flags) };
+        }
+    }
+
+    /// Waits for a client to connect, and returns the connection to it.
+    ///
+    /// Returns `None` if no client connected within the timeout. The client is not yet
+    /// authenticated; that happens when the connection is first read from or written to,
+    /// e g by `Connection::incoming`.
+    pub fn accept(&self, timeout_ms: u32) -> Option<Connection> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms as u64);
+        loop {
+            if let Some(c) = self.i.pending.borrow_mut().pop_front() {
+                return Connection::conn_from_ptr(c, true).ok();
+            }
+            let now = Instant::now();
+            let remaining = if deadline > now { deadline - now } else { Duration::from_secs(0) };
+            let ms = ::timeout::duration_ms(remaining);
+            let mut fds: Vec<_> = self.watch_fds().iter().map(|w| w.to_pollfd()).collect();
+            if fds.is_empty() { return None };
+            let r = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, ms) };
+            if r < 0 && ::std::io::Error::last_os_error().kind() != ::std::io::ErrorKind::Interrupted { return None };
+            for pfd in fds.iter().filter(|pfd| pfd.revents != 0) {
+                self.watch_handle(pfd.fd, WatchEvent::from_revents(pfd.revents));
+            }
+            if r == 0 && self.i.pending.borrow().is_empty() { return None };
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::dbus_server_disconnect(self.i.server);
+            for c in self.i.pending.borrow_mut().drain(..) {
+                ffi::dbus_connection_close(c);
+                ffi::dbus_connection_unref(c);
+            }
+            ffi::dbus_server_set_new_connection_function(self.i.server, None, ptr::null_mut(), None);
+            ffi::dbus_server_set_watch_functions(self.i.server, None, None, None, ptr::null_mut(), None);
+            ffi::dbus_server_set_timeout_functions(self.i.server, None, None, None, ptr::null_mut(), None);
+            ffi::dbus_server_unref(self.i.server);
+        }
+    }
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "D-Bus Server({})", self.address())
+    }
+}
+
+fn iserver<'a>(data: *mut c_void) -> &'a IServer { unsafe { &*(data as *const IServer) } }
+
+extern "C" fn new_connection_cb(_: *mut ffi::DBusServer, conn: *mut ffi::DBusConnection, data: *mut c_void) {
+    let c = unsafe { ffi::dbus_connection_ref(conn) };
+    iserver(data).pending.borrow_mut().push_back(c);
+}
+
+extern "C" fn add_watch_cb(watch: *mut ffi::DBusWatch, data: *mut c_void) -> u32 {
+    iserver(data).watches.borrow_mut().push(watch);
+    1
+}
+
+extern "C" fn remove_watch_cb(watch: *mut ffi::DBusWatch, data: *mut c_void) {
+    iserver(data).watches.borrow_mut().retain(|&w| w != watch);
+}
+
+// Enabled state is read from the watch itself in watch_fds.
+extern "C" fn toggled_watch_cb(_: *mut ffi::DBusWatch, _: *mut c_void) {}
+
+// The server itself has no timeouts; authentication timeouts belong to the accepted connections.
+extern "C" fn add_timeout_cb(_: *mut ffi::DBusTimeout, _: *mut c_void) -> u32 { 1 }
+extern "C" fn remove_timeout_cb(_: *mut ffi::DBusTimeout, _: *mut c_void) {}
+
+#[cfg(test)]
+fn echo_one(c: &Connection) {
+    use MessageType;
+    c.register_object_path("/server").unwrap();
+    for _ in 0..10 {
+        for m in c.incoming(500) {
+            if m.msg_type() != MessageType::MethodCall { continue };
+            let s: String = m.read1().unwrap();
+            c.send(m.method_return().append1(s)).unwrap();
+            return;
+        }
+    }
+    panic!("No method call received");
+}
+
+#[test]
+fn server_listen() {
+    use {Message, ErrorKind};
+    use std::thread;
+
+    let mut addrs = vec!("unix:tmpdir=/tmp".to_string(), "tcp:host=127.0.0.1,port=0".into());
+    if cfg!(target_os = "linux") { addrs.push(format!("unix:abstract=/tmp/dbus-rs-server-{}", ::std::process::id())) };
+    for a in addrs {
+        let s = Server::listen(&a).unwrap();
+        assert!(s.is_connected());
+        let addr = s.address();
+        assert!(addr.starts_with(a.split(':').next().unwrap()), "{} {}", a, addr);
+        assert!(addr.ends_with(&format!("guid={}", s.id())));
+        if a.starts_with("unix:abstract=") { assert!(addr.starts_with("unix:abstract=/tmp/dbus-rs-server-")) };
+        assert!(s.accept(0).is_none());
+
+        let t = thread::spawn(move || {
+            let c = Connection::open_private(&addr).unwrap();
+            let m = Message::new_method_call("com.example.dbusrs", "/server", "com.example.dbusrs.Server", "Echo").unwrap().append1("Hello");
+            let r = c.send_with_reply_and_block(m, 5000).unwrap();
+            r.read1::<String>().unwrap()
+        });
+        let c = s.accept(5000).unwrap();
+        echo_one(&c);
+        assert_eq!(t.join().unwrap(), "Hello");
+    }
+
+    let e = Server::listen("unix:nosuchkey=1").unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::BadAddress);
+}
+
+// Run by socket_activation, in a child process that got the listening socket at fd 3.
+#[test]
+#[ignore]
+fn socket_activated_child() {
+    if ::std::env::var("DBUS_RS_ACTIVATION_TEST").is_err() { return };
+    ::std::env::set_var("LISTEN_PID", ::std::process::id().to_string());
+    let s = Server::from_systemd().unwrap();
+    assert!(::std::env::var("LISTEN_FDS").is_err());
+    let c = s.accept(5000).unwrap();
+    echo_one(&c);
+    c.flush();
+}
+
+#[test]
+fn socket_activation() {
+    use Message;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::UnixListener;
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    assert!(Server::from_systemd().is_err());
+
+    let path = format!("/tmp/dbus-rs-activation-{}", ::std::process::id());
+    let _ = ::std::fs::remove_file(&path);
+    let l = UnixListener::bind(&path).unwrap();
+    let fd = l.as_raw_fd();
+    let mut cmd = Command::new(::std::env::current_exe().unwrap());
+    cmd.args(&["--exact", "server::socket_activated_child", "--ignored", "--nocapture"])
+        .env("DBUS_RS_ACTIVATION_TEST", "1").env("LISTEN_FDS", "1").stdout(Stdio::null());
+    unsafe { cmd.pre_exec(move || {
+        // dup2 clears close-on-exec on the new fd, but does nothing if it is already 3.
+        let r = if fd == 3 { libc::fcntl(3, libc::F_SETFD, 0) } else { libc::dup2(fd, 3) };
+        if r < 0 { Err(::std::io::Error::last_os_error()) } else { Ok(()) }
+    }) };
+    let mut child = cmd.spawn().unwrap();
+    drop(l);
+
+    let c = Connection::open_private(&format!("unix:path={}", path)).unwrap();
+    let m = Message::new_method_call("com.example.dbusrs", "/server", "com.example.dbusrs.Server", "Echo").unwrap().append1("Activated");
+    let r = c.send_with_reply_and_block(m, 10000);
+    assert!(child.wait().unwrap().success());
+    let _ = ::std::fs::remove_file(&path);
+    assert_eq!(r.unwrap().read1::<&str>().unwrap(), "Activated");
+}
@@ -0,0 +1,140 @@
+use {Connection, Error, Message, MessageType, MatchRule, MatchGuard, BusName, SignalArgs, DBusProxy};
+use connection::SignalHandler;
+use stdintf::org_freedesktop_dbus::DBusNameOwnerChanged;
+use std::borrow::Cow;
+
+/// A change of ownership of a watched name, see `ServiceWatcher`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServiceEvent {
+    /// The name got a new owner, with this unique name.
+    Appeared(BusName<'static>),
+    /// The name no longer has an owner.
+    Vanished,
+}
+
+fn match_rule(name: &BusName) -> MatchRule<'static> {
+    let mut m = MatchRule::new();
+    m.msg_type = Some(MessageType::Signal);
+    m.sender = Some("org.freedesktop.DBus".into());
+    m.path = Some("/org/freedesktop/DBus".into());
+    m.interface = Some(DBusNameOwnerChanged::INTERFACE.into());
+    m.member = Some(DBusNameOwnerChanged::NAME.into());
+    m.args.push((0, Cow::Owned(name.to_string())));
+    m
+}
+
+fn parse_event(rule: &MatchRule, msg: &Message) -> Option<ServiceEvent> {
+    if !rule.matches(msg) { return None }
+    let s = DBusNameOwnerChanged::from_message(msg)?;
+    if s.new_owner.is_empty() { Some(ServiceEvent::Vanished) }
+    else { BusName::new(s.new_owner).ok().map(ServiceEvent::Appeared) }
+}
+
+/// Watches a name on the bus for owners appearing and vanishing.
+///
+/// Creating a watcher adds a match rule for the `NameOwnerChanged` signal of that name, which
+/// is removed when the watcher is dropped. The signals arrive as ordinary incoming messages;
+/// either use `event` to parse them while iterating over the connection, or create a
+/// `ServiceHandler` with `handler` and add it to the connection to get a callback.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Connection, BusType, ServiceWatcher, ServiceEvent};
+///
+/// let c = Connection::get_private(BusType::Session).unwrap();
+/// let w = ServiceWatcher::new(&c, "org.freedesktop.Notifications").unwrap();
+/// println!("Currently: {:?}", w.current().unwrap());
+/// for msg in c.incoming(1000) {
+///     match w.event(&msg) {
+///         Some(ServiceEvent::Appeared(owner)) => println!("Appeared, owned by {}", owner),
+///         Some(ServiceEvent::Vanished) => println!("Vanished"),
+///         None => {},
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ServiceWatcher<'a> {
+    conn: &'a Connection,
+    name: BusName<'static>,
+    rule: MatchRule<'static>,
+    _guard: MatchGuard<'a>,
+}
+
+impl<'a> ServiceWatcher<'a> {
+    /// Starts watching the name.
+    pub fn new<'b, N: Into<BusName<'b>>>(c: &'a Connection, name: N) -> Result<Self, Error> {
+        let name = name.into().into_static();
+        let rule = match_rule(&name);
+        let guard = try!(c.add_match_guard(&rule.match_str()));
+        Ok(ServiceWatcher { conn: c, name: name, rule: rule, _guard: guard })
+    }
+
+    /// The watched name.
+    pub fn name(&self) -> &BusName<'static> { &self.name }
+
+    /// Asks the D-Bus server for the current owner of the name.
+    ///
+    /// Returns `ServiceEvent::Vanished` if the name has no owner.
+    pub fn current(&self) -> Result<ServiceEvent, Error> {
+        match DBusProxy::new(self.conn).get_name_owner(&self.name) {
+            Ok(owner) => Ok(ServiceEvent::Appeared(owner)),
+            Err(ref e) if e.name() == Some("org.freedesktop.DBus.Error.NameHasNoOwner") => Ok(ServiceEvent::Vanished),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// If the message is a change of ownership of the watched name, returns what happened.
+    pub fn event(&self, msg: &Message) -> Option<ServiceEvent> { parse_event(&self.rule, msg) }
+
+    /// Returns a message handler that calls `f` whenever the ownership of the name changes.
+    ///
+    /// Add it to the connection with `Connection::add_handler`. The handler consumes the matching
+    /// signals, so they are no longer returned from the connection's iterators.
+    /// Keep the watcher alive for as long as you want callbacks, since it owns the match rule.
+    pub fn handler<F: FnMut(ServiceEvent)>(&self, f: F) -> ServiceHandler<F> {
+        let rule = self.rule.clone();
+        SignalHandler::new(move |msg| parse_event(&rule, msg), true, f)
+    }
+}
+
+/// The handler returned by `ServiceWatcher::handler`, calling a closure whenever the watched name
+/// appears or vanishes.
+pub type ServiceHandler<F> = SignalHandler<ServiceEvent, F>;
+
+#[test]
+fn service_watcher() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    let bus = ::testing::shared_bus();
+
+    let name = "com.example.dbusrs.servicewatcher";
+    let c = bus.connect().unwrap();
+    let w = ServiceWatcher::new(&c, name).unwrap();
+    assert_eq!(&**w.name(), name);
+    assert_eq!(w.current().unwrap(), ServiceEvent::Vanished);
+
+    let c2 = bus.connect().unwrap();
+    let owner: BusName<'static> = c2.unique_name().into();
+    c2.register_name(name, 0).unwrap();
+    assert_eq!(w.current().unwrap(), ServiceEvent::Appeared(owner.clone()));
+    c2.release_name(name).unwrap();
+
+    let mut events = vec!();
+    for msg in c.incoming(1000) {
+        if let Some(e) = w.event(&msg) { events.push(e) };
+        if events.len() == 2 { break };
+    }
+    assert_eq!(events, vec!(ServiceEvent::Appeared(owner.clone()), ServiceEvent::Vanished));
+
+    let events = Rc::new(RefCell::new(vec!()));
+    let e2 = events.clone();
+    c.add_handler(w.handler(move |e| e2.borrow_mut().push(e)));
+    c2.register_name(name, 0).unwrap();
+    drop(c2);
+    for _ in 0..10 {
+        if events.borrow().len() == 2 { break };
+        c.incoming(200).count();
+    }
+    assert_eq!(*events.borrow(), vec!(ServiceEvent::Appeared(owner), ServiceEvent::Vanished));
+}
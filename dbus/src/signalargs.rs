@@ -85,9 +85,9 @@ pub trait SignalArgs: Default {
 
 #[test]
 fn intf_removed() {
-    use {Connection, BusType};
     use stdintf::org_freedesktop_dbus::ObjectManagerInterfacesRemoved as IR;
-    let c = Connection::get_private(BusType::Session).unwrap();
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
     let mstr = IR::match_str(Some(&c.unique_name().into()), Some(&"/hello".into()));
     println!("Match str: {}", mstr);
     c.add_match(&mstr).unwrap();
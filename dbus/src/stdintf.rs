@@ -5,7 +5,7 @@
 //! The code here was originally created by dbus-codegen.
 //!
 //! # Example
-//! ```
+//! ```rust,no_run
 //! use dbus::{Connection, BusType};
 //! use dbus::stdintf::org_freedesktop_dbus::Introspectable;
 //! let c = Connection::get_private(BusType::Session).unwrap();
@@ -181,6 +181,31 @@ impl ::SignalArgs for ObjectManagerInterfacesRemoved {
     }
 }
 
+#[derive(Debug, Default)]
+/// Struct to send/receive the NameOwnerChanged signal of the
+/// [org.freedesktop.DBus](https://dbus.freedesktop.org/doc/dbus-specification.html#bus-messages-name-owner-changed) interface.
+pub struct DBusNameOwnerChanged {
+    pub name: String,
+    pub old_owner: String,
+    pub new_owner: String,
+}
+
+impl ::SignalArgs for DBusNameOwnerChanged {
+    const NAME: &'static str = "NameOwnerChanged";
+    const INTERFACE: &'static str = "org.freedesktop.DBus";
+    fn append(&self, i: &mut arg::IterAppend) {
+        (&self.name as &arg::RefArg).append(i);
+        (&self.old_owner as &arg::RefArg).append(i);
+        (&self.new_owner as &arg::RefArg).append(i);
+    }
+    fn get(&mut self, i: &mut arg::Iter) -> Result<(), arg::TypeMismatchError> {
+        self.name = try!(i.read());
+        self.old_owner = try!(i.read());
+        self.new_owner = try!(i.read());
+        Ok(())
+    }
+}
+
 /// Methods of the [org.freedesktop.DBus.Peer](https://dbus.freedesktop.org/doc/dbus-specification.html#standard-interfaces-peer) interface.
 pub trait Peer {
     type Err;
@@ -0,0 +1,160 @@
+//! Helpers for testing code that uses D-Bus.
+
+use {Connection, ConnectionBuilder, Error, libc};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(test)]
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use std::{fmt, fs, thread};
+
+static NEXT_BUS: AtomicUsize = AtomicUsize::new(1);
+
+fn spawn_failed(msg: &str) -> Error { Error::new_custom("org.freedesktop.DBus.Error.Spawn.Failed", msg) }
+
+/// A private message bus, for tests.
+///
+/// This starts a `dbus-daemon` of its own, configured like a session bus, so that tests neither
+/// depend on nor disturb the session bus of the user running them. Each `TestBus` is independent,
+/// so tests can run in parallel. The daemon is stopped when the `TestBus` is dropped.
+///
+/// Connect to it with `connect` or `builder`, or pass `address` on to the code under test.
+/// `dbus-daemon` must be in the `PATH`; if it is not, `new` returns an `ErrorKind::SpawnFailed` error.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::DBusProxy;
+/// use dbus::testing::TestBus;
+///
+/// let bus = TestBus::new().unwrap();
+/// let c = bus.connect().unwrap();
+/// c.register_name("com.example.dbusrs.test", 0).unwrap();
+/// assert!(DBusProxy::new(&c).get_name_owner("com.example.dbusrs.test").is_ok());
+/// ```
+pub struct TestBus {
+    daemon: Child,
+    address: String,
+}
+
+impl TestBus {
+    /// Starts a bus, listening on a unique abstract socket (on Linux) or a socket in /tmp.
+    pub fn new() -> Result<TestBus, Error> {
+        let n = NEXT_BUS.fetch_add(1, Ordering::Relaxed);
+        let listen = if cfg!(any(target_os = "linux", target_os = "android")) {
+            format!("unix:abstract=/tmp/dbus-rs-test-{}-{}", ::std::process::id(), n)
+        } else { "unix:tmpdir=/tmp".into() };
+        TestBus::with_config(&listen, "")
+    }
+
+    /// Starts a bus listening on `listen`, e g "tcp:host=127.0.0.1,port=0".
+    ///
+    /// `extra_config` is added to the bus configuration file, e g `<auth>ANONYMOUS</auth><allow_anonymous/>`
+    /// or `<servicedir>/path/to/services</servicedir>`. By default, everything is allowed.
+    pub fn with_config(listen: &str, extra_config: &str) -> Result<TestBus, Error> {
+        let n = NEXT_BUS.fetch_add(1, Ordering::Relaxed);
+        let conf = ::std::env::temp_dir().join(format!("dbus-rs-test-{}-{}.conf", ::std::process::id(), n));
+        let r = fs::File::create(&conf).and_then(|mut f| write!(f, r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>{}</listen>
+  {}
+  <policy context="default"><allow send_destination="*" eavesdrop="true"/><allow eavesdrop="true"/><allow own="*"/></policy>
+</busconfig>"#, listen, extra_config));
+        if let Err(e) = r { return Err(spawn_failed(&format!("Writing {}: {}", conf.display(), e))) };
+
+        let spawned = Command::new("dbus-daemon").arg("--nofork").arg("--print-address").arg(format!("--config-file={}", conf.display()))
+            .stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn();
+        let mut daemon = match spawned {
+            Ok(d) => d,
+            Err(e) => { let _ = fs::remove_file(&conf); return Err(spawn_failed(&format!("Starting dbus-daemon: {}", e))) },
+        };
+        // The address is printed once the daemon is listening, and it has read the config by then.
+        let mut address = String::new();
+        let r = BufReader::new(daemon.stdout.take().unwrap()).read_line(&mut address);
+        let _ = fs::remove_file(&conf);
+        let address = address.trim().to_string();
+        if r.is_err() || address.is_empty() {
+            let _ = daemon.kill();
+            let status = daemon.wait().map(|s| s.to_string()).unwrap_or_default();
+            return Err(spawn_failed(&format!("dbus-daemon did not start ({}), check the configuration", status)));
+        }
+        Ok(TestBus { daemon: daemon, address: address })
+    }
+
+    /// The address of the bus, including its guid.
+    pub fn address(&self) -> &str { &self.address }
+
+    /// The process id of the dbus-daemon.
+    pub fn pid(&self) -> u32 { self.daemon.id() }
+
+    /// Opens a new connection to the bus, and registers with it.
+    pub fn connect(&self) -> Result<Connection, Error> { Connection::open_address(&self.address) }
+
+    /// Returns a builder for connecting to the bus, for further configuration.
+    pub fn builder(&self) -> ConnectionBuilder { Connection::builder().address(&self.address, true) }
+}
+
+impl Drop for TestBus {
+    /// Stops the dbus-daemon, which disconnects all connections to it.
+    fn drop(&mut self) {
+        // Ask nicely first, so that the daemon removes its socket files.
+        unsafe { libc::kill(self.daemon.id() as libc::pid_t, libc::SIGTERM) };
+        for _ in 0..50 {
+            if let Ok(Some(_)) = self.daemon.try_wait() { return };
+            thread::sleep(Duration::from_millis(20));
+        }
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+// A bus shared by the crate's own tests, so that they neither need nor disturb the session bus.
+// It is started by the first test that needs it, and stopped once no running test holds on to it.
+#[cfg(test)]
+pub (crate) fn shared_bus() -> Arc<TestBus> {
+    static SHARED: Mutex<Option<Weak<TestBus>>> = Mutex::new(None);
+    let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(b) = shared.as_ref().and_then(|w| w.upgrade()) { return b };
+    let b = Arc::new(TestBus::new().unwrap_or_else(|e| panic!("Starting a test bus: {}", e)));
+    *shared = Some(Arc::downgrade(&b));
+    b
+}
+
+impl fmt::Debug for TestBus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "TestBus({}, pid {})", self.address, self.daemon.id())
+    }
+}
+
+#[test]
+fn test_bus() {
+    use {ErrorKind, Message};
+
+    let bus = match TestBus::new() {
+        Ok(b) => b,
+        Err(ref e) if e.kind() == ErrorKind::SpawnFailed => return, // No dbus-daemon available
+        Err(e) => panic!("{}", e),
+    };
+    if cfg!(target_os = "linux") { assert!(bus.address().starts_with("unix:abstract=/tmp/dbus-rs-test-")) };
+    let bus2 = TestBus::new().unwrap();
+    assert!(bus.address() != bus2.address());
+
+    let c = bus.connect().unwrap();
+    let c2 = bus.builder().build().unwrap();
+    assert!(c.server_id() != bus2.connect().unwrap().server_id());
+
+    c.register_name("com.example.dbusrs.testbus", 0).unwrap();
+    c2.add_match("interface='com.example.dbusrs.TestBus'").unwrap();
+    c.send(Message::new_signal("/", "com.example.dbusrs.TestBus", "Hello").unwrap()).unwrap();
+    assert!(c2.incoming(2000).any(|m| m.member().map_or(false, |x| &*x == "Hello")));
+
+    drop(bus);
+    assert!(c2.incoming(1000).count() <= 1); // The Disconnected signal
+    assert!(!c2.is_connected());
+
+    let e = TestBus::with_config("unix:nosuchkey=1", "").unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::SpawnFailed);
+}
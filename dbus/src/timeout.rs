@@ -0,0 +1,87 @@
+use ffi;
+use std::time::Duration;
+use std::os::raw::c_int;
+
+/// A timeout for blocking operations, such as waiting for a method call reply.
+///
+/// For backwards compatibility, an `i32` or `u32` in milliseconds can be used wherever a Timeout is expected.
+/// A negative value means `Timeout::Default`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use dbus::{Connection, BusType, Message, Timeout};
+/// use std::time::Duration;
+///
+/// let c = Connection::get_private(BusType::Session).unwrap();
+/// let m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetId").unwrap();
+/// c.send_with_reply_and_block(m, Duration::from_secs(2)).unwrap();
+/// let m = Message::new_method_call("org.freedesktop.DBus", "/", "org.freedesktop.DBus", "GetId").unwrap();
+/// c.send_with_reply_and_block(m, Timeout::Default).unwrap();
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Timeout {
+    /// For method calls, use libdbus's default timeout (currently 25 seconds).
+    ///
+    /// When waiting for incoming messages, this is the same as Infinite.
+    Default,
+    /// Wait forever.
+    Infinite,
+    /// Wait for at most this amount of time. Rounded down to whole milliseconds.
+    Duration(Duration),
+}
+
+impl Default for Timeout {
+    fn default() -> Self { Timeout::Default }
+}
+
+impl From<Duration> for Timeout {
+    fn from(d: Duration) -> Self { Timeout::Duration(d) }
+}
+
+impl From<i32> for Timeout {
+    fn from(ms: i32) -> Self {
+        if ms < 0 { Timeout::Default } else { Timeout::Duration(Duration::from_millis(ms as u64)) }
+    }
+}
+
+impl From<u32> for Timeout {
+    fn from(ms: u32) -> Self { Timeout::Duration(Duration::from_millis(ms as u64)) }
+}
+
+pub (crate) fn duration_ms(d: Duration) -> c_int {
+    let ms = d.as_secs().saturating_mul(1000).saturating_add((d.subsec_nanos() / 1_000_000) as u64);
+    if ms > ffi::DBUS_TIMEOUT_INFINITE as u64 { ffi::DBUS_TIMEOUT_INFINITE } else { ms as c_int }
+}
+
+impl Timeout {
+    /// The timeout in milliseconds, as libdbus expects it for method calls.
+    pub (crate) fn call_ms(&self) -> c_int {
+        match *self {
+            Timeout::Default => ffi::DBUS_TIMEOUT_USE_DEFAULT,
+            Timeout::Infinite => ffi::DBUS_TIMEOUT_INFINITE,
+            Timeout::Duration(d) => duration_ms(d),
+        }
+    }
+
+    /// The timeout in milliseconds, as libdbus expects it for reading and writing.
+    pub (crate) fn io_ms(&self) -> c_int {
+        match *self {
+            Timeout::Default | Timeout::Infinite => -1,
+            Timeout::Duration(d) => duration_ms(d),
+        }
+    }
+}
+
+#[test]
+fn timeout_ms() {
+    assert_eq!(Timeout::from(-1), Timeout::Default);
+    assert_eq!(Timeout::from(2000).call_ms(), 2000);
+    assert_eq!(Timeout::from(2000u32), Timeout::from(2000));
+    assert_eq!(Timeout::from(Duration::from_millis(1500)).io_ms(), 1500);
+    assert_eq!(Timeout::Default.call_ms(), -1);
+    assert_eq!(Timeout::Default.io_ms(), -1);
+    assert_eq!(Timeout::Infinite.call_ms(), ffi::DBUS_TIMEOUT_INFINITE);
+    assert_eq!(Timeout::Infinite.io_ms(), -1);
+    assert_eq!(Timeout::from(Duration::from_secs(u64::max_value())).call_ms(), ffi::DBUS_TIMEOUT_INFINITE);
+}
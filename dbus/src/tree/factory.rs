@@ -1,4 +1,4 @@
-use super::{MethodType, DataType, MTFn, MTFnMut, MTSync, MethodResult, MethodInfo};
+use super::{MethodType, DataType, MTFn, MTFnMut, MTSync, MethodResult, MethodInfo, MethodErr};
 use super::{Tree, ObjectPath, Interface, Property, Signal, Method};
 use super::objectpath::IfaceCache;
 use std::sync::Arc;
@@ -77,6 +77,24 @@ impl<M: MethodType<D>, D: DataType> Factory<M, D> {
         super::leaves::new_signal(name.into(), data)
     }
 
+    /// Creates a new signal with typed arguments.
+    ///
+    /// The argument types are used for introspection and to check the arguments given
+    /// to `Signal::msg_args` and `Signal::send`. Argument names are given as a tuple of strings.
+    ///
+    /// # Example
+    /// ```
+    /// use dbus::tree::Factory;
+    /// let f = Factory::new_fn::<()>();
+    /// let s = f.typed_signal::<(String, u32), _>("Changed", (), ("name", "value"));
+    /// let msg = s.msg_args(&"/hello".into(), &"com.example.dbustest".into(), ("Volume", 5u32)).unwrap();
+    /// ```
+    pub fn typed_signal<A: arg::ArgBuilder, T: Into<Member<'static>>>(&self, name: T, data: D::Signal, args: A::strs) -> Signal<D> {
+        let mut a = vec!();
+        A::strs_sig(args, |n, sig| a.push((n, sig)));
+        super::leaves::new_signal(name.into(), data).args(a)
+    }
+
     /// Creates a new interface.
     pub fn interface<T: Into<IfaceName<'static>>>(&self, name: T, data: D::Interface) -> Interface<M, D> {
         super::objectpath::new_interface(name.into(), data)
@@ -92,6 +110,39 @@ impl<M: MethodType<D>, D: DataType> Factory<M, D> {
         super::objectpath::new_tree(data)
     }
 
+    /// Creates a new method with typed input and output arguments.
+    ///
+    /// The input arguments are read from the method call and handed to the handler, and the output
+    /// arguments returned by the handler are appended to the method return. If the input arguments
+    /// do not match, an error is returned to the caller without calling the handler.
+    /// Argument names (used for introspection) are given as tuples of strings.
+    ///
+    /// Works with all factories, but since the handler must be Send + Sync, it cannot mutate its environment
+    /// without synchronisation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dbus::tree::Factory;
+    ///
+    /// let f = Factory::new_fn::<()>();
+    /// let m = f.typed_method::<(String, u32), (String,), _, _>("Repeat", (), ("text", "times"), ("result",),
+    ///     |_, (text, times)| Ok((text.repeat(times as usize),)));
+    /// ```
+    pub fn typed_method<IA, OA, H, T>(&self, t: T, data: D::Method, in_args: IA::strs, out_args: OA::strs, handler: H) -> Method<M, D>
+    where IA: arg::ArgBuilder, OA: arg::ArgBuilder, T: Into<Member<'static>>,
+        H: Fn(&MethodInfo<M, D>, IA) -> Result<OA, MethodErr> + Send + Sync + 'static {
+        let (mut i, mut o) = (vec!(), vec!());
+        IA::strs_sig(in_args, |n, sig| i.push((n, sig)));
+        OA::strs_sig(out_args, |n, sig| o.push((n, sig)));
+        self.method_sync(t, data, move |minfo| {
+            let ia = try!(IA::read(minfo.msg));
+            let mut r = minfo.msg.method_return();
+            try!(handler(minfo, ia)).append(&mut r);
+            Ok(vec!(r))
+        }).in_args(i).out_args(o)
+    }
+
     /// Creates a new method - usually you'll use "method" instead.
     ///
     /// This is useful for being able to create methods in code which is generic over methodtype.
@@ -135,3 +186,53 @@ fn fn_customdata() {
     let o = f.object_path("/test/test", Arc::new(7));
     assert_eq!(**o.get_data(), 7);
 }
+
+#[test]
+fn typed_method() {
+    use Message;
+    let f = Factory::new_fn::<()>();
+    let t = f.tree(()).add(f.object_path("/test", ()).add(f.interface("com.example.test", ())
+        .add_m(f.typed_method::<(String, u32), (String,), _, _>("Repeat", (), ("text", "times"), ("result",),
+            |_, (text, times)| Ok((text.repeat(times as usize),))))
+    ));
+    let xml = t.iter().next().unwrap().introspect(&t);
+    assert!(xml.contains(r#"<arg name="times" type="u" direction="in"/>"#));
+    assert!(xml.contains(r#"<arg name="result" type="s" direction="out"/>"#));
+
+    let mut msg = Message::new_method_call("com.example.test", "/test", "com.example.test", "Repeat").unwrap()
+        .append2("ab", 3u32);
+    ::message::message_set_serial(&mut msg, 4);
+    let res = t.handle(&msg).unwrap();
+    assert_eq!(res[0].get1(), Some("ababab"));
+
+    let mut msg = Message::new_method_call("com.example.test", "/test", "com.example.test", "Repeat").unwrap()
+        .append1("ab");
+    ::message::message_set_serial(&mut msg, 4);
+    let mut res = t.handle(&msg).unwrap();
+    assert!(res[0].as_result().is_err());
+}
+
+#[test]
+fn typed_signal() {
+    let f = Factory::new_fn::<()>();
+    let s = Arc::new(f.typed_signal::<(String, u32), _>("Changed", (), ("name", "value")));
+    let t = f.tree(()).add(f.object_path("/test", ()).introspectable()
+        .add(f.interface("com.example.test", ()).add_s(s.clone())));
+    let m = s.msg_args(&"/test".into(), &"com.example.test".into(), ("Volume", 5u32)).unwrap();
+    assert_eq!(m.read2::<&str, u32>().unwrap(), ("Volume", 5));
+    assert_eq!(&*m.member().unwrap(), "Changed");
+
+    let mut msg = ::Message::new_method_call("com.example", "/test", "org.freedesktop.DBus.Introspectable", "Introspect").unwrap();
+    ::message::message_set_serial(&mut msg, 4);
+    let xml: String = t.handle(&msg).unwrap()[0].read1().unwrap();
+    assert!(xml.contains(r#"<arg name="name" type="s"/>"#));
+    assert!(xml.contains(r#"<arg name="value" type="u"/>"#));
+}
+
+#[test]
+fn typed_signal_mismatch() {
+    let f = Factory::new_fn::<()>();
+    let s = f.typed_signal::<(String, u32), _>("Changed", (), ("name", "value"));
+    let e = s.msg_args(&"/test".into(), &"com.example.test".into(), ("Volume", 5i32)).unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.InvalidArgs"));
+}
@@ -1,7 +1,7 @@
 // Methods, signals, properties, and interfaces.
 use super::utils::{Argument, Annotations, Introspect, introspect_args};
 use super::{MethodType, MethodInfo, MethodResult, MethodErr, DataType, PropInfo, MTFn, MTFnMut, MTSync};
-use {Member, Signature, Message, Path, MessageItem};
+use {Member, Signature, Message, Path, MessageItem, Connection, Error};
 use Interface as IfaceName;
 use arg;
 use std::fmt;
@@ -128,10 +128,20 @@ impl<D: DataType> Signal<D> {
     /// Returns a message which emits the signal when sent.
     ///
     /// Same as "msg" but also takes a "MessageItem" argument.
+    ///
+    /// Panics if the items cannot be appended, use `try_emit` to get an error instead.
+    #[deprecated(note="please use `try_emit` instead")]
     pub fn emit(&self, p: &Path<'static>, i: &IfaceName<'static>, items: &[MessageItem]) -> Message {
+        self.try_emit(p, i, items).unwrap()
+    }
+
+    /// Returns a message which emits the signal with the given MessageItems when sent.
+    ///
+    /// Returns an error if the items cannot be appended, see `Message::append_items`.
+    pub fn try_emit(&self, p: &Path<'static>, i: &IfaceName<'static>, items: &[MessageItem]) -> Result<Message, Error> {
         let mut m = self.msg(p, i);
-        m.append_items(items);
-        m
+        try!(m.append_items(items));
+        Ok(m)
     }
 
     /// Returns a message which emits the signal when sent.
@@ -141,6 +151,33 @@ impl<D: DataType> Signal<D> {
         Message::signal(p, i, &self.name)
     }
 
+    /// Returns a message which emits the signal with the given arguments when sent.
+    ///
+    /// Returns an InvalidArgs error if the arguments do not match the argument types declared for this signal.
+    pub fn msg_args<A: arg::AppendAll>(&self, p: &Path<'static>, i: &IfaceName<'static>, args: A) -> Result<Message, Error> {
+        let mut m = self.msg(p, i);
+        args.append(&mut arg::IterAppend::new(&mut m));
+        let (mut iter, mut sig) = (m.iter_init(), String::new());
+        while iter.arg_type() != arg::ArgType::Invalid {
+            sig.push_str(&iter.signature());
+            iter.next();
+        }
+        let expected: String = self.arguments.iter().map(|a| &**a.signature()).collect();
+        if sig != expected {
+            return Err(Error::new_custom("org.freedesktop.DBus.Error.InvalidArgs",
+                &format!("Signal {} has signature '{}', but arguments have signature '{}'", self.name, expected, sig)));
+        }
+        Ok(m)
+    }
+
+    /// Emits the signal with the given arguments, by sending it on the connection.
+    ///
+    /// Returns an InvalidArgs error if the arguments do not match the argument types declared for this signal.
+    pub fn send<A: arg::AppendAll>(&self, c: &Connection, p: &Path<'static>, i: &IfaceName<'static>, args: A) -> Result<u32, Error> {
+        let m = try!(self.msg_args(p, i, args));
+        c.send(m).map_err(|_| Error::new_custom("org.freedesktop.DBus.Error.Failed", "Sending signal failed"))
+    }
+
 }
 
 impl<D: DataType> Introspect for Signal<D> {
@@ -213,6 +250,11 @@ pub fn prop_append_dict<'v, M: MethodType<D> + 'v, D: DataType + 'v, I: Iterator
 
 #[derive(Debug)]
 /// A D-Bus Property.
+///
+/// When the property is set through the Properties interface, a PropertiesChanged signal is
+/// returned together with the method reply, according to `emits_changed` and `auto_emit_on_set`.
+/// If the value changes in any other way, e g when it is read from somewhere else by the getter,
+/// create the signal with `Tree::properties_changed` and send it yourself.
 pub struct Property<M: MethodType<D>, D: DataType> {
     name: String,
     data: D::Property,
@@ -243,8 +285,8 @@ impl<M: MethodType<D>, D: DataType> Property<M, D> {
     /// When set to true (the default), the behaviour is determined by "emits_changed".
     /// When set to false, no PropertiesChanged signal will be emitted (but the signal
     /// still shows up in introspection data).
-    /// You can still emit the signal manually by, e g, calling `add_propertieschanged`
-    /// and send the resulting message(s).
+    /// You can still emit the signal manually by, e g, calling `Tree::properties_changed`
+    /// or `add_propertieschanged` and send the resulting message(s).
     pub fn auto_emit_on_set(mut self, b: bool) -> Self {
         self.auto_emit = b;
         self
@@ -273,13 +315,16 @@ impl<M: MethodType<D>, D: DataType> Property<M, D> {
     /// Get property name
     pub fn get_name(&self) -> &str { &self.name }
 
+    /// Get the property's signal behavior when changed.
+    pub fn get_emits_changed(&self) -> EmitsChangedSignal { self.emits }
+
     /// Get associated data
     pub fn get_data(&self) -> &D::Property { &self.data }
 
     /// Returns Ok if the property is gettable
     pub fn can_get(&self) -> Result<(), MethodErr> {
         if self.rw == Access::Write || self.get_cb.is_none() { 
-            Err(MethodErr::failed(&format!("Property {} is write only", &self.name)))
+            Err(MethodErr::wo_property(&self.name))
         } else { Ok(()) }
     }
 
@@ -305,7 +350,7 @@ impl<M: MethodType<D>, D: DataType> Property<M, D> {
         if let Some(mut i) = i {
             let mut subiter = try!(i.recurse(arg::Variant::<bool>::ARG_TYPE).ok_or_else(|| MethodErr::invalid_arg(&2)));
             if &*subiter.signature() != &*self.sig {
-               return Err(MethodErr::failed(&format!("Property {} cannot change type", &self.name)))
+               return Err(("org.freedesktop.DBus.Error.InvalidArgs", format!("Property {} cannot change type", &self.name)).into())
             }
         }
         Ok(())
@@ -322,12 +367,6 @@ impl<M: MethodType<D>, D: DataType> Property<M, D> {
         self.get_emits_changed_signal(pinfo)
     }
 
-    /// Gets the signal (if any) associated with the Property.
-    fn get_signal(&self, p: &PropInfo<M, D>) -> Message {
-        Message::signal(p.path.get_name(), &"org.freedesktop.DBus.Properties".into(), &"PropertiesChanged".into())
-            .append1(&**p.iface.get_name())
-    }
-
     /// Adds this property to a list of PropertiesChanged signals.
     ///
     /// "v" is updated with the signal for this property. "new_value" is only called if self.emits is "true",
@@ -358,23 +397,8 @@ impl<M: MethodType<D>, D: DataType> Property<M, D> {
 
     fn get_emits_changed_signal(&self, m: &PropInfo<M, D>) -> Result<Option<Message>, MethodErr> {
         if !self.auto_emit { return Ok(None) }
-        match self.emits {
-            EmitsChangedSignal::False => Ok(None),
-            EmitsChangedSignal::Const => Err(MethodErr::ro_property(&self.name)),
-            EmitsChangedSignal::True => Ok(Some({
-                let mut s = self.get_signal(m);
-                {
-                    let mut iter = arg::IterAppend::new(&mut s);
-                    try!(prop_append_dict(&mut iter, Some(self).into_iter(), &m.to_method_info()));
-                    iter.append(arg::Array::<&str, _>::new(vec!()));
-                }
-                s
-            })),
-            EmitsChangedSignal::Invalidates => Ok(Some(self.get_signal(m).append2(
-                arg::Dict::<&str, arg::Variant<bool>, _>::new(vec!()),
-                arg::Array::new(Some(&*self.name).into_iter())
-            ))),
-        }
+        if self.emits == EmitsChangedSignal::Const { return Err(MethodErr::ro_property(&self.name)) }
+        m.tree.properties_changed(m.path.get_name(), m.iface.get_name(), &[&self.name])
     }
 }
 
@@ -577,14 +601,32 @@ fn test_set_prop() {
         .append3("com.example.dbus.rs", "changes", arg::Variant(5i32));
     ::message::message_set_serial(&mut msg, 20);
     let mut r = tree.handle(&msg).unwrap();
-    assert!(r.get_mut(0).unwrap().as_result().is_err());
+    let e = r.get_mut(0).unwrap().as_result().unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.PropertyReadOnly"));
+
+    // Unknown property
+    let mut msg = Message::new_method_call("com.example.dbus.rs", "/example", "org.freedesktop.DBus.Properties", "Get").unwrap()
+        .append2("com.example.dbus.rs", "nonexistent");
+    ::message::message_set_serial(&mut msg, 25);
+    let mut r = tree.handle(&msg).unwrap();
+    let e = r.get_mut(0).unwrap().as_result().unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.UnknownProperty"));
+
+    // Empty interface name
+    let mut msg = Message::new_method_call("com.example.dbus.rs", "/example", "org.freedesktop.DBus.Properties", "Get").unwrap()
+        .append2("", "changes");
+    ::message::message_set_serial(&mut msg, 26);
+    let mut r = tree.handle(&msg).unwrap();
+    let v: arg::Variant<i32> = r.get_mut(0).unwrap().as_result().unwrap().read1().unwrap();
+    assert_eq!(v.0, 0);
 
     // Wrong type
     let mut msg = Message::new_method_call("com.example.dbus.rs", "/example", "org.freedesktop.DBus.Properties", "Set").unwrap()
         .append3("com.example.dbus.rs", "setme", arg::Variant(8i32));
     ::message::message_set_serial(&mut msg, 30);
     let mut r = tree.handle(&msg).unwrap();
-    assert!(r.get_mut(0).unwrap().as_result().is_err());
+    let e = r.get_mut(0).unwrap().as_result().unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.InvalidArgs"));
 
     // Correct!
     let mut msg = Message::new_method_call("com.example.dbus.rs", "/example", "org.freedesktop.DBus.Properties", "Set").unwrap()
@@ -651,3 +693,14 @@ fn test_sync_prop() {
    }
    assert_eq!(count.load(Ordering::SeqCst), 5);
 }
+
+#[test]
+fn test_signal_try_emit() {
+    let f = super::Factory::new_fn::<()>();
+    let s = f.signal("Changed", ()).sarg::<&str, _>("value");
+    let (p, i) = ("/emit".into(), "com.example.emit".into());
+    let m = s.try_emit(&p, &i, &["Hello".into()]).unwrap();
+    assert_eq!(m.read1::<&str>().unwrap(), "Hello");
+    let e = s.try_emit(&p, &i, &["Hello\0world".into()]).unwrap_err();
+    assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.InvalidArgs"));
+}
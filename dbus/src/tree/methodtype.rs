@@ -1,8 +1,8 @@
 // Methods and method types. Glue to make stuff generic over MFn, MFnMut and MSync
 
 use std::fmt;
-use {ErrorName, Message, stdintf};
-use arg::{Iter, IterAppend, TypeMismatchError};
+use {ErrorName, Message, ParseError, Sender, stdintf};
+use arg::{Iter, IterAppend, AppendAll, TypeMismatchError};
 use std::marker::PhantomData;
 use super::{Method, Interface, Property, ObjectPath, Tree};
 use std::cell::RefCell;
@@ -42,6 +42,23 @@ impl MethodErr {
     pub fn ro_property<T: fmt::Display>(a: &T) -> MethodErr {
         ("org.freedesktop.DBus.Error.PropertyReadOnly", format!("Property {} is read only", a)).into()
     }
+    /// Create a MethodErr that the Property was write-only.
+    pub fn wo_property<T: fmt::Display>(a: &T) -> MethodErr {
+        ("org.freedesktop.DBus.Error.InvalidArgs", format!("Property {} is write only", a)).into()
+    }
+
+    /// Create a MethodErr that the object path was unknown.
+    pub fn no_path<T: fmt::Display>(a: &T) -> MethodErr {
+        ("org.freedesktop.DBus.Error.UnknownObject", format!("Unknown object path {}", a)).into()
+    }
+    /// Create a MethodErr that the caller is not allowed to perform the operation.
+    pub fn access_denied<T: fmt::Display>(a: &T) -> MethodErr {
+        ("org.freedesktop.DBus.Error.AccessDenied", format!("Access denied: {}", a)).into()
+    }
+    /// Create a MethodErr that the requested operation is not supported.
+    pub fn not_supported<T: fmt::Display>(a: &T) -> MethodErr {
+        ("org.freedesktop.DBus.Error.NotSupported", format!("Not supported: {}", a)).into()
+    }
 
     /// Error name accessor
     pub fn errorname(&self) -> &ErrorName<'static> { &self.0 }
@@ -57,10 +74,22 @@ impl MethodErr {
     }
 }
 
+impl fmt::Display for MethodErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{} ({})", self.1, &*self.0) }
+}
+
+impl ::std::error::Error for MethodErr {
+    fn description(&self) -> &str { &self.1 }
+}
+
 impl From<TypeMismatchError> for MethodErr {
     fn from(t: TypeMismatchError) -> MethodErr { ("org.freedesktop.DBus.Error.Failed", format!("{}", t)).into() }
 }
 
+impl From<ParseError> for MethodErr {
+    fn from(t: ParseError) -> MethodErr { ("org.freedesktop.DBus.Error.InvalidArgs", format!("{}", t)).into() }
+}
+
 impl<T: Into<ErrorName<'static>>, M: Into<String>> From<(T, M)> for MethodErr {
     fn from((t, m): (T, M)) -> MethodErr { MethodErr(t.into(), m.into()) }
 }
@@ -221,6 +250,65 @@ impl<'a, M: 'a + MethodType<D>, D: 'a + DataType> MethodInfo<'a, M, D> {
     pub fn to_prop_info(&self, iface: &'a Interface<M, D>, prop: &'a Property<M, D>) -> PropInfo<'a, M, D> {
         PropInfo { msg: self.msg, method: self.method, iface: iface, prop: prop, path: self.path, tree: self.tree }
     }
+
+    /// Defers the reply to this method call.
+    ///
+    /// Return `Ok(vec!())` from the method handler, and reply later (e g from another thread)
+    /// through the returned `DeferredReply`.
+    pub fn defer(&self, sender: Sender) -> DeferredReply {
+        DeferredReply { msg: Some(Message::from_ptr(self.msg.ptr(), true)), sender: sender }
+    }
+}
+
+/// A handle to reply to a method call later, e g when a long-running operation has finished.
+///
+/// Created by `MethodInfo::defer`. It can be sent to another thread, and the reply is sent
+/// through the `Sender` given when it was created. If it is dropped without replying,
+/// an org.freedesktop.DBus.Error.Failed error is sent back to the caller.
+pub struct DeferredReply {
+    msg: Option<Message>,
+    sender: Sender,
+}
+
+impl fmt::Debug for DeferredReply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "DeferredReply({:?})", self.msg) }
+}
+
+impl DeferredReply {
+    /// The method call that is to be replied to.
+    pub fn msg(&self) -> &Message { self.msg.as_ref().unwrap() }
+
+    /// Replies with the given output arguments.
+    pub fn reply<A: AppendAll>(self, args: A) -> Result<u32, ()> {
+        self.finish(Ok(args))
+    }
+
+    /// Replies with an error.
+    pub fn reply_err(self, e: MethodErr) -> Result<u32, ()> {
+        self.finish::<()>(Err(e))
+    }
+
+    /// Replies with either the given output arguments or an error.
+    pub fn finish<A: AppendAll>(mut self, r: Result<A, MethodErr>) -> Result<u32, ()> {
+        let msg = self.msg.take().unwrap();
+        self.sender.send(match r {
+            Ok(args) => {
+                let mut m = msg.method_return();
+                args.append(&mut IterAppend::new(&mut m));
+                m
+            },
+            Err(e) => e.to_message(&msg),
+        })
+    }
+
+}
+
+impl Drop for DeferredReply {
+    fn drop(&mut self) {
+        if let Some(ref msg) = self.msg {
+            let _ = self.sender.send(MethodErr::failed(&"Method call was not answered").to_message(msg));
+        }
+    }
 }
 
 
@@ -273,3 +361,50 @@ impl<'a, M: 'a + MethodType<D>, D: 'a + DataType> PropInfo<'a, M, D> {
         MethodInfo { msg: self.msg, method: self.method, iface: self.iface, path: self.path, tree: self.tree }
     }
 }
+
+#[test]
+fn method_err_names() {
+    let e = MethodErr::no_path(&"/nothing");
+    assert_eq!(&**e.errorname(), "org.freedesktop.DBus.Error.UnknownObject");
+    assert_eq!(e.to_string(), "Unknown object path /nothing (org.freedesktop.DBus.Error.UnknownObject)");
+    assert_eq!(&**MethodErr::access_denied(&"no").errorname(), "org.freedesktop.DBus.Error.AccessDenied");
+    assert_eq!(&**MethodErr::not_supported(&"no").errorname(), "org.freedesktop.DBus.Error.NotSupported");
+    assert_eq!(&**MethodErr::invalid_arg(&5).errorname(), "org.freedesktop.DBus.Error.InvalidArgs");
+}
+
+#[test]
+fn deferred_reply() {
+    use std::sync::mpsc;
+    let bus = ::testing::shared_bus();
+    let c = bus.connect().unwrap();
+    let (sender1, sender2) = (c.sender(), c.sender());
+    let f = super::Factory::new_fn::<()>();
+    let t = f.tree(()).add(f.object_path("/deferred", ()).add(f.interface("com.example.deferred", ())
+        .add_m(f.method("Slow", (), move |m| {
+            let d = m.defer(sender1.clone());
+            ::std::thread::spawn(move || {
+                let n: u32 = d.msg().read1().unwrap();
+                d.reply((n * 2,)).unwrap();
+            });
+            Ok(vec!())
+        }))
+        .add_m(f.method("Forgotten", (), move |m| { m.defer(sender2.clone()); Ok(vec!()) }))
+    ));
+    t.set_registered(&c, true).unwrap();
+    c.add_handler(t);
+
+    let name = c.unique_name();
+    let (tx, rx) = mpsc::channel();
+    let thread = ::std::thread::spawn(move || {
+        let c2 = bus.connect().unwrap();
+        let m = Message::new_method_call(&name, "/deferred", "com.example.deferred", "Slow").unwrap().append1(21u32);
+        let r = c2.send_with_reply_and_block(m, 2000).unwrap();
+        assert_eq!(r.read1::<u32>().unwrap(), 42);
+        let m = Message::new_method_call(&name, "/deferred", "com.example.deferred", "Forgotten").unwrap();
+        let e = c2.send_with_reply_and_block(m, 2000).unwrap_err();
+        assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.Failed"));
+        tx.send(()).unwrap();
+    });
+    while let Err(mpsc::TryRecvError::Empty) = rx.try_recv() { c.incoming(100).next(); }
+    thread.join().unwrap();
+}
@@ -29,7 +29,7 @@ mod objectpath;
 mod factory;
 
 pub use self::utils::{Argument, Iter};
-pub use self::methodtype::{MethodErr, MethodInfo, PropInfo, MethodResult, MethodType, DataType, MTFn, MTFnMut, MTSync};
+pub use self::methodtype::{MethodErr, MethodInfo, DeferredReply, PropInfo, MethodResult, MethodType, DataType, MTFn, MTFnMut, MTSync};
 pub use self::leaves::{Method, Signal, Property, Access, EmitsChangedSignal};
 pub use self::objectpath::{Interface, ObjectPath, Tree, TreeServer};
 pub use self::factory::Factory;
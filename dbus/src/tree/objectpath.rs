@@ -5,6 +5,7 @@ use {Member, Message, Path, Signature, MessageType, Connection, ConnectionItem,
 use Interface as IfaceName;
 use std::fmt;
 use std::ffi::CStr;
+use std::collections::BTreeSet;
 use super::leaves::prop_append_dict;
 
 fn introspect_map<I: fmt::Display, T: Introspect>
@@ -21,6 +22,19 @@ fn introspect_map<I: fmt::Display, T: Introspect>
     })
 }
 
+fn child_prefix(parent: &str) -> String {
+    if parent.ends_with('/') { parent.into() } else { format!("{}/", parent) }
+}
+
+fn introspect_node<M: MethodType<D>, D: DataType>(name: &str, ifacestr: &str, tree: &Tree<M, D>) -> String {
+    let childstr = tree.child_names(name).iter().fold("".to_string(), |na, n|
+        format!("{}  <node name=\"{}\"/>\n", na, n)
+    );
+    format!(r##"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN" "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node name="{}">
+{}{}</node>"##, name, ifacestr, childstr)
+}
+
 #[derive(Debug)]
 /// Represents a D-Bus interface.
 pub struct Interface<M: MethodType<D>, D: DataType> {
@@ -151,15 +165,7 @@ impl<M: MethodType<D>, D: DataType> ObjectPath<M, D> {
 
     pub(super) fn introspect(&self, tree: &Tree<M, D>) -> String {
         let ifacestr = introspect_map(&self.ifaces, "  ");
-        let olen = self.name.len()+1;
-        let childstr = tree.children(self, true).iter().fold("".to_string(), |na, n|
-            format!("{}  <node name=\"{}\"/>\n", na, &n.name[olen..])
-        );
-
-        let nodestr = format!(r##"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN" "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
-<node name="{}">
-{}{}</node>"##, self.name, ifacestr, childstr);
-        nodestr
+        introspect_node(&self.name, &ifacestr, tree)
     }
 
     fn get_iface<'a>(&'a self, iface_name: &'a CStr) -> Result<&Arc<Interface<M, D>>, MethodErr> {
@@ -167,11 +173,20 @@ impl<M: MethodType<D>, D: DataType> ObjectPath<M, D> {
         self.ifaces.get(&j).ok_or_else(|| MethodErr::no_interface(&j))
     }
 
+    // An empty interface name means that the property can be in any interface.
+    fn get_prop<'a>(&'a self, iface_name: &'a CStr, prop_name: &str) -> Result<(&Arc<Interface<M, D>>, &Property<M, D>), MethodErr> {
+        if iface_name.to_bytes().is_empty() {
+            return self.ifaces.values().filter_map(|i| i.properties.get(prop_name).map(|p| (i, &**p))).next()
+                .ok_or_else(|| MethodErr::no_property(&prop_name))
+        }
+        let iface = try!(self.get_iface(iface_name));
+        let prop = try!(iface.properties.get(prop_name).ok_or_else(|| MethodErr::no_property(&prop_name)));
+        Ok((iface, prop))
+    }
+
     fn prop_get(&self, m: &MethodInfo<M, D>) -> MethodResult {
         let (iname, prop_name): (&CStr, &str) = try!(m.msg.read2());
-        let iface = try!(self.get_iface(iname));
-        let prop: &Property<M, D> = try!(iface.properties.get(&String::from(prop_name))
-            .ok_or_else(|| MethodErr::no_property(&prop_name)));
+        let (iface, prop) = try!(self.get_prop(iname, prop_name));
         try!(prop.can_get());
         let mut mret = m.msg.method_return();
         {
@@ -193,9 +208,7 @@ impl<M: MethodType<D>, D: DataType> ObjectPath<M, D> {
 
     fn prop_set(&self, m: &MethodInfo<M, D>) -> MethodResult {
         let (iname, prop_name): (&CStr, &str) = try!(m.msg.read2());
-        let iface = try!(self.get_iface(iname));
-        let prop: &Property<M, D> = try!(iface.properties.get(&String::from(prop_name))
-            .ok_or_else(|| MethodErr::no_property(&prop_name)));
+        let (iface, prop) = try!(self.get_prop(iname, prop_name));
 
         let mut iter = arg::Iter::new(m.msg);
         iter.next(); iter.next();
@@ -221,16 +234,7 @@ impl<M: MethodType<D>, D: DataType> ObjectPath<M, D> {
                 for p in paths {
                     ii.append_dict_entry(|pi| {
                         pi.append(&*p.name);
-                        pi.append_dict(&Signature::make::<&str>(), &Signature::make::<Dict<&str,Variant<()>,()>>(), |pii| {
-                            for ifaces in p.ifaces.values() {
-                                let m2 = MethodInfo { msg: m.msg, path: p, iface: ifaces, tree: m.tree, method: m.method };
-                                pii.append_dict_entry(|ppii| {
-                                    ppii.append(&**ifaces.name);
-                                    result = prop_append_dict(ppii, ifaces.properties.values().map(|v| &**v), &m2);
-                                });
-                                if result.is_err() { break; }
-                            }
-                        });
+                        result = p.append_ifaces_and_props(pi, m);
                     });
                     if result.is_err() { break; }
                 }
@@ -240,10 +244,32 @@ impl<M: MethodType<D>, D: DataType> ObjectPath<M, D> {
         Ok(vec!(r))
     }
 
+    // Appends a dict of all interfaces of this object path, and their properties.
+    fn append_ifaces_and_props(&self, i: &mut arg::IterAppend, m: &MethodInfo<M, D>) -> Result<(), MethodErr> {
+        use arg::{Dict, Variant};
+        let mut result = Ok(());
+        i.append_dict(&Signature::make::<&str>(), &Signature::make::<Dict<&str,Variant<()>,()>>(), |pii| {
+            for ifaces in self.ifaces.values() {
+                let m2 = MethodInfo { msg: m.msg, path: self, iface: ifaces, tree: m.tree, method: m.method };
+                pii.append_dict_entry(|ppii| {
+                    ppii.append(&**ifaces.name);
+                    result = prop_append_dict(ppii, ifaces.properties.values().map(|v| &**v), &m2);
+                });
+                if result.is_err() { break; }
+            }
+        });
+        result
+    }
+
     fn handle(&self, m: &Message, t: &Tree<M, D>) -> MethodResult {
         let i = try!(m.interface().and_then(|i| self.ifaces.get(&i)).ok_or_else(|| MethodErr::no_interface(&"")));
         let me = try!(m.member().and_then(|me| i.methods.get(&me)).ok_or_else(|| MethodErr::no_method(&"")));
         let minfo = MethodInfo { msg: m, tree: t, path: self, iface: i, method: me };
+        if let Some(ref auth) = t.auth {
+            if !(auth.0)(&minfo) {
+                return Err(MethodErr::access_denied(&format!("{}.{} on {}", &**i.name, &**me.get_name(), &*self.name)))
+            }
+        }
         me.call(&minfo)
     }
 
@@ -271,8 +297,11 @@ where <D as DataType>::Interface: Default, <D as DataType>::Method: Default
 
     /// Adds ObjectManager support for this object path.
     ///
-    /// It is not possible to add/remove interfaces while the object path belongs to a tree,
-    /// hence no InterfacesAdded / InterfacesRemoved signals are sent.
+    /// GetManagedObjects returns this object path and all object paths below it in the tree.
+    /// InterfacesAdded / InterfacesRemoved signals are not sent automatically, use
+    /// `Tree::interfaces_added` and `Tree::interfaces_removed` to create them when you
+    /// insert or remove object paths at runtime. (Like for the built-in Properties interface,
+    /// the signals are not listed in the introspection data.)
     pub fn object_manager(mut self) -> Self {
         use arg::{Variant, Dict};
         let ifname = IfaceName::from("org.freedesktop.DBus.ObjectManager");
@@ -316,11 +345,18 @@ pub fn new_objectpath<M: MethodType<D>, D: DataType>(n: Path<'static>, d: D::Obj
 }
 
 
+// Workaround for https://github.com/rust-lang/rust/issues/31518
+struct DebugAuth<M: MethodType<D>, D: DataType>(Box<Fn(&MethodInfo<M, D>) -> bool + Send + Sync + 'static>);
+impl<M: MethodType<D>, D: DataType> fmt::Debug for DebugAuth<M, D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "<Authorize>") }
+}
+
 /// A collection of object paths.
 #[derive(Debug, Default)]
 pub struct Tree<M: MethodType<D>, D: DataType> {
     paths: ArcMap<Arc<Path<'static>>, ObjectPath<M, D>>,
     data: D::Tree,
+    auth: Option<DebugAuth<M, D>>,
 }
 
 impl<M: MethodType<D>, D: DataType> Tree<M, D> {
@@ -333,6 +369,18 @@ impl<M: MethodType<D>, D: DataType> Tree<M, D> {
         self
     }
 
+    /// Builder function that sets a function to authorize incoming method calls.
+    ///
+    /// The function is called before every method handler (including those of the standard interfaces,
+    /// such as Introspectable and Properties). Use `MethodInfo::msg` to get the sender, path, interface
+    /// and member of the call. If it returns false, an org.freedesktop.DBus.Error.AccessDenied
+    /// error is returned to the caller, and the method handler is not called.
+    pub fn authorize<F>(mut self, f: F) -> Self
+    where F: Fn(&MethodInfo<M, D>) -> bool + Send + Sync + 'static {
+        self.auth = Some(DebugAuth(Box::new(f)));
+        self
+    }
+
     /// Get a reference to an object path from the tree.
     pub fn get(&self, p: &Path<'static>) -> Option<&Arc<ObjectPath<M, D>>> {
         self.paths.get(p)
@@ -394,30 +442,130 @@ impl<M: MethodType<D>, D: DataType> Tree<M, D> {
     /// found in this tree, or otherwise a list of messages to be sent back.
     pub fn handle(&self, m: &Message) -> Option<Vec<Message>> {
         if m.msg_type() != MessageType::MethodCall { None }
-        else { m.path().and_then(|p| self.paths.get(&p).map(|s| s.handle(m, &self)
-            .unwrap_or_else(|e| vec!(e.to_message(m))))) }
+        else { m.path().and_then(|p| match self.paths.get(&p) {
+            Some(s) => Some(s.handle(m, &self).unwrap_or_else(|e| vec!(e.to_message(m)))),
+            None => self.introspect_intermediate(m, &p),
+        }) }
     }
 
 
     fn children(&self, o: &ObjectPath<M, D>, direct_only: bool) -> Vec<&ObjectPath<M, D>> {
-        let parent: &str = &o.name;
-        let plen = parent.len()+1;
-        self.paths.values().filter_map(|v| {
+        let prefix = child_prefix(&o.name);
+        self.paths.values().filter(|v| {
             let k: &str = &v.name;
-            if !k.starts_with(parent) || k.len() <= plen || &k[plen-1..plen] != "/" {None} else {
-                let child = &k[plen..];
-                if direct_only && child.contains("/") {None} else {Some(&**v)}
-            }
+            k.starts_with(&*prefix) && k.len() > prefix.len() && !(direct_only && k[prefix.len()..].contains('/'))
+        }).map(|v| &**v).collect()
+    }
+
+    /// Names of the direct child nodes of a path, including nodes
+    /// that are not in the tree themselves, but have descendants that are.
+    fn child_names(&self, parent: &str) -> BTreeSet<&str> {
+        let prefix = child_prefix(parent);
+        self.paths.keys().filter_map(|k| {
+            let k: &str = &k;
+            if !k.starts_with(&*prefix) || k.len() <= prefix.len() { return None }
+            k[prefix.len()..].split('/').next()
         }).collect()
     }
 
+    // Answers Introspect calls for paths that are not in the tree, but have descendants that are,
+    // so that the whole tree can be browsed from the root.
+    fn introspect_intermediate(&self, m: &Message, p: &Path) -> Option<Vec<Message>> {
+        if m.interface().map(|i| &*i != "org.freedesktop.DBus.Introspectable").unwrap_or(true) { return None }
+        if m.member().map(|i| &*i != "Introspect").unwrap_or(true) { return None }
+        if self.child_names(p).is_empty() { return None }
+        Some(vec!(m.method_return().append1(introspect_node(p, "", self))))
+    }
+
     /// Get associated data
     pub fn get_data(&self) -> &D::Tree { &self.data }
 
+    // The closest object path at or above p that implements the ObjectManager interface.
+    fn object_manager_for(&self, p: &str) -> Option<(&ObjectPath<M, D>, &Interface<M, D>, &Method<M, D>)> {
+        let ifname = IfaceName::from("org.freedesktop.DBus.ObjectManager");
+        let mut p = p;
+        loop {
+            let m = self.paths.get(&Path::from(p.to_string())).and_then(|o| o.ifaces.get(&ifname).and_then(|i|
+                i.methods.get(&Member::from("GetManagedObjects")).map(|m| (&**o, &**i, &**m))));
+            if m.is_some() { return m };
+            if p == "/" { return None };
+            p = match p.rfind('/') { Some(0) => "/", Some(i) => &p[..i], None => return None };
+        }
+    }
+
+    /// Creates an InterfacesAdded signal for an object path in the tree, listing all its
+    /// interfaces and their properties.
+    ///
+    /// Call this after inserting an object path at runtime, and send the signal.
+    /// Returns None if the object path is not in the tree, or if no object path at or
+    /// above it has ObjectManager support (see `ObjectPath::object_manager`).
+    /// The property getters are called with an empty signal of the same kind as message.
+    pub fn interfaces_added(&self, p: &Path<'static>) -> Result<Option<Message>, MethodErr> {
+        let o = if let Some(o) = self.paths.get(p) { o } else { return Ok(None) };
+        let (mgr, iface, method) = if let Some(m) = self.object_manager_for(p) { m } else { return Ok(None) };
+        let new_sig = || Message::signal(&mgr.name, &"org.freedesktop.DBus.ObjectManager".into(), &"InterfacesAdded".into());
+        let (sig, mut r) = (new_sig(), new_sig());
+        {
+            let minfo = MethodInfo { msg: &sig, method: method, iface: iface, path: mgr, tree: self };
+            let mut i = arg::IterAppend::new(&mut r);
+            i.append(p);
+            try!(o.append_ifaces_and_props(&mut i, &minfo));
+        }
+        Ok(Some(r))
+    }
+
+    /// Creates a PropertiesChanged signal for properties whose values have changed on the server side.
+    ///
+    /// This is also how the signal is created when a property is set through the Properties interface
+    /// (see `Property::auto_emit_on_set`); call it yourself, and send the signal, for all other changes.
+    ///
+    /// Each property is included according to its EmitsChangedSignal setting: with its new value
+    /// (as returned by its getter, unless it is write-only), as invalidated, or not at all.
+    /// Returns None if the object path is not in the tree, or none of the properties should be included.
+    /// The property getters are called with an empty signal of the same kind as message.
+    pub fn properties_changed(&self, p: &Path<'static>, iface: &IfaceName<'static>, props: &[&str]) -> Result<Option<Message>, MethodErr> {
+        use super::EmitsChangedSignal;
+        let o = if let Some(o) = self.paths.get(p) { o } else { return Ok(None) };
+        let i = try!(o.ifaces.get(iface).ok_or_else(|| MethodErr::no_interface(iface)));
+        let get = try!(o.ifaces.get(&IfaceName::from("org.freedesktop.DBus.Properties"))
+            .and_then(|pi| pi.methods.get(&Member::from("Get"))).ok_or_else(|| MethodErr::no_interface(&"org.freedesktop.DBus.Properties")));
+        let (mut changed, mut invalidated) = (vec!(), vec!());
+        for name in props {
+            let prop = try!(i.properties.get(*name).ok_or_else(|| MethodErr::no_property(name)));
+            match prop.get_emits_changed() {
+                EmitsChangedSignal::True => changed.push(&**prop),
+                EmitsChangedSignal::Invalidates => invalidated.push(*name),
+                EmitsChangedSignal::False | EmitsChangedSignal::Const => {},
+            }
+        }
+        if changed.is_empty() && invalidated.is_empty() { return Ok(None) }
+
+        let new_sig = || Message::signal(p, &"org.freedesktop.DBus.Properties".into(), &"PropertiesChanged".into());
+        let (sig, mut r) = (new_sig(), new_sig());
+        {
+            let minfo = MethodInfo { msg: &sig, method: get, iface: i, path: o, tree: self };
+            let mut ia = arg::IterAppend::new(&mut r);
+            ia.append(&**iface);
+            try!(prop_append_dict(&mut ia, changed.into_iter(), &minfo));
+            ia.append(arg::Array::<&str, _>::new(invalidated));
+        }
+        Ok(Some(r))
+    }
+
+    /// Creates an InterfacesRemoved signal for an object path, listing all its interfaces.
+    ///
+    /// Call this with the object path returned from `Tree::remove`, and send the signal.
+    /// Returns None if no object path above it has ObjectManager support.
+    pub fn interfaces_removed(&self, o: &ObjectPath<M, D>) -> Option<Message> {
+        self.object_manager_for(&o.name).map(|(mgr, _, _)| {
+            Message::signal(&mgr.name, &"org.freedesktop.DBus.ObjectManager".into(), &"InterfacesRemoved".into())
+                .append2(&*o.name, arg::Array::<&str, _>::new(o.ifaces.keys().map(|i| &***i)))
+        })
+    }
 }
 
 pub fn new_tree<M: MethodType<D>, D: DataType>(d: D::Tree) -> Tree<M, D> {
-    Tree { paths: ArcMap::new(), data: d }
+    Tree { paths: ArcMap::new(), data: d, auth: None }
 }
 
 impl<M: MethodType<D>, D: DataType> MsgHandler for Tree<M, D> {
@@ -534,3 +682,111 @@ fn test_introspection() {
     assert_eq!(expected_result, actual_result);   
 }
 
+
+#[test]
+fn test_introspect_intermediate() {
+    let f = super::Factory::new_fn::<()>();
+    let t = f.tree(())
+        .add(f.object_path("/", ()).introspectable())
+        .add(f.object_path("/com/example/a", ()).introspectable()
+            .add(f.interface("com.example.a", ()).annotate("com.example.Note", "\"quoted\" & <escaped>")))
+        .add(f.object_path("/com/example/b", ()).introspectable());
+
+    let mut msg = Message::new_method_call("com.example", "/", "org.freedesktop.DBus.Introspectable", "Introspect").unwrap();
+    ::message::message_set_serial(&mut msg, 4);
+    let xml: String = t.handle(&msg).unwrap()[0].read1().unwrap();
+    assert!(xml.contains(r#"<node name="com"/>"#));
+
+    let mut msg = Message::new_method_call("com.example", "/com/example", "org.freedesktop.DBus.Introspectable", "Introspect").unwrap();
+    ::message::message_set_serial(&mut msg, 4);
+    let xml: String = t.handle(&msg).unwrap()[0].read1().unwrap();
+    assert!(xml.contains(r#"<node name="/com/example">"#));
+    assert!(xml.contains("  <node name=\"a\"/>\n  <node name=\"b\"/>\n</node>"));
+
+    let mut msg = Message::new_method_call("com.example", "/com/example/a", "org.freedesktop.DBus.Introspectable", "Introspect").unwrap();
+    ::message::message_set_serial(&mut msg, 4);
+    let xml: String = t.handle(&msg).unwrap()[0].read1().unwrap();
+    assert!(xml.contains(r#"value="&quot;quoted&quot; &amp; &lt;escaped&gt;""#));
+
+    let mut msg = Message::new_method_call("com.example", "/org", "org.freedesktop.DBus.Introspectable", "Introspect").unwrap();
+    ::message::message_set_serial(&mut msg, 4);
+    assert!(t.handle(&msg).is_none());
+}
+
+#[test]
+fn test_interfaces_added_removed() {
+    let f = super::Factory::new_fn::<()>();
+    let mut t = f.tree(()).add(f.object_path("/", ()).object_manager());
+    t.insert(f.object_path("/com/example/dev0", ()).introspectable()
+        .add(f.interface("com.example.Device", ()).add_p(f.property::<u32,_>("Power", ())
+            .on_get(|i, _| { i.append(5u32); Ok(()) }))));
+
+    let s = t.interfaces_added(&"/com/example/dev0".into()).unwrap().unwrap();
+    assert_eq!(&*s.path().unwrap(), "/");
+    let a: ::stdintf::org_freedesktop_dbus::ObjectManagerInterfacesAdded = ::SignalArgs::from_message(&s).unwrap();
+    assert_eq!(&*a.object, "/com/example/dev0");
+    let props = a.interfaces.get("com.example.Device").unwrap();
+    assert_eq!(props.get("Power").unwrap().0.as_i64(), Some(5));
+    assert!(a.interfaces.contains_key("org.freedesktop.DBus.Introspectable"));
+
+    let mut msg = Message::new_method_call("com.example", "/", "org.freedesktop.DBus.ObjectManager", "GetManagedObjects").unwrap();
+    ::message::message_set_serial(&mut msg, 4);
+    let r = t.handle(&msg).unwrap();
+    let objs: ::std::collections::HashMap<Path, ::std::collections::HashMap<String, ::std::collections::HashMap<String, arg::Variant<Box<arg::RefArg>>>>> = r[0].read1().unwrap();
+    assert!(objs.contains_key(&Path::from("/com/example/dev0")));
+
+    let o = t.remove(&"/com/example/dev0".into()).unwrap();
+    let s = t.interfaces_removed(&o).unwrap();
+    let r: ::stdintf::org_freedesktop_dbus::ObjectManagerInterfacesRemoved = ::SignalArgs::from_message(&s).unwrap();
+    assert_eq!(r.interfaces, vec!("com.example.Device", "org.freedesktop.DBus.Introspectable", "org.freedesktop.DBus.Properties"));
+    assert!(t.interfaces_added(&"/com/example/dev0".into()).unwrap().is_none());
+}
+
+#[test]
+fn test_properties_changed() {
+    use super::{Access, EmitsChangedSignal};
+    let f = super::Factory::new_fn::<()>();
+    let t = f.tree(()).add(f.object_path("/test", ())
+        .add(f.interface("com.example.test", ())
+            .add_p(f.property::<u32,_>("Value", ()).on_get(|i, _| { i.append(7u32); Ok(()) }))
+            .add_p(f.property::<u32,_>("Big", ()).emits_changed(EmitsChangedSignal::Invalidates)
+                .on_get(|i, _| { i.append(8u32); Ok(()) }))
+            .add_p(f.property::<u32,_>("Quiet", ()).access(Access::ReadWrite).emits_changed(EmitsChangedSignal::False)
+                .on_get(|i, _| { i.append(9u32); Ok(()) }))
+            .add_p(f.property::<u32,_>("Secret", ()).access(Access::Write))
+        ));
+    let (p, i) = ("/test".into(), "com.example.test".into());
+    let s = t.properties_changed(&p, &i, &["Value", "Big", "Quiet"]).unwrap().unwrap();
+    let c: ::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged = ::SignalArgs::from_message(&s).unwrap();
+    assert_eq!(&*c.interface_name, "com.example.test");
+    assert_eq!(c.changed_properties.len(), 1);
+    assert_eq!(c.changed_properties.get("Value").unwrap().0.as_i64(), Some(7));
+    assert_eq!(c.invalidated_properties, vec!("Big"));
+
+    assert!(t.properties_changed(&p, &i, &["Quiet"]).unwrap().is_none());
+    let s = t.properties_changed(&p, &i, &["Secret"]).unwrap().unwrap();
+    let c: ::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged = ::SignalArgs::from_message(&s).unwrap();
+    assert!(c.changed_properties.is_empty());
+    assert!(t.properties_changed(&p, &i, &["Nonexistent"]).is_err());
+}
+
+#[test]
+fn test_authorize() {
+    let f = super::Factory::new_sync::<()>();
+    let t = f.tree(()).add(f.object_path("/auth", ()).introspectable()
+        .add(f.interface("com.example.auth", ())
+            .add_m(f.method("Public", (), |m| Ok(vec!(m.msg.method_return()))))
+            .add_m(f.method("Secret", (), |m| Ok(vec!(m.msg.method_return()))))))
+        .authorize(|m| m.msg.sender().map(|s| &*s == ":1.42").unwrap_or(false) || &**m.method.get_name() != "Secret");
+
+    let call = |member: &str, sender: &str| {
+        let mut msg = Message::new_method_call("com.example", "/auth", "com.example.auth", member).unwrap();
+        ::message::message_set_serial(&mut msg, 4);
+        msg.set_sender(Some(sender.into()));
+        let mut r = t.handle(&msg).unwrap();
+        r[0].as_result().map(|_| ()).map_err(|e| e.name().unwrap().to_string())
+    };
+    assert_eq!(call("Public", ":1.1"), Ok(()));
+    assert_eq!(call("Secret", ":1.42"), Ok(()));
+    assert_eq!(call("Secret", ":1.1"), Err("org.freedesktop.DBus.Error.AccessDenied".into()));
+}
@@ -47,12 +47,27 @@ impl Argument {
     pub fn signature(&self) -> &Signature<'static> { &self.1 }
 
     fn introspect(&self, indent: &str, dir: &str) -> String { 
-        let n = self.0.as_ref().map(|n| format!("name=\"{}\" ", n)).unwrap_or("".into());
+        let n = self.0.as_ref().map(|n| format!("name=\"{}\" ", xml_escape(n))).unwrap_or("".into());
         format!("{}<arg {}type=\"{}\"{}/>\n", indent, n, self.1, dir)
     }
 
 }
 
+/// Escapes characters that are not allowed in XML attribute values.
+pub fn xml_escape(s: &str) -> String {
+    s.chars().fold(String::with_capacity(s.len()), |mut r, c| {
+        match c {
+            '&' => r.push_str("&amp;"),
+            '<' => r.push_str("&lt;"),
+            '>' => r.push_str("&gt;"),
+            '"' => r.push_str("&quot;"),
+            '\'' => r.push_str("&apos;"),
+            _ => r.push(c),
+        };
+        r
+    })
+}
+
 pub fn introspect_args(args: &[Argument], indent: &str, dir: &str) -> String {
     args.iter().fold("".to_string(), |aa, az| format!("{}{}", aa, az.introspect(indent, dir)))
 }
@@ -71,7 +86,7 @@ impl Annotations {
 
     pub fn introspect(&self, indent: &str) -> String {
         self.0.as_ref().map(|s| s.iter().fold("".into(), |aa, (ak, av)| {
-            format!("{}{}<annotation name=\"{}\" value=\"{}\"/>\n", aa, indent, ak, av)
+            format!("{}{}<annotation name=\"{}\" value=\"{}\"/>\n", aa, indent, xml_escape(ak), xml_escape(av))
         })).unwrap_or(String::new())
     }
 }
@@ -0,0 +1,90 @@
+use {Error, ffi, libc};
+use std::fmt;
+use std::os::raw::{c_char, c_int, c_void};
+
+/// The version of libdbus loaded at runtime, see `version`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    /// Major version, e g 1 for 1.12.16.
+    pub major: u32,
+    /// Minor version, e g 12 for 1.12.16.
+    pub minor: u32,
+    /// Micro version, e g 16 for 1.12.16.
+    pub micro: u32,
+}
+
+impl Version {
+    /// Creates a version to compare against, e g `version() >= Version::new(1, 12, 0)`.
+    pub fn new(major: u32, minor: u32, micro: u32) -> Version {
+        Version { major: major, minor: minor, micro: micro }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.micro)
+    }
+}
+
+/// Returns the version of libdbus this process is running against.
+///
+/// This can be older than the version this crate was built against, when a binary is
+/// copied to another system. Functionality that needs a newer libdbus than the oldest supported
+/// one (1.6) checks this at runtime and returns an `ErrorKind::NotSupported` error, instead of
+/// failing to load.
+///
+/// Passing file descriptors is not gated by version, as all supported versions can do it; whether
+/// a connection can pass them depends on its transport, see `Connection::can_send_type`.
+pub fn version() -> Version {
+    let (mut major, mut minor, mut micro): (c_int, c_int, c_int) = (0, 0, 0);
+    unsafe { ffi::dbus_get_version(&mut major, &mut minor, &mut micro) };
+    Version::new(major as u32, minor as u32, micro as u32)
+}
+
+/// Returns an error if the running libdbus is older than `min`, which is needed for `what`.
+pub (crate) fn require(min: Version, what: &str) -> Result<(), Error> {
+    let v = version();
+    if v >= min { return Ok(()) };
+    Err(Error::new_custom("org.freedesktop.DBus.Error.NotSupported",
+        &format!("{} requires libdbus {} or later, but {} is loaded", what, min, v)))
+}
+
+/// Looks up a libdbus function that is new in libdbus `min`, which is needed for `what`.
+///
+/// Returns a NotSupported error if the function cannot be found, either because the running
+/// libdbus is too old, or because it is not visible to `dlsym` (e g a statically linked libdbus).
+pub (crate) fn require_fn(name: &'static [u8], min: Version, what: &str) -> Result<*mut c_void, Error> {
+    if let Some(f) = optional_fn(name) { return Ok(f) };
+    try!(require(min, what));
+    Err(Error::new_custom("org.freedesktop.DBus.Error.NotSupported",
+        &format!("{} requires {} from libdbus {} or later, which cannot be found", what,
+        String::from_utf8_lossy(&name[..name.len()-1]), min)))
+}
+
+/// Looks up a libdbus function that is newer than libdbus 1.6.
+///
+/// Calling these directly would make the binary fail to load against an older libdbus,
+/// so they are resolved at runtime instead. `name` must be nul terminated.
+pub (crate) fn optional_fn(name: &'static [u8]) -> Option<*mut c_void> {
+    debug_assert_eq!(name.last(), Some(&0));
+    let p = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr() as *const c_char) };
+    if p.is_null() { None } else { Some(p) }
+}
+
+#[test]
+fn runtime_version() {
+    use ErrorKind;
+    let v = version();
+    assert!(v >= Version::new(1, 6, 0));
+    assert_eq!(format!("{}", Version::new(1, 12, 2)), "1.12.2");
+    assert!(require(v, "Something").is_ok());
+    let e = require(Version::new(v.major + 1, 0, 0), "Something").unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::NotSupported);
+    assert!(e.message().unwrap().starts_with("Something requires libdbus"));
+    assert!(optional_fn(b"dbus_get_version\0").is_some());
+    assert!(optional_fn(b"dbus_no_such_function\0").is_none());
+    assert!(require_fn(b"dbus_get_version\0", Version::new(1, 6, 0), "Something").is_ok());
+    let e = require_fn(b"dbus_no_such_function\0", Version::new(1, 6, 0), "Something").unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::NotSupported);
+    assert_eq!(e.message(), Some("Something requires dbus_no_such_function from libdbus 1.6.0 or later, which cannot be found"));
+}
@@ -10,7 +10,7 @@ use std::os::raw::{c_void, c_uint};
 /// A file descriptor to watch for incoming events (for async I/O).
 ///
 /// # Example
-/// ```
+/// ```rust,no_run
 /// extern crate libc;
 /// extern crate dbus;
 /// fn main() {
@@ -197,14 +197,97 @@ extern "C" fn toggled_watch_cb(watch: *mut ffi::DBusWatch, data: *mut c_void) {
     wlist.update(watch);
 }
 
+/// A timeout that libdbus wants to be called back for (for async I/O).
+///
+/// When the timeout is enabled, call `Connection::timeout_handle` after `interval_ms`
+/// milliseconds have passed. Timeouts are used for, e g, method calls that do not get a reply in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WatchTimeout {
+    id: usize,
+    interval_ms: i32,
+    enabled: bool,
+}
+
+impl WatchTimeout {
+    /// An identifier for this timeout, unique as long as the timeout exists.
+    pub fn id(&self) -> usize { self.id }
+    /// How long, in milliseconds, until the timeout should be handled.
+    pub fn interval_ms(&self) -> i32 { self.interval_ms }
+    /// If false, the timeout has been disabled or removed and should not be handled.
+    pub fn enabled(&self) -> bool { self.enabled }
+
+    unsafe fn from_raw(t: *mut ffi::DBusTimeout, exists: bool) -> Self {
+        WatchTimeout { id: t as usize, interval_ms: ffi::dbus_timeout_get_interval(t),
+            enabled: exists && ffi::dbus_timeout_get_enabled(t) != 0 }
+    }
+}
+
+/// Note - internal struct, not to be used outside API. Moving it outside its box will break things.
+pub struct TimeoutList {
+    timeouts: RwLock<Vec<*mut ffi::DBusTimeout>>,
+    on_update: Mutex<Box<Fn(WatchTimeout) + Send>>,
+}
+
+impl TimeoutList {
+    pub fn new(c: &Connection, on_update: Box<Fn(WatchTimeout) + Send>) -> Box<TimeoutList> {
+        let t = Box::new(TimeoutList { on_update: Mutex::new(on_update), timeouts: RwLock::new(vec!()) });
+        if unsafe { ffi::dbus_connection_set_timeout_functions(super::connection::conn_handle(c),
+            Some(add_timeout_cb), Some(remove_timeout_cb), Some(toggled_timeout_cb), &*t as *const _ as *mut _, None) } == 0 {
+            panic!("dbus_connection_set_timeout_functions failed");
+        }
+        t
+    }
+
+    pub fn set_on_update(&self, on_update: Box<Fn(WatchTimeout) + Send>) { *self.on_update.lock().unwrap() = on_update; }
+
+    pub fn timeout_handle(&self, id: usize) {
+        let t = self.timeouts.read().unwrap().iter().cloned().find(|&t| t as usize == id);
+        if let Some(t) = t {
+            // Might call remove_timeout_cb, so the lock must not be held here.
+            if unsafe { ffi::dbus_timeout_get_enabled(t) } != 0 {
+                unsafe { ffi::dbus_timeout_handle(t) };
+            }
+        }
+    }
+
+    pub fn get_enabled_timeouts(&self) -> Vec<WatchTimeout> {
+        self.timeouts.read().unwrap().iter().map(|&t| unsafe { WatchTimeout::from_raw(t, true) })
+            .filter(|t| t.enabled).collect()
+    }
+
+    fn update(&self, t: WatchTimeout) {
+        let func = self.on_update.lock().unwrap();
+        (*func)(t);
+    }
+}
+
+extern "C" fn add_timeout_cb(timeout: *mut ffi::DBusTimeout, data: *mut c_void) -> u32 {
+    let tlist: &TimeoutList = unsafe { mem::transmute(data) };
+    tlist.timeouts.write().unwrap().push(timeout);
+    tlist.update(unsafe { WatchTimeout::from_raw(timeout, true) });
+    1
+}
+
+extern "C" fn remove_timeout_cb(timeout: *mut ffi::DBusTimeout, data: *mut c_void) {
+    let tlist: &TimeoutList = unsafe { mem::transmute(data) };
+    tlist.timeouts.write().unwrap().retain(|t| *t != timeout);
+    tlist.update(unsafe { WatchTimeout::from_raw(timeout, false) });
+}
+
+extern "C" fn toggled_timeout_cb(timeout: *mut ffi::DBusTimeout, data: *mut c_void) {
+    let tlist: &TimeoutList = unsafe { mem::transmute(data) };
+    tlist.update(unsafe { WatchTimeout::from_raw(timeout, true) });
+}
+
 #[cfg(test)]
 mod test {
     use libc;
-    use super::super::{Connection, Message, BusType, WatchEvent, ConnectionItem, MessageType};
+    use super::super::{Message, WatchEvent, ConnectionItem, MessageType};
 
     #[test]
     fn async() {
-        let c = Connection::get_private(BusType::Session).unwrap();
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
         c.register_object_path("/test").unwrap();
         let m = Message::new_method_call(&c.unique_name(), "/test", "com.example.asynctest", "AsyncTest").unwrap();
         let serial = c.send(m).unwrap();
@@ -233,13 +316,13 @@ mod test {
                             assert_eq!(m.headers(), (MessageType::MethodCall, Some("/test".to_string()),
                                 Some("com.example.asynctest".into()), Some("AsyncTest".to_string())));
                             let mut mr = Message::new_method_return(&m).unwrap();
-                            mr.append_items(&["Goodies".into()]);
+                            mr.append_items(&["Goodies".into()]).unwrap();
                             c.send(mr).unwrap();
                         }
                         ConnectionItem::MethodReturn(m) => {
                             assert_eq!(m.headers().0, MessageType::MethodReturn);
                             assert_eq!(m.get_reply_serial().unwrap(), serial);
-                            let i = m.get_items();
+                            let i = m.get_items().unwrap();
                             let s: &str = i[0].inner().unwrap();
                             assert_eq!(s, "Goodies");
                             success = true;
@@ -251,4 +334,33 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn timeout() {
+        use std::{thread, time};
+        use std::sync::{Arc, Mutex};
+        let bus = ::testing::shared_bus();
+        let c = bus.connect().unwrap();
+        let updates = Arc::new(Mutex::new(vec!()));
+        let updates2 = updates.clone();
+        c.set_timeout_callback(Box::new(move |t| updates2.lock().unwrap().push(t)));
+        c.register_object_path("/timeouttest").unwrap();
+
+        // Nobody will reply to this method call, so it will time out.
+        let m = Message::new_method_call(&c.unique_name(), "/timeouttest", "com.example.timeouttest", "Never").unwrap();
+        let p = c.send_with_pending_call(m, 100).unwrap();
+        let t = c.watch_timeouts();
+        assert_eq!(t.len(), 1);
+        assert_eq!(t[0].interval_ms(), 100);
+        assert!(t[0].enabled());
+        assert_eq!(&*updates.lock().unwrap(), &t);
+
+        thread::sleep(time::Duration::from_millis(150));
+        for _ in c.timeout_handle(&t[0]) {}
+        assert!(p.completed());
+        let e = p.steal_reply().unwrap().as_result().err().unwrap();
+        assert_eq!(e.name(), Some("org.freedesktop.DBus.Error.NoReply"));
+        assert_eq!(c.watch_timeouts().len(), 0);
+        assert!(!updates.lock().unwrap().last().unwrap().enabled());
+    }
 }
@@ -0,0 +1,51 @@
+//! Logging of sent and received messages, enabled with the `log` feature.
+
+use Message;
+
+// The target of all log records for message traffic.
+const TARGET: &'static str = "dbus::wire";
+
+pub fn log_msg(dir: &str, m: &Message) {
+    if !log_enabled!(target: TARGET, ::log::Level::Debug) { return }
+    debug!(target: TARGET, "{} {:?} serial={} reply_serial={} path={} interface={} member={} signature={}", dir, m.msg_type(),
+        m.get_serial(), m.get_reply_serial().unwrap_or(0), m.path().as_ref().map(|x| &**x).unwrap_or(""),
+        m.interface().as_ref().map(|x| &**x).unwrap_or(""), m.member().as_ref().map(|x| &**x).unwrap_or(""), m.signature());
+}
+
+#[cfg(test)]
+mod test {
+    use log::{self, Log, Record, Metadata};
+    use std::sync::Mutex;
+
+    struct TestLogger(Mutex<Vec<String>>);
+
+    impl Log for TestLogger {
+        fn enabled(&self, m: &Metadata) -> bool { m.target() == super::TARGET }
+        fn log(&self, r: &Record) { if self.enabled(r.metadata()) { self.0.lock().unwrap().push(r.args().to_string()) } }
+        fn flush(&self) {}
+    }
+
+    static LOGGER: TestLogger = TestLogger(Mutex::new(Vec::new()));
+
+    #[test]
+    fn log_traffic() {
+        let bus = ::testing::shared_bus();
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+        let c = bus.connect().unwrap();
+        assert!(c.log_traffic());
+        c.call_method("org.freedesktop.DBus", "/com/example/wirelog", "org.freedesktop.DBus.Peer", "Ping", (), 2000).unwrap();
+        let records = LOGGER.0.lock().unwrap().clone();
+        let sent = records.iter().find(|r| r.contains("path=/com/example/wirelog")).unwrap();
+        assert!(sent.starts_with("sent MethodCall serial="));
+        assert!(sent.ends_with("interface=org.freedesktop.DBus.Peer member=Ping signature="));
+        let serial = sent.split(' ').nth(2).unwrap().trim_start_matches("serial=");
+        let reply = format!("reply_serial={} ", serial);
+        assert!(records.iter().any(|r| r.starts_with("received MethodReturn") && r.contains(&reply)));
+
+        c.set_log_traffic(false);
+        LOGGER.0.lock().unwrap().clear();
+        c.call_method("org.freedesktop.DBus", "/com/example/wirelog", "org.freedesktop.DBus.Peer", "Ping", (), 2000).unwrap();
+        assert!(!LOGGER.0.lock().unwrap().iter().any(|r| r.contains("path=/com/example/wirelog")));
+    }
+}
@@ -1,7 +1,50 @@
 extern crate pkg_config;
 
+use std::env;
+
+// Looks up an environment variable, preferring the target specific variant, e g
+// DBUS_LIB_DIR_armv7_unknown_linux_gnueabihf over DBUS_LIB_DIR.
+fn target_var(name: &str, target: &str) -> Option<String> {
+    let specific = format!("{}_{}", name, target.replace('-', "_"));
+    println!("cargo:rerun-if-env-changed={}", specific);
+    println!("cargo:rerun-if-env-changed={}", name);
+    env::var(&specific).or_else(|_| env::var(name)).ok().and_then(|v| if v.is_empty() { None } else { Some(v) })
+}
+
 fn main() {
+    let target = env::var("TARGET").unwrap();
+    let cross = env::var("HOST").unwrap() != target;
+
+    // An explicit location overrides pkg-config, e g for a target rootfs without a usable .pc file.
+    if let Some(lib_dir) = target_var("DBUS_LIB_DIR", &target) {
+        println!("cargo:rustc-link-search=native={}", lib_dir);
+        println!("cargo:rustc-link-lib=dbus-1");
+        if let Some(include_dir) = target_var("DBUS_INCLUDE_DIR", &target) {
+            println!("cargo:include={}", include_dir);
+        }
+        return;
+    }
+
+    // When cross compiling, pkg-config must be pointed at the target's libraries (with
+    // PKG_CONFIG_SYSROOT_DIR and/or PKG_CONFIG_PATH), or it will find the host's libdbus.
+    // The pkg-config crate refuses to run unless this is done or PKG_CONFIG_ALLOW_CROSS is set;
+    // the latter alone is the case where the host library would be silently linked.
+    let sysroot = target_var("PKG_CONFIG_SYSROOT_DIR", &target).is_some();
+    let path = target_var("PKG_CONFIG_PATH", &target).is_some() || target_var("PKG_CONFIG_LIBDIR", &target).is_some();
+    if cross && !sysroot && !path {
+        println!("cargo:warning=Cross compiling for {} without PKG_CONFIG_SYSROOT_DIR or PKG_CONFIG_PATH set; \
+            pkg-config may find the host's libdbus. Set those, or DBUS_LIB_DIR, to use the target's.", target);
+    }
+
     // See https://github.com/joshtriplett/metadeps/issues/9 for why we don't use
     // metadeps here, but instead keep this manually in sync with Cargo.toml.
-    pkg_config::Config::new().atleast_version("1.6").probe("dbus-1").unwrap();
+    match pkg_config::Config::new().atleast_version("1.6").probe("dbus-1") {
+        Ok(lib) => if let Ok(inc) = env::join_paths(&lib.include_paths) {
+            println!("cargo:include={}", inc.to_string_lossy());
+        },
+        Err(e) => panic!("Could not find libdbus with pkg-config: {}\n\
+            Install the libdbus development package (e g libdbus-1-dev), or set DBUS_LIB_DIR \
+            (and optionally DBUS_INCLUDE_DIR) to where libdbus-1.so is.{}", e,
+            if cross { " When cross compiling, point PKG_CONFIG_SYSROOT_DIR and PKG_CONFIG_PATH at the target's rootfs." } else { "" }),
+    }
 }
@@ -1,10 +1,13 @@
-use std::os::raw::{c_void, c_char, c_uint, c_int, c_long};
+use std::os::raw::{c_void, c_char, c_uint, c_int, c_long, c_ulong};
 
 pub type DBusConnection = c_void;
 pub type DBusMessage = c_void;
 pub type DBusWatch = c_void;
 pub type DBusPendingCall = c_void;
 pub type DBusTimeout = c_void;
+pub type DBusPreallocatedSend = c_void;
+pub type DBusAddressEntry = c_void;
+pub type DBusServer = c_void;
 
 #[repr(C)]
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -138,6 +141,8 @@ pub type DBusPendingCallNotifyFunction = Option<extern fn(pending: *mut DBusPend
 
 pub type DBusFreeFunction = Option<extern fn(memory: *mut c_void)>;
 
+pub type DBusNewConnectionFunction = Option<extern fn(server: *mut DBusServer, new_connection: *mut DBusConnection, data: *mut c_void)>;
+
 #[repr(C)]
 pub struct DBusObjectPathVTable {
     pub unregister_function: Option<extern fn(conn: *mut DBusConnection, user_data: *mut c_void)>,
@@ -149,6 +154,7 @@ pub struct DBusObjectPathVTable {
 }
 
 extern "C" {
+    pub fn dbus_bus_get(t: DBusBusType, error: *mut DBusError) -> *mut DBusConnection;
     pub fn dbus_bus_get_private(t: DBusBusType, error: *mut DBusError) -> *mut DBusConnection;
     pub fn dbus_bus_get_unique_name(conn: *mut DBusConnection) -> *const c_char;
     pub fn dbus_bus_request_name(conn: *mut DBusConnection, name: *const c_char,
@@ -164,8 +170,10 @@ extern "C" {
     pub fn dbus_connection_close(conn: *mut DBusConnection);
     pub fn dbus_connection_dispatch(conn: *mut DBusConnection) -> DBusDispatchStatus;
     pub fn dbus_connection_flush(conn: *mut DBusConnection);
+    pub fn dbus_connection_open(address: *const c_char, error: *mut DBusError) -> *mut DBusConnection;
     pub fn dbus_connection_open_private(address: *const c_char, error: *mut DBusError) -> *mut DBusConnection;
     pub fn dbus_connection_unref(conn: *mut DBusConnection);
+    pub fn dbus_connection_ref(conn: *mut DBusConnection) -> *mut DBusConnection;
     pub fn dbus_connection_get_is_connected(conn: *mut DBusConnection) -> u32;
     pub fn dbus_connection_set_exit_on_disconnect(conn: *mut DBusConnection, enable: u32);
     pub fn dbus_connection_send_with_reply_and_block(conn: *mut DBusConnection,
@@ -174,9 +182,20 @@ extern "C" {
         message: *mut DBusMessage, pending_return: *mut *mut DBusPendingCall, timeout_milliseconds: c_int) -> u32;
     pub fn dbus_connection_send(conn: *mut DBusConnection,
         message: *mut DBusMessage, serial: *mut u32) -> u32;
+    pub fn dbus_connection_preallocate_send(conn: *mut DBusConnection) -> *mut DBusPreallocatedSend;
+    pub fn dbus_connection_free_preallocated_send(conn: *mut DBusConnection, preallocated: *mut DBusPreallocatedSend);
+    pub fn dbus_connection_send_preallocated(conn: *mut DBusConnection, preallocated: *mut DBusPreallocatedSend,
+        message: *mut DBusMessage, serial: *mut u32);
     pub fn dbus_connection_read_write_dispatch(conn: *mut DBusConnection,
         timeout_milliseconds: c_int) -> u32;
     pub fn dbus_connection_read_write(conn: *mut DBusConnection, timeout_milliseconds: c_int) -> u32;
+    pub fn dbus_connection_get_unix_fd(conn: *mut DBusConnection, fd: *mut c_int) -> u32;
+    pub fn dbus_connection_can_send_type(conn: *mut DBusConnection, t: c_int) -> u32;
+    pub fn dbus_connection_get_server_id(conn: *mut DBusConnection) -> *mut c_char;
+    pub fn dbus_connection_get_is_authenticated(conn: *mut DBusConnection) -> u32;
+    pub fn dbus_connection_get_is_anonymous(conn: *mut DBusConnection) -> u32;
+    pub fn dbus_connection_get_unix_user(conn: *mut DBusConnection, uid: *mut c_ulong) -> u32;
+    pub fn dbus_connection_get_unix_process_id(conn: *mut DBusConnection, pid: *mut c_ulong) -> u32;
     pub fn dbus_connection_try_register_object_path(conn: *mut DBusConnection,
         path: *const c_char, vtable: *const DBusObjectPathVTable, user_data: *mut c_void,
         error: *mut DBusError) -> u32;
@@ -198,7 +217,15 @@ extern "C" {
         dispatch_function: DBusDispatchStatusFunction, data: *mut c_void, free_data_function: DBusFreeFunction);
     pub fn dbus_connection_set_wakeup_main_function(conn: *mut DBusConnection,
         wakeup_function: DBusWakeupMainFunction, data: *mut c_void, free_data_function: DBusFreeFunction);
+    pub fn dbus_connection_allocate_data_slot(slot_p: *mut i32) -> u32;
+    pub fn dbus_connection_free_data_slot(slot_p: *mut i32);
+    pub fn dbus_connection_set_data(conn: *mut DBusConnection, slot: i32, data: *mut c_void,
+        free_data_func: DBusFreeFunction) -> u32;
+    pub fn dbus_connection_get_data(conn: *mut DBusConnection, slot: i32) -> *mut c_void;
     pub fn dbus_connection_pop_message(conn: *mut DBusConnection) -> *mut DBusMessage;
+    pub fn dbus_connection_borrow_message(conn: *mut DBusConnection) -> *mut DBusMessage;
+    pub fn dbus_connection_return_message(conn: *mut DBusConnection, message: *mut DBusMessage);
+    pub fn dbus_connection_steal_borrowed_message(conn: *mut DBusConnection, message: *mut DBusMessage);
     pub fn dbus_connection_get_dispatch_status(conn: *mut DBusConnection) -> DBusDispatchStatus;
 
     pub fn dbus_error_init(error: *mut DBusError);
@@ -208,6 +235,7 @@ extern "C" {
 
     pub fn dbus_message_new_method_call(destination: *const c_char, path: *const c_char,
         iface: *const c_char, method: *const c_char) -> *mut DBusMessage;
+    pub fn dbus_message_new(message_type: c_int) -> *mut DBusMessage;
     pub fn dbus_message_new_method_return(message: *mut DBusMessage) -> *mut DBusMessage;
     pub fn dbus_message_new_error(message: *mut DBusMessage,
         error_name: *const c_char, error_message: *const c_char) -> *mut DBusMessage;
@@ -219,18 +247,24 @@ extern "C" {
     pub fn dbus_message_is_method_call(message: *mut DBusMessage, iface: *const c_char, method: *const c_char) -> u32;
     pub fn dbus_message_is_signal(message: *mut DBusMessage, iface: *const c_char, signal_name: *const c_char) -> u32;
     pub fn dbus_message_get_reply_serial(message: *mut DBusMessage) -> u32;
+    pub fn dbus_message_set_reply_serial(message: *mut DBusMessage, reply_serial: u32) -> u32;
     pub fn dbus_message_get_serial(message: *mut DBusMessage) -> u32;
     pub fn dbus_message_get_path(message: *mut DBusMessage) -> *const c_char;
     pub fn dbus_message_get_interface(message: *mut DBusMessage) -> *const c_char;
     pub fn dbus_message_get_destination(message: *mut DBusMessage) -> *const c_char;
     pub fn dbus_message_get_member(message: *mut DBusMessage) -> *const c_char;
     pub fn dbus_message_get_sender(message: *mut DBusMessage) -> *const c_char;
+    pub fn dbus_message_get_error_name(message: *mut DBusMessage) -> *const c_char;
+    pub fn dbus_message_get_signature(message: *mut DBusMessage) -> *const c_char;
     pub fn dbus_message_set_serial(message: *mut DBusMessage, serial: u32);
     pub fn dbus_message_set_destination(message: *mut DBusMessage, destination: *const c_char) -> u32;
+    pub fn dbus_message_set_sender(message: *mut DBusMessage, sender: *const c_char) -> u32;
     pub fn dbus_message_get_no_reply(message: *mut DBusMessage) -> u32;
     pub fn dbus_message_set_no_reply(message: *mut DBusMessage, no_reply: u32);
     pub fn dbus_message_get_auto_start(message: *mut DBusMessage) -> u32;
     pub fn dbus_message_set_auto_start(message: *mut DBusMessage, no_reply: u32);
+    pub fn dbus_message_get_allow_interactive_authorization(message: *mut DBusMessage) -> u32;
+    pub fn dbus_message_set_allow_interactive_authorization(message: *mut DBusMessage, allow: u32);
 
     pub fn dbus_message_iter_append_basic(iter: *mut DBusMessageIter, t: c_int, value: *const c_void) -> u32;
     pub fn dbus_message_iter_append_fixed_array(iter: *mut DBusMessageIter, element_type: c_int,
@@ -248,6 +282,7 @@ extern "C" {
     pub fn dbus_message_iter_open_container(iter: *mut DBusMessageIter, _type: c_int,
         contained_signature: *const c_char, sub: *mut DBusMessageIter) -> u32;
     pub fn dbus_message_iter_close_container(iter: *mut DBusMessageIter, sub: *mut DBusMessageIter) -> u32;
+    pub fn dbus_message_iter_abandon_container(iter: *mut DBusMessageIter, sub: *mut DBusMessageIter);
 
     pub fn dbus_free(memory: *mut c_void);
     pub fn dbus_free_string_array(str_array: *mut *mut c_char) -> c_void;
@@ -255,6 +290,7 @@ extern "C" {
     pub fn dbus_signature_validate_single(signature: *const c_char, error: *mut DBusError) -> u32;
 
     pub fn dbus_threads_init_default() -> c_int;
+    pub fn dbus_get_version(major_version_p: *mut c_int, minor_version_p: *mut c_int, micro_version_p: *mut c_int);
 
     pub fn dbus_validate_bus_name(busname: *const c_char, error: *mut DBusError) -> u32;
     pub fn dbus_validate_error_name(errorname: *const c_char, error: *mut DBusError) -> u32;
@@ -282,6 +318,9 @@ extern "C" {
     pub fn dbus_pending_call_set_notify(pending: *mut DBusPendingCall, n: DBusPendingCallNotifyFunction,
         user_data: *mut c_void, free_user_data: DBusFreeFunction) -> u32;
     pub fn dbus_pending_call_steal_reply(pending: *mut DBusPendingCall) -> *mut DBusMessage;
+    pub fn dbus_pending_call_cancel(pending: *mut DBusPendingCall);
+    pub fn dbus_pending_call_get_completed(pending: *mut DBusPendingCall) -> u32;
+    pub fn dbus_pending_call_block(pending: *mut DBusPendingCall);
 
     pub fn dbus_message_marshal(msg: *mut DBusMessage, marshalled_data_p: *mut *mut c_char, len_p: *mut c_int) -> u32;
     pub fn dbus_message_demarshal(s: *const c_char, len: c_int, error: *mut DBusError) -> *mut DBusMessage;
@@ -302,4 +341,29 @@ extern "C" {
     pub fn dbus_connection_has_messages_to_send(conn: *mut DBusConnection) -> u32;
 
     pub fn dbus_try_get_local_machine_id (error: *mut DBusError) -> *mut c_char;
+
+    pub fn dbus_parse_address(address: *const c_char, entry_result: *mut *mut *mut DBusAddressEntry,
+        array_len: *mut c_int, error: *mut DBusError) -> u32;
+    pub fn dbus_address_entries_free(entries: *mut *mut DBusAddressEntry);
+    pub fn dbus_address_entry_get_method(entry: *mut DBusAddressEntry) -> *const c_char;
+    pub fn dbus_address_entry_get_value(entry: *mut DBusAddressEntry, key: *const c_char) -> *const c_char;
+    pub fn dbus_address_escape_value(value: *const c_char) -> *mut c_char;
+    pub fn dbus_address_unescape_value(value: *const c_char, error: *mut DBusError) -> *mut c_char;
+
+    pub fn dbus_server_listen(address: *const c_char, error: *mut DBusError) -> *mut DBusServer;
+    pub fn dbus_server_ref(server: *mut DBusServer) -> *mut DBusServer;
+    pub fn dbus_server_unref(server: *mut DBusServer);
+    pub fn dbus_server_disconnect(server: *mut DBusServer);
+    pub fn dbus_server_get_is_connected(server: *mut DBusServer) -> u32;
+    pub fn dbus_server_get_address(server: *mut DBusServer) -> *mut c_char;
+    pub fn dbus_server_get_id(server: *mut DBusServer) -> *mut c_char;
+    pub fn dbus_server_set_new_connection_function(server: *mut DBusServer, function: DBusNewConnectionFunction,
+        data: *mut c_void, free_data_function: DBusFreeFunction);
+    pub fn dbus_server_set_watch_functions(server: *mut DBusServer, add_function: DBusAddWatchFunction,
+        remove_function: DBusRemoveWatchFunction, toggled_function: DBusWatchToggledFunction,
+        data: *mut c_void, free_data_function: DBusFreeFunction) -> u32;
+    pub fn dbus_server_set_timeout_functions(server: *mut DBusServer, add_function: DBusAddTimeoutFunction,
+        remove_function: DBusRemoveTimeoutFunction, toggled_function: DBusTimeoutToggledFunction,
+        data: *mut c_void, free_data_function: DBusFreeFunction) -> u32;
+    pub fn dbus_server_set_auth_mechanisms(server: *mut DBusServer, mechanisms: *mut *const c_char) -> u32;
 }